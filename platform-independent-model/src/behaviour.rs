@@ -1 +1,17 @@
+//! The 'behaviour' module describes the dynamic behaviour of the RTPS
+//! entities, in terms of the messages they exchange.
+//!
+//! The behaviours are implemented in a 'sans-IO' style. They consume and
+//! produce [`Message`](crate::messages::Message)s, but never own sockets or
+//! timers, so that the same logic can be driven by any transport.
+//!
+//! See [section 8.4](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF) of the relevant specification document.
 
+pub mod flow_control;
+pub mod liveliness;
+pub mod reader;
+pub mod receiver;
+pub mod writer;
+
+#[doc(inline)]
+pub use receiver::MessageReceiver;
@@ -0,0 +1,602 @@
+//! Flow controllers, which decide when the [`Message`]s produced by the
+//! writers may be handed to the transport
+//!
+//! A flow controller sits between the writer behaviours and the transport.
+//! It is passed to the writers as their [`Output`], so every message they
+//! produce is queued in the controller. [`FlowController::release`] then
+//! forwards the messages which may be sent to the real [`Output`].
+//!
+//! - [`Unlimited`] releases every message as soon as possible
+//! - [`TokenBucket`] limits the number of bytes sent in each period
+//! - [`RoundRobin`] takes turns between destinations, so that a backlog for
+//!   one reader doesn't delay the others, and can optionally limit the rate
+//!
+//! # Example
+//!
+//! ```
+//! use rtps_pim::{
+//!     behaviour::{
+//!         flow_control::{FlowController, TokenBucket},
+//!         writer::{ReaderLocator, StatelessWriter},
+//!     },
+//!     messages::Message,
+//!     structure::{
+//!         history::{HistoryCache, Kind},
+//!         Guid, KeyHash, Locator,
+//!     },
+//! };
+//! use std::{
+//!     net::{Ipv4Addr, SocketAddrV4},
+//!     time::{Duration, Instant},
+//! };
+//!
+//! let guid = Guid::new([1; 12], [0, 0, 1, 2]);
+//! let mut writer = StatelessWriter::new(guid, HistoryCache::default());
+//!
+//! let locator = Locator::from(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 7400));
+//! writer.reader_locator_add(ReaderLocator::new(locator, false));
+//!
+//! // at most 500 bytes every 10 milliseconds
+//! let mut flow_controller = TokenBucket::new(500, Duration::from_millis(10));
+//!
+//! let now = Instant::now();
+//! for _ in 0..2 {
+//!     writer
+//!         .new_change(Kind::Alive(vec![0; 400]), KeyHash::default())
+//!         .unwrap();
+//!     writer.send(now, &mut flow_controller);
+//! }
+//!
+//! let mut output: Vec<(Locator, Message<_, _>)> = Vec::new();
+//! flow_controller.release(now, &mut output);
+//! assert_eq!(output.len(), 1);
+//! assert_eq!(flow_controller.metrics().queued_messages, 1);
+//!
+//! flow_controller.release(now + Duration::from_millis(10), &mut output);
+//! assert_eq!(output.len(), 2);
+//! ```
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    convert::TryFrom,
+    ops::Bound,
+    time::{Duration, Instant},
+};
+
+use crate::{behaviour::writer::Output, messages::Message, structure::Locator};
+
+/// Decides when the [`Message`]s queued by the writers may be sent
+///
+/// Messages are queued through the [`Output`] implementation, and forwarded
+/// to the transport by [`FlowController::release`].
+pub trait FlowController<P, Id>: Output<P, Id> {
+    /// Forward the queued messages which may be sent at `now` to the given
+    /// [`Output`]
+    fn release<O>(&mut self, now: Instant, output: &mut O)
+    where
+        O: Output<P, Id>;
+
+    /// The time at which [`FlowController::release`] next needs to be called
+    /// to send messages which are being held back, if any
+    fn next_deadline(&self) -> Option<Instant>;
+
+    /// The current [`Metrics`] of the controller
+    fn metrics(&self) -> Metrics;
+}
+
+/// Counters describing the messages which have passed through a
+/// [`FlowController`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// The number of messages waiting to be sent
+    pub queued_messages: usize,
+
+    /// The total size of the messages waiting to be sent, in bytes
+    pub queued_bytes: usize,
+
+    /// The number of messages which have been released
+    pub sent_messages: u64,
+
+    /// The total size of the messages which have been released, in bytes
+    pub sent_bytes: u64,
+}
+
+impl Metrics {
+    fn queue(&mut self, size: usize) {
+        self.queued_messages += 1;
+        self.queued_bytes += size;
+    }
+
+    fn send(&mut self, size: usize) {
+        self.queued_messages -= 1;
+        self.queued_bytes -= size;
+        self.sent_messages += 1;
+        self.sent_bytes += u64::try_from(size).unwrap_or(u64::MAX);
+    }
+}
+
+/// A message waiting to be sent
+#[derive(Debug, Clone)]
+struct Queued<P, Id> {
+    locator: Locator,
+    message: Message<P, Id>,
+    size: usize,
+}
+
+impl<P, Id> Queued<P, Id> {
+    fn new(locator: Locator, message: Message<P, Id>) -> Self {
+        let size = message.serialized_size();
+        Self {
+            locator,
+            message,
+            size,
+        }
+    }
+}
+
+/// Allows up to `capacity` bytes to be sent in each period
+#[derive(Debug, Clone)]
+struct Bucket {
+    capacity: usize,
+    period: Duration,
+    tokens: usize,
+    last_refill: Option<Instant>,
+}
+
+impl Bucket {
+    fn new(capacity: usize, period: Duration) -> Self {
+        Self {
+            capacity,
+            period,
+            tokens: capacity,
+            last_refill: None,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let last_refill = if let Some(last_refill) = self.last_refill {
+            last_refill
+        } else {
+            self.last_refill = Some(now);
+            return;
+        };
+
+        let elapsed = now.saturating_duration_since(last_refill);
+        let periods = elapsed.as_nanos() / self.period.as_nanos().max(1);
+        if periods == 0 {
+            return;
+        }
+
+        self.tokens = match usize::try_from(periods) {
+            Ok(periods) => self
+                .tokens
+                .saturating_add(periods.saturating_mul(self.capacity))
+                .min(self.capacity),
+            Err(_) => self.capacity,
+        };
+        self.last_refill = Some(
+            u32::try_from(periods)
+                .ok()
+                .and_then(|periods| last_refill.checked_add(self.period * periods))
+                .unwrap_or(now),
+        );
+    }
+
+    /// Take the tokens for a message of the given size, if there are enough
+    ///
+    /// A message larger than the capacity is sent once the bucket is full, so
+    /// that it isn't held back forever.
+    fn take(&mut self, size: usize) -> bool {
+        if size <= self.tokens || self.tokens == self.capacity {
+            self.tokens = self.tokens.saturating_sub(size);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn next_refill(&self) -> Option<Instant> {
+        Some(self.last_refill? + self.period)
+    }
+}
+
+/// A [`FlowController`] which releases every message as soon as possible
+#[derive(Debug, Clone)]
+pub struct Unlimited<P, Id> {
+    queue: VecDeque<Queued<P, Id>>,
+    metrics: Metrics,
+}
+
+impl<P, Id> Default for Unlimited<P, Id> {
+    fn default() -> Self {
+        Self {
+            queue: VecDeque::default(),
+            metrics: Metrics::default(),
+        }
+    }
+}
+
+impl<P, Id> Unlimited<P, Id> {
+    /// Construct a new [`Unlimited`] flow controller
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<P, Id> Output<P, Id> for Unlimited<P, Id> {
+    fn send(&mut self, locator: Locator, message: Message<P, Id>) {
+        let queued = Queued::new(locator, message);
+        self.metrics.queue(queued.size);
+        self.queue.push_back(queued);
+    }
+}
+
+impl<P, Id> FlowController<P, Id> for Unlimited<P, Id> {
+    fn release<O>(&mut self, _now: Instant, output: &mut O)
+    where
+        O: Output<P, Id>,
+    {
+        while let Some(queued) = self.queue.pop_front() {
+            self.metrics.send(queued.size);
+            output.send(queued.locator, queued.message);
+        }
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        None
+    }
+
+    fn metrics(&self) -> Metrics {
+        self.metrics
+    }
+}
+
+/// A [`FlowController`] which sends at most a fixed number of bytes in each
+/// period
+///
+/// Messages are released in the order they were queued. A message which is
+/// larger than the number of bytes per period is released at the start of a
+/// period, so the limit may be exceeded by a single message.
+#[derive(Debug, Clone)]
+pub struct TokenBucket<P, Id> {
+    bucket: Bucket,
+    queue: VecDeque<Queued<P, Id>>,
+    metrics: Metrics,
+}
+
+impl<P, Id> TokenBucket<P, Id> {
+    /// Construct a new [`TokenBucket`] flow controller, which sends at most
+    /// `bytes_per_period` bytes in each `period`
+    #[must_use]
+    pub fn new(bytes_per_period: usize, period: Duration) -> Self {
+        Self {
+            bucket: Bucket::new(bytes_per_period, period),
+            queue: VecDeque::default(),
+            metrics: Metrics::default(),
+        }
+    }
+}
+
+impl<P, Id> Output<P, Id> for TokenBucket<P, Id> {
+    fn send(&mut self, locator: Locator, message: Message<P, Id>) {
+        let queued = Queued::new(locator, message);
+        self.metrics.queue(queued.size);
+        self.queue.push_back(queued);
+    }
+}
+
+impl<P, Id> FlowController<P, Id> for TokenBucket<P, Id> {
+    fn release<O>(&mut self, now: Instant, output: &mut O)
+    where
+        O: Output<P, Id>,
+    {
+        self.bucket.refill(now);
+
+        while let Some(queued) = self.queue.front() {
+            if !self.bucket.take(queued.size) {
+                break;
+            }
+            if let Some(queued) = self.queue.pop_front() {
+                self.metrics.send(queued.size);
+                output.send(queued.locator, queued.message);
+            }
+        }
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        if self.queue.is_empty() {
+            None
+        } else {
+            self.bucket.next_refill()
+        }
+    }
+
+    fn metrics(&self) -> Metrics {
+        self.metrics
+    }
+}
+
+/// A [`FlowController`] which takes turns between the destinations of the
+/// queued messages
+///
+/// Each [`Locator`] has its own queue, and one message is released from each
+/// queue in turn. This way, a large backlog for one reader doesn't delay the
+/// messages for the others. If a rate is configured, at most a fixed number
+/// of bytes are sent in each period, as with a [`TokenBucket`].
+#[derive(Debug, Clone)]
+pub struct RoundRobin<P, Id> {
+    bucket: Option<Bucket>,
+    queues: BTreeMap<Locator, VecDeque<Queued<P, Id>>>,
+    last_served: Option<Locator>,
+    metrics: Metrics,
+}
+
+impl<P, Id> Default for RoundRobin<P, Id> {
+    fn default() -> Self {
+        Self {
+            bucket: None,
+            queues: BTreeMap::default(),
+            last_served: None,
+            metrics: Metrics::default(),
+        }
+    }
+}
+
+impl<P, Id> RoundRobin<P, Id> {
+    /// Construct a new [`RoundRobin`] flow controller, without a limit on the
+    /// rate
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Construct a new [`RoundRobin`] flow controller, which sends at most
+    /// `bytes_per_period` bytes in each `period`
+    #[must_use]
+    pub fn with_rate(bytes_per_period: usize, period: Duration) -> Self {
+        Self {
+            bucket: Some(Bucket::new(bytes_per_period, period)),
+            ..Self::default()
+        }
+    }
+
+    /// The number of bytes queued for the given [`Locator`]
+    #[must_use]
+    pub fn queued_bytes(&self, locator: Locator) -> usize {
+        self.queues
+            .get(&locator)
+            .map_or(0, |queue| queue.iter().map(|queued| queued.size).sum())
+    }
+
+    /// The destination whose turn is next
+    fn next_locator(&self) -> Option<Locator> {
+        let after = match self.last_served {
+            Some(last_served) => Bound::Excluded(last_served),
+            None => Bound::Unbounded,
+        };
+
+        self.queues
+            .range((after, Bound::Unbounded))
+            .chain(self.queues.iter())
+            .map(|(&locator, _)| locator)
+            .next()
+    }
+}
+
+impl<P, Id> Output<P, Id> for RoundRobin<P, Id> {
+    fn send(&mut self, locator: Locator, message: Message<P, Id>) {
+        let queued = Queued::new(locator, message);
+        self.metrics.queue(queued.size);
+        self.queues.entry(locator).or_default().push_back(queued);
+    }
+}
+
+impl<P, Id> FlowController<P, Id> for RoundRobin<P, Id> {
+    fn release<O>(&mut self, now: Instant, output: &mut O)
+    where
+        O: Output<P, Id>,
+    {
+        if let Some(bucket) = &mut self.bucket {
+            bucket.refill(now);
+        }
+
+        while let Some(locator) = self.next_locator() {
+            let queue = match self.queues.get_mut(&locator) {
+                Some(queue) => queue,
+                None => break,
+            };
+
+            let size = queue.front().map_or(0, |queued| queued.size);
+            if let Some(bucket) = &mut self.bucket {
+                if !bucket.take(size) {
+                    break;
+                }
+            }
+
+            if let Some(queued) = queue.pop_front() {
+                self.metrics.send(queued.size);
+                output.send(queued.locator, queued.message);
+            }
+            if queue.is_empty() {
+                self.queues.remove(&locator);
+            }
+            self.last_served = Some(locator);
+        }
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        if self.queues.is_empty() {
+            None
+        } else {
+            self.bucket.as_ref().and_then(Bucket::next_refill)
+        }
+    }
+
+    fn metrics(&self) -> Metrics {
+        self.metrics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FlowController, Metrics, RoundRobin, TokenBucket, Unlimited};
+    use crate::{
+        behaviour::writer::Output,
+        messages::{
+            submessage::{Data, Payload},
+            Header, Message,
+        },
+        structure::{Locator, ProtocolVersion, VendorId},
+    };
+    use std::{
+        net::{Ipv4Addr, SocketAddrV4},
+        time::{Duration, Instant},
+    };
+    use vec1::vec1;
+
+    type Prefix = [u8; 12];
+    type Id = [u8; 4];
+    type Sent = Vec<(Locator, Message<Prefix, Id>)>;
+
+    fn locator(port: u16) -> Locator {
+        SocketAddrV4::new(Ipv4Addr::LOCALHOST, port).into()
+    }
+
+    /// A message of exactly 100 bytes, tagged with the given sequence number
+    fn message(sequence_number: u64) -> Message<Prefix, Id> {
+        let header = Header::new(ProtocolVersion::Latest, VendorId::Unknown, [1; 12]);
+        let data = Data {
+            reader: None,
+            writer: [0, 0, 1, 2],
+            writer_sequence_number: sequence_number,
+            inline_qos: None,
+            payload: Some(Payload::Data(vec![0; 56])),
+            non_standard_payload: false,
+        };
+        let message = Message::new(header, vec1![data.into()]);
+        assert_eq!(message.serialized_size(), 100);
+        message
+    }
+
+    fn sent(sent: &Sent) -> Vec<(u16, u64)> {
+        sent.iter()
+            .map(|(locator, message)| {
+                let port = match locator {
+                    Locator::Udpv4(address) => address.port(),
+                    Locator::Udpv6(address) => address.port(),
+                };
+                let sequence_number = match &message.submessages()[0] {
+                    crate::messages::SubMessage::Data(data) => data.writer_sequence_number,
+                    _ => unreachable!(),
+                };
+                (port, sequence_number)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unlimited_releases_everything() {
+        let mut flow_controller = Unlimited::new();
+        flow_controller.send(locator(1), message(1));
+        flow_controller.send(locator(2), message(2));
+        assert_eq!(
+            flow_controller.metrics(),
+            Metrics {
+                queued_messages: 2,
+                queued_bytes: 200,
+                sent_messages: 0,
+                sent_bytes: 0
+            }
+        );
+
+        let mut output = Sent::new();
+        flow_controller.release(Instant::now(), &mut output);
+        assert_eq!(sent(&output), vec![(1, 1), (2, 2)]);
+        assert_eq!(flow_controller.metrics().queued_bytes, 0);
+        assert_eq!(flow_controller.metrics().sent_bytes, 200);
+        assert_eq!(flow_controller.next_deadline(), None);
+    }
+
+    #[test]
+    fn token_bucket_limits_bytes_per_period() {
+        let period = Duration::from_millis(10);
+        let mut flow_controller = TokenBucket::new(250, period);
+        for sequence_number in 1..=5 {
+            flow_controller.send(locator(1), message(sequence_number));
+        }
+
+        let start = Instant::now();
+        let mut output = Sent::new();
+        flow_controller.release(start, &mut output);
+        assert_eq!(output.len(), 2);
+        assert_eq!(flow_controller.metrics().queued_bytes, 300);
+        assert_eq!(flow_controller.next_deadline(), Some(start + period));
+
+        // no tokens until the period has elapsed
+        flow_controller.release(start + period / 2, &mut output);
+        assert_eq!(output.len(), 2);
+
+        flow_controller.release(start + period, &mut output);
+        assert_eq!(output.len(), 4);
+
+        flow_controller.release(start + period * 3, &mut output);
+        assert_eq!(sent(&output), (1..=5).map(|n| (1, n)).collect::<Vec<_>>());
+        assert_eq!(flow_controller.next_deadline(), None);
+    }
+
+    #[test]
+    fn token_bucket_eventually_sends_oversized_messages() {
+        let period = Duration::from_millis(10);
+        let mut flow_controller = TokenBucket::new(50, period);
+        flow_controller.send(locator(1), message(1));
+        flow_controller.send(locator(1), message(2));
+
+        let start = Instant::now();
+        let mut output = Sent::new();
+        flow_controller.release(start, &mut output);
+        assert_eq!(output.len(), 1);
+
+        // the bucket needs to be full again
+        flow_controller.release(start + period, &mut output);
+        assert_eq!(output.len(), 2);
+    }
+
+    #[test]
+    fn round_robin_takes_turns_between_destinations() {
+        let mut flow_controller = RoundRobin::new();
+        for sequence_number in 1..=3 {
+            flow_controller.send(locator(1), message(sequence_number));
+        }
+        flow_controller.send(locator(2), message(10));
+        flow_controller.send(locator(3), message(20));
+        assert_eq!(flow_controller.queued_bytes(locator(1)), 300);
+
+        let mut output = Sent::new();
+        flow_controller.release(Instant::now(), &mut output);
+        assert_eq!(
+            sent(&output),
+            vec![(1, 1), (2, 10), (3, 20), (1, 2), (1, 3)]
+        );
+    }
+
+    #[test]
+    fn round_robin_with_rate_resumes_where_it_stopped() {
+        let period = Duration::from_millis(10);
+        let mut flow_controller = RoundRobin::with_rate(200, period);
+        for sequence_number in 1..=3 {
+            flow_controller.send(locator(1), message(sequence_number));
+            flow_controller.send(locator(2), message(sequence_number + 10));
+        }
+
+        let start = Instant::now();
+        let mut output = Sent::new();
+        flow_controller.release(start, &mut output);
+        assert_eq!(sent(&output), vec![(1, 1), (2, 11)]);
+
+        flow_controller.send(locator(3), message(20));
+        flow_controller.release(start + period, &mut output);
+        assert_eq!(sent(&output)[2..], [(3, 20), (1, 2)]);
+        assert_eq!(flow_controller.metrics().queued_messages, 3);
+    }
+}
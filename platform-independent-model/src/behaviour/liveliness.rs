@@ -0,0 +1,823 @@
+//! Assertion and monitoring of the liveliness of writers
+//!
+//! Depending on the [`LivelinessKind`] of a writer, its liveliness is
+//! asserted by the writer itself (with a `HEARTBEAT` carrying the liveliness
+//! flag, see [`StatefulWriter::assert_liveliness`]), or on behalf of every
+//! writer in a participant with a [`ParticipantMessageData`] sent by the
+//! built-in [`ParticipantMessageWriter`]. Any data received from a writer
+//! also asserts its liveliness.
+//!
+//! On the reader side, a [`LivelinessMonitor`] tracks the lease of each
+//! matched writer, and reports [`LivelinessEvent`]s when a writer becomes
+//! alive, or stops asserting its liveliness within its lease duration.
+//!
+//! See [section 8.4.13](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF) of the relevant specification document.
+
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    behaviour::writer::{Output, SendError, StatefulWriter},
+    structure::{
+        history::{Cache, Kind},
+        Guid, KeyHash,
+    },
+};
+
+/// The entity ID of the built-in writer of [`ParticipantMessageData`]
+/// (`ENTITYID_P2P_BUILTIN_PARTICIPANT_MESSAGE_WRITER`)
+pub const PARTICIPANT_MESSAGE_WRITER: [u8; 4] = [0x00, 0x02, 0x00, 0xc2];
+
+/// The entity ID of the built-in reader of [`ParticipantMessageData`]
+/// (`ENTITYID_P2P_BUILTIN_PARTICIPANT_MESSAGE_READER`)
+pub const PARTICIPANT_MESSAGE_READER: [u8; 4] = [0x00, 0x02, 0x00, 0xc7];
+
+/// The encapsulation identifiers of little- and big-endian CDR
+const CDR_LE: [u8; 2] = [0x00, 0x01];
+const CDR_BE: [u8; 2] = [0x00, 0x00];
+
+/// How the liveliness of a writer is asserted
+///
+/// This corresponds to the kind of the `LIVELINESS` quality of service
+/// policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum LivelinessKind {
+    /// The participant automatically asserts the liveliness of the writer,
+    /// for as long as the participant is running
+    #[default]
+    Automatic,
+
+    /// The liveliness of the writer is asserted whenever the application
+    /// asserts the liveliness of any writer in the participant
+    ManualByParticipant,
+
+    /// The liveliness of the writer is only asserted when the application
+    /// writes data with it, or asserts its liveliness explicitly
+    ManualByTopic,
+}
+
+/// The `LIVELINESS` quality of service policy of a writer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Liveliness {
+    /// How the liveliness of the writer is asserted
+    pub kind: LivelinessKind,
+
+    /// How long the writer is considered alive after each assertion
+    ///
+    /// `None` is an infinite lease, meaning the writer is never lost.
+    pub lease_duration: Option<Duration>,
+}
+
+/// The kind of a [`ParticipantMessageData`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ParticipantMessageKind {
+    /// Asserts the liveliness of the [`LivelinessKind::Automatic`] writers of
+    /// the participant
+    AutomaticLivelinessUpdate,
+
+    /// Asserts the liveliness of the [`LivelinessKind::Automatic`] and
+    /// [`LivelinessKind::ManualByParticipant`] writers of the participant
+    ManualLivelinessUpdate,
+
+    /// A kind which isn't defined by the specification
+    Other([u8; 4]),
+}
+
+impl From<[u8; 4]> for ParticipantMessageKind {
+    fn from(bytes: [u8; 4]) -> Self {
+        match bytes {
+            [0, 0, 0, 1] => Self::AutomaticLivelinessUpdate,
+            [0, 0, 0, 2] => Self::ManualLivelinessUpdate,
+            bytes => Self::Other(bytes),
+        }
+    }
+}
+
+impl From<ParticipantMessageKind> for [u8; 4] {
+    fn from(kind: ParticipantMessageKind) -> Self {
+        match kind {
+            ParticipantMessageKind::AutomaticLivelinessUpdate => [0, 0, 0, 1],
+            ParticipantMessageKind::ManualLivelinessUpdate => [0, 0, 0, 2],
+            ParticipantMessageKind::Other(bytes) => bytes,
+        }
+    }
+}
+
+impl ParticipantMessageKind {
+    fn asserts(self, kind: LivelinessKind) -> bool {
+        match self {
+            Self::AutomaticLivelinessUpdate => kind == LivelinessKind::Automatic,
+            Self::ManualLivelinessUpdate => kind != LivelinessKind::ManualByTopic,
+            Self::Other(_) => false,
+        }
+    }
+}
+
+/// The data exchanged between the built-in participant message endpoints
+///
+/// See [section 9.6.2.1](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF) of the relevant specification document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParticipantMessageData<P> {
+    /// The [`Guid`] prefix of the participant sending the message
+    pub participant: P,
+
+    /// The kind of message
+    pub kind: ParticipantMessageKind,
+
+    /// Additional data, which is empty for liveliness updates
+    pub data: Vec<u8>,
+}
+
+/// The error returned when a [`ParticipantMessageData`] can't be decoded
+#[derive(Debug, thiserror::Error)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum ParticipantMessageError {
+    /// The payload ends before the end of the message
+    #[error("the participant message is truncated")]
+    Truncated,
+
+    /// The payload isn't encoded with CDR
+    #[error("unsupported encapsulation {0:?}")]
+    Encapsulation([u8; 2]),
+
+    /// The participant's [`Guid`] prefix can't be decoded
+    #[error("invalid participant guid prefix")]
+    InvalidPrefix,
+}
+
+impl<P> ParticipantMessageData<P> {
+    /// Construct a new [`ParticipantMessageData`] which asserts the
+    /// liveliness of the given participant
+    #[must_use]
+    pub fn new(participant: P, kind: ParticipantMessageKind) -> Self {
+        Self {
+            participant,
+            kind,
+            data: Vec::new(),
+        }
+    }
+
+    /// The [`KeyHash`] of the message
+    ///
+    /// The message is keyed on the participant and the kind, so that a
+    /// history cache only needs to hold the latest message of each kind.
+    #[must_use]
+    pub fn key_hash(&self) -> KeyHash
+    where
+        P: AsRef<[u8]>,
+    {
+        let mut bytes = [0; 16];
+        let participant = self.participant.as_ref();
+        let len = participant.len().min(12);
+        bytes[..len].copy_from_slice(&participant[..len]);
+        bytes[12..].copy_from_slice(&<[u8; 4]>::from(self.kind));
+        KeyHash::new(bytes)
+    }
+
+    /// Serialize the message as a little-endian CDR payload
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8>
+    where
+        P: AsRef<[u8]>,
+    {
+        let length = u32::try_from(self.data.len()).unwrap_or(u32::MAX);
+
+        let mut bytes = Vec::with_capacity(24 + self.data.len());
+        bytes.extend_from_slice(&CDR_LE);
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(self.participant.as_ref());
+        bytes.extend_from_slice(&<[u8; 4]>::from(self.kind));
+        bytes.extend_from_slice(&length.to_le_bytes());
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// Deserialize a message from a CDR payload
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the payload is truncated, or isn't encoded
+    /// with CDR.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ParticipantMessageError>
+    where
+        P: for<'a> TryFrom<&'a [u8]>,
+    {
+        let field = |range: std::ops::Range<usize>| {
+            bytes.get(range).ok_or(ParticipantMessageError::Truncated)
+        };
+
+        let encapsulation =
+            <[u8; 2]>::try_from(field(0..2)?).map_err(|_| ParticipantMessageError::Truncated)?;
+        let participant =
+            P::try_from(field(4..16)?).map_err(|_| ParticipantMessageError::InvalidPrefix)?;
+        let kind = <[u8; 4]>::try_from(field(16..20)?)
+            .map_err(|_| ParticipantMessageError::Truncated)?
+            .into();
+        let length =
+            <[u8; 4]>::try_from(field(20..24)?).map_err(|_| ParticipantMessageError::Truncated)?;
+        let length = match encapsulation {
+            CDR_LE => u32::from_le_bytes(length),
+            CDR_BE => u32::from_be_bytes(length),
+            other => return Err(ParticipantMessageError::Encapsulation(other)),
+        };
+        let length = usize::try_from(length).map_err(|_| ParticipantMessageError::Truncated)?;
+        let data = field(24..24 + length)?.to_vec();
+
+        Ok(Self {
+            participant,
+            kind,
+            data,
+        })
+    }
+}
+
+/// The built-in writer which asserts the liveliness of the writers in a
+/// participant, on their behalf
+///
+/// A [`ParticipantMessageKind::AutomaticLivelinessUpdate`] is written
+/// periodically, and a [`ParticipantMessageKind::ManualLivelinessUpdate`]
+/// whenever the application calls
+/// [`ParticipantMessageWriter::assert_participant`]. Only the latest message
+/// of each kind is kept in the history cache.
+///
+/// The automatic period should be shorter than the lease duration of every
+/// [`LivelinessKind::Automatic`] writer in the participant.
+///
+/// The [`StatefulWriter`] should use the [`PARTICIPANT_MESSAGE_WRITER`]
+/// entity ID, and be matched with the [`PARTICIPANT_MESSAGE_READER`] of each
+/// remote participant.
+#[derive(Debug)]
+pub struct ParticipantMessageWriter<C, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    writer: StatefulWriter<C, P, Id>,
+    automatic_period: Option<Duration>,
+    last_automatic: Option<Instant>,
+    manual_pending: bool,
+    latest: BTreeMap<ParticipantMessageKind, u64>,
+}
+
+impl<C, P, Id> ParticipantMessageWriter<C, P, Id>
+where
+    P: Copy + PartialEq,
+    Id: Copy + PartialEq,
+{
+    /// Construct a new [`ParticipantMessageWriter`], which writes its
+    /// messages with the given [`StatefulWriter`]
+    ///
+    /// If `automatic_period` is `None`, no automatic liveliness updates are
+    /// written.
+    #[must_use]
+    pub fn new(writer: StatefulWriter<C, P, Id>, automatic_period: Option<Duration>) -> Self {
+        Self {
+            writer,
+            automatic_period,
+            last_automatic: None,
+            manual_pending: false,
+            latest: BTreeMap::default(),
+        }
+    }
+
+    /// The underlying [`StatefulWriter`]
+    #[must_use]
+    pub fn writer(&self) -> &StatefulWriter<C, P, Id> {
+        &self.writer
+    }
+
+    /// Mutable access to the underlying [`StatefulWriter`]
+    ///
+    /// This can be used to match remote readers, and to pass it the
+    /// submessages they send.
+    pub fn writer_mut(&mut self) -> &mut StatefulWriter<C, P, Id> {
+        &mut self.writer
+    }
+
+    /// Assert the liveliness of the [`LivelinessKind::ManualByParticipant`]
+    /// writers in the participant
+    ///
+    /// The update is written by the next call to
+    /// [`ParticipantMessageWriter::send`].
+    pub fn assert_participant(&mut self) {
+        self.manual_pending = true;
+    }
+
+    /// The time at which [`ParticipantMessageWriter::send`] next needs to be
+    /// called, if any
+    #[must_use]
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let automatic = self
+            .automatic_period
+            .zip(self.last_automatic)
+            .map(|(period, last_automatic)| last_automatic + period);
+
+        self.writer
+            .next_deadline()
+            .into_iter()
+            .chain(automatic)
+            .min()
+    }
+
+    /// Write any liveliness updates which are due, and send any messages
+    /// which are due to the matched readers
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if an update can't be added to the cache, or if a
+    /// submessage doesn't fit in a message.
+    pub fn send<O>(&mut self, now: Instant, output: &mut O) -> Result<(), SendError<C::AddErr>>
+    where
+        C: Cache<Vec<u8>, Prefix = P, EntityId = Id, SqnN = u64>,
+        P: AsRef<[u8]>,
+        O: Output<P, Id>,
+    {
+        let automatic_due = match (self.automatic_period, self.last_automatic) {
+            (Some(_), None) => true,
+            (Some(period), Some(last_automatic)) => now >= last_automatic + period,
+            (None, _) => false,
+        };
+
+        if automatic_due {
+            self.write(ParticipantMessageKind::AutomaticLivelinessUpdate)
+                .map_err(SendError::Cache)?;
+            self.last_automatic = Some(now);
+        }
+
+        if self.manual_pending {
+            self.write(ParticipantMessageKind::ManualLivelinessUpdate)
+                .map_err(SendError::Cache)?;
+            self.manual_pending = false;
+        }
+
+        self.writer.send(now, output)?;
+        Ok(())
+    }
+
+    fn write(&mut self, kind: ParticipantMessageKind) -> Result<(), C::AddErr>
+    where
+        C: Cache<Vec<u8>, Prefix = P, EntityId = Id, SqnN = u64>,
+        P: AsRef<[u8]>,
+    {
+        let message = ParticipantMessageData::new(self.writer.guid().prefix(), kind);
+        let sequence_number = self
+            .writer
+            .new_change(Kind::Alive(message.to_bytes()), message.key_hash())?;
+
+        // only the latest message of each kind is kept
+        if let Some(previous) = self.latest.insert(kind, sequence_number) {
+            let _ = self.writer.cache_mut().remove(previous);
+        }
+
+        Ok(())
+    }
+}
+
+/// The liveliness status of the writers matched with a reader
+///
+/// This corresponds to the `LIVELINESS_CHANGED` status of a DDS reader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LivelinessChangedStatus {
+    /// The number of matched writers which are currently alive
+    pub alive_count: usize,
+
+    /// The number of matched writers which were alive, but have since failed
+    /// to assert their liveliness within their lease duration
+    pub not_alive_count: usize,
+}
+
+/// A change in the liveliness of a writer, reported by a
+/// [`LivelinessMonitor`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LivelinessEvent<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    /// A writer became alive, stopped being alive, or was removed while
+    /// alive
+    Changed {
+        /// The writer whose liveliness changed
+        writer: Guid<P, Id>,
+
+        /// Whether the writer is now alive
+        alive: bool,
+
+        /// The status after the change
+        status: LivelinessChangedStatus,
+    },
+
+    /// A writer failed to assert its liveliness within its lease duration
+    Lost {
+        /// The writer which was lost
+        writer: Guid<P, Id>,
+    },
+}
+
+/// The liveliness of a single writer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// The writer hasn't asserted its liveliness yet
+    Unknown,
+
+    /// The writer is alive until the given time, if any
+    Alive(Option<Instant>),
+
+    /// The writer has been lost
+    NotAlive,
+}
+
+#[derive(Debug, Clone)]
+struct WriterLiveliness {
+    liveliness: Liveliness,
+    state: State,
+}
+
+/// Tracks the lease of each writer matched with a reader
+///
+/// A writer is considered alive from the first time its liveliness is
+/// asserted, until its lease duration elapses without another assertion.
+/// Each transition is reported as a [`LivelinessEvent`].
+///
+/// The [`StatefulReader`](crate::behaviour::reader::StatefulReader) keeps a
+/// monitor up to date with its matched writers, and asserts their liveliness
+/// whenever it receives data, or a `HEARTBEAT` with the liveliness flag.
+/// Updates received by the built-in participant message reader are passed on
+/// with [`LivelinessMonitor::assert_participant`].
+///
+/// # Example
+///
+/// ```
+/// use rtps_pim::{
+///     behaviour::liveliness::{Liveliness, LivelinessEvent, LivelinessKind, LivelinessMonitor},
+///     structure::Guid,
+/// };
+/// use std::time::{Duration, Instant};
+///
+/// let writer = Guid::new([1; 12], [0, 0, 1, 2]);
+/// let liveliness = Liveliness {
+///     kind: LivelinessKind::ManualByTopic,
+///     lease_duration: Some(Duration::from_secs(1)),
+/// };
+///
+/// let mut monitor = LivelinessMonitor::default();
+/// monitor.writer_add(writer, liveliness);
+///
+/// let now = Instant::now();
+/// monitor.assert_writer(writer, now);
+/// assert!(monitor.is_alive(writer));
+///
+/// monitor.update(now + Duration::from_secs(1));
+/// assert!(!monitor.is_alive(writer));
+/// assert!(monitor
+///     .take_events()
+///     .contains(&LivelinessEvent::Lost { writer }));
+/// ```
+#[derive(Debug, Clone)]
+pub struct LivelinessMonitor<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    writers: BTreeMap<Guid<P, Id>, WriterLiveliness>,
+    events: Vec<LivelinessEvent<P, Id>>,
+}
+
+impl<P, Id> Default for LivelinessMonitor<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    fn default() -> Self {
+        Self {
+            writers: BTreeMap::default(),
+            events: Vec::default(),
+        }
+    }
+}
+
+impl<P, Id> LivelinessMonitor<P, Id>
+where
+    P: Copy + Ord,
+    Id: Copy + Ord,
+{
+    /// Start tracking the liveliness of a writer
+    ///
+    /// The writer is not alive until its liveliness is first asserted.
+    pub fn writer_add(&mut self, writer: Guid<P, Id>, liveliness: Liveliness) {
+        self.writer_remove(writer);
+        self.writers.insert(
+            writer,
+            WriterLiveliness {
+                liveliness,
+                state: State::Unknown,
+            },
+        );
+    }
+
+    /// Stop tracking the liveliness of a writer
+    pub fn writer_remove(&mut self, writer: Guid<P, Id>) {
+        if let Some(removed) = self.writers.remove(&writer) {
+            if let State::Alive(_) = removed.state {
+                self.changed(writer, false);
+            }
+        }
+    }
+
+    /// Assert the liveliness of a single writer
+    pub fn assert_writer(&mut self, writer: Guid<P, Id>, now: Instant) {
+        let became_alive = match self.writers.get_mut(&writer) {
+            Some(tracked) => tracked.assert(now),
+            None => return,
+        };
+        if became_alive {
+            self.changed(writer, true);
+        }
+    }
+
+    /// Assert the liveliness of the writers of a remote participant, in
+    /// response to a [`ParticipantMessageData`]
+    ///
+    /// Only the writers whose [`LivelinessKind`] is covered by the kind of
+    /// message are asserted.
+    pub fn assert_participant(
+        &mut self,
+        participant: P,
+        kind: ParticipantMessageKind,
+        now: Instant,
+    ) {
+        let mut became_alive = Vec::new();
+        for (&writer, tracked) in &mut self.writers {
+            if writer.prefix() == participant
+                && kind.asserts(tracked.liveliness.kind)
+                && tracked.assert(now)
+            {
+                became_alive.push(writer);
+            }
+        }
+
+        for writer in became_alive {
+            self.changed(writer, true);
+        }
+    }
+
+    /// Mark the writers whose lease has expired as lost
+    pub fn update(&mut self, now: Instant) {
+        let lost: Vec<_> = self
+            .writers
+            .iter_mut()
+            .filter_map(|(&writer, tracked)| match tracked.state {
+                State::Alive(Some(expiry)) if expiry <= now => {
+                    tracked.state = State::NotAlive;
+                    Some(writer)
+                }
+                _ => None,
+            })
+            .collect();
+
+        for writer in lost {
+            self.events.push(LivelinessEvent::Lost { writer });
+            self.changed(writer, false);
+        }
+    }
+
+    /// The time at which the next lease expires, if any
+    ///
+    /// [`LivelinessMonitor::update`] should be called at this time.
+    #[must_use]
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.writers
+            .values()
+            .filter_map(|tracked| match tracked.state {
+                State::Alive(expiry) => expiry,
+                _ => None,
+            })
+            .min()
+    }
+
+    /// Whether the given writer is currently alive
+    #[must_use]
+    pub fn is_alive(&self, writer: Guid<P, Id>) -> bool {
+        self.writers
+            .get(&writer)
+            .map_or(false, |tracked| matches!(tracked.state, State::Alive(_)))
+    }
+
+    /// The current [`LivelinessChangedStatus`]
+    #[must_use]
+    pub fn status(&self) -> LivelinessChangedStatus {
+        let mut status = LivelinessChangedStatus::default();
+        for tracked in self.writers.values() {
+            match tracked.state {
+                State::Alive(_) => status.alive_count += 1,
+                State::NotAlive => status.not_alive_count += 1,
+                State::Unknown => {}
+            }
+        }
+        status
+    }
+
+    /// Take the [`LivelinessEvent`]s which have occurred since the last call
+    pub fn take_events(&mut self) -> Vec<LivelinessEvent<P, Id>> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn changed(&mut self, writer: Guid<P, Id>, alive: bool) {
+        let status = self.status();
+        self.events.push(LivelinessEvent::Changed {
+            writer,
+            alive,
+            status,
+        });
+    }
+}
+
+impl WriterLiveliness {
+    /// Renew the lease of the writer
+    ///
+    /// Returns `true` if the writer wasn't alive before.
+    fn assert(&mut self, now: Instant) -> bool {
+        let was_alive = matches!(self.state, State::Alive(_));
+        self.state = State::Alive(
+            self.liveliness
+                .lease_duration
+                .map(|lease_duration| now + lease_duration),
+        );
+        !was_alive
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Liveliness, LivelinessChangedStatus, LivelinessEvent, LivelinessKind, LivelinessMonitor,
+        ParticipantMessageData, ParticipantMessageError, ParticipantMessageKind,
+        ParticipantMessageWriter,
+    };
+    use crate::{
+        behaviour::writer::{ReaderProxy, StatefulWriter},
+        messages::{Message, SubMessage},
+        structure::{
+            history::{Cache, HistoryCache},
+            Guid, Locator,
+        },
+    };
+    use std::{
+        net::{Ipv4Addr, SocketAddrV4},
+        time::{Duration, Instant},
+    };
+    use test_case::test_case;
+
+    type Prefix = [u8; 12];
+    type Id = [u8; 4];
+
+    const LEASE: Duration = Duration::from_secs(1);
+
+    fn liveliness(kind: LivelinessKind) -> Liveliness {
+        Liveliness {
+            kind,
+            lease_duration: Some(LEASE),
+        }
+    }
+
+    #[test]
+    fn participant_message_round_trip() {
+        let message = ParticipantMessageData {
+            participant: [7; 12],
+            kind: ParticipantMessageKind::ManualLivelinessUpdate,
+            data: vec![1, 2, 3],
+        };
+        let bytes = message.to_bytes();
+        assert_eq!(&bytes[16..20], &[0, 0, 0, 2]);
+        assert_eq!(ParticipantMessageData::from_bytes(&bytes).unwrap(), message);
+
+        assert_eq!(
+            ParticipantMessageData::<Prefix>::from_bytes(&bytes[..25]),
+            Err(ParticipantMessageError::Truncated)
+        );
+    }
+
+    #[test]
+    fn lease_expiry_reports_events() {
+        let writer = Guid::new([1; 12], [0, 0, 1, 2]);
+        let mut monitor = LivelinessMonitor::default();
+        monitor.writer_add(writer, liveliness(LivelinessKind::ManualByTopic));
+
+        let start = Instant::now();
+        monitor.update(start + LEASE * 10);
+        assert!(monitor.take_events().is_empty());
+
+        monitor.assert_writer(writer, start);
+        monitor.assert_writer(writer, start + LEASE / 2);
+        assert_eq!(
+            monitor.take_events(),
+            vec![LivelinessEvent::Changed {
+                writer,
+                alive: true,
+                status: LivelinessChangedStatus {
+                    alive_count: 1,
+                    not_alive_count: 0
+                }
+            }]
+        );
+        assert_eq!(monitor.next_deadline(), Some(start + LEASE * 3 / 2));
+
+        monitor.update(start + LEASE);
+        assert!(monitor.is_alive(writer));
+
+        monitor.update(start + LEASE * 3 / 2);
+        assert_eq!(
+            monitor.take_events(),
+            vec![
+                LivelinessEvent::Lost { writer },
+                LivelinessEvent::Changed {
+                    writer,
+                    alive: false,
+                    status: LivelinessChangedStatus {
+                        alive_count: 0,
+                        not_alive_count: 1
+                    }
+                }
+            ]
+        );
+        assert_eq!(monitor.next_deadline(), None);
+    }
+
+    #[test_case(ParticipantMessageKind::AutomaticLivelinessUpdate => vec![true, false, false])]
+    #[test_case(ParticipantMessageKind::ManualLivelinessUpdate => vec![true, true, false])]
+    fn participant_messages_assert_writers_by_kind(kind: ParticipantMessageKind) -> Vec<bool> {
+        let kinds = [
+            LivelinessKind::Automatic,
+            LivelinessKind::ManualByParticipant,
+            LivelinessKind::ManualByTopic,
+        ];
+
+        let mut monitor = LivelinessMonitor::default();
+        for (entity, &kind) in (1..).zip(kinds.iter()) {
+            monitor.writer_add(Guid::new([1; 12], [0, 0, entity, 2]), liveliness(kind));
+        }
+        let other_participant = Guid::new([2; 12], [0, 0, 1, 2]);
+        monitor.writer_add(other_participant, liveliness(LivelinessKind::Automatic));
+
+        monitor.assert_participant([1; 12], kind, Instant::now());
+        assert!(!monitor.is_alive(other_participant));
+
+        (1..=3)
+            .map(|entity| monitor.is_alive(Guid::new([1; 12], [0, 0, entity, 2])))
+            .collect()
+    }
+
+    #[test]
+    fn participant_message_writer_keeps_latest_update() {
+        let guid = Guid::new([1; 12], super::PARTICIPANT_MESSAGE_WRITER);
+        let writer = StatefulWriter::new(guid, HistoryCache::<Vec<u8>, Prefix, Id>::default());
+        let mut writer = ParticipantMessageWriter::new(writer, Some(LEASE));
+
+        let locator: Locator = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 7410).into();
+        writer.writer_mut().matched_reader_add(ReaderProxy::new(
+            Guid::new([2; 12], super::PARTICIPANT_MESSAGE_READER),
+            false,
+            vec![locator],
+            Vec::new(),
+        ));
+
+        let start = Instant::now();
+        let mut output: Vec<(Locator, Message<Prefix, Id>)> = Vec::new();
+        writer.send(start, &mut output).unwrap();
+        assert_eq!(writer.next_deadline(), Some(start + LEASE));
+
+        writer.assert_participant();
+        writer.send(start + LEASE / 2, &mut output).unwrap();
+        writer.send(start + LEASE, &mut output).unwrap();
+
+        // the first automatic update has been replaced
+        let cache = writer.writer().cache();
+        assert_eq!(cache.min_sequence_number(), Some(2));
+        assert_eq!(cache.max_sequence_number(), Some(3));
+
+        let kinds: Vec<_> = output
+            .iter()
+            .flat_map(|(_, message)| message.submessages())
+            .filter_map(|submessage| match submessage {
+                SubMessage::Data(data) => data.payload.as_ref(),
+                _ => None,
+            })
+            .map(|payload| {
+                ParticipantMessageData::<Prefix>::from_bytes(payload.as_bytes())
+                    .unwrap()
+                    .kind
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ParticipantMessageKind::AutomaticLivelinessUpdate,
+                ParticipantMessageKind::ManualLivelinessUpdate,
+                ParticipantMessageKind::AutomaticLivelinessUpdate
+            ]
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Behaviour of the RTPS reader endpoints
+//!
+//! See [section 8.4.10](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF) of the relevant specification document.
+
+use std::convert::TryFrom;
+
+use crate::{
+    messages::submessage::{
+        self,
+        elements::{parameter_id, status_info},
+        Payload,
+    },
+    structure::{
+        history::{Change, Kind},
+        Guid, KeyHash,
+    },
+};
+
+pub mod instance;
+pub mod reassembly;
+pub mod stateful;
+pub mod stateless;
+pub mod writer_proxy;
+
+#[doc(inline)]
+pub use instance::{InstanceState, Instances, SampleInfo, SampleState, ViewState};
+#[doc(inline)]
+pub use reassembly::{ReassemblyBuffer, ReassemblyLimits};
+#[doc(inline)]
+pub use stateful::StatefulReader;
+#[doc(inline)]
+pub use stateless::StatelessReader;
+#[doc(inline)]
+pub use writer_proxy::WriterProxy;
+
+/// The order in which a reader delivers the changes of each instance to its
+/// history cache, when they come from several writers
+///
+/// The changes of each writer are always delivered in the order they were
+/// written. This corresponds to the `DESTINATION_ORDER` quality of service
+/// policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DestinationOrder {
+    /// Changes are delivered in the order they are received, so readers of
+    /// an instance updated by several writers may end up with different
+    /// values
+    #[default]
+    ByReceptionTimestamp,
+
+    /// Changes are ordered by their source timestamp across every writer of
+    /// an instance. A change with an earlier source timestamp than the last
+    /// change delivered for its instance is discarded, so that every reader
+    /// ends up with the same value. Changes with the same source timestamp
+    /// are ordered by the [`Guid`] of their writer.
+    BySourceTimestamp,
+}
+
+/// Construct the [`Change`] communicated by a [`Data`](submessage::Data)
+/// submessage
+///
+/// The instance is identified by the key hash in the inline quality of
+/// service parameters. If there isn't one, the data-object has no key, and
+/// there is only a single instance.
+///
+/// Returns `None` if the submessage carries neither a value nor a change to
+/// the lifecycle of the instance.
+pub(crate) fn change_from_data<Data, P, Id>(
+    writer_guid: Guid<P, Id>,
+    data: submessage::Data<Id>,
+) -> Option<Change<Data, P, Id>>
+where
+    Data: From<Vec<u8>>,
+    P: Copy,
+    Id: Copy,
+{
+    let inline_qos = data.inline_qos.unwrap_or_default();
+
+    let instance = inline_qos
+        .get(parameter_id::KEY_HASH)
+        .and_then(|parameter| <[u8; 16]>::try_from(parameter.value()).ok())
+        .map(KeyHash::new)
+        .unwrap_or_default();
+
+    let flags = inline_qos
+        .get(parameter_id::STATUS_INFO)
+        .and_then(|parameter| parameter.value().last().copied())
+        .unwrap_or_default();
+
+    let kind = if flags & status_info::DISPOSED != 0 {
+        Kind::NotAliveDisposed
+    } else if flags & status_info::UNREGISTERED != 0 {
+        Kind::NotAliveUnregistered
+    } else if flags & status_info::FILTERED != 0 {
+        Kind::AliveFiltered
+    } else if let Some(Payload::Data(bytes)) = data.payload {
+        Kind::Alive(Data::from(bytes))
+    } else {
+        return None;
+    };
+
+    Some(Change::new(kind, writer_guid, instance))
+}
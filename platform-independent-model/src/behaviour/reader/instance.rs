@@ -0,0 +1,362 @@
+//! Tracks the lifecycle of data-object instances on the reader side
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::structure::{
+    history::{Change, Kind},
+    Guid, KeyHash,
+};
+
+/// Whether any writers are alive, and whether the instance has been disposed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstanceState {
+    /// At least one writer is registered with the instance, and it has not
+    /// been disposed
+    Alive,
+
+    /// A writer has disposed of the instance
+    NotAliveDisposed,
+
+    /// Every writer which was registered with the instance has unregistered
+    /// it (or been lost), without disposing of it
+    NotAliveNoWriters,
+}
+
+/// Whether the application has seen the current incarnation of an instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewState {
+    /// This is the first time the application has seen the instance, or the
+    /// instance has come back to life since it was last viewed
+    New,
+
+    /// The application has already seen the instance
+    NotNew,
+}
+
+/// Whether the application has read a sample
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleState {
+    /// The sample has been read
+    Read,
+
+    /// The sample has not been read
+    NotRead,
+}
+
+/// The state of an instance, at the time a sample was received
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleInfo {
+    /// The state of the sample
+    pub sample_state: SampleState,
+
+    /// The view state of the instance
+    pub view_state: ViewState,
+
+    /// The state of the instance
+    pub instance_state: InstanceState,
+
+    /// The number of times the instance has become alive again after being
+    /// disposed
+    pub disposed_generation_count: u32,
+
+    /// The number of times the instance has become alive again after losing
+    /// all of its writers
+    pub no_writers_generation_count: u32,
+
+    /// Whether the sample contains a value. This is `false` for samples which
+    /// only communicate a change in the instance's lifecycle.
+    pub valid_data: bool,
+}
+
+impl SampleInfo {
+    /// Mark the sample as read
+    pub fn mark_read(&mut self) {
+        self.sample_state = SampleState::Read;
+    }
+}
+
+/// The lifecycle of a single instance
+#[derive(Debug, Clone)]
+struct Instance<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    state: InstanceState,
+    view_state: ViewState,
+    disposed_generation_count: u32,
+    no_writers_generation_count: u32,
+    writers: BTreeSet<Guid<P, Id>>,
+}
+
+impl<P, Id> Instance<P, Id>
+where
+    P: Copy + Ord,
+    Id: Copy + Ord,
+{
+    fn new() -> Self {
+        Self {
+            state: InstanceState::Alive,
+            view_state: ViewState::New,
+            disposed_generation_count: 0,
+            no_writers_generation_count: 0,
+            writers: BTreeSet::default(),
+        }
+    }
+
+    fn become_alive(&mut self) {
+        match self.state {
+            InstanceState::Alive => return,
+            InstanceState::NotAliveDisposed => self.disposed_generation_count += 1,
+            InstanceState::NotAliveNoWriters => self.no_writers_generation_count += 1,
+        }
+        self.state = InstanceState::Alive;
+        self.view_state = ViewState::New;
+    }
+
+    fn unregister(&mut self, writer: Guid<P, Id>) {
+        self.writers.remove(&writer);
+        if self.writers.is_empty() && self.state == InstanceState::Alive {
+            self.state = InstanceState::NotAliveNoWriters;
+        }
+    }
+
+    fn sample_info(&self, valid_data: bool) -> SampleInfo {
+        SampleInfo {
+            sample_state: SampleState::NotRead,
+            view_state: self.view_state,
+            instance_state: self.state,
+            disposed_generation_count: self.disposed_generation_count,
+            no_writers_generation_count: self.no_writers_generation_count,
+            valid_data,
+        }
+    }
+}
+
+/// Tracks the state of each data-object instance known to a reader, as
+/// [`Change`]s are received from writers.
+///
+/// Each change updates the state of its instance according to its [`Kind`],
+/// and produces a [`SampleInfo`] which describes the instance at the time
+/// the change was received. This allows an application to distinguish a new
+/// instance from one which has been disposed, or which has lost all of its
+/// writers.
+///
+/// For details, see section 2.2.2.5.1 of the DDS specification.
+///
+/// # Example
+///
+/// ```
+/// use rtps_pim::{
+///     behaviour::reader::{InstanceState, Instances, ViewState},
+///     structure::{
+///         history::{Change, Kind},
+///         Guid, KeyHash,
+///     },
+/// };
+///
+/// let writer = Guid::new([0; 12], [0, 0, 1, 2]);
+/// let instance = KeyHash::new([1; 16]);
+///
+/// let mut instances = Instances::default();
+///
+/// let info = instances.update(&Change::new(Kind::Alive(42), writer, instance));
+/// assert_eq!(info.view_state, ViewState::New);
+/// assert_eq!(info.instance_state, InstanceState::Alive);
+///
+/// let info = instances.update(&Change::<i32, _, _>::new(Kind::NotAliveDisposed, writer, instance));
+/// assert_eq!(info.instance_state, InstanceState::NotAliveDisposed);
+/// ```
+#[derive(Debug)]
+pub struct Instances<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    instances: BTreeMap<KeyHash, Instance<P, Id>>,
+}
+
+impl<P, Id> Default for Instances<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    fn default() -> Self {
+        Self {
+            instances: BTreeMap::default(),
+        }
+    }
+}
+
+impl<P, Id> Instances<P, Id>
+where
+    P: Copy + Ord,
+    Id: Copy + Ord,
+{
+    /// Update the state of an instance with a received [`Change`]
+    ///
+    /// Returns the [`SampleInfo`] for the change.
+    pub fn update<Data>(&mut self, change: &Change<Data, P, Id>) -> SampleInfo {
+        let writer = change.writer_guid();
+        let instance = self
+            .instances
+            .entry(change.instance())
+            .or_insert_with(Instance::new);
+
+        match change.kind() {
+            Kind::Alive(_) | Kind::AliveFiltered => {
+                instance.become_alive();
+                instance.writers.insert(writer);
+            }
+            Kind::NotAliveDisposed => {
+                instance.writers.insert(writer);
+                instance.state = InstanceState::NotAliveDisposed;
+            }
+            Kind::NotAliveUnregistered => instance.unregister(writer),
+        }
+
+        instance.sample_info(matches!(change.kind(), Kind::Alive(_)))
+    }
+
+    /// Unregister a writer from every instance, for example because it has
+    /// been lost or removed.
+    ///
+    /// Returns the instances which lost their last writer as a result.
+    pub fn remove_writer(&mut self, writer: Guid<P, Id>) -> Vec<KeyHash> {
+        let mut lost = Vec::new();
+
+        for (&key, instance) in &mut self.instances {
+            if instance.writers.contains(&writer) {
+                instance.unregister(writer);
+                if instance.state == InstanceState::NotAliveNoWriters {
+                    lost.push(key);
+                }
+            }
+        }
+
+        lost
+    }
+
+    /// Record that the application has viewed the instance, so that
+    /// subsequent samples have a [`ViewState::NotNew`] view state
+    pub fn mark_viewed(&mut self, instance: KeyHash) {
+        if let Some(instance) = self.instances.get_mut(&instance) {
+            instance.view_state = ViewState::NotNew;
+        }
+    }
+
+    /// The current state of an instance, or `None` if it is unknown
+    #[must_use]
+    pub fn instance_state(&self, instance: KeyHash) -> Option<InstanceState> {
+        self.instances.get(&instance).map(|instance| instance.state)
+    }
+
+    /// The current view state of an instance, or `None` if it is unknown
+    #[must_use]
+    pub fn view_state(&self, instance: KeyHash) -> Option<ViewState> {
+        self.instances
+            .get(&instance)
+            .map(|instance| instance.view_state)
+    }
+
+    /// Stop tracking an instance, once the application no longer holds any of
+    /// its samples
+    pub fn remove(&mut self, instance: KeyHash) {
+        self.instances.remove(&instance);
+    }
+
+    /// The number of instances being tracked
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    /// Returns `true` if no instances are being tracked
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InstanceState, Instances, SampleState, ViewState};
+    use crate::structure::{
+        history::{Change, Kind},
+        Guid, KeyHash,
+    };
+
+    type Prefix = [u8; 12];
+    type Id = [u8; 4];
+
+    const INSTANCE: KeyHash = KeyHash::new([1; 16]);
+
+    fn change(writer: u8, kind: Kind<u32>) -> Change<u32, Prefix, Id> {
+        Change::new(kind, Guid::new([writer; 12], [0, 0, 1, 2]), INSTANCE)
+    }
+
+    #[test]
+    fn disposed_instance_comes_back_to_life() {
+        let mut instances = Instances::default();
+
+        let info = instances.update(&change(1, Kind::Alive(1)));
+        assert_eq!(info.view_state, ViewState::New);
+        assert_eq!(info.sample_state, SampleState::NotRead);
+        assert!(info.valid_data);
+
+        instances.mark_viewed(INSTANCE);
+        let info = instances.update(&change(1, Kind::Alive(2)));
+        assert_eq!(info.view_state, ViewState::NotNew);
+
+        let info = instances.update(&change(1, Kind::NotAliveDisposed));
+        assert_eq!(info.instance_state, InstanceState::NotAliveDisposed);
+        assert!(!info.valid_data);
+
+        let info = instances.update(&change(1, Kind::Alive(3)));
+        assert_eq!(info.instance_state, InstanceState::Alive);
+        assert_eq!(info.view_state, ViewState::New);
+        assert_eq!(info.disposed_generation_count, 1);
+        assert_eq!(info.no_writers_generation_count, 0);
+    }
+
+    #[test]
+    fn no_writers_once_every_writer_unregisters() {
+        let mut instances = Instances::default();
+
+        instances.update(&change(1, Kind::Alive(1)));
+        instances.update(&change(2, Kind::Alive(2)));
+
+        let info = instances.update(&change(1, Kind::NotAliveUnregistered));
+        assert_eq!(info.instance_state, InstanceState::Alive);
+
+        let info = instances.update(&change(2, Kind::NotAliveUnregistered));
+        assert_eq!(info.instance_state, InstanceState::NotAliveNoWriters);
+
+        let info = instances.update(&change(1, Kind::AliveFiltered));
+        assert_eq!(info.instance_state, InstanceState::Alive);
+        assert_eq!(info.no_writers_generation_count, 1);
+        assert!(!info.valid_data);
+    }
+
+    #[test]
+    fn removing_a_writer() {
+        let mut instances = Instances::default();
+        instances.update(&change(1, Kind::Alive(1)));
+
+        let lost = instances.remove_writer(Guid::new([1; 12], [0, 0, 1, 2]));
+        assert_eq!(lost, vec![INSTANCE]);
+        assert_eq!(
+            instances.instance_state(INSTANCE),
+            Some(InstanceState::NotAliveNoWriters)
+        );
+    }
+
+    #[test]
+    fn disposed_instance_does_not_lose_writers() {
+        let mut instances = Instances::default();
+        instances.update(&change(1, Kind::NotAliveDisposed));
+
+        let info = instances.update(&change(1, Kind::NotAliveUnregistered));
+        assert_eq!(info.instance_state, InstanceState::NotAliveDisposed);
+    }
+}
@@ -0,0 +1,689 @@
+//! Contains the [`ReassemblyBuffer`], which reassembles changes sent as
+//! `DATA_FRAG`s
+
+use std::{collections::BTreeMap, convert::TryFrom, num::NonZeroU32};
+
+use crate::{
+    messages::submessage::{
+        elements::{FragmentNumberSet, ParameterList},
+        Data, DataFrag, Payload,
+    },
+    structure::Guid,
+};
+
+/// The largest range of fragment numbers which can be requested by a single
+/// `NACK_FRAG`
+const MAX_SET_RANGE: u32 = 256;
+
+/// Limits on the memory used to reassemble fragmented changes, from each
+/// writer and in total
+///
+/// A value of `None` means that the resource is unlimited. The total limits
+/// also bound the memory used by readers which accept changes from any
+/// number of writers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReassemblyLimits {
+    /// The maximum number of partially received changes from any single
+    /// writer
+    pub max_samples_per_writer: Option<usize>,
+
+    /// The maximum total size of the partially received changes from any
+    /// single writer, in bytes
+    pub max_bytes_per_writer: Option<usize>,
+
+    /// The maximum number of partially received changes, across all writers
+    pub max_samples: Option<usize>,
+
+    /// The maximum total size of the partially received changes, across all
+    /// writers, in bytes
+    pub max_bytes: Option<usize>,
+}
+
+impl ReassemblyLimits {
+    /// The size of the largest change which can be reassembled, if limited
+    fn max_sample_size(self) -> Option<usize> {
+        [self.max_bytes_per_writer, self.max_bytes]
+            .iter()
+            .flatten()
+            .copied()
+            .min()
+    }
+}
+
+impl Default for ReassemblyLimits {
+    /// At most 32 changes, and 64 MiB, per writer, and at most 256 changes,
+    /// and 256 MiB, in total
+    fn default() -> Self {
+        Self {
+            max_samples_per_writer: Some(32),
+            max_bytes_per_writer: Some(64 * 1024 * 1024),
+            max_samples: Some(256),
+            max_bytes: Some(256 * 1024 * 1024),
+        }
+    }
+}
+
+/// Reassembles changes which are sent as `DATA_FRAG`s
+///
+/// Partially received changes are keyed by the [`Guid`] of the writer and
+/// the sequence number of the change. Fragments may arrive in any order, and
+/// duplicate or overlapping fragments are tolerated. Once every fragment of
+/// a change has been received, the change is returned as a complete
+/// [`Data`] submessage.
+///
+/// The memory used for each writer, and in total, is bounded by the
+/// [`ReassemblyLimits`]. When a new change would exceed the limits of its
+/// writer, the oldest partially received changes from that writer are evicted
+/// to make room. When it would exceed the total limits, the changes which
+/// were started first are evicted, whichever writer they are from.
+///
+/// For details, see the [specification, section 8.4.14.1](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+#[derive(Debug, Clone)]
+pub struct ReassemblyBuffer<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    limits: ReassemblyLimits,
+    writers: BTreeMap<Guid<P, Id>, BTreeMap<u64, Partial<Id>>>,
+    started: u64,
+}
+
+/// A partially received change
+#[derive(Debug, Clone)]
+struct Partial<Id> {
+    started: u64,
+    reader: Option<Id>,
+    inline_qos: Option<ParameterList>,
+    key: bool,
+    non_standard_payload: bool,
+    fragment_size: u16,
+    bytes: Vec<u8>,
+    received: Vec<bool>,
+    remaining: u32,
+    last_available: Option<u32>,
+}
+
+impl<Id> Partial<Id> {
+    fn total_fragments(&self) -> u32 {
+        u32::try_from(self.received.len()).unwrap_or(u32::MAX)
+    }
+
+    fn is_received(&self, fragment_number: u32) -> bool {
+        self.received[fragment_number as usize - 1]
+    }
+}
+
+impl<P, Id> Default for ReassemblyBuffer<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    fn default() -> Self {
+        Self::new(ReassemblyLimits::default())
+    }
+}
+
+impl<P, Id> ReassemblyBuffer<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    /// Construct a new, empty [`ReassemblyBuffer`] with the given limits
+    #[must_use]
+    pub fn new(limits: ReassemblyLimits) -> Self {
+        Self {
+            limits,
+            writers: BTreeMap::default(),
+            started: 0,
+        }
+    }
+
+    /// The [`ReassemblyLimits`] of the buffer
+    #[must_use]
+    pub fn limits(&self) -> ReassemblyLimits {
+        self.limits
+    }
+}
+
+impl<P, Id> ReassemblyBuffer<P, Id>
+where
+    P: Copy + Ord,
+    Id: Copy + Ord,
+{
+    /// Add the fragments in a `DATA_FRAG` received from the given writer
+    ///
+    /// Returns the complete change if this was the last missing fragment.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the `DATA_FRAG` is inconsistent with itself or
+    /// with fragments already received, or if the change is larger than the
+    /// configured limits. The fragments are then discarded.
+    pub fn insert(
+        &mut self,
+        writer_guid: Guid<P, Id>,
+        data_frag: DataFrag<Id>,
+    ) -> Result<Option<Data<Id>>, ReassemblyError> {
+        let fragment_size = usize::from(data_frag.fragment_size);
+        let sample_size = data_frag.sample_size as usize;
+        let first = data_frag.fragment_starting_number.get();
+        let count = data_frag.fragments_in_submessage();
+        let total = data_frag.total_fragments();
+
+        if fragment_size == 0
+            || count == 0
+            || u64::from(first) - 1 + u64::from(count) > u64::from(total)
+        {
+            return Err(ReassemblyError::Inconsistent);
+        }
+
+        // only the last fragment of the change may be shorter
+        let offset = (first as usize - 1) * fragment_size;
+        let end = offset + data_frag.fragments.len();
+        if end > sample_size
+            || (end < sample_size && data_frag.fragments.len() % fragment_size != 0)
+        {
+            return Err(ReassemblyError::Inconsistent);
+        }
+
+        if let Some(limit) = self.limits.max_sample_size() {
+            if sample_size > limit {
+                return Err(ReassemblyError::TooLarge {
+                    size: sample_size,
+                    limit,
+                });
+            }
+        }
+
+        let sequence_number = data_frag.writer_sequence_number;
+
+        if !self.contains(writer_guid, sequence_number) {
+            if let Some(samples) = self.writers.get_mut(&writer_guid) {
+                evict(samples, self.limits, sample_size);
+            }
+            self.evict_first_started(sample_size);
+
+            self.started += 1;
+            self.writers.entry(writer_guid).or_default().insert(
+                sequence_number,
+                Partial {
+                    started: self.started,
+                    reader: data_frag.reader,
+                    inline_qos: None,
+                    key: data_frag.key,
+                    non_standard_payload: data_frag.non_standard_payload,
+                    fragment_size: data_frag.fragment_size,
+                    bytes: vec![0; sample_size],
+                    received: vec![false; total as usize],
+                    remaining: total,
+                    last_available: None,
+                },
+            );
+        }
+
+        let samples = self
+            .writers
+            .get_mut(&writer_guid)
+            .ok_or(ReassemblyError::Inconsistent)?;
+        let partial = samples
+            .get_mut(&sequence_number)
+            .ok_or(ReassemblyError::Inconsistent)?;
+
+        if partial.fragment_size != data_frag.fragment_size || partial.bytes.len() != sample_size {
+            return Err(ReassemblyError::Inconsistent);
+        }
+
+        partial.bytes[offset..end].copy_from_slice(&data_frag.fragments);
+        for fragment_number in first..first + count {
+            let received = &mut partial.received[fragment_number as usize - 1];
+            if !*received {
+                *received = true;
+                partial.remaining -= 1;
+            }
+        }
+        if partial.inline_qos.is_none() {
+            partial.inline_qos = data_frag.inline_qos;
+        }
+
+        if partial.remaining > 0 {
+            return Ok(None);
+        }
+
+        let partial = samples
+            .remove(&sequence_number)
+            .ok_or(ReassemblyError::Inconsistent)?;
+        if samples.is_empty() {
+            self.writers.remove(&writer_guid);
+        }
+
+        let payload = if partial.key {
+            Payload::Key(partial.bytes)
+        } else {
+            Payload::Data(partial.bytes)
+        };
+
+        Ok(Some(Data {
+            reader: partial.reader,
+            writer: writer_guid.entity_id(),
+            writer_sequence_number: sequence_number,
+            inline_qos: partial.inline_qos,
+            payload: Some(payload),
+            non_standard_payload: partial.non_standard_payload,
+        }))
+    }
+
+    /// Record that the writer has made the fragments of a change available up
+    /// to and including `last_fragment_number`, as announced by a
+    /// `HEARTBEAT_FRAG`
+    ///
+    /// This has no effect if none of the fragments of the change have been
+    /// received.
+    pub fn fragments_available(
+        &mut self,
+        writer_guid: Guid<P, Id>,
+        sequence_number: u64,
+        last_fragment_number: u32,
+    ) {
+        if let Some(partial) = self
+            .writers
+            .get_mut(&writer_guid)
+            .and_then(|samples| samples.get_mut(&sequence_number))
+        {
+            partial.last_available = Some(
+                partial
+                    .last_available
+                    .map_or(last_fragment_number, |last| last.max(last_fragment_number)),
+            );
+        }
+    }
+
+    /// The fragments of a partially received change which are still missing,
+    /// as requested by a `NACK_FRAG`
+    ///
+    /// Only fragments which the writer has made available are included. Since
+    /// a [`FragmentNumberSet`] spans at most 256 fragments, the set starts at
+    /// the first missing fragment. Returns `None` if nothing is missing, or the
+    /// change isn't partially received.
+    #[must_use]
+    pub fn missing_fragments(
+        &self,
+        writer_guid: Guid<P, Id>,
+        sequence_number: u64,
+    ) -> Option<FragmentNumberSet> {
+        let partial = self.writers.get(&writer_guid)?.get(&sequence_number)?;
+        let last = partial
+            .last_available
+            .unwrap_or(u32::MAX)
+            .min(partial.total_fragments());
+
+        let mut missing = (1..=last).filter(|&n| !partial.is_received(n));
+        let base = missing.next()?;
+
+        let mut set = FragmentNumberSet::new(NonZeroU32::new(base)?);
+        let _ = set.insert_value(base);
+        for fragment_number in missing.take_while(|&n| n < base + MAX_SET_RANGE) {
+            let _ = set.insert_value(fragment_number);
+        }
+
+        Some(set)
+    }
+
+    /// Returns `true` if some, but not all, of the fragments of the change
+    /// have been received
+    #[must_use]
+    pub fn contains(&self, writer_guid: Guid<P, Id>, sequence_number: u64) -> bool {
+        self.writers
+            .get(&writer_guid)
+            .map_or(false, |samples| samples.contains_key(&sequence_number))
+    }
+
+    /// The sequence numbers of the partially received changes from the given
+    /// writer, in order
+    pub fn sequence_numbers(&self, writer_guid: Guid<P, Id>) -> impl Iterator<Item = u64> + '_ {
+        self.writers
+            .get(&writer_guid)
+            .into_iter()
+            .flat_map(BTreeMap::keys)
+            .copied()
+    }
+
+    /// The total size of the partially received changes from the given
+    /// writer, in bytes
+    #[must_use]
+    pub fn bytes(&self, writer_guid: Guid<P, Id>) -> usize {
+        self.writers
+            .get(&writer_guid)
+            .map_or(0, |samples| bytes(samples))
+    }
+
+    /// Discard a partially received change
+    pub fn remove(&mut self, writer_guid: Guid<P, Id>, sequence_number: u64) {
+        self.retain(writer_guid, |n| n != sequence_number);
+    }
+
+    /// Discard the partially received changes from the given writer with a
+    /// sequence number no higher than `sequence_number`, for example because
+    /// they are no longer relevant
+    pub fn remove_up_to(&mut self, writer_guid: Guid<P, Id>, sequence_number: u64) {
+        self.retain(writer_guid, |n| n > sequence_number);
+    }
+
+    /// Discard every partially received change from the given writer
+    pub fn remove_writer(&mut self, writer_guid: Guid<P, Id>) {
+        self.writers.remove(&writer_guid);
+    }
+
+    /// The number of partially received changes, across all writers
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.writers.values().map(BTreeMap::len).sum()
+    }
+
+    /// Returns `true` if there are no partially received changes
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.writers.is_empty()
+    }
+
+    /// Evict the changes which were started first, from any writer, until
+    /// there is room for a new change of the given size within the total
+    /// limits
+    fn evict_first_started(&mut self, size: usize) {
+        let limits = self.limits;
+        loop {
+            let too_many = limits
+                .max_samples
+                .map_or(false, |limit| self.len() >= limit);
+            let too_large = limits.max_bytes.map_or(false, |limit| {
+                self.writers.values().map(bytes).sum::<usize>() + size > limit
+            });
+            if !too_many && !too_large {
+                return;
+            }
+
+            let first_started = self
+                .writers
+                .iter()
+                .flat_map(|(&writer_guid, samples)| {
+                    samples.iter().map(move |(&sequence_number, partial)| {
+                        (partial.started, writer_guid, sequence_number)
+                    })
+                })
+                .min();
+            match first_started {
+                Some((_, writer_guid, sequence_number)) => {
+                    self.remove(writer_guid, sequence_number);
+                }
+                None => return,
+            }
+        }
+    }
+
+    fn retain(&mut self, writer_guid: Guid<P, Id>, mut f: impl FnMut(u64) -> bool) {
+        if let Some(samples) = self.writers.get_mut(&writer_guid) {
+            samples.retain(|&n, _| f(n));
+            if samples.is_empty() {
+                self.writers.remove(&writer_guid);
+            }
+        }
+    }
+}
+
+fn bytes<Id>(samples: &BTreeMap<u64, Partial<Id>>) -> usize {
+    samples.values().map(|partial| partial.bytes.len()).sum()
+}
+
+/// Evict the oldest changes until there is room for a new one of the given
+/// size
+fn evict<Id>(samples: &mut BTreeMap<u64, Partial<Id>>, limits: ReassemblyLimits, size: usize) {
+    let too_many = |samples: &BTreeMap<u64, Partial<Id>>| {
+        limits
+            .max_samples_per_writer
+            .map_or(false, |limit| samples.len() >= limit)
+    };
+    let too_large = |samples: &BTreeMap<u64, Partial<Id>>| {
+        limits
+            .max_bytes_per_writer
+            .map_or(false, |limit| bytes(samples) + size > limit)
+    };
+
+    while !samples.is_empty() && (too_many(samples) || too_large(samples)) {
+        let oldest = samples.keys().next().copied();
+        if let Some(oldest) = oldest {
+            samples.remove(&oldest);
+        }
+    }
+}
+
+/// The error returned when the fragments of a change can't be reassembled
+#[derive(Debug, thiserror::Error)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum ReassemblyError {
+    /// The `DATA_FRAG` doesn't describe a valid fragment, or disagrees with
+    /// fragments of the same change which were already received
+    #[error("the fragment is inconsistent with the change")]
+    Inconsistent,
+
+    /// The change is larger than the memory available for the writer, or in
+    /// total
+    #[error("change of {size} bytes exceeds the limit of {limit} bytes")]
+    TooLarge {
+        /// The size of the change, in bytes
+        size: usize,
+
+        /// The configured limit
+        limit: usize,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReassemblyBuffer, ReassemblyError, ReassemblyLimits};
+    use crate::{
+        messages::submessage::{DataFrag, Payload},
+        structure::Guid,
+    };
+    use std::num::NonZeroU32;
+
+    type Prefix = [u8; 12];
+    type Id = [u8; 4];
+
+    const FRAGMENT_SIZE: u16 = 4;
+
+    fn writer() -> Guid<Prefix, Id> {
+        Guid::new([2; 12], [0, 0, 1, 2])
+    }
+
+    fn sample() -> Vec<u8> {
+        (0..10).collect()
+    }
+
+    /// A `DATA_FRAG` carrying `count` fragments of [`sample`], starting at
+    /// `first`
+    fn data_frag(sequence_number: u64, first: u32, count: u32) -> DataFrag<Id> {
+        let sample = sample();
+        let start = (first as usize - 1) * usize::from(FRAGMENT_SIZE);
+        let end = (start + count as usize * usize::from(FRAGMENT_SIZE)).min(sample.len());
+
+        DataFrag {
+            reader: None,
+            writer: writer().entity_id(),
+            writer_sequence_number: sequence_number,
+            fragment_starting_number: NonZeroU32::new(first).unwrap(),
+            fragment_size: FRAGMENT_SIZE,
+            sample_size: 10,
+            inline_qos: None,
+            key: false,
+            non_standard_payload: false,
+            fragments: sample[start..end].to_vec(),
+        }
+    }
+
+    #[test]
+    fn reassembles_out_of_order_and_duplicate_fragments() {
+        let mut buffer = ReassemblyBuffer::default();
+
+        assert_eq!(buffer.insert(writer(), data_frag(1, 3, 1)), Ok(None));
+        assert_eq!(buffer.insert(writer(), data_frag(1, 3, 1)), Ok(None));
+        assert_eq!(buffer.insert(writer(), data_frag(1, 1, 1)), Ok(None));
+        assert!(buffer.contains(writer(), 1));
+
+        let missing = buffer.missing_fragments(writer(), 1).unwrap();
+        assert_eq!(missing.values().collect::<Vec<_>>(), vec![2]);
+
+        // overlaps the fragment which was already received
+        let data = buffer
+            .insert(writer(), data_frag(1, 1, 2))
+            .unwrap()
+            .unwrap();
+        assert_eq!(data.writer_sequence_number, 1);
+        assert_eq!(data.payload, Some(Payload::Data(sample())));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn missing_fragments_are_limited_to_those_available() {
+        let mut buffer = ReassemblyBuffer::default();
+
+        buffer.insert(writer(), data_frag(1, 2, 1)).unwrap();
+        assert_eq!(
+            buffer
+                .missing_fragments(writer(), 1)
+                .unwrap()
+                .values()
+                .collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+
+        buffer.fragments_available(writer(), 1, 2);
+        assert_eq!(
+            buffer
+                .missing_fragments(writer(), 1)
+                .unwrap()
+                .values()
+                .collect::<Vec<_>>(),
+            vec![1]
+        );
+
+        assert!(buffer.missing_fragments(writer(), 2).is_none());
+    }
+
+    #[test]
+    fn evicts_the_oldest_changes() {
+        let mut buffer = ReassemblyBuffer::new(ReassemblyLimits {
+            max_samples_per_writer: Some(2),
+            max_bytes_per_writer: Some(25),
+            ..ReassemblyLimits::default()
+        });
+
+        for sequence_number in 1..=3 {
+            buffer
+                .insert(writer(), data_frag(sequence_number, 1, 1))
+                .unwrap();
+        }
+        assert_eq!(
+            buffer.sequence_numbers(writer()).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert_eq!(buffer.bytes(writer()), 20);
+
+        let mut buffer = ReassemblyBuffer::new(ReassemblyLimits {
+            max_samples_per_writer: None,
+            max_bytes_per_writer: Some(25),
+            ..ReassemblyLimits::default()
+        });
+        for sequence_number in 1..=3 {
+            buffer
+                .insert(writer(), data_frag(sequence_number, 1, 1))
+                .unwrap();
+        }
+        assert_eq!(
+            buffer.sequence_numbers(writer()).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+
+        buffer.remove_up_to(writer(), 2);
+        assert_eq!(
+            buffer.sequence_numbers(writer()).collect::<Vec<_>>(),
+            vec![3]
+        );
+    }
+
+    #[test]
+    fn limits_the_changes_of_all_writers() {
+        let mut buffer = ReassemblyBuffer::new(ReassemblyLimits {
+            max_samples_per_writer: None,
+            max_bytes_per_writer: None,
+            max_samples: Some(3),
+            max_bytes: Some(25),
+        });
+        let writers: Vec<_> = (0..3).map(|n| Guid::new([n; 12], [0, 0, 1, 2])).collect();
+
+        // a change from each writer in turn, until the oldest are evicted
+        for (sequence_number, &writer) in (1..).zip(writers.iter().cycle().take(4)) {
+            buffer
+                .insert(writer, data_frag(sequence_number, 1, 1))
+                .unwrap();
+        }
+        assert_eq!(buffer.len(), 2);
+        assert!(!buffer.contains(writers[0], 1));
+        assert!(!buffer.contains(writers[1], 2));
+        assert!(buffer.contains(writers[2], 3));
+        assert!(buffer.contains(writers[0], 4));
+
+        let mut fragment = data_frag(5, 1, 1);
+        fragment.sample_size = u32::MAX;
+        assert_eq!(
+            buffer.insert(writers[1], fragment),
+            Err(ReassemblyError::TooLarge {
+                size: u32::MAX as usize,
+                limit: 25
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_fragments() {
+        let mut buffer = ReassemblyBuffer::new(ReassemblyLimits {
+            max_samples_per_writer: None,
+            max_bytes_per_writer: Some(8),
+            ..ReassemblyLimits::default()
+        });
+        assert_eq!(
+            buffer.insert(writer(), data_frag(1, 1, 1)),
+            Err(ReassemblyError::TooLarge { size: 10, limit: 8 })
+        );
+
+        // a sample size close to the largest u32 doesn't overflow
+        let mut fragment = data_frag(1, 1, 1);
+        fragment.sample_size = u32::MAX;
+        assert_eq!(
+            buffer.insert(writer(), fragment),
+            Err(ReassemblyError::TooLarge {
+                size: u32::MAX as usize,
+                limit: 8
+            })
+        );
+
+        let mut buffer = ReassemblyBuffer::default();
+
+        // beyond the end of the change
+        let mut fragment = data_frag(1, 3, 1);
+        fragment.fragment_starting_number = NonZeroU32::new(4).unwrap();
+        assert_eq!(
+            buffer.insert(writer(), fragment),
+            Err(ReassemblyError::Inconsistent)
+        );
+
+        // disagrees with the fragment size already received
+        buffer.insert(writer(), data_frag(1, 1, 1)).unwrap();
+        let mut fragment = data_frag(1, 2, 1);
+        fragment.fragment_size = 2;
+        fragment.fragments.truncate(2);
+        assert_eq!(
+            buffer.insert(writer(), fragment),
+            Err(ReassemblyError::Inconsistent)
+        );
+    }
+}
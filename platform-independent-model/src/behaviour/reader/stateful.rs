@@ -0,0 +1,1034 @@
+//! Contains the reliable [`StatefulReader`] and its [`Builder`]
+
+use std::{
+    collections::BTreeMap,
+    num::NonZeroU64,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+
+use super::{change_from_data, DestinationOrder, ReassemblyBuffer, ReassemblyLimits, WriterProxy};
+use crate::{
+    behaviour::{
+        liveliness::LivelinessMonitor,
+        receiver::{Context, ReaderSubMessage},
+        writer::{Output, DEFAULT_MAX_MESSAGE_SIZE},
+    },
+    messages::{
+        builder::TooLargeError,
+        submessage::{self, elements::SequenceNumberSet, AckNack, NackFrag},
+        Header, MessageBuilder,
+    },
+    structure::{history::Cache, Guid, KeyHash, ProtocolVersion, VendorId},
+};
+
+/// The default delay before responding to a `HEARTBEAT`
+pub const DEFAULT_HEARTBEAT_RESPONSE_DELAY: Duration = Duration::from_millis(500);
+
+/// The default duration for which `HEARTBEAT`s are ignored after one is
+/// processed
+pub const DEFAULT_HEARTBEAT_SUPPRESSION_DURATION: Duration = Duration::from_secs(0);
+
+/// The largest range of sequence numbers which can be requested by a single
+/// `ACKNACK`
+const MAX_SET_RANGE: u64 = 256;
+
+/// The source timestamp and writer of the newest change delivered for each
+/// instance
+type Latest<P, Id> = BTreeMap<KeyHash, (DateTime<Utc>, Guid<P, Id>)>;
+
+/// A reliable reader which keeps track of the state of each matched remote
+/// writer using a [`WriterProxy`].
+///
+/// Changes are only accepted from matched writers. `HEARTBEAT`s tell the
+/// reader which changes the writer has available, and the reader responds
+/// with an `ACKNACK` requesting any which are missing after the heartbeat
+/// response delay. `GAP`s mark changes as irrelevant, so that they are no
+/// longer requested.
+///
+/// Changes sent as `DATA_FRAG`s are reassembled in a [`ReassemblyBuffer`].
+/// Missing fragments of a partially received change are requested with a
+/// `NACK_FRAG` alongside the `ACKNACK`, rather than requesting the whole
+/// change again.
+///
+/// The changes of each writer are delivered to the history [`Cache`] in the
+/// order they were written, so a change is held until every earlier change
+/// has been received or is known to be irrelevant. Changes to an instance
+/// from different writers are ordered according to the [`DestinationOrder`]
+/// of the reader.
+///
+/// The reader doesn't own any sockets or timers. The current time is passed
+/// to each method which depends on it, so the reader can be driven by any
+/// clock, and messages are produced through an [`Output`].
+///
+/// For details, see the [specification, section 8.4.12](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+#[derive(Debug)]
+pub struct StatefulReader<C, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    guid: Guid<P, Id>,
+    cache: C,
+    header: Header<P>,
+    max_message_size: usize,
+    destination_order: DestinationOrder,
+    heartbeat_response_delay: Duration,
+    heartbeat_suppression_duration: Duration,
+    matched_writers: Vec<WriterProxy<P, Id>>,
+    latest: Latest<P, Id>,
+    reassembly: ReassemblyBuffer<P, Id>,
+    liveliness: LivelinessMonitor<P, Id>,
+}
+
+/// A builder for a [`StatefulReader`]
+///
+/// See the [`StatefulReader`] docs for details
+#[derive(Debug)]
+#[must_use]
+pub struct Builder<C, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    guid: Guid<P, Id>,
+    cache: C,
+    protocol_version: Option<ProtocolVersion>,
+    vendor_id: Option<VendorId>,
+    max_message_size: Option<usize>,
+    destination_order: Option<DestinationOrder>,
+    heartbeat_response_delay: Option<Duration>,
+    heartbeat_suppression_duration: Option<Duration>,
+    reassembly_limits: Option<ReassemblyLimits>,
+}
+
+impl<C, P, Id> Builder<C, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    fn new(guid: Guid<P, Id>, cache: C) -> Self {
+        Self {
+            guid,
+            cache,
+            protocol_version: None,
+            vendor_id: None,
+            max_message_size: None,
+            destination_order: None,
+            heartbeat_response_delay: None,
+            heartbeat_suppression_duration: None,
+            reassembly_limits: None,
+        }
+    }
+
+    /// Set the protocol version used in the [`Header`] of each message
+    pub fn protocol_version(mut self, major: u16, minor: u16) -> Self {
+        self.protocol_version = Some(ProtocolVersion::Specified { major, minor });
+        self
+    }
+
+    /// Set the vendor ID used in the [`Header`] of each message
+    pub fn vendor_id(mut self, id: [u8; 2]) -> Self {
+        self.vendor_id = Some(VendorId::Known(id));
+        self
+    }
+
+    /// Set the maximum size of each message, in bytes
+    ///
+    /// Defaults to [`DEFAULT_MAX_MESSAGE_SIZE`].
+    pub fn max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = Some(max_message_size);
+        self
+    }
+
+    /// Set the order in which changes to an instance from different writers
+    /// are delivered to the cache
+    ///
+    /// Defaults to [`DestinationOrder::ByReceptionTimestamp`].
+    pub fn destination_order(mut self, destination_order: DestinationOrder) -> Self {
+        self.destination_order = Some(destination_order);
+        self
+    }
+
+    /// Set the delay before responding to a `HEARTBEAT`
+    ///
+    /// Defaults to [`DEFAULT_HEARTBEAT_RESPONSE_DELAY`].
+    pub fn heartbeat_response_delay(mut self, delay: Duration) -> Self {
+        self.heartbeat_response_delay = Some(delay);
+        self
+    }
+
+    /// Set the duration for which `HEARTBEAT`s from a writer are ignored after
+    /// one is processed
+    ///
+    /// Defaults to [`DEFAULT_HEARTBEAT_SUPPRESSION_DURATION`].
+    pub fn heartbeat_suppression_duration(mut self, duration: Duration) -> Self {
+        self.heartbeat_suppression_duration = Some(duration);
+        self
+    }
+
+    /// Set the limits on the memory used to reassemble fragmented changes
+    ///
+    /// Defaults to [`ReassemblyLimits::default`].
+    pub fn reassembly_limits(mut self, limits: ReassemblyLimits) -> Self {
+        self.reassembly_limits = Some(limits);
+        self
+    }
+
+    /// Consume the [`Builder`] and return a configured [`StatefulReader`]
+    #[must_use]
+    pub fn build(self) -> StatefulReader<C, P, Id> {
+        let header = Header::new(
+            self.protocol_version.unwrap_or_default(),
+            self.vendor_id.unwrap_or_default(),
+            self.guid.prefix(),
+        );
+
+        StatefulReader {
+            guid: self.guid,
+            cache: self.cache,
+            header,
+            max_message_size: self.max_message_size.unwrap_or(DEFAULT_MAX_MESSAGE_SIZE),
+            destination_order: self.destination_order.unwrap_or_default(),
+            heartbeat_response_delay: self
+                .heartbeat_response_delay
+                .unwrap_or(DEFAULT_HEARTBEAT_RESPONSE_DELAY),
+            heartbeat_suppression_duration: self
+                .heartbeat_suppression_duration
+                .unwrap_or(DEFAULT_HEARTBEAT_SUPPRESSION_DURATION),
+            matched_writers: Vec::default(),
+            latest: BTreeMap::default(),
+            reassembly: ReassemblyBuffer::new(self.reassembly_limits.unwrap_or_default()),
+            liveliness: LivelinessMonitor::default(),
+        }
+    }
+}
+
+impl<C, P, Id> StatefulReader<C, P, Id>
+where
+    P: Copy + Ord,
+    Id: Copy + Ord,
+{
+    /// Construct a new [`StatefulReader`] which delivers changes to the given
+    /// history [`Cache`]
+    ///
+    /// For additional options, use [`StatefulReader::builder`] instead.
+    #[must_use]
+    pub fn new(guid: Guid<P, Id>, cache: C) -> Self {
+        Builder::new(guid, cache).build()
+    }
+
+    /// Construct a new [`StatefulReader`] with additional options
+    pub fn builder(guid: Guid<P, Id>, cache: C) -> Builder<C, P, Id> {
+        Builder::new(guid, cache)
+    }
+
+    /// The [`Guid`] of the reader
+    #[must_use]
+    pub fn guid(&self) -> Guid<P, Id> {
+        self.guid
+    }
+
+    /// The history [`Cache`] of the reader
+    #[must_use]
+    pub fn cache(&self) -> &C {
+        &self.cache
+    }
+
+    /// Mutable access to the history [`Cache`] of the reader
+    ///
+    /// This can be used to take changes which have been delivered.
+    pub fn cache_mut(&mut self) -> &mut C {
+        &mut self.cache
+    }
+
+    /// Add a [`WriterProxy`] for a newly matched remote writer
+    pub fn matched_writer_add(&mut self, writer_proxy: WriterProxy<P, Id>) {
+        self.matched_writer_remove(writer_proxy.remote_writer_guid());
+        self.liveliness
+            .writer_add(writer_proxy.remote_writer_guid(), writer_proxy.liveliness());
+        self.matched_writers.push(writer_proxy);
+    }
+
+    /// Remove the [`WriterProxy`] of the remote writer with the given [`Guid`]
+    pub fn matched_writer_remove(
+        &mut self,
+        writer_guid: Guid<P, Id>,
+    ) -> Option<WriterProxy<P, Id>> {
+        self.reassembly.remove_writer(writer_guid);
+        self.liveliness.writer_remove(writer_guid);
+        let index = self
+            .matched_writers
+            .iter()
+            .position(|proxy| proxy.remote_writer_guid() == writer_guid)?;
+        Some(self.matched_writers.remove(index))
+    }
+
+    /// Find the [`WriterProxy`] of the remote writer with the given [`Guid`]
+    #[must_use]
+    pub fn matched_writer_lookup(&self, writer_guid: Guid<P, Id>) -> Option<&WriterProxy<P, Id>> {
+        self.matched_writers
+            .iter()
+            .find(|proxy| proxy.remote_writer_guid() == writer_guid)
+    }
+
+    /// The [`WriterProxy`]s of every matched remote writer
+    #[must_use]
+    pub fn matched_writers(&self) -> &[WriterProxy<P, Id>] {
+        &self.matched_writers
+    }
+
+    /// The [`ReassemblyBuffer`] holding partially received fragmented changes
+    #[must_use]
+    pub fn reassembly(&self) -> &ReassemblyBuffer<P, Id> {
+        &self.reassembly
+    }
+
+    /// The [`LivelinessMonitor`] tracking the liveliness of the matched
+    /// writers
+    #[must_use]
+    pub fn liveliness(&self) -> &LivelinessMonitor<P, Id> {
+        &self.liveliness
+    }
+
+    /// Mutable access to the [`LivelinessMonitor`]
+    ///
+    /// This can be used to take its events, and to pass on the updates
+    /// received by the built-in participant message reader.
+    pub fn liveliness_mut(&mut self) -> &mut LivelinessMonitor<P, Id> {
+        &mut self.liveliness
+    }
+
+    /// The time at which [`StatefulReader::send`] next needs to be called to
+    /// send an `ACKNACK`, or to check the lease of a matched writer, if any
+    #[must_use]
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.matched_writers
+            .iter()
+            .filter_map(WriterProxy::acknack_deadline)
+            .chain(self.liveliness.next_deadline())
+            .min()
+    }
+
+    /// Process a submessage sent to the reader by a remote writer
+    ///
+    /// Returns the number of changes which were delivered to the [`Cache`].
+    /// Submessages from writers which aren't matched are ignored. Data from a
+    /// writer, and `HEARTBEAT`s with the liveliness flag, assert the
+    /// liveliness of the writer.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if a change can't be added to the cache. The
+    /// change is then treated as lost.
+    pub fn receive<Data>(
+        &mut self,
+        now: Instant,
+        context: &Context<P>,
+        submessage: ReaderSubMessage<Id>,
+    ) -> Result<usize, C::AddErr>
+    where
+        C: Cache<Data, Prefix = P, EntityId = Id>,
+        Data: From<Vec<u8>>,
+    {
+        let writer_guid = Guid::new(context.source_guid_prefix(), submessage.writer());
+        let proxy = match self
+            .matched_writers
+            .iter_mut()
+            .find(|proxy| proxy.remote_writer_guid() == writer_guid)
+        {
+            Some(proxy) => proxy,
+            None => return Ok(0),
+        };
+
+        if asserts_liveliness(&submessage) {
+            self.liveliness.assert_writer(writer_guid, now);
+        }
+
+        let mut delivered = 0;
+
+        let submessage = match submessage {
+            ReaderSubMessage::DataFrag(data_frag) => {
+                if proxy.is_received(data_frag.writer_sequence_number) {
+                    return Ok(0);
+                }
+                // invalid fragments are dropped
+                match self.reassembly.insert(writer_guid, data_frag) {
+                    Ok(Some(data)) => ReaderSubMessage::Data(data),
+                    _ => return Ok(0),
+                }
+            }
+            submessage => submessage,
+        };
+
+        match submessage {
+            ReaderSubMessage::Data(data) => {
+                let sequence_number = data.writer_sequence_number;
+                if proxy.is_received(sequence_number) {
+                    return Ok(0);
+                }
+                proxy.received_change_set(sequence_number);
+                proxy.hold(data, context.timestamp());
+            }
+            ReaderSubMessage::Heartbeat(heartbeat) => {
+                if !proxy.accept_heartbeat(
+                    heartbeat.count,
+                    now,
+                    self.heartbeat_suppression_duration,
+                ) {
+                    return Ok(0);
+                }
+
+                proxy.lost_changes_update(heartbeat.first_sequence_number);
+                proxy.missing_changes_update(heartbeat.last_sequence_number);
+
+                self.reassembly
+                    .remove_up_to(writer_guid, proxy.available_changes_max());
+
+                let has_missing = proxy.missing_changes().next().is_some();
+                if !heartbeat.final_flag || has_missing {
+                    proxy.schedule_acknack(now + self.heartbeat_response_delay);
+                }
+            }
+            ReaderSubMessage::Gap(gap) => {
+                proxy.irrelevant_changes_set(gap.gap_start..gap.gap_list.base());
+                for sequence_number in gap.gap_list.values() {
+                    proxy.irrelevant_change_set(sequence_number);
+                    self.reassembly.remove(writer_guid, sequence_number);
+                }
+                self.reassembly
+                    .remove_up_to(writer_guid, proxy.available_changes_max());
+            }
+            ReaderSubMessage::HeartbeatFrag(heartbeat_frag) => {
+                let sequence_number = heartbeat_frag.writer_sequence_number;
+                self.reassembly.fragments_available(
+                    writer_guid,
+                    sequence_number,
+                    heartbeat_frag.last_fragment_number,
+                );
+                if self
+                    .reassembly
+                    .missing_fragments(writer_guid, sequence_number)
+                    .is_some()
+                {
+                    proxy.schedule_acknack(now + self.heartbeat_response_delay);
+                }
+            }
+            ReaderSubMessage::DataFrag(_) => {}
+        }
+
+        while let Some((data, timestamp)) = proxy.release() {
+            let latest = match self.destination_order {
+                DestinationOrder::ByReceptionTimestamp => None,
+                DestinationOrder::BySourceTimestamp => Some(&mut self.latest),
+            };
+            delivered += deliver(&mut self.cache, latest, writer_guid, data, timestamp)?;
+        }
+
+        Ok(delivered)
+    }
+
+    /// Send an `ACKNACK` to each matched writer for which one is due
+    ///
+    /// The `ACKNACK` acknowledges every change up to
+    /// [`WriterProxy::available_changes_max`], and requests any which are
+    /// missing. The missing fragments of partially received changes are
+    /// requested with a `NACK_FRAG` instead.
+    ///
+    /// Matched writers whose lease has expired are also marked as lost by
+    /// the [`LivelinessMonitor`].
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if a submessage can't be sent because the maximum
+    /// message size leaves no room for it. The other submessages are still
+    /// sent.
+    pub fn send<O>(&mut self, now: Instant, output: &mut O) -> Result<(), TooLargeError>
+    where
+        O: Output<P, Id>,
+    {
+        self.liveliness.update(now);
+
+        let reader = self.guid.entity_id();
+        let reassembly = &self.reassembly;
+        let mut result = Ok(());
+
+        for proxy in &mut self.matched_writers {
+            let count = match proxy.take_acknack(now) {
+                Some(count) => count,
+                None => continue,
+            };
+
+            // every change up to the base is acknowledged. The base is never
+            // zero, and only overflows once every sequence number is used.
+            let base = match proxy
+                .available_changes_max()
+                .checked_add(1)
+                .and_then(NonZeroU64::new)
+            {
+                Some(base) => base,
+                None => continue,
+            };
+            let writer_guid = proxy.remote_writer_guid();
+            let mut reader_sequence_number_state = SequenceNumberSet::new(base);
+            for sequence_number in proxy
+                .missing_changes()
+                .take_while(|&sequence_number| {
+                    sequence_number < base.get().saturating_add(MAX_SET_RANGE)
+                })
+                .filter(|&sequence_number| !reassembly.contains(writer_guid, sequence_number))
+            {
+                let _ = reader_sequence_number_state.insert_value(sequence_number);
+            }
+
+            let final_flag = reader_sequence_number_state.values().next().is_none();
+            let ack_nack = AckNack {
+                reader,
+                writer: writer_guid.entity_id(),
+                reader_sequence_number_state,
+                count,
+                final_flag,
+            };
+
+            let mut builder = MessageBuilder::new(self.header, self.max_message_size);
+            result = result.and(builder.push(Some(writer_guid.prefix()), None, ack_nack.into()));
+
+            for sequence_number in reassembly.sequence_numbers(writer_guid) {
+                let fragment_number_state =
+                    match reassembly.missing_fragments(writer_guid, sequence_number) {
+                        Some(fragment_number_state) => fragment_number_state,
+                        None => continue,
+                    };
+                let nack_frag = NackFrag {
+                    reader,
+                    writer: writer_guid.entity_id(),
+                    writer_sequence_number: sequence_number,
+                    fragment_number_state,
+                    count: proxy.next_nack_frag_count(),
+                };
+                result =
+                    result.and(builder.push(Some(writer_guid.prefix()), None, nack_frag.into()));
+            }
+
+            for message in builder.finish() {
+                for &locator in proxy.locators() {
+                    output.send(locator, message.clone());
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// Whether the submessage asserts the liveliness of the writer which sent it
+fn asserts_liveliness<Id>(submessage: &ReaderSubMessage<Id>) -> bool {
+    match submessage {
+        ReaderSubMessage::Data(_) | ReaderSubMessage::DataFrag(_) => true,
+        ReaderSubMessage::Heartbeat(heartbeat) => heartbeat.liveliness_flag,
+        ReaderSubMessage::Gap(_) | ReaderSubMessage::HeartbeatFrag(_) => false,
+    }
+}
+
+/// Deliver a change to the cache
+///
+/// `latest` holds the source timestamp and writer of the newest change
+/// delivered for each instance, if changes are ordered by source timestamp.
+/// A change which is older than that is discarded. Changes without a source
+/// timestamp are always delivered.
+fn deliver<C, Data, P, Id>(
+    cache: &mut C,
+    latest: Option<&mut Latest<P, Id>>,
+    writer_guid: Guid<P, Id>,
+    data: submessage::Data<Id>,
+    timestamp: Option<DateTime<Utc>>,
+) -> Result<usize, C::AddErr>
+where
+    C: Cache<Data, Prefix = P, EntityId = Id>,
+    Data: From<Vec<u8>>,
+    P: Copy + Ord,
+    Id: Copy + Ord,
+{
+    let change = match change_from_data(writer_guid, data) {
+        Some(change) => change,
+        None => return Ok(0),
+    };
+    let instance = change.instance();
+    let source = timestamp.map(|timestamp| (timestamp, writer_guid));
+
+    if let (Some(latest), Some(source)) = (&latest, source) {
+        if latest
+            .get(&instance)
+            .map_or(false, |&newest| source < newest)
+        {
+            return Ok(0);
+        }
+    }
+
+    cache.add(change)?;
+
+    if let (Some(latest), Some(source)) = (latest, source) {
+        latest.insert(instance, source);
+    }
+
+    Ok(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatefulReader;
+    use crate::{
+        behaviour::{
+            liveliness::{Liveliness, LivelinessEvent, LivelinessKind},
+            reader::{DestinationOrder, WriterProxy},
+            receiver::{Context, Dispatch, ReaderSubMessage, WriterSubMessage},
+            MessageReceiver,
+        },
+        messages::{
+            submessage::{
+                elements::SequenceNumberSet, Data, DataFrag, Gap, Heartbeat, InfoTimestamp, Payload,
+            },
+            Header, Message, SubMessage,
+        },
+        structure::{
+            history::{HistoryCache, Kind},
+            Guid, Locator, ProtocolVersion, VendorId,
+        },
+    };
+    use std::{
+        convert::TryFrom,
+        net::{Ipv4Addr, SocketAddrV4},
+        num::{NonZeroU32, NonZeroU64},
+        time::{Duration, Instant},
+    };
+    use chrono::{TimeZone, Utc};
+    use test_case::test_case;
+    use vec1::Vec1;
+
+    type Prefix = [u8; 12];
+    type Id = [u8; 4];
+    type TestReader = StatefulReader<HistoryCache<Vec<u8>, Prefix, Id>, Prefix, Id>;
+    type Sent = Vec<(Locator, Message<Prefix, Id>)>;
+
+    const LOCAL: Prefix = [1; 12];
+    const REMOTE: Prefix = [2; 12];
+    const WRITER: Id = [0, 0, 1, 2];
+    const OTHER_WRITER: Id = [0, 0, 2, 2];
+
+    const RESPONSE_DELAY: Duration = Duration::from_millis(100);
+
+    /// Routes every reader submessage to a single reader
+    struct Router<'a> {
+        reader: &'a mut TestReader,
+        now: Instant,
+    }
+
+    impl Dispatch<Prefix, Id> for Router<'_> {
+        fn dispatch_to_reader(
+            &mut self,
+            _reader: Option<Id>,
+            context: &Context<Prefix>,
+            submessage: ReaderSubMessage<Id>,
+        ) {
+            self.reader.receive(self.now, context, submessage).unwrap();
+        }
+
+        fn dispatch_to_writer(
+            &mut self,
+            _writer: Id,
+            _context: &Context<Prefix>,
+            _submessage: WriterSubMessage<Id>,
+        ) {
+        }
+    }
+
+    fn locator() -> Locator {
+        SocketAddrV4::new(Ipv4Addr::LOCALHOST, 7410).into()
+    }
+
+    fn reader(destination_order: DestinationOrder) -> TestReader {
+        let mut reader =
+            StatefulReader::builder(Guid::new(LOCAL, [0, 0, 1, 7]), HistoryCache::default())
+                .destination_order(destination_order)
+                .heartbeat_response_delay(RESPONSE_DELAY)
+                .build();
+        reader.matched_writer_add(WriterProxy::new(
+            Guid::new(REMOTE, WRITER),
+            vec![locator()],
+            Vec::new(),
+        ));
+        reader
+    }
+
+    fn data(sequence_number: u64) -> SubMessage<Prefix, Id> {
+        Data {
+            reader: None,
+            writer: WRITER,
+            writer_sequence_number: sequence_number,
+            inline_qos: None,
+            payload: Some(Payload::Data(vec![u8::try_from(sequence_number).unwrap()])),
+            non_standard_payload: false,
+        }
+        .into()
+    }
+
+    fn heartbeat(first: u64, last: u64, count: u32, final_flag: bool) -> SubMessage<Prefix, Id> {
+        Heartbeat {
+            reader: None,
+            writer: WRITER,
+            first_sequence_number: first,
+            last_sequence_number: last,
+            count,
+            final_flag,
+            liveliness_flag: false,
+        }
+        .into()
+    }
+
+    fn gap(start: u64, end: u64) -> SubMessage<Prefix, Id> {
+        Gap {
+            reader: None,
+            writer: WRITER,
+            gap_start: start,
+            gap_list: SequenceNumberSet::new(NonZeroU64::new(end + 1).unwrap()),
+        }
+        .into()
+    }
+
+    fn receive(reader: &mut TestReader, now: Instant, submessages: Vec<SubMessage<Prefix, Id>>) {
+        let header = Header::new(ProtocolVersion::Latest, VendorId::Unknown, REMOTE);
+        let message = Message::new(header, Vec1::try_from_vec(submessages).unwrap());
+
+        MessageReceiver::new(LOCAL)
+            .receive(None, message, &mut Router { reader, now })
+            .unwrap();
+    }
+
+    fn delivered(reader: &TestReader) -> Vec<u8> {
+        reader
+            .cache()
+            .iter()
+            .map(|(_, change)| match change.kind() {
+                Kind::Alive(data) => data[0],
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    /// The base and requested sequence numbers of each `ACKNACK` sent
+    fn acknacks(reader: &mut TestReader, now: Instant) -> Vec<(u64, Vec<u64>)> {
+        let mut sent = Sent::new();
+        reader.send(now, &mut sent).unwrap();
+
+        sent.iter()
+            .flat_map(|(_, message)| message.submessages())
+            .filter_map(|submessage| match submessage {
+                SubMessage::AckNack(ack_nack) => {
+                    let state = &ack_nack.reader_sequence_number_state;
+                    Some((state.base(), state.values().collect()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn requests_missing_changes_after_response_delay() {
+        let mut reader = reader(DestinationOrder::ByReceptionTimestamp);
+        let now = Instant::now();
+
+        receive(
+            &mut reader,
+            now,
+            vec![data(1), data(3), heartbeat(1, 5, 1, false)],
+        );
+        assert_eq!(delivered(&reader), vec![1]);
+        assert_eq!(reader.next_deadline(), Some(now + RESPONSE_DELAY));
+
+        assert!(acknacks(&mut reader, now).is_empty());
+        assert_eq!(
+            acknacks(&mut reader, now + RESPONSE_DELAY),
+            vec![(2, vec![2, 4, 5])]
+        );
+
+        // the repairs arrive
+        receive(&mut reader, now, vec![data(2), data(4), data(5), data(3)]);
+        assert_eq!(delivered(&reader), vec![1, 2, 3, 4, 5]);
+
+        receive(&mut reader, now, vec![heartbeat(1, 5, 2, false)]);
+        assert_eq!(
+            acknacks(&mut reader, now + RESPONSE_DELAY),
+            vec![(6, vec![])]
+        );
+    }
+
+    #[test]
+    fn final_heartbeat_without_missing_changes_needs_no_response() {
+        let mut reader = reader(DestinationOrder::ByReceptionTimestamp);
+        let now = Instant::now();
+
+        receive(&mut reader, now, vec![data(1), heartbeat(1, 1, 1, true)]);
+        assert_eq!(reader.next_deadline(), None);
+
+        receive(&mut reader, now, vec![heartbeat(1, 2, 2, true)]);
+        assert_eq!(reader.next_deadline(), Some(now + RESPONSE_DELAY));
+    }
+
+    #[test]
+    fn gaps_and_lost_changes_are_not_requested() {
+        let mut reader = reader(DestinationOrder::ByReceptionTimestamp);
+        let now = Instant::now();
+
+        receive(
+            &mut reader,
+            now,
+            vec![gap(3, 4), data(6), heartbeat(2, 7, 1, false)],
+        );
+
+        let proxy = reader
+            .matched_writer_lookup(Guid::new(REMOTE, WRITER))
+            .unwrap();
+        assert_eq!(proxy.missing_changes().collect::<Vec<_>>(), vec![2, 5, 7]);
+
+        receive(&mut reader, now, vec![heartbeat(3, 7, 2, false)]);
+        assert_eq!(
+            acknacks(&mut reader, now + RESPONSE_DELAY),
+            vec![(5, vec![5, 7])]
+        );
+    }
+
+    #[test]
+    fn gaps_cover_huge_ranges_at_once() {
+        let mut reader = reader(DestinationOrder::ByReceptionTimestamp);
+        let now = Instant::now();
+        let end = 1 << 40;
+
+        // a gap which doesn't start at the next expected change
+        receive(
+            &mut reader,
+            now,
+            vec![gap(3, end), heartbeat(1, end + 2, 1, false)],
+        );
+
+        let proxy = reader
+            .matched_writer_lookup(Guid::new(REMOTE, WRITER))
+            .unwrap();
+        assert!(proxy.is_received(end / 2));
+        assert_eq!(
+            proxy.missing_changes().collect::<Vec<_>>(),
+            vec![1, 2, end + 1, end + 2]
+        );
+
+        receive(&mut reader, now, vec![data(1), data(2)]);
+        let proxy = reader
+            .matched_writer_lookup(Guid::new(REMOTE, WRITER))
+            .unwrap();
+        assert_eq!(proxy.available_changes_max(), end);
+        assert_eq!(delivered(&reader), vec![1, 2]);
+    }
+
+    #[test]
+    fn heartbeat_suppression() {
+        let mut reader =
+            StatefulReader::builder(Guid::new(LOCAL, [0, 0, 1, 7]), HistoryCache::default())
+                .heartbeat_response_delay(Duration::ZERO)
+                .heartbeat_suppression_duration(Duration::from_secs(1))
+                .build();
+        reader.matched_writer_add(WriterProxy::new(
+            Guid::new(REMOTE, WRITER),
+            vec![locator()],
+            Vec::new(),
+        ));
+        let now = Instant::now();
+
+        receive(&mut reader, now, vec![heartbeat(1, 1, 1, false)]);
+        assert_eq!(acknacks(&mut reader, now), vec![(1, vec![1])]);
+
+        receive(&mut reader, now, vec![heartbeat(1, 2, 2, false)]);
+        assert!(acknacks(&mut reader, now).is_empty());
+
+        let later = now + Duration::from_secs(1);
+        receive(&mut reader, later, vec![heartbeat(1, 2, 3, false)]);
+        assert_eq!(acknacks(&mut reader, later), vec![(1, vec![1, 2])]);
+    }
+
+    #[test]
+    fn delivers_the_changes_of_each_writer_in_order() {
+        let mut reader = reader(DestinationOrder::ByReceptionTimestamp);
+        let now = Instant::now();
+
+        receive(&mut reader, now, vec![data(2), data(4)]);
+        assert!(delivered(&reader).is_empty());
+
+        receive(&mut reader, now, vec![data(1)]);
+        assert_eq!(delivered(&reader), vec![1, 2]);
+
+        receive(&mut reader, now, vec![gap(3, 3)]);
+        assert_eq!(delivered(&reader), vec![1, 2, 4]);
+    }
+
+    #[test_case(DestinationOrder::ByReceptionTimestamp => vec![1, 2, 3] ; "by reception")]
+    #[test_case(DestinationOrder::BySourceTimestamp => vec![1, 3] ; "by source")]
+    fn orders_the_changes_of_several_writers(destination_order: DestinationOrder) -> Vec<u8> {
+        let mut reader = reader(destination_order);
+        reader.matched_writer_add(WriterProxy::new(
+            Guid::new(REMOTE, OTHER_WRITER),
+            vec![locator()],
+            Vec::new(),
+        ));
+
+        let timestamp = |seconds| -> SubMessage<Prefix, Id> {
+            InfoTimestamp {
+                timestamp: Some(Utc.timestamp_opt(seconds, 0).unwrap()),
+            }
+            .into()
+        };
+        let from_other_writer = |sequence_number, value| -> SubMessage<Prefix, Id> {
+            Data {
+                reader: None,
+                writer: OTHER_WRITER,
+                writer_sequence_number: sequence_number,
+                inline_qos: None,
+                payload: Some(Payload::Data(vec![value])),
+                non_standard_payload: false,
+            }
+            .into()
+        };
+
+        // the change from the other writer was written before the first, and
+        // the third change has no source timestamp
+        receive(
+            &mut reader,
+            Instant::now(),
+            vec![
+                timestamp(20),
+                data(1),
+                timestamp(10),
+                from_other_writer(1, 2),
+                InfoTimestamp { timestamp: None }.into(),
+                from_other_writer(2, 3),
+            ],
+        );
+
+        delivered(&reader)
+    }
+
+    #[test]
+    fn ignores_unmatched_writers() {
+        let mut reader = reader(DestinationOrder::ByReceptionTimestamp);
+        reader.matched_writer_remove(Guid::new(REMOTE, WRITER));
+
+        receive(&mut reader, Instant::now(), vec![data(1)]);
+        assert!(delivered(&reader).is_empty());
+    }
+
+    #[test]
+    fn tracks_the_liveliness_of_matched_writers() {
+        let lease_duration = Duration::from_secs(1);
+        let writer = Guid::new(REMOTE, WRITER);
+
+        let mut reader = reader(DestinationOrder::ByReceptionTimestamp);
+        reader.matched_writer_add(
+            WriterProxy::new(writer, vec![locator()], Vec::new()).with_liveliness(Liveliness {
+                kind: LivelinessKind::ManualByTopic,
+                lease_duration: Some(lease_duration),
+            }),
+        );
+
+        // a heartbeat without the liveliness flag doesn't assert liveliness
+        let start = Instant::now();
+        receive(&mut reader, start, vec![heartbeat(1, 0, 1, true)]);
+        assert!(!reader.liveliness().is_alive(writer));
+
+        let mut liveliness_heartbeat = heartbeat(1, 0, 2, true);
+        if let SubMessage::Heartbeat(heartbeat) = &mut liveliness_heartbeat {
+            heartbeat.liveliness_flag = true;
+        }
+        receive(&mut reader, start, vec![liveliness_heartbeat]);
+        assert!(reader.liveliness().is_alive(writer));
+
+        receive(&mut reader, start + lease_duration / 2, vec![data(1)]);
+        assert_eq!(reader.next_deadline(), Some(start + lease_duration * 3 / 2));
+
+        reader
+            .send(start + lease_duration * 3 / 2, &mut Sent::new())
+            .unwrap();
+        assert!(!reader.liveliness().is_alive(writer));
+        assert!(
+            reader
+                .liveliness_mut()
+                .take_events()
+                .contains(&LivelinessEvent::Lost { writer })
+        );
+    }
+
+    #[test]
+    fn reassembles_fragments_and_requests_missing_ones() {
+        let mut reader = reader(DestinationOrder::ByReceptionTimestamp);
+        let now = Instant::now();
+
+        // a change of 8 bytes, in 2 fragments
+        let data_frag = |first: u32| -> SubMessage<Prefix, Id> {
+            DataFrag {
+                reader: None,
+                writer: WRITER,
+                writer_sequence_number: 1,
+                fragment_starting_number: NonZeroU32::new(first).unwrap(),
+                fragment_size: 4,
+                sample_size: 8,
+                inline_qos: None,
+                key: false,
+                non_standard_payload: false,
+                fragments: vec![1; 4],
+            }
+            .into()
+        };
+
+        receive(
+            &mut reader,
+            now,
+            vec![data_frag(2), heartbeat(1, 1, 1, false)],
+        );
+        assert!(delivered(&reader).is_empty());
+        assert!(reader.reassembly().contains(Guid::new(REMOTE, WRITER), 1));
+
+        let mut sent = Sent::new();
+        reader.send(now + RESPONSE_DELAY, &mut sent).unwrap();
+        let submessages: Vec<_> = sent
+            .iter()
+            .flat_map(|(_, message)| message.submessages())
+            .collect();
+
+        // the partially received change is requested with a `NACK_FRAG`
+        let requested: Vec<_> = submessages
+            .iter()
+            .filter_map(|submessage| match submessage {
+                SubMessage::AckNack(ack_nack) => Some(
+                    ack_nack
+                        .reader_sequence_number_state
+                        .values()
+                        .collect::<Vec<_>>(),
+                ),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(requested, vec![vec![]]);
+
+        let nack_frags: Vec<_> = submessages
+            .iter()
+            .filter_map(|submessage| match submessage {
+                SubMessage::NackFrag(nack_frag) => Some((
+                    nack_frag.writer_sequence_number,
+                    nack_frag.fragment_number_state.values().collect::<Vec<_>>(),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(nack_frags, vec![(1, vec![1])]);
+
+        receive(&mut reader, now, vec![data_frag(1), data_frag(1)]);
+        assert_eq!(delivered(&reader), vec![1]);
+        assert!(reader.reassembly().is_empty());
+    }
+}
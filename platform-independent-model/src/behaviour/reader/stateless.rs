@@ -0,0 +1,458 @@
+//! Contains the best-effort [`StatelessReader`] and its [`Builder`]
+
+use std::collections::BTreeMap;
+
+use super::{change_from_data, ReassemblyBuffer, ReassemblyLimits};
+use crate::{
+    behaviour::receiver::{Context, ReaderSubMessage},
+    structure::{history::Cache, Guid},
+};
+
+/// A best-effort reader which accepts changes from any writer, without
+/// keeping any state about the remote writers beyond the highest sequence
+/// number received from each.
+///
+/// Changes are delivered to the history [`Cache`] in the order they arrive.
+/// A change with a sequence number no higher than one already received from
+/// the same writer is a duplicate, or arrived out of order, and is dropped.
+///
+/// The reader only handles `DATA` and `DATA_FRAG` submessages. Changes sent
+/// as `DATA_FRAG`s are reassembled in a [`ReassemblyBuffer`] before they are
+/// delivered. Reliability submessages such as `HEARTBEAT` and `GAP` are
+/// ignored, so a change with a lost fragment is never delivered.
+///
+/// For details, see the [specification, section 8.4.11](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+#[derive(Debug)]
+pub struct StatelessReader<C, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    guid: Guid<P, Id>,
+    cache: C,
+    highest_sequence_numbers: BTreeMap<Guid<P, Id>, u64>,
+    reassembly: ReassemblyBuffer<P, Id>,
+}
+
+/// A builder for a [`StatelessReader`]
+///
+/// See the [`StatelessReader`] docs for details
+#[derive(Debug)]
+#[must_use]
+pub struct Builder<C, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    guid: Guid<P, Id>,
+    cache: C,
+    reassembly_limits: Option<ReassemblyLimits>,
+}
+
+impl<C, P, Id> Builder<C, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    fn new(guid: Guid<P, Id>, cache: C) -> Self {
+        Self {
+            guid,
+            cache,
+            reassembly_limits: None,
+        }
+    }
+
+    /// Set the limits on the memory used to reassemble fragmented changes
+    ///
+    /// Defaults to [`ReassemblyLimits::default`].
+    pub fn reassembly_limits(mut self, limits: ReassemblyLimits) -> Self {
+        self.reassembly_limits = Some(limits);
+        self
+    }
+
+    /// Consume the [`Builder`] and return a configured [`StatelessReader`]
+    #[must_use]
+    pub fn build(self) -> StatelessReader<C, P, Id> {
+        StatelessReader {
+            guid: self.guid,
+            cache: self.cache,
+            highest_sequence_numbers: BTreeMap::default(),
+            reassembly: ReassemblyBuffer::new(self.reassembly_limits.unwrap_or_default()),
+        }
+    }
+}
+
+impl<C, P, Id> StatelessReader<C, P, Id>
+where
+    P: Copy + Ord,
+    Id: Copy + Ord,
+{
+    /// Construct a new [`StatelessReader`] which delivers changes to the given
+    /// history [`Cache`]
+    ///
+    /// For additional options, use [`StatelessReader::builder`] instead.
+    #[must_use]
+    pub fn new(guid: Guid<P, Id>, cache: C) -> Self {
+        Builder::new(guid, cache).build()
+    }
+
+    /// Construct a new [`StatelessReader`] with additional options
+    pub fn builder(guid: Guid<P, Id>, cache: C) -> Builder<C, P, Id> {
+        Builder::new(guid, cache)
+    }
+
+    /// The [`Guid`] of the reader
+    #[must_use]
+    pub fn guid(&self) -> Guid<P, Id> {
+        self.guid
+    }
+
+    /// The history [`Cache`] of the reader
+    #[must_use]
+    pub fn cache(&self) -> &C {
+        &self.cache
+    }
+
+    /// Mutable access to the history [`Cache`] of the reader
+    ///
+    /// This can be used to take changes which have been delivered.
+    pub fn cache_mut(&mut self) -> &mut C {
+        &mut self.cache
+    }
+
+    /// The [`ReassemblyBuffer`] holding partially received fragmented changes
+    #[must_use]
+    pub fn reassembly(&self) -> &ReassemblyBuffer<P, Id> {
+        &self.reassembly
+    }
+
+    /// The highest sequence number received from the given writer, if any
+    #[must_use]
+    pub fn highest_sequence_number(&self, writer: Guid<P, Id>) -> Option<u64> {
+        self.highest_sequence_numbers.get(&writer).copied()
+    }
+
+    /// Process a submessage addressed to the reader
+    ///
+    /// Returns the sequence number assigned to the delivered change by the
+    /// [`Cache`], or `None` if nothing was delivered.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the change can't be added to the cache. The
+    /// change is then treated as lost, and won't be accepted if it is received
+    /// again.
+    pub fn receive<Data>(
+        &mut self,
+        context: &Context<P>,
+        submessage: ReaderSubMessage<Id>,
+    ) -> Result<Option<C::SqnN>, C::AddErr>
+    where
+        C: Cache<Data, Prefix = P, EntityId = Id>,
+        Data: From<Vec<u8>>,
+    {
+        let writer_guid = Guid::new(context.source_guid_prefix(), submessage.writer());
+        let highest = self
+            .highest_sequence_numbers
+            .get(&writer_guid)
+            .copied()
+            .unwrap_or_default();
+
+        let data = match submessage {
+            ReaderSubMessage::Data(data) => data,
+            ReaderSubMessage::DataFrag(data_frag) => {
+                if data_frag.writer_sequence_number <= highest {
+                    return Ok(None);
+                }
+                // invalid fragments are dropped
+                match self.reassembly.insert(writer_guid, data_frag) {
+                    Ok(Some(data)) => data,
+                    _ => return Ok(None),
+                }
+            }
+            _ => return Ok(None),
+        };
+
+        let sequence_number = data.writer_sequence_number;
+        if sequence_number <= highest {
+            return Ok(None);
+        }
+        self.highest_sequence_numbers
+            .insert(writer_guid, sequence_number);
+
+        // older changes would be dropped once complete
+        self.reassembly.remove_up_to(writer_guid, sequence_number);
+
+        match change_from_data(writer_guid, data) {
+            Some(change) => self.cache.add(change).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Forget the state of a writer, for example because it has been lost
+    ///
+    /// Subsequent changes from the writer will be accepted regardless of their
+    /// sequence number.
+    pub fn remove_writer(&mut self, writer: Guid<P, Id>) {
+        self.highest_sequence_numbers.remove(&writer);
+        self.reassembly.remove_writer(writer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatelessReader;
+    use crate::{
+        behaviour::{
+            receiver::{Context, Dispatch, ReaderSubMessage, WriterSubMessage},
+            writer::{ReaderLocator, StatelessWriter},
+            MessageReceiver,
+        },
+        messages::{
+            submessage::{
+                elements::{parameter_id, Parameter},
+                Data, DataFrag, Heartbeat, InfoSource, Payload,
+            },
+            Header, Message, SubMessage,
+        },
+        structure::{
+            history::{Change, HistoryCache, Kind},
+            Guid, KeyHash, ProtocolVersion, VendorId,
+        },
+    };
+    use std::{
+        net::{Ipv4Addr, SocketAddrV4},
+        num::NonZeroU32,
+        time::Instant,
+    };
+    use vec1::Vec1;
+
+    type Prefix = [u8; 12];
+    type Id = [u8; 4];
+    type TestReader = StatelessReader<HistoryCache<Vec<u8>, Prefix, Id>, Prefix, Id>;
+
+    const LOCAL: Prefix = [1; 12];
+    const REMOTE: Prefix = [2; 12];
+    const WRITER: Id = [0, 0, 1, 2];
+
+    /// Routes every reader submessage to a single reader
+    struct Router<'a>(&'a mut TestReader);
+
+    impl Dispatch<Prefix, Id> for Router<'_> {
+        fn dispatch_to_reader(
+            &mut self,
+            _reader: Option<Id>,
+            context: &Context<Prefix>,
+            submessage: ReaderSubMessage<Id>,
+        ) {
+            self.0.receive(context, submessage).unwrap();
+        }
+
+        fn dispatch_to_writer(
+            &mut self,
+            _writer: Id,
+            _context: &Context<Prefix>,
+            _submessage: WriterSubMessage<Id>,
+        ) {
+        }
+    }
+
+    fn reader() -> TestReader {
+        StatelessReader::new(Guid::new(LOCAL, [0, 0, 1, 7]), HistoryCache::default())
+    }
+
+    fn data(sequence_number: u64, value: u8) -> SubMessage<Prefix, Id> {
+        Data {
+            reader: None,
+            writer: WRITER,
+            writer_sequence_number: sequence_number,
+            inline_qos: None,
+            payload: Some(Payload::Data(vec![value])),
+            non_standard_payload: false,
+        }
+        .into()
+    }
+
+    fn receive(reader: &mut TestReader, submessages: Vec<SubMessage<Prefix, Id>>) {
+        let header = Header::new(ProtocolVersion::Latest, VendorId::Unknown, REMOTE);
+        let message = Message::new(header, Vec1::try_from_vec(submessages).unwrap());
+
+        MessageReceiver::new(LOCAL)
+            .receive(None, message, &mut Router(reader))
+            .unwrap();
+    }
+
+    fn delivered(reader: &TestReader) -> Vec<Change<Vec<u8>, Prefix, Id>> {
+        reader
+            .cache()
+            .iter()
+            .map(|(_, change)| change.clone())
+            .collect()
+    }
+
+    fn alive(prefix: Prefix, value: u8) -> Change<Vec<u8>, Prefix, Id> {
+        Change::new(
+            Kind::Alive(vec![value]),
+            Guid::new(prefix, WRITER),
+            KeyHash::default(),
+        )
+    }
+
+    #[test]
+    fn drops_duplicate_and_out_of_order_samples() {
+        let mut reader = reader();
+
+        receive(&mut reader, vec![data(1, 1), data(3, 3), data(2, 2)]);
+        receive(&mut reader, vec![data(3, 3), data(4, 4)]);
+
+        assert_eq!(
+            delivered(&reader),
+            vec![alive(REMOTE, 1), alive(REMOTE, 3), alive(REMOTE, 4)]
+        );
+        assert_eq!(
+            reader.highest_sequence_number(Guid::new(REMOTE, WRITER)),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn tracks_each_writer_separately() {
+        let mut reader = reader();
+        let other: Prefix = [3; 12];
+
+        let info_source = InfoSource {
+            protocol_version: ProtocolVersion::Latest,
+            vendor_id: VendorId::Unknown,
+            guid_prefix: other,
+        };
+
+        receive(
+            &mut reader,
+            vec![data(5, 1), info_source.into(), data(1, 2)],
+        );
+
+        assert_eq!(delivered(&reader), vec![alive(REMOTE, 1), alive(other, 2)]);
+    }
+
+    #[test]
+    fn ignores_reliability_submessages() {
+        let mut reader = reader();
+
+        let heartbeat = Heartbeat {
+            reader: None,
+            writer: WRITER,
+            first_sequence_number: 1,
+            last_sequence_number: 10,
+            count: 1,
+            final_flag: false,
+            liveliness_flag: false,
+        };
+
+        receive(&mut reader, vec![heartbeat.into(), data(1, 1)]);
+        assert_eq!(delivered(&reader), vec![alive(REMOTE, 1)]);
+    }
+
+    #[test]
+    fn delivers_disposal() {
+        let mut reader = reader();
+        let instance = KeyHash::new([9; 16]);
+
+        let dispose = Data {
+            reader: None,
+            writer: WRITER,
+            writer_sequence_number: 1,
+            inline_qos: Some(
+                vec![
+                    Parameter::new(parameter_id::KEY_HASH, instance.as_ref().to_vec()),
+                    Parameter::new(parameter_id::STATUS_INFO, vec![0, 0, 0, 1]),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+            payload: None,
+            non_standard_payload: false,
+        };
+
+        receive(&mut reader, vec![dispose.into()]);
+
+        assert_eq!(
+            delivered(&reader),
+            vec![Change::new(
+                Kind::NotAliveDisposed,
+                Guid::new(REMOTE, WRITER),
+                instance
+            )]
+        );
+    }
+
+    #[test]
+    fn receives_each_instance_from_a_stateless_writer() {
+        let mut reader = reader();
+        let mut writer = StatelessWriter::new(Guid::new(REMOTE, WRITER), HistoryCache::default());
+        let locator = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 7400).into();
+        writer.reader_locator_add(ReaderLocator::new(locator, false));
+
+        let first = KeyHash::new([1; 16]);
+        let second = KeyHash::new([2; 16]);
+        writer.new_change(Kind::Alive(vec![1]), first).unwrap();
+        writer.new_change(Kind::Alive(vec![2]), second).unwrap();
+
+        let mut sent = Vec::new();
+        writer.send(Instant::now(), &mut sent).unwrap();
+        for (_, message) in sent {
+            MessageReceiver::new(LOCAL)
+                .receive(None, message, &mut Router(&mut reader))
+                .unwrap();
+        }
+
+        let writer_guid = Guid::new(REMOTE, WRITER);
+        assert_eq!(
+            delivered(&reader),
+            vec![
+                Change::new(Kind::Alive(vec![1]), writer_guid, first),
+                Change::new(Kind::Alive(vec![2]), writer_guid, second),
+            ]
+        );
+    }
+
+    #[test]
+    fn reassembles_fragmented_changes() {
+        let mut reader = reader();
+
+        let data_frag = |first: u32, fragments: Vec<u8>| -> SubMessage<Prefix, Id> {
+            DataFrag {
+                reader: None,
+                writer: WRITER,
+                writer_sequence_number: 1,
+                fragment_starting_number: NonZeroU32::new(first).unwrap(),
+                fragment_size: 2,
+                sample_size: 5,
+                inline_qos: None,
+                key: false,
+                non_standard_payload: false,
+                fragments,
+            }
+            .into()
+        };
+
+        receive(
+            &mut reader,
+            vec![data_frag(3, vec![5]), data_frag(1, vec![1, 2])],
+        );
+        assert!(delivered(&reader).is_empty());
+
+        receive(&mut reader, vec![data_frag(2, vec![3, 4])]);
+        assert_eq!(
+            delivered(&reader),
+            vec![Change::new(
+                Kind::Alive(vec![1, 2, 3, 4, 5]),
+                Guid::new(REMOTE, WRITER),
+                KeyHash::default()
+            )]
+        );
+
+        // fragments of a change which was already delivered are dropped
+        receive(&mut reader, vec![data_frag(1, vec![1, 2])]);
+        assert!(reader.reassembly().is_empty());
+    }
+}
@@ -0,0 +1,392 @@
+//! Contains the [`WriterProxy`], which tracks the state of a remote writer
+
+use std::{collections::BTreeMap, time::Instant};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    behaviour::liveliness::Liveliness,
+    messages::submessage::Data,
+    structure::{Guid, Locator},
+};
+
+/// Tracks the state of a remote writer which is matched with a
+/// [`StatefulReader`](super::StatefulReader)
+///
+/// The proxy keeps track of which changes have been received from the
+/// writer, which are known to be missing, and which are lost or irrelevant.
+///
+/// Every sequence number up to [`WriterProxy::available_changes_max`] has
+/// either been received, or is known to be irrelevant or lost. Above that,
+/// changes announced by the writer which haven't been received are missing.
+///
+/// For details, see the [specification, section 8.4.10.4](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+#[derive(Debug, Clone)]
+pub struct WriterProxy<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    remote_writer_guid: Guid<P, Id>,
+    unicast_locators: Vec<Locator>,
+    multicast_locators: Vec<Locator>,
+    liveliness: Liveliness,
+    available_changes_max: u64,
+    received: Ranges,
+    highest_announced: u64,
+    pending: BTreeMap<u64, (Data<Id>, Option<DateTime<Utc>>)>,
+    last_heartbeat_count: Option<u32>,
+    last_heartbeat_at: Option<Instant>,
+    acknack_deadline: Option<Instant>,
+    acknack_count: u32,
+    nack_frag_count: u32,
+}
+
+impl<P, Id> WriterProxy<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    /// Construct a new [`WriterProxy`] for the remote writer with the given
+    /// [`Guid`]
+    ///
+    /// Acknowledgements are sent to the unicast [`Locator`]s of the writer,
+    /// or to its multicast [`Locator`]s if it has none.
+    #[must_use]
+    pub fn new(
+        remote_writer_guid: Guid<P, Id>,
+        unicast_locators: Vec<Locator>,
+        multicast_locators: Vec<Locator>,
+    ) -> Self {
+        Self {
+            remote_writer_guid,
+            unicast_locators,
+            multicast_locators,
+            liveliness: Liveliness::default(),
+            available_changes_max: 0,
+            received: Ranges::default(),
+            highest_announced: 0,
+            pending: BTreeMap::default(),
+            last_heartbeat_count: None,
+            last_heartbeat_at: None,
+            acknack_deadline: None,
+            acknack_count: 0,
+            nack_frag_count: 0,
+        }
+    }
+
+    /// Set the [`Liveliness`] offered by the remote writer
+    ///
+    /// Defaults to an automatic liveliness with an infinite lease.
+    #[must_use]
+    pub fn with_liveliness(mut self, liveliness: Liveliness) -> Self {
+        self.liveliness = liveliness;
+        self
+    }
+
+    /// The [`Guid`] of the remote writer
+    #[must_use]
+    pub fn remote_writer_guid(&self) -> Guid<P, Id> {
+        self.remote_writer_guid
+    }
+
+    /// The [`Liveliness`] offered by the remote writer
+    #[must_use]
+    pub fn liveliness(&self) -> Liveliness {
+        self.liveliness
+    }
+
+    /// The unicast [`Locator`]s of the writer
+    #[must_use]
+    pub fn unicast_locators(&self) -> &[Locator] {
+        &self.unicast_locators
+    }
+
+    /// The multicast [`Locator`]s of the writer
+    #[must_use]
+    pub fn multicast_locators(&self) -> &[Locator] {
+        &self.multicast_locators
+    }
+
+    /// The [`Locator`]s to which messages for the writer are sent
+    #[must_use]
+    pub fn locators(&self) -> &[Locator] {
+        if self.unicast_locators.is_empty() {
+            &self.multicast_locators
+        } else {
+            &self.unicast_locators
+        }
+    }
+
+    /// The highest sequence number up to which every change has been
+    /// received, or is known to be irrelevant or lost
+    #[must_use]
+    pub fn available_changes_max(&self) -> u64 {
+        self.available_changes_max
+    }
+
+    /// Returns `true` if the change with the given sequence number has been
+    /// received, or is known to be irrelevant or lost
+    #[must_use]
+    pub fn is_received(&self, sequence_number: u64) -> bool {
+        sequence_number <= self.available_changes_max || self.received.contains(sequence_number)
+    }
+
+    /// Record that the change with the given sequence number has been
+    /// received
+    pub fn received_change_set(&mut self, sequence_number: u64) {
+        self.highest_announced = self.highest_announced.max(sequence_number);
+        if sequence_number > self.available_changes_max {
+            self.received.insert(sequence_number, sequence_number);
+            self.advance();
+        }
+    }
+
+    /// Record that the change with the given sequence number is irrelevant,
+    /// and will never be received
+    pub fn irrelevant_change_set(&mut self, sequence_number: u64) {
+        self.received_change_set(sequence_number);
+    }
+
+    /// Record that every change in the given range is irrelevant
+    ///
+    /// The range is recorded as a whole, so this takes the same time and
+    /// space however many sequence numbers it covers.
+    pub fn irrelevant_changes_set(&mut self, range: std::ops::Range<u64>) {
+        if range.is_empty() {
+            return;
+        }
+        let last = range.end - 1;
+        self.highest_announced = self.highest_announced.max(last);
+
+        if range.start <= self.available_changes_max.saturating_add(1) {
+            self.lost_changes_update(range.end);
+        } else {
+            self.received.insert(range.start, last);
+        }
+    }
+
+    /// Record that changes with a sequence number below `first_available` are
+    /// no longer available from the writer. Any which haven't been received
+    /// are lost.
+    pub fn lost_changes_update(&mut self, first_available: u64) {
+        if first_available > self.available_changes_max.saturating_add(1) {
+            self.available_changes_max = first_available - 1;
+            self.received.remove_below(first_available);
+            self.advance();
+        }
+    }
+
+    /// Record that the writer has every change up to and including
+    /// `last_available`. Any which haven't been received are missing.
+    pub fn missing_changes_update(&mut self, last_available: u64) {
+        self.highest_announced = self.highest_announced.max(last_available);
+    }
+
+    /// The changes which the writer has announced, but which haven't been
+    /// received
+    pub fn missing_changes(&self) -> impl Iterator<Item = u64> + '_ {
+        self.received.gaps(
+            self.available_changes_max.saturating_add(1),
+            self.highest_announced,
+        )
+    }
+
+    /// The time at which an `ACKNACK` is due to be sent to the writer, if any
+    #[must_use]
+    pub fn acknack_deadline(&self) -> Option<Instant> {
+        self.acknack_deadline
+    }
+
+    fn advance(&mut self) {
+        if let Some(last) = self
+            .received
+            .remove_from(self.available_changes_max.saturating_add(1))
+        {
+            self.available_changes_max = last;
+        }
+    }
+
+    /// Hold a received change, along with its source timestamp, until every
+    /// change before it has been received
+    pub(crate) fn hold(&mut self, data: Data<Id>, timestamp: Option<DateTime<Utc>>) {
+        self.pending
+            .insert(data.writer_sequence_number, (data, timestamp));
+    }
+
+    /// Remove and return the next held change, if every change before it has
+    /// been received or is known to be irrelevant
+    pub(crate) fn release(&mut self) -> Option<(Data<Id>, Option<DateTime<Utc>>)> {
+        let (&sequence_number, _) = self.pending.iter().next()?;
+        if sequence_number <= self.available_changes_max {
+            self.pending.remove(&sequence_number)
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if a `HEARTBEAT` with the given count should be
+    /// processed, and records it
+    pub(crate) fn accept_heartbeat(
+        &mut self,
+        count: u32,
+        now: Instant,
+        suppression: std::time::Duration,
+    ) -> bool {
+        if self
+            .last_heartbeat_count
+            .map_or(false, |last| count <= last)
+        {
+            return false;
+        }
+        if let Some(last_heartbeat_at) = self.last_heartbeat_at {
+            if now < last_heartbeat_at + suppression {
+                return false;
+            }
+        }
+        self.last_heartbeat_count = Some(count);
+        self.last_heartbeat_at = Some(now);
+        true
+    }
+
+    pub(crate) fn schedule_acknack(&mut self, deadline: Instant) {
+        if self.acknack_deadline.is_none() {
+            self.acknack_deadline = Some(deadline);
+        }
+    }
+
+    /// If an `ACKNACK` is due, return its count
+    pub(crate) fn take_acknack(&mut self, now: Instant) -> Option<u32> {
+        match self.acknack_deadline {
+            Some(deadline) if now >= deadline => {
+                self.acknack_deadline = None;
+                self.acknack_count = self.acknack_count.wrapping_add(1);
+                Some(self.acknack_count)
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn next_nack_frag_count(&mut self) -> u32 {
+        self.nack_frag_count = self.nack_frag_count.wrapping_add(1);
+        self.nack_frag_count
+    }
+}
+
+/// A set of sequence numbers, stored as disjoint ranges so that a large range
+/// takes no more space than a single sequence number
+#[derive(Debug, Clone, Default)]
+struct Ranges(BTreeMap<u64, u64>);
+
+impl Ranges {
+    /// Returns `true` if the sequence number is in one of the ranges
+    fn contains(&self, sequence_number: u64) -> bool {
+        self.0
+            .range(..=sequence_number)
+            .next_back()
+            .map_or(false, |(_, &last)| sequence_number <= last)
+    }
+
+    /// Add the range `first..=last`, merging it with any ranges it overlaps
+    /// or adjoins
+    fn insert(&mut self, mut first: u64, mut last: u64) {
+        if let Some((&start, &end)) = self.0.range(..first).next_back() {
+            if end.saturating_add(1) >= first {
+                first = start;
+                last = last.max(end);
+            }
+        }
+
+        while let Some((&start, &end)) = self.0.range(first..).next() {
+            if start > last.saturating_add(1) {
+                break;
+            }
+            self.0.remove(&start);
+            last = last.max(end);
+        }
+
+        self.0.insert(first, last);
+    }
+
+    /// Remove every sequence number below `first`
+    fn remove_below(&mut self, first: u64) {
+        let overlapping = self
+            .0
+            .range(..first)
+            .next_back()
+            .map(|(_, &last)| last)
+            .filter(|&last| last >= first);
+
+        self.0 = self.0.split_off(&first);
+        if let Some(last) = overlapping {
+            self.0.insert(first, last);
+        }
+    }
+
+    /// Remove the range which starts at `first`, and return its end
+    fn remove_from(&mut self, first: u64) -> Option<u64> {
+        self.0.remove(&first)
+    }
+
+    /// The sequence numbers from `first` to `last` (inclusive) which aren't in
+    /// any range
+    fn gaps(&self, first: u64, last: u64) -> impl Iterator<Item = u64> + '_ {
+        let mut next = Some(first);
+        std::iter::from_fn(move || {
+            loop {
+                let sequence_number = next.filter(|&n| n <= last)?;
+                let covered = self
+                    .0
+                    .range(..=sequence_number)
+                    .next_back()
+                    .map(|(_, &end)| end)
+                    .filter(|&end| end >= sequence_number);
+
+                if let Some(end) = covered {
+                    // skip to the end of the range in a single step
+                    next = end.checked_add(1);
+                } else {
+                    next = sequence_number.checked_add(1);
+                    return Some(sequence_number);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Ranges;
+
+    #[test]
+    fn ranges_merge_when_they_overlap_or_adjoin() {
+        let mut ranges = Ranges::default();
+        ranges.insert(10, 20);
+        ranges.insert(30, 40);
+        ranges.insert(21, 25);
+        ranges.insert(24, 29);
+        assert_eq!(ranges.0.into_iter().collect::<Vec<_>>(), vec![(10, 40)]);
+    }
+
+    #[test]
+    fn gaps_skip_whole_ranges() {
+        let mut ranges = Ranges::default();
+        ranges.insert(2, 3);
+        ranges.insert(5, u64::MAX - 1);
+
+        let gaps: Vec<_> = ranges.gaps(1, u64::MAX).collect();
+        assert_eq!(gaps, vec![1, 4, u64::MAX]);
+    }
+
+    #[test]
+    fn removing_below_splits_a_range() {
+        let mut ranges = Ranges::default();
+        ranges.insert(1, 3);
+        ranges.insert(5, 10);
+        ranges.remove_below(7);
+
+        assert!(!ranges.contains(6));
+        assert!(ranges.contains(7));
+        assert_eq!(ranges.remove_from(7), Some(10));
+    }
+}
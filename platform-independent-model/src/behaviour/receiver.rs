@@ -0,0 +1,569 @@
+//! Contains the [`MessageReceiver`], which interprets incoming [`Message`]s
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    messages::{
+        submessage::{
+            AckNack, Data, DataFrag, Gap, Heartbeat, HeartbeatFrag, InfoDestination, InfoReply,
+            InfoSource, NackFrag,
+        },
+        Message, SubMessage,
+    },
+    structure::{Locator, ProtocolVersion, VendorId},
+};
+
+/// The highest major version of the protocol which can be interpreted
+const SUPPORTED_MAJOR_VERSION: u16 = 2;
+
+/// The [`MessageReceiver`] interprets the [`SubMessage`]s of incoming
+/// [`Message`]s, and dispatches them to the relevant readers and writers.
+///
+/// 'Interpreter' submessages are used to update the [`Context`] of the
+/// receiver, while 'entity' submessages are passed, along with the current
+/// [`Context`], to a [`Dispatch`] implementation.
+///
+/// The receiver doesn't own any sockets, so the same logic can be driven by
+/// any transport.
+///
+/// For details, see the [specification, section 8.3.4](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+#[derive(Debug)]
+pub struct MessageReceiver<P> {
+    guid_prefix: P,
+}
+
+impl<P> MessageReceiver<P>
+where
+    P: Copy + PartialEq,
+{
+    /// Construct a new [`MessageReceiver`] for the participant with the given
+    /// [`Guid`](crate::structure::Guid) prefix
+    #[must_use]
+    pub fn new(guid_prefix: P) -> Self {
+        Self { guid_prefix }
+    }
+
+    /// The [`Guid`](crate::structure::Guid) prefix of the participant which
+    /// owns this receiver
+    #[must_use]
+    pub fn guid_prefix(&self) -> P {
+        self.guid_prefix
+    }
+
+    /// Interpret an incoming [`Message`], dispatching each of its entity
+    /// submessages.
+    ///
+    /// If known, the `source` [`Locator`] is used as the default reply
+    /// locator for the message.
+    ///
+    /// Entity submessages which are addressed to a different participant are
+    /// silently dropped.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the message was encoded with a version of the
+    /// protocol which cannot be interpreted. In this case, none of the
+    /// submessages are dispatched.
+    pub fn receive<Id, D>(
+        &self,
+        source: Option<Locator>,
+        message: Message<P, Id>,
+        dispatcher: &mut D,
+    ) -> Result<(), ReceiveError>
+    where
+        Id: Copy,
+        D: Dispatch<P, Id>,
+    {
+        let (header, submessages) = message.into_parts();
+
+        if let ProtocolVersion::Specified { major, .. } = header.protocol_version() {
+            if major > SUPPORTED_MAJOR_VERSION {
+                return Err(ReceiveError::UnsupportedVersion { major });
+            }
+        }
+
+        let mut context = Context {
+            source_version: header.protocol_version(),
+            source_vendor_id: header.vendor_id(),
+            source_guid_prefix: header.guid_prefix(),
+            dest_guid_prefix: self.guid_prefix,
+            unicast_reply_locators: source.into_iter().collect(),
+            multicast_reply_locators: Vec::default(),
+            timestamp: None,
+        };
+
+        for submessage in submessages {
+            match submessage {
+                SubMessage::InfoSource(info) => context.apply_info_source(&info),
+                SubMessage::InfoDestination(info) => {
+                    context.apply_info_destination(&info, self.guid_prefix);
+                }
+                SubMessage::InfoReply(info) => context.apply_info_reply(info),
+                SubMessage::InfoTimestamp(info) => context.timestamp = info.timestamp,
+                SubMessage::Pad => {}
+                _ if context.dest_guid_prefix != self.guid_prefix => {}
+                SubMessage::Data(data) => {
+                    dispatcher.dispatch_to_reader(data.reader, &context, data.into());
+                }
+                SubMessage::DataFrag(data_frag) => {
+                    dispatcher.dispatch_to_reader(data_frag.reader, &context, data_frag.into());
+                }
+                SubMessage::Heartbeat(heartbeat) => {
+                    dispatcher.dispatch_to_reader(heartbeat.reader, &context, heartbeat.into());
+                }
+                SubMessage::HeartbeatFrag(heartbeat_frag) => {
+                    dispatcher.dispatch_to_reader(
+                        heartbeat_frag.reader,
+                        &context,
+                        heartbeat_frag.into(),
+                    );
+                }
+                SubMessage::Gap(gap) => {
+                    dispatcher.dispatch_to_reader(gap.reader, &context, gap.into());
+                }
+                SubMessage::AckNack(ack_nack) => {
+                    dispatcher.dispatch_to_writer(ack_nack.writer, &context, ack_nack.into());
+                }
+                SubMessage::NackFrag(nack_frag) => {
+                    dispatcher.dispatch_to_writer(nack_frag.writer, &context, nack_frag.into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Errors that can occur when receiving a [`Message`]
+#[derive(Debug, thiserror::Error)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum ReceiveError {
+    /// The message was encoded using a newer major version of the protocol
+    #[error("unsupported protocol version: {major}.x")]
+    UnsupportedVersion {
+        /// The major version of the received message
+        major: u16,
+    },
+}
+
+/// The state of the [`MessageReceiver`] at the time that an entity submessage
+/// is interpreted.
+///
+/// The context is reset at the start of each [`Message`], and modified by any
+/// interpreter submessages which precede the entity submessage.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Context<P> {
+    source_version: ProtocolVersion,
+    source_vendor_id: VendorId,
+    source_guid_prefix: P,
+    dest_guid_prefix: P,
+    unicast_reply_locators: Vec<Locator>,
+    multicast_reply_locators: Vec<Locator>,
+    timestamp: Option<DateTime<Utc>>,
+}
+
+impl<P> Context<P>
+where
+    P: Copy,
+{
+    fn apply_info_source(&mut self, info: &InfoSource<P>) {
+        self.source_version = info.protocol_version;
+        self.source_vendor_id = info.vendor_id;
+        self.source_guid_prefix = info.guid_prefix;
+        self.unicast_reply_locators.clear();
+        self.multicast_reply_locators.clear();
+        self.timestamp = None;
+    }
+
+    fn apply_info_destination(&mut self, info: &InfoDestination<P>, own_guid_prefix: P) {
+        self.dest_guid_prefix = info.guid_prefix.unwrap_or(own_guid_prefix);
+    }
+
+    fn apply_info_reply(&mut self, info: InfoReply) {
+        self.unicast_reply_locators = info.unicast_locators;
+        // without the multicast flag, the multicast reply locators are reset
+        self.multicast_reply_locators = info.multicast_locators.unwrap_or_default();
+    }
+
+    /// The protocol version of the source of the submessage
+    #[must_use]
+    pub fn source_version(&self) -> ProtocolVersion {
+        self.source_version
+    }
+
+    /// The vendor ID of the source of the submessage
+    #[must_use]
+    pub fn source_vendor_id(&self) -> VendorId {
+        self.source_vendor_id
+    }
+
+    /// The [`Guid`](crate::structure::Guid) prefix of the participant which
+    /// sent the submessage
+    ///
+    /// Combined with the writer or reader ID of an entity submessage, this
+    /// gives the [`Guid`](crate::structure::Guid) of the remote entity.
+    #[must_use]
+    pub fn source_guid_prefix(&self) -> P {
+        self.source_guid_prefix
+    }
+
+    /// The [`Guid`](crate::structure::Guid) prefix of the participant which
+    /// the submessage is addressed to
+    #[must_use]
+    pub fn dest_guid_prefix(&self) -> P {
+        self.dest_guid_prefix
+    }
+
+    /// The unicast [`Locator`]s to which any reply should be sent
+    #[must_use]
+    pub fn unicast_reply_locators(&self) -> &[Locator] {
+        &self.unicast_reply_locators
+    }
+
+    /// The multicast [`Locator`]s to which any reply may be sent
+    #[must_use]
+    pub fn multicast_reply_locators(&self) -> &[Locator] {
+        &self.multicast_reply_locators
+    }
+
+    /// The source timestamp of the submessage, if one was provided
+    #[must_use]
+    pub fn timestamp(&self) -> Option<DateTime<Utc>> {
+        self.timestamp
+    }
+
+    /// Returns `true` if a source timestamp was provided for the submessage
+    #[must_use]
+    pub fn have_timestamp(&self) -> bool {
+        self.timestamp.is_some()
+    }
+}
+
+/// An entity submessage which is sent by a writer, to a reader
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReaderSubMessage<Id> {
+    /// see [`Data`]
+    Data(Data<Id>),
+
+    /// see [`DataFrag`]
+    DataFrag(DataFrag<Id>),
+
+    /// see [`Heartbeat`]
+    Heartbeat(Heartbeat<Id>),
+
+    /// see [`HeartbeatFrag`]
+    HeartbeatFrag(HeartbeatFrag<Id>),
+
+    /// see [`Gap`]
+    Gap(Gap<Id>),
+}
+
+impl<Id> ReaderSubMessage<Id>
+where
+    Id: Copy,
+{
+    /// The ID of the writer which sent the submessage
+    #[must_use]
+    pub fn writer(&self) -> Id {
+        match self {
+            Self::Data(submessage) => submessage.writer,
+            Self::DataFrag(submessage) => submessage.writer,
+            Self::Heartbeat(submessage) => submessage.writer,
+            Self::HeartbeatFrag(submessage) => submessage.writer,
+            Self::Gap(submessage) => submessage.writer,
+        }
+    }
+}
+
+/// An entity submessage which is sent by a reader, to a writer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriterSubMessage<Id> {
+    /// see [`AckNack`]
+    AckNack(AckNack<Id>),
+
+    /// see [`NackFrag`]
+    NackFrag(NackFrag<Id>),
+}
+
+impl<Id> WriterSubMessage<Id>
+where
+    Id: Copy,
+{
+    /// The ID of the reader which sent the submessage
+    #[must_use]
+    pub fn reader(&self) -> Id {
+        match self {
+            Self::AckNack(submessage) => submessage.reader,
+            Self::NackFrag(submessage) => submessage.reader,
+        }
+    }
+}
+
+macro_rules! impl_from {
+    ($target:ident: $($variant:ident),*) => {
+        $(
+            impl<Id> From<$variant<Id>> for $target<Id> {
+                fn from(submessage: $variant<Id>) -> Self {
+                    Self::$variant(submessage)
+                }
+            }
+        )*
+    };
+}
+
+impl_from!(ReaderSubMessage: Data, DataFrag, Heartbeat, HeartbeatFrag, Gap);
+impl_from!(WriterSubMessage: AckNack, NackFrag);
+
+/// Trait implemented by the owner of a set of local readers and writers, which
+/// routes entity submessages to the right endpoint.
+pub trait Dispatch<P, Id> {
+    /// Deliver a submessage to a local reader
+    ///
+    /// If `reader` is `None`, the submessage is addressed to every reader which
+    /// is matched with the sending writer.
+    fn dispatch_to_reader(
+        &mut self,
+        reader: Option<Id>,
+        context: &Context<P>,
+        submessage: ReaderSubMessage<Id>,
+    );
+
+    /// Deliver a submessage to a local writer
+    fn dispatch_to_writer(
+        &mut self,
+        writer: Id,
+        context: &Context<P>,
+        submessage: WriterSubMessage<Id>,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Context, Dispatch, MessageReceiver, ReaderSubMessage, WriterSubMessage};
+    use crate::{
+        messages::{
+            submessage::{
+                elements::SequenceNumberSet, AckNack, Data, InfoDestination, InfoReply, InfoSource,
+                InfoTimestamp, Payload,
+            },
+            Header, Message, SubMessage,
+        },
+        structure::{Locator, ProtocolVersion, VendorId},
+    };
+    use chrono::{TimeZone, Utc};
+    use std::{
+        net::{Ipv4Addr, SocketAddrV4},
+        num::NonZeroU64,
+    };
+    use vec1::vec1;
+
+    type Prefix = [u8; 12];
+    type Id = [u8; 4];
+
+    const LOCAL: Prefix = [1; 12];
+    const REMOTE: Prefix = [2; 12];
+
+    #[derive(Default)]
+    struct Recorder {
+        readers: Vec<(Option<Id>, Context<Prefix>, ReaderSubMessage<Id>)>,
+        writers: Vec<(Id, Context<Prefix>, WriterSubMessage<Id>)>,
+    }
+
+    impl Dispatch<Prefix, Id> for Recorder {
+        fn dispatch_to_reader(
+            &mut self,
+            reader: Option<Id>,
+            context: &Context<Prefix>,
+            submessage: ReaderSubMessage<Id>,
+        ) {
+            self.readers.push((reader, context.clone(), submessage));
+        }
+
+        fn dispatch_to_writer(
+            &mut self,
+            writer: Id,
+            context: &Context<Prefix>,
+            submessage: WriterSubMessage<Id>,
+        ) {
+            self.writers.push((writer, context.clone(), submessage));
+        }
+    }
+
+    fn data(sequence_number: u64) -> Data<Id> {
+        Data {
+            reader: None,
+            writer: [0, 0, 1, 2],
+            writer_sequence_number: sequence_number,
+            inline_qos: None,
+            payload: Some(Payload::Data(vec![1, 2, 3])),
+            non_standard_payload: false,
+        }
+    }
+
+    fn locator(port: u16) -> Locator {
+        SocketAddrV4::new(Ipv4Addr::LOCALHOST, port).into()
+    }
+
+    #[test]
+    fn applies_interpreter_submessages() {
+        let timestamp = Utc.timestamp_opt(1_000, 0).unwrap();
+        let relayed: Prefix = [3; 12];
+        let message = Message::new(
+            Header::new(ProtocolVersion::Latest, VendorId::Unknown, REMOTE),
+            vec1![
+                data(1).into(),
+                InfoTimestamp {
+                    timestamp: Some(timestamp)
+                }
+                .into(),
+                InfoReply {
+                    unicast_locators: vec![locator(1234)],
+                    multicast_locators: None,
+                }
+                .into(),
+                data(2).into(),
+                InfoSource {
+                    protocol_version: ProtocolVersion::Latest,
+                    vendor_id: VendorId::Known([1, 2]),
+                    guid_prefix: relayed,
+                }
+                .into(),
+                data(3).into(),
+            ],
+        );
+
+        let receiver = MessageReceiver::new(LOCAL);
+        let mut recorder = Recorder::default();
+        receiver
+            .receive(Some(locator(7400)), message, &mut recorder)
+            .unwrap();
+
+        assert_eq!(recorder.readers.len(), 3);
+
+        let (_, first, _) = &recorder.readers[0];
+        assert_eq!(first.source_guid_prefix(), REMOTE);
+        assert_eq!(first.unicast_reply_locators(), &[locator(7400)]);
+        assert!(!first.have_timestamp());
+
+        let (_, second, _) = &recorder.readers[1];
+        assert_eq!(second.timestamp(), Some(timestamp));
+        assert_eq!(second.unicast_reply_locators(), &[locator(1234)]);
+
+        let (_, third, _) = &recorder.readers[2];
+        assert_eq!(third.source_guid_prefix(), relayed);
+        assert_eq!(third.source_vendor_id(), VendorId::Known([1, 2]));
+        assert!(third.unicast_reply_locators().is_empty());
+        assert!(!third.have_timestamp());
+    }
+
+    #[test]
+    fn info_reply_resets_multicast_reply_locators() {
+        let message = Message::new(
+            Header::new(ProtocolVersion::Latest, VendorId::Unknown, REMOTE),
+            vec1![
+                InfoReply {
+                    unicast_locators: vec![locator(1234)],
+                    multicast_locators: Some(vec![locator(1235)]),
+                }
+                .into(),
+                data(1).into(),
+                InfoReply {
+                    unicast_locators: vec![locator(1236)],
+                    multicast_locators: None,
+                }
+                .into(),
+                data(2).into(),
+            ],
+        );
+
+        let mut recorder = Recorder::default();
+        MessageReceiver::new(LOCAL)
+            .receive(None, message, &mut recorder)
+            .unwrap();
+
+        let (_, first, _) = &recorder.readers[0];
+        assert_eq!(first.multicast_reply_locators(), &[locator(1235)]);
+
+        let (_, second, _) = &recorder.readers[1];
+        assert_eq!(second.unicast_reply_locators(), &[locator(1236)]);
+        assert!(second.multicast_reply_locators().is_empty());
+    }
+
+    #[test]
+    fn drops_submessages_for_other_participants() {
+        let message = Message::new(
+            Header::new(ProtocolVersion::Latest, VendorId::Unknown, REMOTE),
+            vec1![
+                InfoDestination {
+                    guid_prefix: Some([9; 12])
+                }
+                .into(),
+                data(1).into(),
+                InfoDestination {
+                    guid_prefix: Some(LOCAL)
+                }
+                .into(),
+                data(2).into(),
+                InfoDestination { guid_prefix: None }.into(),
+                data(3).into(),
+            ],
+        );
+
+        let receiver = MessageReceiver::new(LOCAL);
+        let mut recorder = Recorder::default();
+        receiver.receive(None, message, &mut recorder).unwrap();
+
+        let sequence_numbers: Vec<_> = recorder
+            .readers
+            .iter()
+            .map(|(_, _, submessage)| match submessage {
+                ReaderSubMessage::Data(data) => data.writer_sequence_number,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(sequence_numbers, vec![2, 3]);
+    }
+
+    #[test]
+    fn routes_acknack_to_writer() {
+        let ack_nack: SubMessage<Prefix, Id> = AckNack {
+            reader: [0, 0, 1, 7],
+            writer: [0, 0, 1, 2],
+            reader_sequence_number_state: SequenceNumberSet::new(NonZeroU64::new(1).unwrap()),
+            count: 1,
+            final_flag: false,
+        }
+        .into();
+        let message = Message::new(
+            Header::new(ProtocolVersion::Latest, VendorId::Unknown, REMOTE),
+            vec1![ack_nack],
+        );
+
+        let receiver = MessageReceiver::new(LOCAL);
+        let mut recorder = Recorder::default();
+        receiver.receive(None, message, &mut recorder).unwrap();
+
+        assert!(recorder.readers.is_empty());
+        let (writer, context, submessage) = &recorder.writers[0];
+        assert_eq!(writer, &[0, 0, 1, 2]);
+        assert_eq!(context.source_guid_prefix(), REMOTE);
+        assert_eq!(submessage.reader(), [0, 0, 1, 7]);
+    }
+
+    #[test]
+    fn rejects_unsupported_version() {
+        let message: Message<Prefix, Id> = Message::new(
+            Header::new(
+                ProtocolVersion::Specified { major: 3, minor: 0 },
+                VendorId::Unknown,
+                REMOTE,
+            ),
+            vec1![data(1).into()],
+        );
+
+        let receiver = MessageReceiver::new(LOCAL);
+        let mut recorder = Recorder::default();
+        assert!(receiver.receive(None, message, &mut recorder).is_err());
+        assert!(recorder.readers.is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+//! Behaviour of the RTPS writer endpoints
+//!
+//! See [section 8.4.7](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF) of the relevant specification document.
+
+use std::num::NonZeroU64;
+
+use crate::{
+    messages::{
+        builder::TooLargeError,
+        submessage::{
+            self,
+            elements::{parameter_id, status_info, Parameter, ParameterList, SequenceNumberSet},
+            Gap, Payload,
+        },
+        Message,
+    },
+    structure::{
+        history::{Change, Kind},
+        KeyHash, Locator,
+    },
+};
+
+pub mod fragmentation;
+pub mod reader_locator;
+pub mod reader_proxy;
+pub mod stateful;
+pub mod stateless;
+
+#[doc(inline)]
+pub use reader_locator::ReaderLocator;
+#[doc(inline)]
+pub use reader_proxy::ReaderProxy;
+#[doc(inline)]
+pub use stateful::StatefulWriter;
+#[doc(inline)]
+pub use stateless::StatelessWriter;
+
+/// The default maximum size of a [`Message`], in bytes
+///
+/// This is the largest payload of a UDP datagram over IPv4.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 65_507;
+
+/// The destination of the [`Message`]s produced by a writer
+///
+/// Writers don't own any sockets. Instead, each [`Message`] is handed to an
+/// [`Output`] along with the [`Locator`] it should be sent to, so that the
+/// same logic can be driven by any transport.
+pub trait Output<P, Id> {
+    /// Send a [`Message`] to the given [`Locator`]
+    fn send(&mut self, locator: Locator, message: Message<P, Id>);
+}
+
+/// Collects every [`Message`] along with its destination, which is useful for
+/// testing
+impl<P, Id> Output<P, Id> for Vec<(Locator, Message<P, Id>)> {
+    fn send(&mut self, locator: Locator, message: Message<P, Id>) {
+        self.push((locator, message));
+    }
+}
+
+/// The error returned when a writer which adds changes to its cache as it
+/// sends fails
+#[derive(Debug, thiserror::Error)]
+pub enum SendError<E>
+where
+    E: std::error::Error + 'static,
+{
+    /// A change couldn't be added to the cache
+    #[error("failed to add the change to the cache")]
+    Cache(#[source] E),
+
+    /// A submessage didn't fit in a message of the maximum size
+    #[error(transparent)]
+    TooLarge(#[from] TooLargeError),
+}
+
+/// Construct the [`Data`](submessage::Data) submessage which communicates a
+/// [`Change`] to a reader
+///
+/// The key hash is always sent with a change to a keyed instance, since it is
+/// how the reader identifies the instance. Otherwise, it is only sent to
+/// readers which expect inline quality of service parameters.
+///
+/// Returns `None` for changes which are not sent to readers.
+pub(crate) fn data_submessage<Data, P, Id>(
+    writer: Id,
+    reader: Option<Id>,
+    sequence_number: u64,
+    change: &Change<Data, P, Id>,
+    expects_inline_qos: bool,
+) -> Option<submessage::Data<Id>>
+where
+    Data: AsRef<[u8]>,
+    P: Copy,
+    Id: Copy,
+{
+    let key_hash = Parameter::new(parameter_id::KEY_HASH, change.instance().as_ref().to_vec());
+    let status_info = |flags| Parameter::new(parameter_id::STATUS_INFO, vec![0, 0, 0, flags]);
+
+    let (inline_qos, payload) = match change.kind() {
+        Kind::Alive(data) => {
+            let keyed = change.instance() != KeyHash::default();
+            let inline_qos = if keyed || expects_inline_qos {
+                Some(std::iter::once(key_hash).collect::<ParameterList>())
+            } else {
+                None
+            };
+            (inline_qos, Some(Payload::Data(data.as_ref().to_vec())))
+        }
+        Kind::AliveFiltered => return None,
+        Kind::NotAliveDisposed => (
+            Some(
+                vec![key_hash, status_info(status_info::DISPOSED)]
+                    .into_iter()
+                    .collect(),
+            ),
+            None,
+        ),
+        Kind::NotAliveUnregistered => (
+            Some(
+                vec![key_hash, status_info(status_info::UNREGISTERED)]
+                    .into_iter()
+                    .collect(),
+            ),
+            None,
+        ),
+    };
+
+    Some(submessage::Data {
+        reader,
+        writer,
+        writer_sequence_number: sequence_number,
+        inline_qos,
+        payload,
+        non_standard_payload: false,
+    })
+}
+
+/// Construct the [`Gap`] submessages which mark the given sequence numbers as
+/// irrelevant
+///
+/// The sequence numbers must be in increasing order.
+pub(crate) fn gap_submessages<Id>(
+    writer: Id,
+    reader: Option<Id>,
+    sequence_numbers: &[u64],
+) -> Vec<Gap<Id>>
+where
+    Id: Copy,
+{
+    let mut gaps = Vec::new();
+    let mut remaining = sequence_numbers.iter().copied().peekable();
+
+    while let Some(gap_start) = remaining.next() {
+        // the contiguous range starting at `gap_start`
+        let mut end = gap_start;
+        while remaining.peek() == Some(&(end + 1)) {
+            end += 1;
+            remaining.next();
+        }
+
+        let base = NonZeroU64::new(end + 1).expect("sequence numbers start at 1");
+        let mut gap_list = SequenceNumberSet::new(base);
+        while let Some(&sequence_number) = remaining.peek() {
+            if gap_list.insert_value(sequence_number).is_err() {
+                break;
+            }
+            remaining.next();
+        }
+
+        gaps.push(Gap {
+            reader,
+            writer,
+            gap_start,
+            gap_list,
+        });
+    }
+
+    gaps
+}
@@ -0,0 +1,275 @@
+//! Splitting changes which are too large for a single message into
+//! `DATA_FRAG` submessages
+//!
+//! The serialized payload of a change is divided into fixed-size fragments,
+//! numbered from 1. Every fragment except the last is exactly the fragment
+//! size. As many consecutive fragments as fit in a message are packed into
+//! each `DATA_FRAG`.
+//!
+//! Only complete changes are fragmented. A sample which is still being
+//! written can't be sent until its payload is complete.
+//!
+//! See [section 8.4.14.1](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF) of the relevant specification document.
+
+use std::{convert::TryFrom, num::NonZeroU32};
+
+use crate::messages::{
+    builder::TooLargeError,
+    submessage::{Data, DataFrag, Payload},
+    MessageBuilder, SubMessage,
+};
+
+/// The default size of each fragment, in bytes
+///
+/// This is small enough that a `DATA_FRAG` carrying a single fragment fits in
+/// an Ethernet frame.
+pub const DEFAULT_FRAGMENT_SIZE: u16 = 1344;
+
+/// The number of fragments needed to send a payload of the given size
+#[must_use]
+pub fn fragment_count(sample_size: usize, fragment_size: u16) -> u32 {
+    let fragment_size = usize::from(fragment_size.max(1));
+    u32::try_from((sample_size + fragment_size - 1) / fragment_size).unwrap_or(u32::MAX)
+}
+
+/// Push a [`Data`] submessage to the builder, splitting it into `DATA_FRAG`s
+/// if it doesn't fit in a single message
+///
+/// Returns the number of fragments if the change was fragmented.
+///
+/// # Errors
+///
+/// This function will fail if the submessage doesn't fit in a message, and
+/// can't be fragmented because it has no payload, or if even a single
+/// fragment doesn't fit.
+pub(crate) fn push_data<P, Id>(
+    builder: &mut MessageBuilder<P, Id>,
+    destination: Option<P>,
+    data: Data<Id>,
+    fragment_size: u16,
+) -> Result<Option<u32>, TooLargeError>
+where
+    P: Copy + PartialEq,
+    Id: Copy,
+{
+    let fits = SubMessage::<P, Id>::from(data.clone()).serialized_size()
+        <= builder.capacity(destination, None);
+
+    match &data.payload {
+        Some(payload) if !fits => {
+            let total = fragment_count(payload.as_bytes().len(), fragment_size);
+            push_fragments(builder, destination, &data, fragment_size, 1..=total)
+        }
+        _ => builder.push(destination, None, data.into()).map(|()| None),
+    }
+}
+
+/// Push the given fragments of a [`Data`] submessage to the builder as
+/// `DATA_FRAG`s
+///
+/// The fragment size is reduced if a single fragment wouldn't fit in a
+/// message. Fragment numbers which are out of range are ignored.
+///
+/// Returns the total number of fragments in the change, or `None` if the
+/// change has no payload to fragment.
+///
+/// # Errors
+///
+/// This function will fail if the payload is too large to be fragmented, or
+/// if there isn't room for a single fragment in a message.
+pub(crate) fn push_fragments<P, Id>(
+    builder: &mut MessageBuilder<P, Id>,
+    destination: Option<P>,
+    data: &Data<Id>,
+    fragment_size: u16,
+    fragments: impl IntoIterator<Item = u32>,
+) -> Result<Option<u32>, TooLargeError>
+where
+    P: Copy + PartialEq,
+    Id: Copy,
+{
+    let payload = match &data.payload {
+        Some(payload) => payload,
+        None => return Ok(None),
+    };
+    let bytes = payload.as_bytes();
+    let sample_size = u32::try_from(bytes.len())
+        .map_err(|_| TooLargeError::new(bytes.len(), builder.max_size()))?;
+
+    let empty = DataFrag {
+        reader: data.reader,
+        writer: data.writer,
+        writer_sequence_number: data.writer_sequence_number,
+        fragment_starting_number: NonZeroU32::new(1).expect("one is non-zero"),
+        fragment_size: 0,
+        sample_size,
+        inline_qos: data.inline_qos.clone(),
+        key: matches!(payload, Payload::Key(_)),
+        non_standard_payload: data.non_standard_payload,
+        fragments: Vec::new(),
+    };
+
+    // fragments are padded to a multiple of 4 bytes
+    let overhead = SubMessage::<P, Id>::from(empty.clone()).serialized_size();
+    let room = builder.capacity(destination, None).saturating_sub(overhead) & !3;
+    let fragment_size = u16::try_from(room).unwrap_or(u16::MAX).min(fragment_size);
+    if fragment_size == 0 {
+        return Err(TooLargeError::new(overhead + 4, builder.max_size()));
+    }
+
+    let total = fragment_count(bytes.len(), fragment_size);
+    let per_submessage = u32::try_from(room / usize::from(fragment_size)).unwrap_or(u32::MAX);
+
+    let mut fragments: Vec<u32> = fragments
+        .into_iter()
+        .filter(|&n| n >= 1 && n <= total)
+        .collect();
+    fragments.sort_unstable();
+    fragments.dedup();
+
+    let mut remaining = fragments.into_iter().peekable();
+    while let Some(first) = remaining.next() {
+        // consecutive fragments which fit in a single submessage
+        let mut last = first;
+        while last - first + 1 < per_submessage && remaining.peek() == Some(&(last + 1)) {
+            last += 1;
+            remaining.next();
+        }
+
+        let start = (first as usize - 1) * usize::from(fragment_size);
+        let end = (last as usize * usize::from(fragment_size)).min(bytes.len());
+
+        let data_frag = DataFrag {
+            fragment_starting_number: NonZeroU32::new(first).expect("fragments start at 1"),
+            fragment_size,
+            fragments: bytes[start..end].to_vec(),
+            ..empty.clone()
+        };
+        builder.push(destination, None, data_frag.into())?;
+    }
+
+    Ok(Some(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fragment_count, push_data, push_fragments};
+    use crate::messages::{
+        submessage::{Data, DataFrag, Payload},
+        Header, Message, MessageBuilder, SubMessage,
+    };
+    use crate::structure::{ProtocolVersion, VendorId};
+    use test_case::test_case;
+
+    type Prefix = [u8; 12];
+    type Id = [u8; 4];
+
+    fn builder(max_size: usize) -> MessageBuilder<Prefix, Id> {
+        let header = Header::new(ProtocolVersion::Latest, VendorId::Unknown, [0; 12]);
+        MessageBuilder::new(header, max_size)
+    }
+
+    fn data(payload: Vec<u8>) -> Data<Id> {
+        Data {
+            reader: None,
+            writer: [0, 0, 1, 2],
+            writer_sequence_number: 1,
+            inline_qos: None,
+            payload: Some(Payload::Data(payload)),
+            non_standard_payload: false,
+        }
+    }
+
+    fn data_frags(builder: MessageBuilder<Prefix, Id>) -> Vec<DataFrag<Id>> {
+        builder
+            .finish()
+            .iter()
+            .flat_map(Message::submessages)
+            .filter_map(|submessage| match submessage {
+                SubMessage::DataFrag(data_frag) => Some(data_frag.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test_case(0, 10 => 0)]
+    #[test_case(10, 10 => 1)]
+    #[test_case(11, 10 => 2)]
+    #[test_case(100, 10 => 10)]
+    fn count(sample_size: usize, fragment_size: u16) -> u32 {
+        fragment_count(sample_size, fragment_size)
+    }
+
+    #[test]
+    fn small_changes_are_not_fragmented() {
+        let mut builder = builder(1500);
+        assert_eq!(
+            push_data(&mut builder, None, data(vec![1; 100]), 64).unwrap(),
+            None
+        );
+        assert!(data_frags(builder).is_empty());
+    }
+
+    #[test]
+    fn packs_fragments_into_messages() {
+        let payload: Vec<u8> = (0..=255).cycle().take(1000).collect();
+
+        // room for 3 fragments of 64 bytes in each message
+        let mut builder = builder(20 + 32 + 3 * 64 + 10);
+        assert_eq!(
+            push_data(&mut builder, None, data(payload.clone()), 64).unwrap(),
+            Some(16)
+        );
+
+        let data_frags = data_frags(builder);
+        assert_eq!(data_frags.len(), 6);
+        assert!(
+            data_frags
+                .iter()
+                .all(|data_frag| data_frag.fragment_size == 64 && data_frag.sample_size == 1000)
+        );
+
+        let reassembled: Vec<u8> = data_frags
+            .iter()
+            .flat_map(|data_frag| data_frag.fragments.clone())
+            .collect();
+        assert_eq!(reassembled, payload);
+        assert_eq!(data_frags[5].fragments_in_submessage(), 1);
+        assert_eq!(data_frags[5].fragments.len(), 1000 - 15 * 64);
+    }
+
+    #[test]
+    fn fails_if_a_fragment_does_not_fit() {
+        let mut builder = builder(48);
+        assert!(push_data(&mut builder, None, data(vec![1; 100]), 64).is_err());
+        assert!(builder.finish().is_empty());
+    }
+
+    #[test]
+    fn sends_only_the_requested_fragments() {
+        let payload: Vec<u8> = (0..=255).cycle().take(1000).collect();
+
+        let mut builder = builder(1500);
+        push_fragments(
+            &mut builder,
+            None,
+            &data(payload.clone()),
+            64,
+            vec![9, 2, 3, 40],
+        )
+        .unwrap();
+
+        let data_frags = data_frags(builder);
+        let sent: Vec<_> = data_frags
+            .iter()
+            .map(|data_frag| {
+                (
+                    data_frag.fragment_starting_number.get(),
+                    data_frag.fragments_in_submessage(),
+                )
+            })
+            .collect();
+        assert_eq!(sent, vec![(2, 2), (9, 1)]);
+        assert_eq!(data_frags[1].fragments, payload[512..576].to_vec());
+    }
+}
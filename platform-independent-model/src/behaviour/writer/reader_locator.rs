@@ -0,0 +1,71 @@
+//! Contains the [`ReaderLocator`], which tracks the changes sent to a
+//! [`Locator`]
+
+use crate::structure::{history::Cache, Locator};
+
+/// Tracks which changes have been sent to a [`Locator`] by a
+/// [`StatelessWriter`](super::StatelessWriter)
+///
+/// A stateless writer doesn't know which readers are listening on a
+/// [`Locator`], so it simply sends every change to it in order of increasing
+/// sequence number.
+///
+/// For details, see the [specification, section 8.4.7.5](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReaderLocator {
+    locator: Locator,
+    expects_inline_qos: bool,
+    highest_sent_sequence_number: u64,
+}
+
+impl ReaderLocator {
+    /// Construct a new [`ReaderLocator`]
+    ///
+    /// If `expects_inline_qos` is `true`, every change sent to the locator
+    /// includes its inline quality of service parameters.
+    #[must_use]
+    pub fn new(locator: Locator, expects_inline_qos: bool) -> Self {
+        Self {
+            locator,
+            expects_inline_qos,
+            highest_sent_sequence_number: 0,
+        }
+    }
+
+    /// The [`Locator`] to which changes are sent
+    #[must_use]
+    pub fn locator(&self) -> Locator {
+        self.locator
+    }
+
+    /// Whether changes sent to the locator include inline quality of service
+    /// parameters
+    #[must_use]
+    pub fn expects_inline_qos(&self) -> bool {
+        self.expects_inline_qos
+    }
+
+    /// The sequence number of the next change in the [`Cache`] which has not
+    /// yet been sent to the locator
+    pub fn next_unsent_change<Data, C>(&self, cache: &C) -> Option<u64>
+    where
+        C: Cache<Data, SqnN = u64>,
+    {
+        let start = cache
+            .min_sequence_number()?
+            .max(self.highest_sent_sequence_number + 1);
+        let end = cache.max_sequence_number()?;
+
+        (start..=end).find(|&sequence_number| cache.get(sequence_number).is_some())
+    }
+
+    /// Record that the change with the given sequence number has been sent
+    pub fn mark_sent(&mut self, sequence_number: u64) {
+        self.highest_sent_sequence_number = self.highest_sent_sequence_number.max(sequence_number);
+    }
+
+    /// Mark every change as unsent, so that they are all sent again
+    pub fn unsent_changes_reset(&mut self) {
+        self.highest_sent_sequence_number = 0;
+    }
+}
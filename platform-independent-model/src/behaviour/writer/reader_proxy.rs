@@ -0,0 +1,283 @@
+//! Contains the [`ReaderProxy`], which tracks the state of a remote reader
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{Duration, Instant},
+};
+
+use crate::structure::{history::Cache, Guid, Locator};
+
+/// Tracks the state of a remote reader which is matched with a
+/// [`StatefulWriter`](super::StatefulWriter)
+///
+/// The proxy keeps track of which changes have been sent to the reader, which
+/// have been acknowledged, and which the reader has requested again.
+///
+/// For details, see the [specification, section 8.4.7.4](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+#[derive(Debug, Clone)]
+pub struct ReaderProxy<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    remote_reader_guid: Guid<P, Id>,
+    expects_inline_qos: bool,
+    unicast_locators: Vec<Locator>,
+    multicast_locators: Vec<Locator>,
+    highest_sent_sequence_number: u64,
+    highest_acked_sequence_number: u64,
+    requested_changes: BTreeSet<u64>,
+    requested_fragments: BTreeMap<u64, BTreeSet<u32>>,
+    sent_at: BTreeMap<u64, Instant>,
+    repair_deadline: Option<Instant>,
+    last_acknack_count: Option<u32>,
+    last_nack_frag_count: Option<u32>,
+}
+
+impl<P, Id> ReaderProxy<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    /// Construct a new [`ReaderProxy`] for the remote reader with the given
+    /// [`Guid`]
+    ///
+    /// Changes are sent to the unicast [`Locator`]s of the reader, or to its
+    /// multicast [`Locator`]s if it has none.
+    #[must_use]
+    pub fn new(
+        remote_reader_guid: Guid<P, Id>,
+        expects_inline_qos: bool,
+        unicast_locators: Vec<Locator>,
+        multicast_locators: Vec<Locator>,
+    ) -> Self {
+        Self {
+            remote_reader_guid,
+            expects_inline_qos,
+            unicast_locators,
+            multicast_locators,
+            highest_sent_sequence_number: 0,
+            highest_acked_sequence_number: 0,
+            requested_changes: BTreeSet::default(),
+            requested_fragments: BTreeMap::default(),
+            sent_at: BTreeMap::default(),
+            repair_deadline: None,
+            last_acknack_count: None,
+            last_nack_frag_count: None,
+        }
+    }
+
+    /// The [`Guid`] of the remote reader
+    #[must_use]
+    pub fn remote_reader_guid(&self) -> Guid<P, Id> {
+        self.remote_reader_guid
+    }
+
+    /// Whether changes sent to the reader include inline quality of service
+    /// parameters
+    #[must_use]
+    pub fn expects_inline_qos(&self) -> bool {
+        self.expects_inline_qos
+    }
+
+    /// The unicast [`Locator`]s of the reader
+    #[must_use]
+    pub fn unicast_locators(&self) -> &[Locator] {
+        &self.unicast_locators
+    }
+
+    /// The multicast [`Locator`]s of the reader
+    #[must_use]
+    pub fn multicast_locators(&self) -> &[Locator] {
+        &self.multicast_locators
+    }
+
+    /// The [`Locator`]s to which messages for the reader are sent
+    #[must_use]
+    pub fn locators(&self) -> &[Locator] {
+        if self.unicast_locators.is_empty() {
+            &self.multicast_locators
+        } else {
+            &self.unicast_locators
+        }
+    }
+
+    /// The highest sequence number which the reader has acknowledged. Every
+    /// change up to and including this one has been received.
+    #[must_use]
+    pub fn highest_acked_sequence_number(&self) -> u64 {
+        self.highest_acked_sequence_number
+    }
+
+    /// The highest sequence number which has been sent to the reader
+    #[must_use]
+    pub fn highest_sent_sequence_number(&self) -> u64 {
+        self.highest_sent_sequence_number
+    }
+
+    /// Record that the reader has received every change up to and including
+    /// the given sequence number
+    pub fn acked_changes_set(&mut self, committed_sequence_number: u64) {
+        self.highest_acked_sequence_number = self
+            .highest_acked_sequence_number
+            .max(committed_sequence_number);
+
+        let acked = self.highest_acked_sequence_number;
+        self.requested_changes.retain(|&n| n > acked);
+        self.requested_fragments = self.requested_fragments.split_off(&(acked + 1));
+        self.sent_at = self.sent_at.split_off(&(acked + 1));
+
+        // a reader can't have received changes which were never sent
+        self.highest_sent_sequence_number = self.highest_sent_sequence_number.max(acked);
+    }
+
+    /// Record that the reader has requested the given changes again
+    ///
+    /// Changes which were sent less than `suppression` before `now` are
+    /// ignored, since the request probably crossed with the change on the
+    /// wire. Requests for changes after `last_sequence_number`, which the
+    /// writer hasn't written yet, are also ignored.
+    pub fn requested_changes_set(
+        &mut self,
+        sequence_numbers: impl IntoIterator<Item = u64>,
+        last_sequence_number: u64,
+        now: Instant,
+        suppression: Duration,
+    ) {
+        for sequence_number in sequence_numbers {
+            let suppressed = self
+                .sent_at
+                .get(&sequence_number)
+                .map_or(false, |&sent_at| now < sent_at + suppression);
+
+            if sequence_number > self.highest_acked_sequence_number
+                && sequence_number <= last_sequence_number
+                && !suppressed
+            {
+                self.requested_changes.insert(sequence_number);
+                // the whole change will be sent again
+                self.requested_fragments.remove(&sequence_number);
+            }
+        }
+    }
+
+    /// Record that the reader has requested the given fragments of a change
+    /// again
+    ///
+    /// Requests for changes which have been acknowledged, or which are
+    /// already going to be sent again in full, are ignored.
+    pub fn requested_fragments_set(
+        &mut self,
+        sequence_number: u64,
+        fragment_numbers: impl IntoIterator<Item = u32>,
+    ) {
+        if sequence_number <= self.highest_acked_sequence_number
+            || self.requested_changes.contains(&sequence_number)
+        {
+            return;
+        }
+
+        self.requested_fragments
+            .entry(sequence_number)
+            .or_default()
+            .extend(fragment_numbers);
+    }
+
+    /// Remove and return the lowest change for which the reader has requested
+    /// fragments, along with the requested fragment numbers in order
+    pub fn next_requested_fragments(&mut self) -> Option<(u64, Vec<u32>)> {
+        let next = self.requested_fragments.keys().next().copied()?;
+        let fragment_numbers = self.requested_fragments.remove(&next)?;
+        Some((next, fragment_numbers.into_iter().collect()))
+    }
+
+    /// The changes which the reader has requested again, in order
+    pub fn requested_changes(&self) -> impl Iterator<Item = u64> + '_ {
+        self.requested_changes.iter().copied()
+    }
+
+    /// Remove and return the lowest change which the reader has requested
+    pub fn next_requested_change(&mut self) -> Option<u64> {
+        let next = self.requested_changes.iter().next().copied()?;
+        self.requested_changes.remove(&next);
+        Some(next)
+    }
+
+    /// The sequence number of the next change which has not yet been sent to
+    /// the reader, up to and including `last_sequence_number`
+    ///
+    /// This includes sequence numbers which are no longer in the cache, for
+    /// which a `GAP` must be sent.
+    #[must_use]
+    pub fn next_unsent_change(&self, last_sequence_number: u64) -> Option<u64> {
+        let next = self.highest_sent_sequence_number + 1;
+        if next <= last_sequence_number {
+            Some(next)
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if there are changes in the [`Cache`] which the reader
+    /// has not yet acknowledged
+    pub fn has_unacked_changes<Data, C>(&self, cache: &C) -> bool
+    where
+        C: Cache<Data, SqnN = u64>,
+    {
+        cache
+            .max_sequence_number()
+            .map_or(false, |max| max > self.highest_acked_sequence_number)
+    }
+
+    /// Record that a change was sent to the reader at the given time
+    pub fn mark_sent(&mut self, sequence_number: u64, now: Instant) {
+        self.highest_sent_sequence_number = self.highest_sent_sequence_number.max(sequence_number);
+        self.sent_at.insert(sequence_number, now);
+    }
+
+    /// The time at which the requested changes are due to be sent, if any
+    #[must_use]
+    pub fn repair_deadline(&self) -> Option<Instant> {
+        self.repair_deadline
+    }
+
+    pub(crate) fn schedule_repair(&mut self, deadline: Instant) {
+        let requested = !self.requested_changes.is_empty() || !self.requested_fragments.is_empty();
+        if requested && self.repair_deadline.is_none() {
+            self.repair_deadline = Some(deadline);
+        }
+    }
+
+    pub(crate) fn take_repair(&mut self, now: Instant) -> bool {
+        match self.repair_deadline {
+            Some(deadline) if now >= deadline => {
+                self.repair_deadline = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the count of an `ACKNACK` has not been seen before,
+    /// and records it
+    pub(crate) fn is_new_acknack(&mut self, count: u32) -> bool {
+        if self.last_acknack_count.map_or(false, |last| count <= last) {
+            return false;
+        }
+        self.last_acknack_count = Some(count);
+        true
+    }
+
+    /// Returns `true` if the count of a `NACK_FRAG` has not been seen before,
+    /// and records it
+    pub(crate) fn is_new_nack_frag(&mut self, count: u32) -> bool {
+        if self
+            .last_nack_frag_count
+            .map_or(false, |last| count <= last)
+        {
+            return false;
+        }
+        self.last_nack_frag_count = Some(count);
+        true
+    }
+}
@@ -0,0 +1,1141 @@
+//! Contains the reliable [`StatefulWriter`] and its [`Builder`]
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use super::{
+    data_submessage,
+    fragmentation::{push_data, push_fragments, DEFAULT_FRAGMENT_SIZE},
+    gap_submessages, Output, ReaderProxy, DEFAULT_MAX_MESSAGE_SIZE,
+};
+use crate::{
+    behaviour::receiver::{Context, WriterSubMessage},
+    messages::{
+        builder::TooLargeError,
+        submessage::{self, Heartbeat, HeartbeatFrag},
+        Header, MessageBuilder,
+    },
+    structure::{
+        history::{Cache, Change, Kind},
+        Guid, KeyHash, ProtocolVersion, VendorId,
+    },
+};
+
+/// The default period between `HEARTBEAT`s
+pub const DEFAULT_HEARTBEAT_PERIOD: Duration = Duration::from_secs(3);
+
+/// The default delay before responding to a negative acknowledgement
+pub const DEFAULT_NACK_RESPONSE_DELAY: Duration = Duration::from_millis(200);
+
+/// The default duration for which negative acknowledgements are ignored after
+/// a change is sent
+pub const DEFAULT_NACK_SUPPRESSION_DURATION: Duration = Duration::from_secs(0);
+
+/// A reliable writer which keeps track of the state of each matched remote
+/// reader using a [`ReaderProxy`].
+///
+/// New changes are written to the history [`Cache`] with
+/// [`StatefulWriter::new_change`]. In push mode (the default), they are sent
+/// to every matched reader the next time [`StatefulWriter::send`] is called.
+///
+/// Readers are periodically sent a `HEARTBEAT` while they have unacknowledged
+/// changes. When a reader responds with an `ACKNACK` requesting changes
+/// again, they are repaired after the nack response delay. Changes which are
+/// no longer in the cache are marked as irrelevant with a `GAP`.
+///
+/// Changes which are too large to fit in a single message are split into
+/// `DATA_FRAG`s. A `HEARTBEAT_FRAG` announcing the available fragments is
+/// sent with each `HEARTBEAT` while such a change is unacknowledged, and
+/// fragments requested with a `NACK_FRAG` are repaired individually.
+///
+/// Samples which are still being written are not supported. A change is only
+/// added to the cache once its payload is complete, so a `HEARTBEAT_FRAG`
+/// always announces every fragment of the change. It serves to prompt the
+/// reader to request the fragments it is missing with a `NACK_FRAG`, rather
+/// than the whole change with an `ACKNACK`.
+///
+/// The liveliness of a writer with
+/// [`LivelinessKind::ManualByTopic`](crate::behaviour::liveliness::LivelinessKind::ManualByTopic)
+/// can be asserted without writing data, using
+/// [`StatefulWriter::assert_liveliness`].
+///
+/// The writer doesn't own any sockets or timers. The current time is passed
+/// to each method which depends on it, so the writer can be driven by any
+/// clock, and messages are produced through an [`Output`].
+///
+/// For details, see the [specification, section 8.4.9](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+///
+/// # Example
+///
+/// ```
+/// use rtps_pim::{
+///     behaviour::writer::{ReaderProxy, StatefulWriter},
+///     messages::Message,
+///     structure::{
+///         history::{HistoryCache, Kind},
+///         Guid, KeyHash, Locator,
+///     },
+/// };
+/// use std::{
+///     net::{Ipv4Addr, SocketAddrV4},
+///     time::{Duration, Instant},
+/// };
+///
+/// let guid = Guid::new([1; 12], [0, 0, 1, 2]);
+/// let mut writer = StatefulWriter::builder(guid, HistoryCache::default())
+///     .heartbeat_period(Duration::from_millis(100))
+///     .build();
+///
+/// let locator = Locator::from(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 7410));
+/// let reader = Guid::new([2; 12], [0, 0, 1, 7]);
+/// writer.matched_reader_add(ReaderProxy::new(reader, false, vec![locator], Vec::new()));
+///
+/// writer
+///     .new_change(Kind::Alive(vec![1, 2, 3]), KeyHash::default())
+///     .unwrap();
+///
+/// let mut output: Vec<(Locator, Message<_, _>)> = Vec::new();
+/// writer.send(Instant::now(), &mut output);
+/// assert_eq!(output.len(), 1);
+/// ```
+#[derive(Debug)]
+pub struct StatefulWriter<C, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    guid: Guid<P, Id>,
+    cache: C,
+    header: Header<P>,
+    max_message_size: usize,
+    fragment_size: u16,
+    push_mode: bool,
+    heartbeat_period: Duration,
+    nack_response_delay: Duration,
+    nack_suppression_duration: Duration,
+    last_sequence_number: u64,
+    heartbeat_count: u32,
+    heartbeat_frag_count: u32,
+    last_heartbeat: Option<Instant>,
+    liveliness_asserted: bool,
+    fragmented_changes: BTreeMap<u64, u32>,
+    matched_readers: Vec<ReaderProxy<P, Id>>,
+}
+
+/// A builder for a [`StatefulWriter`]
+///
+/// See the [`StatefulWriter`] docs for details
+#[derive(Debug)]
+#[must_use]
+pub struct Builder<C, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    guid: Guid<P, Id>,
+    cache: C,
+    protocol_version: Option<ProtocolVersion>,
+    vendor_id: Option<VendorId>,
+    max_message_size: Option<usize>,
+    fragment_size: Option<u16>,
+    push_mode: Option<bool>,
+    heartbeat_period: Option<Duration>,
+    nack_response_delay: Option<Duration>,
+    nack_suppression_duration: Option<Duration>,
+}
+
+impl<C, P, Id> Builder<C, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    fn new(guid: Guid<P, Id>, cache: C) -> Self {
+        Self {
+            guid,
+            cache,
+            protocol_version: None,
+            vendor_id: None,
+            max_message_size: None,
+            fragment_size: None,
+            push_mode: None,
+            heartbeat_period: None,
+            nack_response_delay: None,
+            nack_suppression_duration: None,
+        }
+    }
+
+    /// Set the protocol version used in the [`Header`] of each message
+    pub fn protocol_version(mut self, major: u16, minor: u16) -> Self {
+        self.protocol_version = Some(ProtocolVersion::Specified { major, minor });
+        self
+    }
+
+    /// Set the vendor ID used in the [`Header`] of each message
+    pub fn vendor_id(mut self, id: [u8; 2]) -> Self {
+        self.vendor_id = Some(VendorId::Known(id));
+        self
+    }
+
+    /// Set the maximum size of each message, in bytes
+    ///
+    /// Defaults to [`DEFAULT_MAX_MESSAGE_SIZE`].
+    pub fn max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = Some(max_message_size);
+        self
+    }
+
+    /// Set the size of each fragment of a change which is too large to fit in
+    /// a single message, in bytes
+    ///
+    /// Defaults to [`DEFAULT_FRAGMENT_SIZE`].
+    pub fn fragment_size(mut self, fragment_size: u16) -> Self {
+        self.fragment_size = Some(fragment_size);
+        self
+    }
+
+    /// Set whether new changes are pushed to the readers as soon as they are
+    /// written
+    ///
+    /// If `false`, new changes are only announced with a `HEARTBEAT`, and sent
+    /// when a reader requests them. Defaults to `true`.
+    pub fn push_mode(mut self, push_mode: bool) -> Self {
+        self.push_mode = Some(push_mode);
+        self
+    }
+
+    /// Set the period between `HEARTBEAT`s
+    ///
+    /// Defaults to [`DEFAULT_HEARTBEAT_PERIOD`].
+    pub fn heartbeat_period(mut self, period: Duration) -> Self {
+        self.heartbeat_period = Some(period);
+        self
+    }
+
+    /// Set the delay before responding to a negative acknowledgement
+    ///
+    /// Defaults to [`DEFAULT_NACK_RESPONSE_DELAY`].
+    pub fn nack_response_delay(mut self, delay: Duration) -> Self {
+        self.nack_response_delay = Some(delay);
+        self
+    }
+
+    /// Set the duration for which negative acknowledgements of a change are
+    /// ignored after it is sent
+    ///
+    /// Defaults to [`DEFAULT_NACK_SUPPRESSION_DURATION`].
+    pub fn nack_suppression_duration(mut self, duration: Duration) -> Self {
+        self.nack_suppression_duration = Some(duration);
+        self
+    }
+
+    /// Consume the [`Builder`] and return a configured [`StatefulWriter`]
+    #[must_use]
+    pub fn build(self) -> StatefulWriter<C, P, Id> {
+        let header = Header::new(
+            self.protocol_version.unwrap_or_default(),
+            self.vendor_id.unwrap_or_default(),
+            self.guid.prefix(),
+        );
+
+        StatefulWriter {
+            guid: self.guid,
+            cache: self.cache,
+            header,
+            max_message_size: self.max_message_size.unwrap_or(DEFAULT_MAX_MESSAGE_SIZE),
+            fragment_size: self.fragment_size.unwrap_or(DEFAULT_FRAGMENT_SIZE),
+            push_mode: self.push_mode.unwrap_or(true),
+            heartbeat_period: self.heartbeat_period.unwrap_or(DEFAULT_HEARTBEAT_PERIOD),
+            nack_response_delay: self
+                .nack_response_delay
+                .unwrap_or(DEFAULT_NACK_RESPONSE_DELAY),
+            nack_suppression_duration: self
+                .nack_suppression_duration
+                .unwrap_or(DEFAULT_NACK_SUPPRESSION_DURATION),
+            last_sequence_number: 0,
+            heartbeat_count: 0,
+            heartbeat_frag_count: 0,
+            last_heartbeat: None,
+            liveliness_asserted: false,
+            fragmented_changes: BTreeMap::default(),
+            matched_readers: Vec::default(),
+        }
+    }
+}
+
+impl<C, P, Id> StatefulWriter<C, P, Id>
+where
+    P: Copy + PartialEq,
+    Id: Copy + PartialEq,
+{
+    /// Construct a new [`StatefulWriter`] which stores its changes in the
+    /// given history [`Cache`]
+    ///
+    /// For additional options, use [`StatefulWriter::builder`] instead.
+    #[must_use]
+    pub fn new(guid: Guid<P, Id>, cache: C) -> Self {
+        Builder::new(guid, cache).build()
+    }
+
+    /// Construct a new [`StatefulWriter`] with additional options
+    pub fn builder(guid: Guid<P, Id>, cache: C) -> Builder<C, P, Id> {
+        Builder::new(guid, cache)
+    }
+
+    /// The [`Guid`] of the writer
+    #[must_use]
+    pub fn guid(&self) -> Guid<P, Id> {
+        self.guid
+    }
+
+    /// The history [`Cache`] of the writer
+    #[must_use]
+    pub fn cache(&self) -> &C {
+        &self.cache
+    }
+
+    /// Mutable access to the history [`Cache`] of the writer
+    ///
+    /// This can be used to remove changes which have been acknowledged by
+    /// every reader.
+    pub fn cache_mut(&mut self) -> &mut C {
+        &mut self.cache
+    }
+
+    /// Add a [`ReaderProxy`] for a newly matched remote reader
+    pub fn matched_reader_add(&mut self, reader_proxy: ReaderProxy<P, Id>) {
+        self.matched_reader_remove(reader_proxy.remote_reader_guid());
+        self.matched_readers.push(reader_proxy);
+    }
+
+    /// Remove the [`ReaderProxy`] of the remote reader with the given [`Guid`]
+    pub fn matched_reader_remove(
+        &mut self,
+        reader_guid: Guid<P, Id>,
+    ) -> Option<ReaderProxy<P, Id>> {
+        let index = self
+            .matched_readers
+            .iter()
+            .position(|proxy| proxy.remote_reader_guid() == reader_guid)?;
+        Some(self.matched_readers.remove(index))
+    }
+
+    /// Find the [`ReaderProxy`] of the remote reader with the given [`Guid`]
+    #[must_use]
+    pub fn matched_reader_lookup(&self, reader_guid: Guid<P, Id>) -> Option<&ReaderProxy<P, Id>> {
+        self.matched_readers
+            .iter()
+            .find(|proxy| proxy.remote_reader_guid() == reader_guid)
+    }
+
+    /// The [`ReaderProxy`]s of every matched remote reader
+    #[must_use]
+    pub fn matched_readers(&self) -> &[ReaderProxy<P, Id>] {
+        &self.matched_readers
+    }
+
+    /// Returns `true` if every matched reader has acknowledged the change with
+    /// the given sequence number
+    #[must_use]
+    pub fn is_acked_by_all(&self, sequence_number: u64) -> bool {
+        self.matched_readers
+            .iter()
+            .all(|proxy| proxy.highest_acked_sequence_number() >= sequence_number)
+    }
+
+    /// The time at which [`StatefulWriter::send`] next needs to be called,
+    /// either to send a `HEARTBEAT` or to repair changes, if any
+    #[must_use]
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let heartbeat = if self.matched_readers.is_empty() {
+            None
+        } else {
+            self.last_heartbeat
+                .map(|last_heartbeat| last_heartbeat + self.heartbeat_period)
+        };
+
+        self.matched_readers
+            .iter()
+            .filter_map(ReaderProxy::repair_deadline)
+            .chain(heartbeat)
+            .min()
+    }
+
+    /// Process a submessage sent to the writer by a remote reader
+    ///
+    /// Changes requested by an `ACKNACK`, and fragments requested by a
+    /// `NACK_FRAG`, are sent after the nack response delay. Submessages from
+    /// readers which aren't matched, or which have already been processed, are
+    /// ignored.
+    pub fn receive(
+        &mut self,
+        now: Instant,
+        context: &Context<P>,
+        submessage: WriterSubMessage<Id>,
+    ) {
+        let reader = match &submessage {
+            WriterSubMessage::AckNack(ack_nack) => ack_nack.reader,
+            WriterSubMessage::NackFrag(nack_frag) => nack_frag.reader,
+        };
+
+        let reader_guid = Guid::new(context.source_guid_prefix(), reader);
+        let proxy = match self
+            .matched_readers
+            .iter_mut()
+            .find(|proxy| proxy.remote_reader_guid() == reader_guid)
+        {
+            Some(proxy) => proxy,
+            None => return,
+        };
+
+        match submessage {
+            WriterSubMessage::AckNack(ack_nack) => {
+                if !proxy.is_new_acknack(ack_nack.count) {
+                    return;
+                }
+
+                let state = &ack_nack.reader_sequence_number_state;
+                proxy.acked_changes_set(state.base() - 1);
+                proxy.requested_changes_set(
+                    state.values(),
+                    self.last_sequence_number,
+                    now,
+                    self.nack_suppression_duration,
+                );
+            }
+            WriterSubMessage::NackFrag(nack_frag) => {
+                if !proxy.is_new_nack_frag(nack_frag.count) {
+                    return;
+                }
+
+                proxy.requested_fragments_set(
+                    nack_frag.writer_sequence_number,
+                    nack_frag.fragment_number_state.values(),
+                );
+            }
+        }
+
+        proxy.schedule_repair(now + self.nack_response_delay);
+    }
+
+    /// Write a new change to the history [`Cache`]
+    ///
+    /// Returns the sequence number of the change. In push mode, it is sent to
+    /// the matched readers by the next call to [`StatefulWriter::send`].
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the change can't be added to the cache.
+    pub fn new_change<Data>(
+        &mut self,
+        kind: Kind<Data>,
+        instance: KeyHash,
+    ) -> Result<u64, C::AddErr>
+    where
+        C: Cache<Data, Prefix = P, EntityId = Id, SqnN = u64>,
+    {
+        let sequence_number = self.cache.add(Change::new(kind, self.guid, instance))?;
+        self.last_sequence_number = self.last_sequence_number.max(sequence_number);
+        Ok(sequence_number)
+    }
+
+    /// Assert the liveliness of the writer
+    ///
+    /// The next call to [`StatefulWriter::send`] sends a `HEARTBEAT` with the
+    /// liveliness flag to every matched reader.
+    pub fn assert_liveliness(&mut self) {
+        self.liveliness_asserted = true;
+    }
+
+    /// Whether a periodic `HEARTBEAT` is due, restarting the period if so
+    fn take_heartbeat(&mut self, now: Instant) -> bool {
+        let heartbeat_due = self.last_heartbeat.map_or(true, |last_heartbeat| {
+            now >= last_heartbeat + self.heartbeat_period
+        });
+        if heartbeat_due {
+            self.last_heartbeat = Some(now);
+        }
+        heartbeat_due
+    }
+
+    /// Send any messages which are due to each matched reader
+    ///
+    /// This sends new changes (in push mode), repairs which are due, `GAP`s
+    /// for irrelevant changes, and `HEARTBEAT`s. Changes which are too large
+    /// to fit in a single message are sent as `DATA_FRAG`s.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if a submessage can't be sent because the maximum
+    /// message size leaves no room for it. The other submessages are still
+    /// sent.
+    pub fn send<Data, O>(&mut self, now: Instant, output: &mut O) -> Result<(), TooLargeError>
+    where
+        C: Cache<Data, Prefix = P, EntityId = Id, SqnN = u64>,
+        Data: AsRef<[u8]>,
+        O: Output<P, Id>,
+    {
+        let last_sequence_number = self
+            .last_sequence_number
+            .max(self.cache.max_sequence_number().unwrap_or_default());
+        self.last_sequence_number = last_sequence_number;
+        let first_sequence_number = self
+            .cache
+            .min_sequence_number()
+            .unwrap_or(last_sequence_number + 1);
+
+        let heartbeat_due = self.take_heartbeat(now);
+
+        // forget fragmented changes which are no longer in the cache
+        self.fragmented_changes = self.fragmented_changes.split_off(&first_sequence_number);
+
+        let assert_liveliness = std::mem::take(&mut self.liveliness_asserted);
+        let writer = self.guid.entity_id();
+        let fragment_size = self.fragment_size;
+        let cache = &self.cache;
+        let fragmented_changes = &mut self.fragmented_changes;
+        let mut result = Ok(());
+
+        for proxy in &mut self.matched_readers {
+            let destination = Some(proxy.remote_reader_guid().prefix());
+            let reader = Some(proxy.remote_reader_guid().entity_id());
+
+            let mut builder = MessageBuilder::new(self.header, self.max_message_size);
+            let mut irrelevant = Vec::new();
+            let mut sent_data = false;
+
+            let mut send_change = |sequence_number: u64, proxy: &mut ReaderProxy<P, Id>| {
+                proxy.mark_sent(sequence_number, now);
+
+                match change_data(cache, writer, proxy, sequence_number) {
+                    Some(data) => {
+                        let total = push_data(&mut builder, destination, data, fragment_size)?;
+                        if let Some(total) = total {
+                            fragmented_changes.insert(sequence_number, total);
+                        }
+                        sent_data = true;
+                    }
+                    None => irrelevant.push(sequence_number),
+                }
+                Ok(())
+            };
+
+            let repair = proxy.take_repair(now);
+            if repair {
+                while let Some(sequence_number) = proxy.next_requested_change() {
+                    result = result.and(send_change(sequence_number, proxy));
+                }
+            }
+
+            if self.push_mode {
+                while let Some(sequence_number) = proxy.next_unsent_change(last_sequence_number) {
+                    result = result.and(send_change(sequence_number, proxy));
+                }
+            }
+
+            if repair {
+                let repaired = repair_fragments(
+                    &mut builder,
+                    proxy,
+                    cache,
+                    writer,
+                    fragment_size,
+                    &mut irrelevant,
+                );
+                // fragments may have been sent before a failure
+                sent_data |= repaired.as_ref().map_or(true, |&sent| sent);
+                result = result.and(repaired.map(|_| ()));
+            }
+
+            irrelevant.sort_unstable();
+            irrelevant.dedup();
+            for gap in gap_submessages(writer, reader, &irrelevant) {
+                result = result.and(builder.push(destination, None, gap.into()));
+            }
+
+            let unacked = (heartbeat_due || sent_data) && proxy.has_unacked_changes(cache);
+            if unacked || assert_liveliness {
+                self.heartbeat_count = self.heartbeat_count.wrapping_add(1);
+                let heartbeat = Heartbeat {
+                    reader,
+                    writer,
+                    first_sequence_number,
+                    last_sequence_number,
+                    count: self.heartbeat_count,
+                    final_flag: !unacked,
+                    liveliness_flag: assert_liveliness,
+                };
+                result = result.and(builder.push(destination, None, heartbeat.into()));
+            }
+
+            if unacked {
+                for heartbeat_frag in heartbeat_frags(
+                    proxy,
+                    fragmented_changes,
+                    writer,
+                    &mut self.heartbeat_frag_count,
+                ) {
+                    result = result.and(builder.push(destination, None, heartbeat_frag.into()));
+                }
+            }
+
+            for message in builder.finish() {
+                for &locator in proxy.locators() {
+                    output.send(locator, message.clone());
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// The `DATA` which sends the change with the given sequence number to the
+/// reader, or `None` if the change is irrelevant to the reader
+fn change_data<C, Data, P, Id>(
+    cache: &C,
+    writer: Id,
+    proxy: &ReaderProxy<P, Id>,
+    sequence_number: u64,
+) -> Option<submessage::Data<Id>>
+where
+    C: Cache<Data, Prefix = P, EntityId = Id, SqnN = u64>,
+    Data: AsRef<[u8]>,
+    P: Copy,
+    Id: Copy,
+{
+    let reader = Some(proxy.remote_reader_guid().entity_id());
+    cache.get(sequence_number).and_then(|change| {
+        data_submessage(
+            writer,
+            reader,
+            sequence_number,
+            change,
+            proxy.expects_inline_qos(),
+        )
+    })
+}
+
+/// Push the fragments which the reader has requested with a `NACK_FRAG`
+///
+/// Returns `true` if any fragments were pushed. Changes which are no longer
+/// in the cache are added to `irrelevant`. If the fragments of a change can't
+/// be pushed, the fragments of the other changes are still pushed, and the
+/// error is returned.
+fn repair_fragments<C, Data, P, Id>(
+    builder: &mut MessageBuilder<P, Id>,
+    proxy: &mut ReaderProxy<P, Id>,
+    cache: &C,
+    writer: Id,
+    fragment_size: u16,
+    irrelevant: &mut Vec<u64>,
+) -> Result<bool, TooLargeError>
+where
+    C: Cache<Data, Prefix = P, EntityId = Id, SqnN = u64>,
+    Data: AsRef<[u8]>,
+    P: Copy + PartialEq,
+    Id: Copy,
+{
+    let destination = Some(proxy.remote_reader_guid().prefix());
+    let mut sent = false;
+    let mut result = Ok(());
+
+    while let Some((sequence_number, fragment_numbers)) = proxy.next_requested_fragments() {
+        match change_data(cache, writer, proxy, sequence_number) {
+            Some(data) => {
+                let pushed =
+                    push_fragments(builder, destination, &data, fragment_size, fragment_numbers);
+                sent |= pushed.is_ok();
+                result = result.and(pushed.map(|_| ()));
+            }
+            None => irrelevant.push(sequence_number),
+        }
+    }
+
+    result.map(|()| sent)
+}
+
+/// The `HEARTBEAT_FRAG`s announcing the fragments of each fragmented change
+/// which has been sent to the reader, but not yet acknowledged
+///
+/// Changes in the cache are always complete, so the last fragment announced
+/// is the last fragment of the change.
+fn heartbeat_frags<P, Id>(
+    proxy: &ReaderProxy<P, Id>,
+    fragmented_changes: &BTreeMap<u64, u32>,
+    writer: Id,
+    count: &mut u32,
+) -> Vec<HeartbeatFrag<Id>>
+where
+    P: Copy,
+    Id: Copy,
+{
+    let reader = Some(proxy.remote_reader_guid().entity_id());
+    let unacked = proxy.highest_acked_sequence_number() + 1..=proxy.highest_sent_sequence_number();
+
+    fragmented_changes
+        .range(unacked)
+        .map(|(&sequence_number, &total)| {
+            *count = count.wrapping_add(1);
+            HeartbeatFrag {
+                reader,
+                writer,
+                writer_sequence_number: sequence_number,
+                last_fragment_number: total,
+                count: *count,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatefulWriter;
+    use crate::{
+        behaviour::{
+            receiver::{Context, Dispatch, ReaderSubMessage, WriterSubMessage},
+            writer::ReaderProxy,
+            MessageReceiver,
+        },
+        messages::{
+            submessage::{
+                elements::{FragmentNumberSet, SequenceNumberSet},
+                AckNack, NackFrag,
+            },
+            Header, Message, SubMessage,
+        },
+        structure::{
+            history::{Cache, HistoryCache, Kind},
+            Guid, KeyHash, Locator, ProtocolVersion, VendorId,
+        },
+    };
+    use std::{
+        net::{Ipv4Addr, SocketAddrV4},
+        num::{NonZeroU32, NonZeroU64},
+        time::{Duration, Instant},
+    };
+    use vec1::vec1;
+
+    type Prefix = [u8; 12];
+    type Id = [u8; 4];
+    type TestWriter = StatefulWriter<HistoryCache<Vec<u8>, Prefix, Id>, Prefix, Id>;
+    type Sent = Vec<(Locator, Message<Prefix, Id>)>;
+
+    const LOCAL: Prefix = [1; 12];
+    const REMOTE: Prefix = [2; 12];
+    const READER: Id = [0, 0, 1, 7];
+
+    const HEARTBEAT_PERIOD: Duration = Duration::from_secs(1);
+    const NACK_RESPONSE_DELAY: Duration = Duration::from_millis(100);
+
+    /// Routes every writer submessage to a single writer
+    struct Router<'a> {
+        writer: &'a mut TestWriter,
+        now: Instant,
+    }
+
+    impl Dispatch<Prefix, Id> for Router<'_> {
+        fn dispatch_to_reader(
+            &mut self,
+            _reader: Option<Id>,
+            _context: &Context<Prefix>,
+            _submessage: ReaderSubMessage<Id>,
+        ) {
+        }
+
+        fn dispatch_to_writer(
+            &mut self,
+            _writer: Id,
+            context: &Context<Prefix>,
+            submessage: WriterSubMessage<Id>,
+        ) {
+            self.writer.receive(self.now, context, submessage);
+        }
+    }
+
+    fn locator() -> Locator {
+        SocketAddrV4::new(Ipv4Addr::LOCALHOST, 7410).into()
+    }
+
+    fn writer(nack_suppression_duration: Duration) -> TestWriter {
+        let mut writer =
+            StatefulWriter::builder(Guid::new(LOCAL, [0, 0, 1, 2]), HistoryCache::default())
+                .heartbeat_period(HEARTBEAT_PERIOD)
+                .nack_response_delay(NACK_RESPONSE_DELAY)
+                .nack_suppression_duration(nack_suppression_duration)
+                .build();
+        writer.matched_reader_add(ReaderProxy::new(
+            Guid::new(REMOTE, READER),
+            false,
+            vec![locator()],
+            Vec::new(),
+        ));
+        writer
+    }
+
+    fn acknack(writer: &mut TestWriter, now: Instant, count: u32, base: u64, missing: &[u64]) {
+        let mut state = SequenceNumberSet::new(NonZeroU64::new(base).unwrap());
+        for &sequence_number in missing {
+            state.insert_value(sequence_number).unwrap();
+        }
+
+        let ack_nack = AckNack {
+            reader: READER,
+            writer: [0, 0, 1, 2],
+            reader_sequence_number_state: state,
+            count,
+            final_flag: false,
+        };
+
+        let header = Header::new(ProtocolVersion::Latest, VendorId::Unknown, REMOTE);
+        let message = Message::new(header, vec1![ack_nack.into()]);
+
+        MessageReceiver::new(LOCAL)
+            .receive(None, message, &mut Router { writer, now })
+            .unwrap();
+    }
+
+    fn send(writer: &mut TestWriter, now: Instant) -> Vec<SubMessage<Prefix, Id>> {
+        let mut sent = Sent::new();
+        writer.send(now, &mut sent).unwrap();
+        sent.into_iter()
+            .flat_map(|(_, message)| {
+                let (_, submessages) = message.into_parts();
+                Vec::from(submessages)
+            })
+            .filter(|submessage| !submessage.is_interpreter())
+            .collect()
+    }
+
+    fn data_sequence_numbers(submessages: &[SubMessage<Prefix, Id>]) -> Vec<u64> {
+        submessages
+            .iter()
+            .filter_map(|submessage| match submessage {
+                SubMessage::Data(data) => Some(data.writer_sequence_number),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn heartbeats(submessages: &[SubMessage<Prefix, Id>]) -> Vec<(u64, u64)> {
+        submessages
+            .iter()
+            .filter_map(|submessage| match submessage {
+                SubMessage::Heartbeat(heartbeat) => Some((
+                    heartbeat.first_sequence_number,
+                    heartbeat.last_sequence_number,
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn write(writer: &mut TestWriter, count: u8) {
+        for value in 0..count {
+            writer
+                .new_change(Kind::Alive(vec![value]), KeyHash::default())
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn reports_submessages_which_do_not_fit() {
+        let mut writer =
+            StatefulWriter::builder(Guid::new(LOCAL, [0, 0, 1, 2]), HistoryCache::default())
+                .max_message_size(60)
+                .build();
+        writer.matched_reader_add(ReaderProxy::new(
+            Guid::new(REMOTE, READER),
+            false,
+            vec![locator()],
+            Vec::new(),
+        ));
+        write(&mut writer, 1);
+
+        // neither the change nor the heartbeat fit after the header and the
+        // destination
+        let mut sent = Sent::new();
+        assert!(writer.send(Instant::now(), &mut sent).is_err());
+        assert!(sent.is_empty());
+    }
+
+    #[test]
+    fn pushes_new_changes_with_a_heartbeat() {
+        let mut writer = writer(Duration::ZERO);
+        let now = Instant::now();
+        write(&mut writer, 3);
+
+        let mut sent = Sent::new();
+        writer.send(now, &mut sent).unwrap();
+        assert!(
+            sent.iter()
+                .all(|(destination, _)| *destination == locator())
+        );
+
+        let submessages = send(&mut writer, now);
+        assert!(submessages.is_empty());
+
+        let mut sent = Sent::new();
+        write(&mut writer, 1);
+        writer.send(now, &mut sent).unwrap();
+        let (_, message) = &sent[0];
+
+        // the submessages are addressed to the reader's participant
+        assert!(matches!(
+            &message.submessages()[0],
+            SubMessage::InfoDestination(info) if info.guid_prefix == Some(REMOTE)
+        ));
+
+        let submessages: Vec<_> = message.submessages().to_vec();
+        assert_eq!(data_sequence_numbers(&submessages), vec![4]);
+        assert_eq!(heartbeats(&submessages), vec![(1, 4)]);
+    }
+
+    #[test]
+    fn repairs_after_nack_response_delay() {
+        let mut writer = writer(Duration::ZERO);
+        let start = Instant::now();
+        write(&mut writer, 4);
+        send(&mut writer, start);
+
+        // the reader has received 1 and 3
+        acknack(&mut writer, start, 1, 2, &[2, 4]);
+        let proxy = writer
+            .matched_reader_lookup(Guid::new(REMOTE, READER))
+            .unwrap();
+        assert_eq!(proxy.highest_acked_sequence_number(), 1);
+        assert_eq!(proxy.requested_changes().collect::<Vec<_>>(), vec![2, 4]);
+        assert_eq!(writer.next_deadline(), Some(start + NACK_RESPONSE_DELAY));
+
+        assert!(data_sequence_numbers(&send(&mut writer, start)).is_empty());
+
+        let submessages = send(&mut writer, start + NACK_RESPONSE_DELAY);
+        assert_eq!(data_sequence_numbers(&submessages), vec![2, 4]);
+
+        // a duplicate acknack is ignored
+        acknack(&mut writer, start, 1, 2, &[2, 4]);
+        let proxy = writer
+            .matched_reader_lookup(Guid::new(REMOTE, READER))
+            .unwrap();
+        assert_eq!(proxy.requested_changes().count(), 0);
+    }
+
+    #[test]
+    fn ignores_requests_for_unwritten_changes() {
+        let mut writer = writer(Duration::ZERO);
+        let start = Instant::now();
+        write(&mut writer, 2);
+        send(&mut writer, start);
+
+        acknack(&mut writer, start, 1, 2, &[2, 3, 4]);
+        let proxy = writer
+            .matched_reader_lookup(Guid::new(REMOTE, READER))
+            .unwrap();
+        assert_eq!(proxy.requested_changes().collect::<Vec<_>>(), vec![2]);
+
+        // no GAP is sent for the changes which haven't been written
+        let submessages = send(&mut writer, start + NACK_RESPONSE_DELAY);
+        assert_eq!(data_sequence_numbers(&submessages), vec![2]);
+        assert!(
+            !submessages
+                .iter()
+                .any(|submessage| matches!(submessage, SubMessage::Gap(_)))
+        );
+    }
+
+    #[test]
+    fn heartbeats_until_acknowledged() {
+        let mut writer = writer(Duration::ZERO);
+        let start = Instant::now();
+        write(&mut writer, 2);
+        send(&mut writer, start);
+
+        let submessages = send(&mut writer, start + HEARTBEAT_PERIOD);
+        assert_eq!(heartbeats(&submessages), vec![(1, 2)]);
+        assert!(data_sequence_numbers(&submessages).is_empty());
+
+        acknack(&mut writer, start, 1, 3, &[]);
+        assert!(writer.is_acked_by_all(2));
+
+        let submessages = send(&mut writer, start + HEARTBEAT_PERIOD * 2);
+        assert!(submessages.is_empty());
+    }
+
+    #[test]
+    fn asserts_liveliness_with_a_final_heartbeat() {
+        let mut writer = writer(Duration::ZERO);
+        let now = Instant::now();
+        assert!(send(&mut writer, now).is_empty());
+
+        writer.assert_liveliness();
+        let submessages = send(&mut writer, now);
+        assert!(submessages.iter().any(|submessage| matches!(
+            submessage,
+            SubMessage::Heartbeat(heartbeat) if heartbeat.liveliness_flag && heartbeat.final_flag
+        )));
+
+        assert!(send(&mut writer, now).is_empty());
+    }
+
+    #[test]
+    fn sends_gap_for_removed_changes() {
+        let mut writer = writer(Duration::ZERO);
+        let start = Instant::now();
+        write(&mut writer, 5);
+        writer.cache_mut().remove(2).unwrap();
+        writer.cache_mut().remove(3).unwrap();
+
+        let submessages = send(&mut writer, start);
+        assert_eq!(data_sequence_numbers(&submessages), vec![1, 4, 5]);
+
+        let gaps: Vec<Vec<u64>> = submessages
+            .iter()
+            .filter_map(|submessage| match submessage {
+                SubMessage::Gap(gap) => Some(gap.sequence_numbers().collect()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(gaps, vec![vec![2, 3]]);
+    }
+
+    #[test]
+    fn suppresses_nacks_for_recently_sent_changes() {
+        let suppression = Duration::from_millis(500);
+        let mut writer = writer(suppression);
+        let start = Instant::now();
+        write(&mut writer, 2);
+        send(&mut writer, start);
+
+        acknack(
+            &mut writer,
+            start + Duration::from_millis(10),
+            1,
+            1,
+            &[1, 2],
+        );
+        assert_eq!(writer.next_deadline(), Some(start + HEARTBEAT_PERIOD));
+
+        acknack(&mut writer, start + suppression, 2, 1, &[1, 2]);
+        let proxy = writer
+            .matched_reader_lookup(Guid::new(REMOTE, READER))
+            .unwrap();
+        assert_eq!(proxy.requested_changes().collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn pull_mode_only_sends_requested_changes() {
+        let mut writer =
+            StatefulWriter::builder(Guid::new(LOCAL, [0, 0, 1, 2]), HistoryCache::default())
+                .push_mode(false)
+                .nack_response_delay(Duration::ZERO)
+                .build();
+        writer.matched_reader_add(ReaderProxy::new(
+            Guid::new(REMOTE, READER),
+            false,
+            vec![locator()],
+            Vec::new(),
+        ));
+        let now = Instant::now();
+        write(&mut writer, 2);
+
+        let submessages = send(&mut writer, now);
+        assert!(data_sequence_numbers(&submessages).is_empty());
+        assert_eq!(heartbeats(&submessages), vec![(1, 2)]);
+
+        acknack(&mut writer, now, 1, 1, &[1, 2]);
+        let submessages = send(&mut writer, now);
+        assert_eq!(data_sequence_numbers(&submessages), vec![1, 2]);
+        assert_eq!(heartbeats(&submessages), vec![(1, 2)]);
+    }
+
+    #[test]
+    fn repairs_requested_fragments() {
+        let mut writer =
+            StatefulWriter::builder(Guid::new(LOCAL, [0, 0, 1, 2]), HistoryCache::default())
+                .heartbeat_period(HEARTBEAT_PERIOD)
+                .nack_response_delay(NACK_RESPONSE_DELAY)
+                .max_message_size(1500)
+                .fragment_size(1000)
+                .build();
+        writer.matched_reader_add(ReaderProxy::new(
+            Guid::new(REMOTE, READER),
+            false,
+            vec![locator()],
+            Vec::new(),
+        ));
+        let now = Instant::now();
+
+        writer
+            .new_change(Kind::Alive(vec![7; 5000]), KeyHash::default())
+            .unwrap();
+
+        let fragments = |submessages: &[SubMessage<Prefix, Id>]| -> Vec<u32> {
+            submessages
+                .iter()
+                .filter_map(|submessage| match submessage {
+                    SubMessage::DataFrag(data_frag) => {
+                        Some(data_frag.fragment_starting_number.get())
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let submessages = send(&mut writer, now);
+        assert_eq!(fragments(&submessages), vec![1, 2, 3, 4, 5]);
+        assert!(data_sequence_numbers(&submessages).is_empty());
+
+        // the available fragments are announced along with the heartbeat
+        let heartbeat_frags: Vec<_> = submessages
+            .iter()
+            .filter_map(|submessage| match submessage {
+                SubMessage::HeartbeatFrag(heartbeat_frag) => Some((
+                    heartbeat_frag.writer_sequence_number,
+                    heartbeat_frag.last_fragment_number,
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(heartbeat_frags, vec![(1, 5)]);
+
+        let mut fragment_number_state = FragmentNumberSet::new(NonZeroU32::new(2).unwrap());
+        fragment_number_state.insert_value(2).unwrap();
+        fragment_number_state.insert_value(4).unwrap();
+        let nack_frag = NackFrag {
+            reader: READER,
+            writer: [0, 0, 1, 2],
+            writer_sequence_number: 1,
+            fragment_number_state,
+            count: 1,
+        };
+
+        let header = Header::new(ProtocolVersion::Latest, VendorId::Unknown, REMOTE);
+        let message = Message::new(header, vec1![nack_frag.into()]);
+        MessageReceiver::new(LOCAL)
+            .receive(
+                None,
+                message,
+                &mut Router {
+                    writer: &mut writer,
+                    now,
+                },
+            )
+            .unwrap();
+
+        assert!(fragments(&send(&mut writer, now)).is_empty());
+        assert_eq!(
+            fragments(&send(&mut writer, now + NACK_RESPONSE_DELAY)),
+            vec![2, 4]
+        );
+
+        // once acknowledged, the change is no longer announced
+        acknack(&mut writer, now, 1, 2, &[]);
+        let submessages = send(&mut writer, now + HEARTBEAT_PERIOD);
+        assert!(
+            !submessages
+                .iter()
+                .any(|submessage| matches!(submessage, SubMessage::HeartbeatFrag(_)))
+        );
+    }
+}
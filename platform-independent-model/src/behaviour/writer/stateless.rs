@@ -0,0 +1,521 @@
+//! Contains the best-effort [`StatelessWriter`] and its [`Builder`]
+
+use std::time::{Duration, Instant};
+
+use super::{
+    data_submessage,
+    fragmentation::{push_data, DEFAULT_FRAGMENT_SIZE},
+    Output, ReaderLocator, DEFAULT_MAX_MESSAGE_SIZE,
+};
+use crate::{
+    messages::{builder::TooLargeError, Header, MessageBuilder},
+    structure::{
+        history::{Cache, Change, Kind},
+        Guid, KeyHash, Locator, ProtocolVersion, VendorId,
+    },
+};
+
+/// A best-effort writer which sends every change to a configured set of
+/// [`Locator`]s, without keeping any state about the remote readers.
+///
+/// New changes are written to the history [`Cache`] with
+/// [`StatelessWriter::new_change`], and sent to every [`ReaderLocator`] the
+/// next time [`StatelessWriter::send`] is called. If a resend period is
+/// configured, every change in the cache is periodically sent again, so that
+/// late-joining readers receive it. This is how SPDP announcements are
+/// made.
+///
+/// Changes which are too large to fit in a single message are split into
+/// `DATA_FRAG`s. Since there is no feedback from the readers, a lost fragment
+/// means the whole change is lost.
+///
+/// The writer doesn't own any sockets or timers. The current time is passed
+/// to [`StatelessWriter::send`], and messages are produced through an
+/// [`Output`].
+///
+/// For details, see the [specification, section 8.4.8](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+///
+/// # Example
+///
+/// ```
+/// use rtps_pim::{
+///     behaviour::writer::{ReaderLocator, StatelessWriter},
+///     messages::Message,
+///     structure::{
+///         history::{HistoryCache, Kind},
+///         Guid, KeyHash, Locator,
+///     },
+/// };
+/// use std::{
+///     net::{Ipv4Addr, SocketAddrV4},
+///     time::Instant,
+/// };
+///
+/// let guid = Guid::new([1; 12], [0, 0, 1, 2]);
+/// let mut writer = StatelessWriter::new(guid, HistoryCache::default());
+///
+/// let locator = Locator::from(SocketAddrV4::new(Ipv4Addr::new(239, 255, 0, 1), 7400));
+/// writer.reader_locator_add(ReaderLocator::new(locator, false));
+///
+/// writer
+///     .new_change(Kind::Alive(vec![1, 2, 3]), KeyHash::default())
+///     .unwrap();
+///
+/// let mut output: Vec<(Locator, Message<_, _>)> = Vec::new();
+/// writer.send(Instant::now(), &mut output).unwrap();
+/// assert_eq!(output.len(), 1);
+/// ```
+#[derive(Debug)]
+pub struct StatelessWriter<C, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    guid: Guid<P, Id>,
+    cache: C,
+    header: Header<P>,
+    max_message_size: usize,
+    fragment_size: u16,
+    resend_period: Option<Duration>,
+    last_reset: Option<Instant>,
+    reader_locators: Vec<ReaderLocator>,
+}
+
+/// A builder for a [`StatelessWriter`]
+///
+/// See the [`StatelessWriter`] docs for details
+#[derive(Debug)]
+#[must_use]
+pub struct Builder<C, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    guid: Guid<P, Id>,
+    cache: C,
+    protocol_version: Option<ProtocolVersion>,
+    vendor_id: Option<VendorId>,
+    max_message_size: Option<usize>,
+    fragment_size: Option<u16>,
+    resend_period: Option<Duration>,
+}
+
+impl<C, P, Id> Builder<C, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    fn new(guid: Guid<P, Id>, cache: C) -> Self {
+        Self {
+            guid,
+            cache,
+            protocol_version: None,
+            vendor_id: None,
+            max_message_size: None,
+            fragment_size: None,
+            resend_period: None,
+        }
+    }
+
+    /// Set the protocol version used in the [`Header`] of each message
+    pub fn protocol_version(mut self, major: u16, minor: u16) -> Self {
+        self.protocol_version = Some(ProtocolVersion::Specified { major, minor });
+        self
+    }
+
+    /// Set the vendor ID used in the [`Header`] of each message
+    pub fn vendor_id(mut self, id: [u8; 2]) -> Self {
+        self.vendor_id = Some(VendorId::Known(id));
+        self
+    }
+
+    /// Set the maximum size of each message, in bytes
+    ///
+    /// Defaults to [`DEFAULT_MAX_MESSAGE_SIZE`].
+    pub fn max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = Some(max_message_size);
+        self
+    }
+
+    /// Set the size of each fragment of a change which is too large to fit in
+    /// a single message, in bytes
+    ///
+    /// Defaults to [`DEFAULT_FRAGMENT_SIZE`].
+    pub fn fragment_size(mut self, fragment_size: u16) -> Self {
+        self.fragment_size = Some(fragment_size);
+        self
+    }
+
+    /// Periodically send every change in the cache again
+    ///
+    /// By default, each change is only sent once.
+    pub fn resend_period(mut self, period: Duration) -> Self {
+        self.resend_period = Some(period);
+        self
+    }
+
+    /// Consume the [`Builder`] and return a configured [`StatelessWriter`]
+    #[must_use]
+    pub fn build(self) -> StatelessWriter<C, P, Id> {
+        let header = Header::new(
+            self.protocol_version.unwrap_or_default(),
+            self.vendor_id.unwrap_or_default(),
+            self.guid.prefix(),
+        );
+
+        StatelessWriter {
+            guid: self.guid,
+            cache: self.cache,
+            header,
+            max_message_size: self.max_message_size.unwrap_or(DEFAULT_MAX_MESSAGE_SIZE),
+            fragment_size: self.fragment_size.unwrap_or(DEFAULT_FRAGMENT_SIZE),
+            resend_period: self.resend_period,
+            last_reset: None,
+            reader_locators: Vec::default(),
+        }
+    }
+}
+
+impl<C, P, Id> StatelessWriter<C, P, Id>
+where
+    P: Copy + PartialEq,
+    Id: Copy,
+{
+    /// Construct a new [`StatelessWriter`] which stores its changes in the
+    /// given history [`Cache`]
+    ///
+    /// For additional options, use [`StatelessWriter::builder`] instead.
+    #[must_use]
+    pub fn new(guid: Guid<P, Id>, cache: C) -> Self {
+        Builder::new(guid, cache).build()
+    }
+
+    /// Construct a new [`StatelessWriter`] with additional options
+    pub fn builder(guid: Guid<P, Id>, cache: C) -> Builder<C, P, Id> {
+        Builder::new(guid, cache)
+    }
+
+    /// The [`Guid`] of the writer
+    #[must_use]
+    pub fn guid(&self) -> Guid<P, Id> {
+        self.guid
+    }
+
+    /// The history [`Cache`] of the writer
+    #[must_use]
+    pub fn cache(&self) -> &C {
+        &self.cache
+    }
+
+    /// Mutable access to the history [`Cache`] of the writer
+    ///
+    /// This can be used to remove changes which are no longer needed.
+    pub fn cache_mut(&mut self) -> &mut C {
+        &mut self.cache
+    }
+
+    /// Add a [`ReaderLocator`] to which changes will be sent
+    ///
+    /// Every change in the cache will be sent to the new locator.
+    pub fn reader_locator_add(&mut self, reader_locator: ReaderLocator) {
+        self.reader_locators.push(reader_locator);
+    }
+
+    /// Remove the [`ReaderLocator`] with the given [`Locator`]
+    pub fn reader_locator_remove(&mut self, locator: Locator) {
+        self.reader_locators
+            .retain(|reader_locator| reader_locator.locator() != locator);
+    }
+
+    /// The [`ReaderLocator`]s to which changes are sent
+    #[must_use]
+    pub fn reader_locators(&self) -> &[ReaderLocator] {
+        &self.reader_locators
+    }
+
+    /// Mark every change as unsent to every [`ReaderLocator`], so that they are
+    /// all sent again
+    pub fn unsent_changes_reset(&mut self) {
+        for reader_locator in &mut self.reader_locators {
+            reader_locator.unsent_changes_reset();
+        }
+    }
+
+    /// The time at which the next periodic resend is due, if any
+    #[must_use]
+    pub fn next_deadline(&self) -> Option<Instant> {
+        Some(self.last_reset? + self.resend_period?)
+    }
+
+    /// Write a new change to the history [`Cache`]
+    ///
+    /// Returns the sequence number of the change. It is sent to the reader
+    /// locators by the next call to [`StatelessWriter::send`].
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the change can't be added to the cache.
+    pub fn new_change<Data>(
+        &mut self,
+        kind: Kind<Data>,
+        instance: KeyHash,
+    ) -> Result<u64, C::AddErr>
+    where
+        C: Cache<Data, Prefix = P, EntityId = Id, SqnN = u64>,
+    {
+        self.cache.add(Change::new(kind, self.guid, instance))
+    }
+
+    /// Send any unsent changes to each [`ReaderLocator`]
+    ///
+    /// If the resend period has elapsed, every change in the cache is sent
+    /// again. Changes which are too large to fit in a single message are sent
+    /// as `DATA_FRAG`s.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if a change can't be sent because the maximum
+    /// message size leaves no room for it, even as `DATA_FRAG`s. The other
+    /// changes are still sent.
+    pub fn send<Data, O>(&mut self, now: Instant, output: &mut O) -> Result<(), TooLargeError>
+    where
+        C: Cache<Data, Prefix = P, EntityId = Id, SqnN = u64>,
+        Data: AsRef<[u8]>,
+        O: Output<P, Id>,
+    {
+        match self.next_deadline() {
+            Some(deadline) if now >= deadline => {
+                self.unsent_changes_reset();
+                self.last_reset = Some(now);
+            }
+            None => self.last_reset = Some(now),
+            _ => (),
+        }
+
+        let writer = self.guid.entity_id();
+        let fragment_size = self.fragment_size;
+        let mut result = Ok(());
+
+        for reader_locator in &mut self.reader_locators {
+            let mut builder = MessageBuilder::new(self.header, self.max_message_size);
+
+            while let Some(sequence_number) = reader_locator.next_unsent_change(&self.cache) {
+                reader_locator.mark_sent(sequence_number);
+
+                let data = self.cache.get(sequence_number).and_then(|change| {
+                    data_submessage(
+                        writer,
+                        None,
+                        sequence_number,
+                        change,
+                        reader_locator.expects_inline_qos(),
+                    )
+                });
+
+                if let Some(data) = data {
+                    result =
+                        result.and(push_data(&mut builder, None, data, fragment_size).map(|_| ()));
+                }
+            }
+
+            for message in builder.finish() {
+                output.send(reader_locator.locator(), message);
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatelessWriter;
+    use crate::{
+        behaviour::writer::ReaderLocator,
+        messages::{
+            submessage::{elements::parameter_id, Data, Payload},
+            Message, SubMessage,
+        },
+        structure::{
+            history::{Cache, HistoryCache, Kind},
+            Guid, KeyHash, Locator,
+        },
+    };
+    use std::{
+        net::{Ipv4Addr, SocketAddrV4},
+        time::{Duration, Instant},
+    };
+
+    type Prefix = [u8; 12];
+    type Id = [u8; 4];
+    type TestWriter = StatelessWriter<HistoryCache<Vec<u8>, Prefix, Id>, Prefix, Id>;
+    type Sent = Vec<(Locator, Message<Prefix, Id>)>;
+
+    fn locator(port: u16) -> Locator {
+        SocketAddrV4::new(Ipv4Addr::LOCALHOST, port).into()
+    }
+
+    fn writer() -> TestWriter {
+        let mut writer =
+            StatelessWriter::builder(Guid::new([1; 12], [0, 0, 1, 2]), HistoryCache::default())
+                .resend_period(Duration::from_secs(1))
+                .build();
+        writer.reader_locator_add(ReaderLocator::new(locator(1), false));
+        writer.reader_locator_add(ReaderLocator::new(locator(2), true));
+        writer
+    }
+
+    fn sent_data(sent: &Sent, locator: Locator) -> Vec<Data<Id>> {
+        sent.iter()
+            .filter(|(destination, _)| *destination == locator)
+            .flat_map(|(_, message)| message.submessages())
+            .filter_map(|submessage| match submessage {
+                SubMessage::Data(data) => Some(data.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sends_new_changes_to_every_locator() {
+        let mut writer = writer();
+        let now = Instant::now();
+
+        writer
+            .new_change(Kind::Alive(vec![1]), KeyHash::default())
+            .unwrap();
+        writer
+            .new_change(Kind::Alive(vec![2]), KeyHash::default())
+            .unwrap();
+
+        let mut sent = Sent::new();
+        writer.send(now, &mut sent).unwrap();
+
+        for port in [1, 2] {
+            let data = sent_data(&sent, locator(port));
+            let sequence_numbers: Vec<_> = data.iter().map(|d| d.writer_sequence_number).collect();
+            assert_eq!(sequence_numbers, vec![1, 2]);
+            assert_eq!(data[0].payload, Some(Payload::Data(vec![1])));
+            assert_eq!(data[0].reader, None);
+        }
+
+        // only the second locator expects inline qos
+        assert!(sent_data(&sent, locator(1))[0].inline_qos.is_none());
+        assert!(sent_data(&sent, locator(2))[0].inline_qos.is_some());
+
+        // nothing left to send
+        let mut sent = Sent::new();
+        writer.send(now, &mut sent).unwrap();
+        assert!(sent.is_empty());
+
+        writer
+            .new_change(Kind::Alive(vec![3]), KeyHash::default())
+            .unwrap();
+        writer.send(now, &mut sent).unwrap();
+        assert_eq!(sent_data(&sent, locator(1)).len(), 1);
+    }
+
+    #[test]
+    fn resends_periodically() {
+        let mut writer = writer();
+        let start = Instant::now();
+
+        writer
+            .new_change(Kind::Alive(vec![1]), KeyHash::default())
+            .unwrap();
+        writer.send(start, &mut Sent::new()).unwrap();
+        assert_eq!(writer.next_deadline(), Some(start + Duration::from_secs(1)));
+
+        let mut sent = Sent::new();
+        writer
+            .send(start + Duration::from_millis(500), &mut sent)
+            .unwrap();
+        assert!(sent.is_empty());
+
+        writer
+            .send(start + Duration::from_secs(1), &mut sent)
+            .unwrap();
+        assert_eq!(sent_data(&sent, locator(1)).len(), 1);
+        assert_eq!(sent_data(&sent, locator(2)).len(), 1);
+    }
+
+    #[test]
+    fn skips_removed_changes() {
+        let mut writer = writer();
+
+        writer
+            .new_change(Kind::Alive(vec![1]), KeyHash::default())
+            .unwrap();
+        writer
+            .new_change(Kind::Alive(vec![2]), KeyHash::default())
+            .unwrap();
+        writer.cache_mut().remove(1).unwrap();
+
+        let mut sent = Sent::new();
+        writer.send(Instant::now(), &mut sent).unwrap();
+
+        let data = sent_data(&sent, locator(1));
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0].writer_sequence_number, 2);
+    }
+
+    #[test]
+    fn fragments_large_changes() {
+        let mut writer =
+            StatelessWriter::builder(Guid::new([1; 12], [0, 0, 1, 2]), HistoryCache::default())
+                .max_message_size(1500)
+                .fragment_size(1000)
+                .build();
+        writer.reader_locator_add(ReaderLocator::new(locator(1), false));
+
+        writer
+            .new_change(Kind::Alive(vec![7; 5000]), KeyHash::default())
+            .unwrap();
+
+        let mut sent = Sent::new();
+        writer.send(Instant::now(), &mut sent).unwrap();
+
+        assert_eq!(sent.len(), 5);
+        assert!(
+            sent.iter()
+                .all(|(_, message)| message.serialized_size() <= 1500)
+        );
+
+        let data_frags: Vec<_> = sent
+            .iter()
+            .flat_map(|(_, message)| message.submessages())
+            .filter_map(|submessage| match submessage {
+                SubMessage::DataFrag(data_frag) => Some(data_frag),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(data_frags.len(), 5);
+        assert_eq!(data_frags[0].total_fragments(), 5);
+    }
+
+    #[test]
+    fn disposal_carries_status_info() {
+        let mut writer = writer();
+        let instance = KeyHash::new([7; 16]);
+
+        writer
+            .new_change(Kind::<Vec<u8>>::NotAliveDisposed, instance)
+            .unwrap();
+
+        let mut sent = Sent::new();
+        writer.send(Instant::now(), &mut sent).unwrap();
+
+        let data = &sent_data(&sent, locator(1))[0];
+        assert_eq!(data.payload, None);
+
+        let inline_qos = data.inline_qos.as_ref().unwrap();
+        assert_eq!(
+            inline_qos.get(parameter_id::KEY_HASH).unwrap().value(),
+            instance.as_ref()
+        );
+        assert_eq!(
+            inline_qos.get(parameter_id::STATUS_INFO).unwrap().value(),
+            &[0, 0, 0, 1]
+        );
+    }
+}
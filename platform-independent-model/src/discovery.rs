@@ -1 +1,20 @@
+//! Discovery of remote participants and their endpoints
+//!
+//! Discovery is built on the same behaviours as user traffic. Each
+//! participant uses built-in readers and writers, with well-known entity IDs,
+//! to exchange descriptions of itself and its endpoints.
+//!
+//! See [section 8.5](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF) of the relevant specification document.
 
+pub mod sedp;
+pub mod server;
+pub mod spdp;
+
+pub(crate) use crate::messages::encoding;
+pub use encoding::DecodeError;
+#[doc(inline)]
+pub use sedp::{DiscoveredReaderData, DiscoveredWriterData, EndpointDiscovery, MatchedEndpoints};
+#[doc(inline)]
+pub use server::DiscoveryServer;
+#[doc(inline)]
+pub use spdp::{DiscoveredParticipantData, ParticipantDiscovery, ParticipantEvent};
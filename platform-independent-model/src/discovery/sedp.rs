@@ -0,0 +1,1551 @@
+//! The Simple Endpoint Discovery Protocol (SEDP)
+//!
+//! Once two participants have discovered each other with SPDP, they exchange
+//! a [`DiscoveredWriterData`] for each of their writers, and a
+//! [`DiscoveredReaderData`] for each of their readers. These announcements
+//! are sent by reliable built-in [`StatefulWriter`]s, so that every remote
+//! participant eventually has a complete picture of the endpoints of the
+//! local participant.
+//!
+//! A local writer and a remote reader (or vice versa) match if they use the
+//! same topic and data type, share a partition, and have compatible quality of
+//! service policies.
+//! Matched endpoints are reported through the
+//! [`MatchedEndpoints`] trait, which adds or removes the corresponding
+//! [`ReaderProxy`] or [`WriterProxy`].
+//!
+//! See [section 8.5.4](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF) of the relevant specification document.
+
+use std::{collections::BTreeMap, convert::TryFrom, time::Instant};
+
+use super::{
+    encoding::{self, parameter, Parameters},
+    DecodeError, DiscoveredParticipantData,
+};
+use crate::{
+    behaviour::{
+        reader::{StatefulReader, WriterProxy},
+        receiver::{Context, ReaderSubMessage, WriterSubMessage},
+        writer::{Output, ReaderProxy, StatefulWriter},
+    },
+    messages::{
+        builder::TooLargeError,
+        submessage::elements::{parameter_id, Parameter, ParameterList},
+    },
+    structure::{
+        history::{Cache, Change, Kind},
+        qos::{check_compatibility, ReaderQos, WriterQos},
+        BuiltinEndpointSet, Guid, KeyHash, Locator,
+    },
+};
+
+/// The entity ID of the built-in writer which announces publications
+/// (`ENTITYID_SEDP_BUILTIN_PUBLICATIONS_ANNOUNCER`)
+pub const SEDP_PUBLICATIONS_WRITER: [u8; 4] = [0x00, 0x00, 0x03, 0xc2];
+
+/// The entity ID of the built-in reader which receives publications
+/// (`ENTITYID_SEDP_BUILTIN_PUBLICATIONS_DETECTOR`)
+pub const SEDP_PUBLICATIONS_READER: [u8; 4] = [0x00, 0x00, 0x03, 0xc7];
+
+/// The entity ID of the built-in writer which announces subscriptions
+/// (`ENTITYID_SEDP_BUILTIN_SUBSCRIPTIONS_ANNOUNCER`)
+pub const SEDP_SUBSCRIPTIONS_WRITER: [u8; 4] = [0x00, 0x00, 0x04, 0xc2];
+
+/// The entity ID of the built-in reader which receives subscriptions
+/// (`ENTITYID_SEDP_BUILTIN_SUBSCRIPTIONS_DETECTOR`)
+pub const SEDP_SUBSCRIPTIONS_READER: [u8; 4] = [0x00, 0x00, 0x04, 0xc7];
+
+/// The data announced for each writer (`DiscoveredWriterData`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredWriterData<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    /// The [`Guid`] of the writer
+    pub guid: Guid<P, Id>,
+
+    /// The name of the topic to which the writer publishes
+    pub topic_name: String,
+
+    /// The name of the data type of the topic
+    pub type_name: String,
+
+    /// The unicast [`Locator`]s of the writer
+    ///
+    /// If the writer has no locators, those of its participant are used.
+    pub unicast_locators: Vec<Locator>,
+
+    /// The multicast [`Locator`]s of the writer
+    pub multicast_locators: Vec<Locator>,
+
+    /// The quality of service policies offered by the writer
+    pub qos: WriterQos,
+}
+
+impl<P, Id> DiscoveredWriterData<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    /// Construct a new [`DiscoveredWriterData`] for the writer with the given
+    /// [`Guid`], which publishes to the given topic
+    ///
+    /// The writer has no locators, and the default [`WriterQos`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtps_pim::{discovery::sedp::DiscoveredWriterData, structure::Guid};
+    ///
+    /// let guid = Guid::new([1; 12], [0, 0, 1, 0x02]);
+    /// let data = DiscoveredWriterData::new(guid, "Square", "ShapeType");
+    ///
+    /// assert_eq!(data.topic_name, "Square");
+    /// ```
+    #[must_use]
+    pub fn new(
+        guid: Guid<P, Id>,
+        topic_name: impl Into<String>,
+        type_name: impl Into<String>,
+    ) -> Self {
+        Self {
+            guid,
+            topic_name: topic_name.into(),
+            type_name: type_name.into(),
+            unicast_locators: Vec::new(),
+            multicast_locators: Vec::new(),
+            qos: WriterQos::default(),
+        }
+    }
+}
+
+impl<P, Id> DiscoveredWriterData<P, Id>
+where
+    P: Copy + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+    Id: Copy + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+{
+    /// The [`KeyHash`] of the writer, derived from its [`Guid`]
+    #[must_use]
+    pub fn key_hash(&self) -> KeyHash {
+        key_hash(self.guid)
+    }
+
+    /// Convert the data to a [`ParameterList`]
+    #[must_use]
+    pub fn to_parameter_list(&self) -> ParameterList {
+        endpoint_parameters(
+            self.guid,
+            &self.topic_name,
+            &self.type_name,
+            &self.unicast_locators,
+            &self.multicast_locators,
+        )
+        .chain(self.qos.to_parameter_list())
+        .collect()
+    }
+
+    /// Serialize the data as a `PL_CDR` payload
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encoding::to_bytes(&self.to_parameter_list())
+    }
+
+    /// Deserialize the data from a `PL_CDR` payload
+    ///
+    /// Parameters which aren't recognised are ignored.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the payload is malformed, or the endpoint
+    /// [`Guid`], topic name or type name is missing.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let parameters = Parameters::from_bytes(bytes)?;
+        let mut data = Self::new(
+            parameters.required(parameter_id::ENDPOINT_GUID)?,
+            parameters.required::<String>(parameter_id::TOPIC_NAME)?,
+            parameters.required::<String>(parameter_id::TYPE_NAME)?,
+        );
+
+        data.qos = WriterQos::from_parameters(&parameters)?;
+        data.unicast_locators = parameters.all(parameter_id::UNICAST_LOCATOR)?;
+        data.multicast_locators = parameters.all(parameter_id::MULTICAST_LOCATOR)?;
+
+        Ok(data)
+    }
+}
+
+/// The data announced for each reader (`DiscoveredReaderData`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredReaderData<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    /// The [`Guid`] of the reader
+    pub guid: Guid<P, Id>,
+
+    /// The name of the topic to which the reader subscribes
+    pub topic_name: String,
+
+    /// The name of the data type of the topic
+    pub type_name: String,
+
+    /// The unicast [`Locator`]s of the reader
+    ///
+    /// If the reader has no locators, those of its participant are used.
+    pub unicast_locators: Vec<Locator>,
+
+    /// The multicast [`Locator`]s of the reader
+    pub multicast_locators: Vec<Locator>,
+
+    /// Whether the reader expects inline quality of service parameters
+    pub expects_inline_qos: bool,
+
+    /// The quality of service policies requested by the reader
+    pub qos: ReaderQos,
+}
+
+impl<P, Id> DiscoveredReaderData<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    /// Construct a new [`DiscoveredReaderData`] for the reader with the given
+    /// [`Guid`], which subscribes to the given topic
+    ///
+    /// The reader has no locators, the default [`ReaderQos`], and doesn't
+    /// expect inline quality of service parameters.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtps_pim::{discovery::sedp::DiscoveredReaderData, structure::Guid};
+    ///
+    /// let guid = Guid::new([1; 12], [0, 0, 1, 0x07]);
+    /// let data = DiscoveredReaderData::new(guid, "Square", "ShapeType");
+    ///
+    /// assert_eq!(data.type_name, "ShapeType");
+    /// ```
+    #[must_use]
+    pub fn new(
+        guid: Guid<P, Id>,
+        topic_name: impl Into<String>,
+        type_name: impl Into<String>,
+    ) -> Self {
+        Self {
+            guid,
+            topic_name: topic_name.into(),
+            type_name: type_name.into(),
+            unicast_locators: Vec::new(),
+            multicast_locators: Vec::new(),
+            expects_inline_qos: false,
+            qos: ReaderQos::default(),
+        }
+    }
+}
+
+impl<P, Id> DiscoveredReaderData<P, Id>
+where
+    P: Copy + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+    Id: Copy + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+{
+    /// The [`KeyHash`] of the reader, derived from its [`Guid`]
+    #[must_use]
+    pub fn key_hash(&self) -> KeyHash {
+        key_hash(self.guid)
+    }
+
+    /// Convert the data to a [`ParameterList`]
+    #[must_use]
+    pub fn to_parameter_list(&self) -> ParameterList {
+        endpoint_parameters(
+            self.guid,
+            &self.topic_name,
+            &self.type_name,
+            &self.unicast_locators,
+            &self.multicast_locators,
+        )
+        .chain(Some(parameter(
+            parameter_id::EXPECTS_INLINE_QOS,
+            &self.expects_inline_qos,
+        )))
+        .chain(self.qos.to_parameter_list())
+        .collect()
+    }
+
+    /// Serialize the data as a `PL_CDR` payload
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encoding::to_bytes(&self.to_parameter_list())
+    }
+
+    /// Deserialize the data from a `PL_CDR` payload
+    ///
+    /// Parameters which aren't recognised are ignored.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the payload is malformed, or the endpoint
+    /// [`Guid`], topic name or type name is missing.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let parameters = Parameters::from_bytes(bytes)?;
+        let mut data = Self::new(
+            parameters.required(parameter_id::ENDPOINT_GUID)?,
+            parameters.required::<String>(parameter_id::TOPIC_NAME)?,
+            parameters.required::<String>(parameter_id::TYPE_NAME)?,
+        );
+
+        if let Some(expects_inline_qos) = parameters.optional(parameter_id::EXPECTS_INLINE_QOS)? {
+            data.expects_inline_qos = expects_inline_qos;
+        }
+        data.qos = ReaderQos::from_parameters(&parameters)?;
+        data.unicast_locators = parameters.all(parameter_id::UNICAST_LOCATOR)?;
+        data.multicast_locators = parameters.all(parameter_id::MULTICAST_LOCATOR)?;
+
+        Ok(data)
+    }
+}
+
+/// The parameters shared by writer and reader announcements
+fn endpoint_parameters<'a, P, Id>(
+    guid: Guid<P, Id>,
+    topic_name: &'a str,
+    type_name: &'a str,
+    unicast_locators: &'a [Locator],
+    multicast_locators: &'a [Locator],
+) -> impl Iterator<Item = Parameter> + 'a
+where
+    P: Copy + AsRef<[u8]> + for<'b> TryFrom<&'b [u8]>,
+    Id: Copy + AsRef<[u8]> + for<'b> TryFrom<&'b [u8]>,
+{
+    vec![
+        parameter(parameter_id::ENDPOINT_GUID, &guid),
+        parameter(parameter_id::TOPIC_NAME, &topic_name.to_owned()),
+        parameter(parameter_id::TYPE_NAME, &type_name.to_owned()),
+    ]
+    .into_iter()
+    .chain(
+        unicast_locators
+            .iter()
+            .map(|locator| parameter(parameter_id::UNICAST_LOCATOR, locator)),
+    )
+    .chain(
+        multicast_locators
+            .iter()
+            .map(|locator| parameter(parameter_id::MULTICAST_LOCATOR, locator)),
+    )
+}
+
+/// The [`KeyHash`] of the endpoint with the given [`Guid`]
+fn key_hash<P, Id>(guid: Guid<P, Id>) -> KeyHash
+where
+    P: Copy + AsRef<[u8]>,
+    Id: Copy + AsRef<[u8]>,
+{
+    let mut bytes = [0; 16];
+    let prefix = guid.prefix();
+    let prefix = prefix.as_ref();
+    let len = prefix.len().min(12);
+    bytes[..len].copy_from_slice(&prefix[..len]);
+
+    let entity_id = guid.entity_id();
+    let entity_id = entity_id.as_ref();
+    let len = entity_id.len().min(4);
+    bytes[12..12 + len].copy_from_slice(&entity_id[..len]);
+    KeyHash::new(bytes)
+}
+
+/// Whether a writer and a reader are compatible
+///
+/// They must use the same topic and data type, share a
+/// [`Partition`](crate::structure::qos::Partition), and
+/// the quality of service policies offered by the writer must satisfy those
+/// requested by the reader.
+pub(crate) fn matches<P, Id>(
+    writer: &DiscoveredWriterData<P, Id>,
+    reader: &DiscoveredReaderData<P, Id>,
+) -> bool
+where
+    P: Copy,
+    Id: Copy,
+{
+    writer.topic_name == reader.topic_name
+        && writer.type_name == reader.type_name
+        && writer.qos.partition.matches(&reader.qos.partition)
+        && check_compatibility(&writer.qos, &reader.qos).is_ok()
+}
+
+/// The local endpoints which are matched with remote endpoints by an
+/// [`EndpointDiscovery`]
+///
+/// This is usually implemented by the participant, which forwards each call
+/// to the [`StatefulWriter`] or [`StatefulReader`] with the given [`Guid`].
+pub trait MatchedEndpoints<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    /// Add a [`ReaderProxy`] to the local writer with the given [`Guid`]
+    fn matched_reader_add(&mut self, writer: Guid<P, Id>, reader_proxy: ReaderProxy<P, Id>);
+
+    /// Remove a remote reader from the local writer with the given [`Guid`]
+    fn matched_reader_remove(&mut self, writer: Guid<P, Id>, reader: Guid<P, Id>);
+
+    /// Add a [`WriterProxy`] to the local reader with the given [`Guid`]
+    fn matched_writer_add(&mut self, reader: Guid<P, Id>, writer_proxy: WriterProxy<P, Id>);
+
+    /// Remove a remote writer from the local reader with the given [`Guid`]
+    fn matched_writer_remove(&mut self, reader: Guid<P, Id>, writer: Guid<P, Id>);
+}
+
+/// The error returned when a submessage sent to a built-in SEDP reader
+/// can't be processed
+#[derive(Debug, thiserror::Error)]
+pub enum ReceiveError<E>
+where
+    E: std::error::Error + 'static,
+{
+    /// A change couldn't be added to the cache of the built-in reader
+    #[error("failed to add the announcement to the cache")]
+    Cache(#[source] E),
+
+    /// An announcement couldn't be decoded
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+}
+
+/// Announces the local endpoints, and matches them with the remote endpoints
+/// discovered with SEDP
+///
+/// Local endpoints are registered with [`EndpointDiscovery::writer_add`] and
+/// [`EndpointDiscovery::reader_add`], and announced by the built-in SEDP
+/// writers. When an endpoint is deleted, its announcement is disposed of and
+/// unregistered, so that remote participants unmatch it.
+///
+/// The built-in endpoints of each remote participant are matched by
+/// [`EndpointDiscovery::participant_add`], usually in response to a
+/// [`ParticipantEvent`](super::ParticipantEvent). Submessages sent to the
+/// built-in readers and writers are passed to [`EndpointDiscovery::receive`]
+/// and [`EndpointDiscovery::receive_from_reader`] respectively.
+#[derive(Debug)]
+pub struct EndpointDiscovery<C, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    guid_prefix: P,
+    publications_writer: StatefulWriter<C, P, Id>,
+    subscriptions_writer: StatefulWriter<C, P, Id>,
+    publications_reader: StatefulReader<C, P, Id>,
+    subscriptions_reader: StatefulReader<C, P, Id>,
+    announcements: BTreeMap<Guid<P, Id>, Vec<u64>>,
+    local_writers: BTreeMap<Guid<P, Id>, DiscoveredWriterData<P, Id>>,
+    local_readers: BTreeMap<Guid<P, Id>, DiscoveredReaderData<P, Id>>,
+    participants: BTreeMap<P, DiscoveredParticipantData<P>>,
+    remote_writers: BTreeMap<Guid<P, Id>, DiscoveredWriterData<P, Id>>,
+    remote_readers: BTreeMap<Guid<P, Id>, DiscoveredReaderData<P, Id>>,
+}
+
+impl<C, P, Id> EndpointDiscovery<C, P, Id>
+where
+    C: Cache<Vec<u8>, Prefix = P, EntityId = Id, SqnN = u64>,
+    P: Copy + Ord + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+    Id: Copy + Ord + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]> + From<[u8; 4]>,
+{
+    /// Construct a new [`EndpointDiscovery`] for the participant with the
+    /// given [`Guid`] prefix
+    ///
+    /// Each built-in endpoint uses a new, default history [`Cache`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtps_pim::{
+    ///     discovery::sedp::{EndpointDiscovery, SEDP_PUBLICATIONS_WRITER},
+    ///     structure::history::HistoryCache,
+    /// };
+    ///
+    /// let discovery: EndpointDiscovery<HistoryCache<Vec<u8>, _, _>, _, [u8; 4]> =
+    ///     EndpointDiscovery::new([1; 12]);
+    ///
+    /// assert_eq!(
+    ///     discovery.publications_writer().guid().entity_id(),
+    ///     SEDP_PUBLICATIONS_WRITER
+    /// );
+    /// ```
+    #[must_use]
+    pub fn new(guid_prefix: P) -> Self
+    where
+        C: Default,
+    {
+        let guid = |entity_id| Guid::new(guid_prefix, Id::from(entity_id));
+        Self {
+            guid_prefix,
+            publications_writer: StatefulWriter::new(guid(SEDP_PUBLICATIONS_WRITER), C::default()),
+            subscriptions_writer: StatefulWriter::new(
+                guid(SEDP_SUBSCRIPTIONS_WRITER),
+                C::default(),
+            ),
+            publications_reader: StatefulReader::new(guid(SEDP_PUBLICATIONS_READER), C::default()),
+            subscriptions_reader: StatefulReader::new(
+                guid(SEDP_SUBSCRIPTIONS_READER),
+                C::default(),
+            ),
+            announcements: BTreeMap::default(),
+            local_writers: BTreeMap::default(),
+            local_readers: BTreeMap::default(),
+            participants: BTreeMap::default(),
+            remote_writers: BTreeMap::default(),
+            remote_readers: BTreeMap::default(),
+        }
+    }
+
+    /// The built-in [`StatefulWriter`] which announces the local writers
+    #[must_use]
+    pub fn publications_writer(&self) -> &StatefulWriter<C, P, Id> {
+        &self.publications_writer
+    }
+
+    /// The built-in [`StatefulWriter`] which announces the local readers
+    #[must_use]
+    pub fn subscriptions_writer(&self) -> &StatefulWriter<C, P, Id> {
+        &self.subscriptions_writer
+    }
+
+    /// The built-in [`StatefulReader`] which receives remote writers
+    #[must_use]
+    pub fn publications_reader(&self) -> &StatefulReader<C, P, Id> {
+        &self.publications_reader
+    }
+
+    /// The built-in [`StatefulReader`] which receives remote readers
+    #[must_use]
+    pub fn subscriptions_reader(&self) -> &StatefulReader<C, P, Id> {
+        &self.subscriptions_reader
+    }
+
+    /// The [`DiscoveredWriterData`] of every known remote writer
+    pub fn remote_writers(&self) -> impl Iterator<Item = &DiscoveredWriterData<P, Id>> + '_ {
+        self.remote_writers.values()
+    }
+
+    /// The [`DiscoveredReaderData`] of every known remote reader
+    pub fn remote_readers(&self) -> impl Iterator<Item = &DiscoveredReaderData<P, Id>> + '_ {
+        self.remote_readers.values()
+    }
+
+    /// The time at which [`EndpointDiscovery::send`] next needs to be called
+    #[must_use]
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.publications_writer
+            .next_deadline()
+            .into_iter()
+            .chain(self.subscriptions_writer.next_deadline())
+            .chain(self.publications_reader.next_deadline())
+            .chain(self.subscriptions_reader.next_deadline())
+            .min()
+    }
+
+    /// Match the built-in endpoints of a remote participant
+    ///
+    /// Only the built-in endpoints which the participant advertises in its
+    /// [`BuiltinEndpointSet`] are matched. They are reached through the
+    /// metatraffic [`Locator`]s of the participant, while its default locators
+    /// are used for remote endpoints which don't announce any locators of
+    /// their own. Adding a participant which is already known only updates
+    /// its data, unless its built-in endpoints or metatraffic locators have
+    /// changed.
+    pub fn participant_add(&mut self, data: DiscoveredParticipantData<P>) {
+        let guid_prefix = data.guid_prefix;
+        if guid_prefix == self.guid_prefix {
+            return;
+        }
+
+        let unchanged = self.participants.get(&guid_prefix).map_or(false, |known| {
+            known.builtin_endpoints == data.builtin_endpoints
+                && known.metatraffic_unicast_locators == data.metatraffic_unicast_locators
+                && known.metatraffic_multicast_locators == data.metatraffic_multicast_locators
+        });
+        if !unchanged {
+            let guid = |entity_id| Guid::new(guid_prefix, Id::from(entity_id));
+            let advertised = |endpoint| data.builtin_endpoints.contains(endpoint);
+            let unicast = &data.metatraffic_unicast_locators;
+            let multicast = &data.metatraffic_multicast_locators;
+
+            for (writer, reader, endpoint) in [
+                (
+                    &mut self.publications_writer,
+                    SEDP_PUBLICATIONS_READER,
+                    BuiltinEndpointSet::PUBLICATIONS_DETECTOR,
+                ),
+                (
+                    &mut self.subscriptions_writer,
+                    SEDP_SUBSCRIPTIONS_READER,
+                    BuiltinEndpointSet::SUBSCRIPTIONS_DETECTOR,
+                ),
+            ] {
+                if advertised(endpoint) {
+                    writer.matched_reader_add(ReaderProxy::new(
+                        guid(reader),
+                        false,
+                        unicast.clone(),
+                        multicast.clone(),
+                    ));
+                } else {
+                    writer.matched_reader_remove(guid(reader));
+                }
+            }
+            for (reader, writer, endpoint) in [
+                (
+                    &mut self.publications_reader,
+                    SEDP_PUBLICATIONS_WRITER,
+                    BuiltinEndpointSet::PUBLICATIONS_ANNOUNCER,
+                ),
+                (
+                    &mut self.subscriptions_reader,
+                    SEDP_SUBSCRIPTIONS_WRITER,
+                    BuiltinEndpointSet::SUBSCRIPTIONS_ANNOUNCER,
+                ),
+            ] {
+                if advertised(endpoint) {
+                    reader.matched_writer_add(WriterProxy::new(
+                        guid(writer),
+                        unicast.clone(),
+                        multicast.clone(),
+                    ));
+                } else {
+                    reader.matched_writer_remove(guid(writer));
+                }
+            }
+        }
+
+        self.participants.insert(guid_prefix, data);
+    }
+
+    /// Forget a remote participant, and unmatch all of its endpoints
+    pub fn participant_remove<M>(&mut self, guid_prefix: P, endpoints: &mut M)
+    where
+        M: MatchedEndpoints<P, Id>,
+    {
+        if self.participants.remove(&guid_prefix).is_none() {
+            return;
+        }
+
+        let guid = |entity_id| Guid::new(guid_prefix, Id::from(entity_id));
+        self.publications_writer
+            .matched_reader_remove(guid(SEDP_PUBLICATIONS_READER));
+        self.subscriptions_writer
+            .matched_reader_remove(guid(SEDP_SUBSCRIPTIONS_READER));
+        self.publications_reader
+            .matched_writer_remove(guid(SEDP_PUBLICATIONS_WRITER));
+        self.subscriptions_reader
+            .matched_writer_remove(guid(SEDP_SUBSCRIPTIONS_WRITER));
+
+        let writers: Vec<_> = self
+            .remote_writers
+            .keys()
+            .filter(|writer| writer.prefix() == guid_prefix)
+            .copied()
+            .collect();
+        for writer in writers {
+            self.remote_writer_remove(writer, endpoints);
+        }
+
+        let readers: Vec<_> = self
+            .remote_readers
+            .keys()
+            .filter(|reader| reader.prefix() == guid_prefix)
+            .copied()
+            .collect();
+        for reader in readers {
+            self.remote_reader_remove(reader, endpoints);
+        }
+    }
+
+    /// Announce a local writer, and match it with the known remote readers
+    ///
+    /// Adding a writer which is already known replaces its announcement.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the announcement can't be added to the cache.
+    /// Any previous announcement is then kept.
+    pub fn writer_add<M>(
+        &mut self,
+        data: DiscoveredWriterData<P, Id>,
+        endpoints: &mut M,
+    ) -> Result<(), C::AddErr>
+    where
+        M: MatchedEndpoints<P, Id>,
+    {
+        announce(
+            &mut self.publications_writer,
+            &mut self.announcements,
+            data.guid,
+            vec![Kind::Alive(data.to_bytes())],
+        )?;
+
+        let previous = self.local_writers.get(&data.guid);
+        for reader in self.remote_readers.values() {
+            if matches(&data, reader) {
+                endpoints.matched_reader_add(data.guid, self.reader_proxy(reader));
+            } else if previous.map_or(false, |previous| matches(previous, reader)) {
+                endpoints.matched_reader_remove(data.guid, reader.guid);
+            }
+        }
+
+        self.local_writers.insert(data.guid, data);
+        Ok(())
+    }
+
+    /// Delete a local writer
+    ///
+    /// The writer is unmatched from the remote readers, and its announcement
+    /// is disposed of and unregistered.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the disposal can't be added to the cache. The
+    /// writer is then still announced and matched.
+    pub fn writer_remove<M>(
+        &mut self,
+        guid: Guid<P, Id>,
+        endpoints: &mut M,
+    ) -> Result<(), C::AddErr>
+    where
+        M: MatchedEndpoints<P, Id>,
+    {
+        if !self.local_writers.contains_key(&guid) {
+            return Ok(());
+        }
+
+        announce(
+            &mut self.publications_writer,
+            &mut self.announcements,
+            guid,
+            vec![Kind::NotAliveDisposed, Kind::NotAliveUnregistered],
+        )?;
+
+        if let Some(data) = self.local_writers.remove(&guid) {
+            for reader in self.remote_readers.values() {
+                if matches(&data, reader) {
+                    endpoints.matched_reader_remove(guid, reader.guid);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Announce a local reader, and match it with the known remote writers
+    ///
+    /// Adding a reader which is already known replaces its announcement.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the announcement can't be added to the cache.
+    /// Any previous announcement is then kept.
+    pub fn reader_add<M>(
+        &mut self,
+        data: DiscoveredReaderData<P, Id>,
+        endpoints: &mut M,
+    ) -> Result<(), C::AddErr>
+    where
+        M: MatchedEndpoints<P, Id>,
+    {
+        announce(
+            &mut self.subscriptions_writer,
+            &mut self.announcements,
+            data.guid,
+            vec![Kind::Alive(data.to_bytes())],
+        )?;
+
+        let previous = self.local_readers.get(&data.guid);
+        for writer in self.remote_writers.values() {
+            if matches(writer, &data) {
+                endpoints.matched_writer_add(data.guid, self.writer_proxy(writer));
+            } else if previous.map_or(false, |previous| matches(writer, previous)) {
+                endpoints.matched_writer_remove(data.guid, writer.guid);
+            }
+        }
+
+        self.local_readers.insert(data.guid, data);
+        Ok(())
+    }
+
+    /// Delete a local reader
+    ///
+    /// The reader is unmatched from the remote writers, and its announcement
+    /// is disposed of and unregistered.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the disposal can't be added to the cache. The
+    /// reader is then still announced and matched.
+    pub fn reader_remove<M>(
+        &mut self,
+        guid: Guid<P, Id>,
+        endpoints: &mut M,
+    ) -> Result<(), C::AddErr>
+    where
+        M: MatchedEndpoints<P, Id>,
+    {
+        if !self.local_readers.contains_key(&guid) {
+            return Ok(());
+        }
+
+        announce(
+            &mut self.subscriptions_writer,
+            &mut self.announcements,
+            guid,
+            vec![Kind::NotAliveDisposed, Kind::NotAliveUnregistered],
+        )?;
+
+        if let Some(data) = self.local_readers.remove(&guid) {
+            for writer in self.remote_writers.values() {
+                if matches(writer, &data) {
+                    endpoints.matched_writer_remove(guid, writer.guid);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Process a submessage sent to one of the built-in SEDP readers
+    ///
+    /// Each announcement delivered by the reader adds, updates or removes a
+    /// remote endpoint, and matches or unmatches the compatible local
+    /// endpoints. Submessages from other writers are ignored.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if a change can't be added to the cache of the
+    /// built-in reader, or an announcement can't be decoded. The remaining
+    /// announcements are still processed.
+    pub fn receive<M>(
+        &mut self,
+        now: Instant,
+        context: &Context<P>,
+        submessage: ReaderSubMessage<Id>,
+        endpoints: &mut M,
+    ) -> Result<(), ReceiveError<C::AddErr>>
+    where
+        M: MatchedEndpoints<P, Id>,
+    {
+        let writer = submessage.writer();
+        let mut result = Ok(());
+
+        if writer == Id::from(SEDP_PUBLICATIONS_WRITER) {
+            self.publications_reader
+                .receive::<Vec<u8>>(now, context, submessage)
+                .map_err(ReceiveError::Cache)?;
+            for change in take_changes(self.publications_reader.cache_mut()) {
+                if let Err(err) = self.publication_receive(change, endpoints) {
+                    result = Err(err.into());
+                }
+            }
+        } else if writer == Id::from(SEDP_SUBSCRIPTIONS_WRITER) {
+            self.subscriptions_reader
+                .receive::<Vec<u8>>(now, context, submessage)
+                .map_err(ReceiveError::Cache)?;
+            for change in take_changes(self.subscriptions_reader.cache_mut()) {
+                if let Err(err) = self.subscription_receive(change, endpoints) {
+                    result = Err(err.into());
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Process a submessage sent to one of the built-in SEDP writers by a
+    /// remote reader
+    ///
+    /// Submessages sent to other writers are ignored.
+    pub fn receive_from_reader(
+        &mut self,
+        now: Instant,
+        writer: Id,
+        context: &Context<P>,
+        submessage: WriterSubMessage<Id>,
+    ) {
+        if writer == Id::from(SEDP_PUBLICATIONS_WRITER) {
+            self.publications_writer.receive(now, context, submessage);
+        } else if writer == Id::from(SEDP_SUBSCRIPTIONS_WRITER) {
+            self.subscriptions_writer.receive(now, context, submessage);
+        }
+    }
+
+    /// Send any messages which are due from the built-in endpoints
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if a submessage doesn't fit in a message. The
+    /// messages of the other built-in endpoints are still sent.
+    pub fn send<O>(&mut self, now: Instant, output: &mut O) -> Result<(), TooLargeError>
+    where
+        O: Output<P, Id>,
+    {
+        let publications = self.publications_writer.send::<Vec<u8>, _>(now, output);
+        let subscriptions = self.subscriptions_writer.send::<Vec<u8>, _>(now, output);
+        let publications_acks = self.publications_reader.send(now, output);
+        let subscriptions_acks = self.subscriptions_reader.send(now, output);
+
+        publications
+            .and(subscriptions)
+            .and(publications_acks)
+            .and(subscriptions_acks)
+    }
+
+    fn publication_receive<M>(
+        &mut self,
+        change: Change<Vec<u8>, P, Id>,
+        endpoints: &mut M,
+    ) -> Result<(), DecodeError>
+    where
+        M: MatchedEndpoints<P, Id>,
+    {
+        let instance = change.instance();
+        match change.into_kind() {
+            Kind::Alive(bytes) => {
+                let data = DiscoveredWriterData::from_bytes(&bytes)?;
+                self.remote_writer_add(data, endpoints);
+            }
+            Kind::NotAliveDisposed | Kind::NotAliveUnregistered => {
+                let writer = self
+                    .remote_writers
+                    .keys()
+                    .find(|&&writer| key_hash(writer) == instance)
+                    .copied();
+                if let Some(writer) = writer {
+                    self.remote_writer_remove(writer, endpoints);
+                }
+            }
+            Kind::AliveFiltered => {}
+        }
+        Ok(())
+    }
+
+    fn subscription_receive<M>(
+        &mut self,
+        change: Change<Vec<u8>, P, Id>,
+        endpoints: &mut M,
+    ) -> Result<(), DecodeError>
+    where
+        M: MatchedEndpoints<P, Id>,
+    {
+        let instance = change.instance();
+        match change.into_kind() {
+            Kind::Alive(bytes) => {
+                let data = DiscoveredReaderData::from_bytes(&bytes)?;
+                self.remote_reader_add(data, endpoints);
+            }
+            Kind::NotAliveDisposed | Kind::NotAliveUnregistered => {
+                let reader = self
+                    .remote_readers
+                    .keys()
+                    .find(|&&reader| key_hash(reader) == instance)
+                    .copied();
+                if let Some(reader) = reader {
+                    self.remote_reader_remove(reader, endpoints);
+                }
+            }
+            Kind::AliveFiltered => {}
+        }
+        Ok(())
+    }
+
+    /// Add or update a remote writer, and match it with the compatible local
+    /// readers
+    ///
+    /// This is done automatically for the writers announced with SEDP, but may
+    /// also be used to declare remote writers statically, without SEDP. The
+    /// endpoints of the local participant are ignored, since they may be
+    /// relayed back by a [discovery server](super::server).
+    pub fn remote_writer_add<M>(&mut self, data: DiscoveredWriterData<P, Id>, endpoints: &mut M)
+    where
+        M: MatchedEndpoints<P, Id>,
+    {
+        if data.guid.prefix() == self.guid_prefix {
+            return;
+        }
+
+        let previous = self.remote_writers.get(&data.guid);
+        if previous == Some(&data) {
+            return;
+        }
+
+        for reader in self.local_readers.values() {
+            if matches(&data, reader) {
+                endpoints.matched_writer_add(reader.guid, self.writer_proxy(&data));
+            } else if previous.map_or(false, |previous| matches(previous, reader)) {
+                endpoints.matched_writer_remove(reader.guid, data.guid);
+            }
+        }
+
+        self.remote_writers.insert(data.guid, data);
+    }
+
+    /// Forget a remote writer, and unmatch it from the local readers
+    pub fn remote_writer_remove<M>(&mut self, guid: Guid<P, Id>, endpoints: &mut M)
+    where
+        M: MatchedEndpoints<P, Id>,
+    {
+        if let Some(data) = self.remote_writers.remove(&guid) {
+            for reader in self.local_readers.values() {
+                if matches(&data, reader) {
+                    endpoints.matched_writer_remove(reader.guid, guid);
+                }
+            }
+        }
+    }
+
+    /// Add or update a remote reader, and match it with the compatible local
+    /// writers
+    ///
+    /// This is done automatically for the readers announced with SEDP, but may
+    /// also be used to declare remote readers statically, without SEDP. The
+    /// endpoints of the local participant are ignored, since they may be
+    /// relayed back by a [discovery server](super::server).
+    pub fn remote_reader_add<M>(&mut self, data: DiscoveredReaderData<P, Id>, endpoints: &mut M)
+    where
+        M: MatchedEndpoints<P, Id>,
+    {
+        if data.guid.prefix() == self.guid_prefix {
+            return;
+        }
+
+        let previous = self.remote_readers.get(&data.guid);
+        if previous == Some(&data) {
+            return;
+        }
+
+        for writer in self.local_writers.values() {
+            if matches(writer, &data) {
+                endpoints.matched_reader_add(writer.guid, self.reader_proxy(&data));
+            } else if previous.map_or(false, |previous| matches(writer, previous)) {
+                endpoints.matched_reader_remove(writer.guid, data.guid);
+            }
+        }
+
+        self.remote_readers.insert(data.guid, data);
+    }
+
+    /// Forget a remote reader, and unmatch it from the local writers
+    pub fn remote_reader_remove<M>(&mut self, guid: Guid<P, Id>, endpoints: &mut M)
+    where
+        M: MatchedEndpoints<P, Id>,
+    {
+        if let Some(data) = self.remote_readers.remove(&guid) {
+            for writer in self.local_writers.values() {
+                if matches(writer, &data) {
+                    endpoints.matched_reader_remove(writer.guid, guid);
+                }
+            }
+        }
+    }
+
+    /// The [`Locator`]s of a remote endpoint, falling back to the default
+    /// locators of its participant
+    fn locators(
+        &self,
+        guid_prefix: P,
+        unicast: &[Locator],
+        multicast: &[Locator],
+    ) -> (Vec<Locator>, Vec<Locator>) {
+        match self.participants.get(&guid_prefix) {
+            Some(participant) if unicast.is_empty() && multicast.is_empty() => (
+                participant.default_unicast_locators.clone(),
+                participant.default_multicast_locators.clone(),
+            ),
+            _ => (unicast.to_vec(), multicast.to_vec()),
+        }
+    }
+
+    fn reader_proxy(&self, data: &DiscoveredReaderData<P, Id>) -> ReaderProxy<P, Id> {
+        let (unicast, multicast) = self.locators(
+            data.guid.prefix(),
+            &data.unicast_locators,
+            &data.multicast_locators,
+        );
+        ReaderProxy::new(data.guid, data.expects_inline_qos, unicast, multicast)
+    }
+
+    fn writer_proxy(&self, data: &DiscoveredWriterData<P, Id>) -> WriterProxy<P, Id> {
+        let (unicast, multicast) = self.locators(
+            data.guid.prefix(),
+            &data.unicast_locators,
+            &data.multicast_locators,
+        );
+        WriterProxy::new(data.guid, unicast, multicast).with_liveliness(data.qos.liveliness)
+    }
+}
+
+/// Write the changes announcing an endpoint, replacing its previous
+/// announcement
+///
+/// The previous announcement is only removed once every new change has been
+/// written. If one can't be written, the previous announcement still stands,
+/// and any of the new changes which were written are removed.
+fn announce<C, P, Id>(
+    writer: &mut StatefulWriter<C, P, Id>,
+    announcements: &mut BTreeMap<Guid<P, Id>, Vec<u64>>,
+    guid: Guid<P, Id>,
+    kinds: Vec<Kind<Vec<u8>>>,
+) -> Result<(), C::AddErr>
+where
+    C: Cache<Vec<u8>, Prefix = P, EntityId = Id, SqnN = u64>,
+    P: Copy + Ord + AsRef<[u8]>,
+    Id: Copy + Ord + AsRef<[u8]>,
+{
+    let instance = key_hash(guid);
+    let mut sequence_numbers = Vec::new();
+    for kind in kinds {
+        match writer.new_change(kind, instance) {
+            Ok(sequence_number) => sequence_numbers.push(sequence_number),
+            Err(err) => {
+                for sequence_number in sequence_numbers {
+                    let _ = writer.cache_mut().remove(sequence_number);
+                }
+                return Err(err);
+            }
+        }
+    }
+
+    // only the latest announcement of each endpoint is kept
+    for previous in announcements
+        .insert(guid, sequence_numbers)
+        .into_iter()
+        .flatten()
+    {
+        let _ = writer.cache_mut().remove(previous);
+    }
+    Ok(())
+}
+
+/// Remove every change delivered to the cache of a built-in reader
+fn take_changes<C, P, Id>(cache: &mut C) -> Vec<Change<Vec<u8>, P, Id>>
+where
+    C: Cache<Vec<u8>, Prefix = P, EntityId = Id>,
+    P: Copy,
+    Id: Copy,
+{
+    let mut changes = Vec::new();
+    while let Some(sequence_number) = cache.min_sequence_number() {
+        match cache.remove(sequence_number) {
+            Ok(change) => changes.push(change),
+            Err(_) => break,
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DiscoveredReaderData, DiscoveredWriterData, EndpointDiscovery, MatchedEndpoints,
+        ReceiveError,
+    };
+    use crate::{
+        behaviour::{
+            liveliness::{Liveliness, LivelinessKind},
+            reader::WriterProxy,
+            receiver::{Context, Dispatch, ReaderSubMessage, WriterSubMessage},
+            writer::ReaderProxy,
+            MessageReceiver,
+        },
+        discovery::DiscoveredParticipantData,
+        messages::Message,
+        structure::{
+            history::{Cache, Change, HistoryCache, Kind, LimitError, NotFoundError},
+            qos::{Durability, ReaderQos, WriterQos},
+            BuiltinEndpointSet, Guid, Locator,
+        },
+    };
+    use std::{
+        cell::Cell,
+        net::{Ipv4Addr, SocketAddrV4},
+        time::{Duration, Instant},
+    };
+
+    type Prefix = [u8; 12];
+    type Id = [u8; 4];
+    type TestDiscovery = EndpointDiscovery<HistoryCache<Vec<u8>, Prefix, Id>, Prefix, Id>;
+    type Sent = Vec<(Locator, Message<Prefix, Id>)>;
+
+    /// A change to the matched endpoints
+    #[derive(Debug, PartialEq)]
+    enum Matched {
+        Reader(Guid<Prefix, Id>, Guid<Prefix, Id>, Vec<Locator>),
+        ReaderRemoved(Guid<Prefix, Id>, Guid<Prefix, Id>),
+        Writer(Guid<Prefix, Id>, Guid<Prefix, Id>, Liveliness),
+        WriterRemoved(Guid<Prefix, Id>, Guid<Prefix, Id>),
+    }
+
+    impl MatchedEndpoints<Prefix, Id> for Vec<Matched> {
+        fn matched_reader_add(
+            &mut self,
+            writer: Guid<Prefix, Id>,
+            reader_proxy: ReaderProxy<Prefix, Id>,
+        ) {
+            self.push(Matched::Reader(
+                writer,
+                reader_proxy.remote_reader_guid(),
+                reader_proxy.unicast_locators().to_vec(),
+            ));
+        }
+
+        fn matched_reader_remove(&mut self, writer: Guid<Prefix, Id>, reader: Guid<Prefix, Id>) {
+            self.push(Matched::ReaderRemoved(writer, reader));
+        }
+
+        fn matched_writer_add(
+            &mut self,
+            reader: Guid<Prefix, Id>,
+            writer_proxy: WriterProxy<Prefix, Id>,
+        ) {
+            self.push(Matched::Writer(
+                reader,
+                writer_proxy.remote_writer_guid(),
+                writer_proxy.liveliness(),
+            ));
+        }
+
+        fn matched_writer_remove(&mut self, reader: Guid<Prefix, Id>, writer: Guid<Prefix, Id>) {
+            self.push(Matched::WriterRemoved(reader, writer));
+        }
+    }
+
+    /// Routes every submessage to a single discovery instance
+    struct Router<'a> {
+        discovery: &'a mut TestDiscovery,
+        matched: &'a mut Vec<Matched>,
+        now: Instant,
+        result: Result<(), ReceiveError<LimitError>>,
+    }
+
+    impl Dispatch<Prefix, Id> for Router<'_> {
+        fn dispatch_to_reader(
+            &mut self,
+            _reader: Option<Id>,
+            context: &Context<Prefix>,
+            submessage: ReaderSubMessage<Id>,
+        ) {
+            self.result = self
+                .discovery
+                .receive(self.now, context, submessage, self.matched);
+        }
+
+        fn dispatch_to_writer(
+            &mut self,
+            writer: Id,
+            context: &Context<Prefix>,
+            submessage: WriterSubMessage<Id>,
+        ) {
+            self.discovery
+                .receive_from_reader(self.now, writer, context, submessage);
+        }
+    }
+
+    fn unicast(guid_prefix: Prefix) -> Locator {
+        SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, guid_prefix[0]), 7410).into()
+    }
+
+    fn participant(guid_prefix: Prefix) -> DiscoveredParticipantData<Prefix> {
+        DiscoveredParticipantData {
+            default_unicast_locators: vec![unicast(guid_prefix)],
+            metatraffic_unicast_locators: vec![unicast(guid_prefix)],
+            ..DiscoveredParticipantData::new(guid_prefix)
+        }
+    }
+
+    /// Two participants which have discovered each other
+    fn peers() -> (TestDiscovery, TestDiscovery) {
+        let mut a = TestDiscovery::new([1; 12]);
+        let mut b = TestDiscovery::new([2; 12]);
+        a.participant_add(participant([2; 12]));
+        b.participant_add(participant([1; 12]));
+        (a, b)
+    }
+
+    /// Deliver every message sent by `from` to `to`
+    fn deliver(from: &mut TestDiscovery, to: &mut TestDiscovery, matched: &mut Vec<Matched>) {
+        let now = Instant::now();
+        let mut sent = Sent::new();
+        from.send(now, &mut sent).unwrap();
+        let receiver = MessageReceiver::new(to.guid_prefix);
+        for (_, message) in sent {
+            let mut router = Router {
+                discovery: to,
+                matched,
+                now,
+                result: Ok(()),
+            };
+            receiver.receive(None, message, &mut router).unwrap();
+            router.result.unwrap();
+        }
+    }
+
+    /// Exchange messages in both directions until the peers are in sync
+    fn exchange(
+        a: &mut TestDiscovery,
+        a_matched: &mut Vec<Matched>,
+        b: &mut TestDiscovery,
+        b_matched: &mut Vec<Matched>,
+    ) {
+        for _ in 0..3 {
+            deliver(a, b, b_matched);
+            deliver(b, a, a_matched);
+        }
+    }
+
+    #[test]
+    fn endpoint_data_round_trip() {
+        let writer = DiscoveredWriterData {
+            unicast_locators: vec![unicast([1; 12])],
+            qos: WriterQos {
+                liveliness: Liveliness {
+                    kind: LivelinessKind::ManualByTopic,
+                    lease_duration: Some(Duration::from_millis(1500)),
+                },
+                ..WriterQos::default()
+            },
+            ..DiscoveredWriterData::new(Guid::new([1; 12], [0, 0, 1, 0x02]), "Square", "Shape")
+        };
+        assert_eq!(
+            DiscoveredWriterData::from_bytes(&writer.to_bytes()).unwrap(),
+            writer
+        );
+
+        let reader = DiscoveredReaderData {
+            expects_inline_qos: true,
+            qos: ReaderQos {
+                durability: Durability::TransientLocal,
+                ..ReaderQos::default()
+            },
+            ..DiscoveredReaderData::new(Guid::new([2; 12], [0, 0, 1, 0x07]), "Circle", "Shape")
+        };
+        assert_eq!(
+            DiscoveredReaderData::from_bytes(&reader.to_bytes()).unwrap(),
+            reader
+        );
+    }
+
+    #[test]
+    fn matches_and_unmatches_endpoints() {
+        let (mut a, mut b) = peers();
+        let (mut a_matched, mut b_matched) = (Vec::new(), Vec::new());
+
+        let writer = Guid::new([1; 12], [0, 0, 1, 0x02]);
+        let reader = Guid::new([2; 12], [0, 0, 1, 0x07]);
+        let other = Guid::new([2; 12], [0, 0, 2, 0x07]);
+        a.writer_add(
+            DiscoveredWriterData::new(writer, "Square", "Shape"),
+            &mut a_matched,
+        )
+        .unwrap();
+        b.reader_add(
+            DiscoveredReaderData::new(reader, "Square", "Shape"),
+            &mut b_matched,
+        )
+        .unwrap();
+        b.reader_add(
+            DiscoveredReaderData {
+                qos: ReaderQos {
+                    durability: Durability::TransientLocal,
+                    ..ReaderQos::default()
+                },
+                ..DiscoveredReaderData::new(other, "Square", "Shape")
+            },
+            &mut b_matched,
+        )
+        .unwrap();
+        exchange(&mut a, &mut a_matched, &mut b, &mut b_matched);
+
+        // endpoints without locators are reached through their participant,
+        // and endpoints with incompatible policies aren't matched
+        assert_eq!(
+            a_matched,
+            vec![Matched::Reader(writer, reader, vec![unicast([2; 12])])]
+        );
+        assert_eq!(
+            b_matched,
+            vec![Matched::Writer(reader, writer, Liveliness::default())]
+        );
+        assert_eq!(a.remote_readers().count(), 2);
+
+        a_matched.clear();
+        b_matched.clear();
+        a.writer_remove(writer, &mut a_matched).unwrap();
+        exchange(&mut a, &mut a_matched, &mut b, &mut b_matched);
+        assert_eq!(a_matched, vec![Matched::ReaderRemoved(writer, reader)]);
+        assert_eq!(b_matched, vec![Matched::WriterRemoved(reader, writer)]);
+        assert_eq!(b.remote_writers().count(), 0);
+    }
+
+    #[test]
+    fn unmatches_the_endpoints_of_lost_participants() {
+        let (mut a, mut b) = peers();
+        let (mut a_matched, mut b_matched) = (Vec::new(), Vec::new());
+
+        let writer = Guid::new([1; 12], [0, 0, 1, 0x02]);
+        let reader = Guid::new([2; 12], [0, 0, 1, 0x07]);
+        a.writer_add(
+            DiscoveredWriterData::new(writer, "Square", "Shape"),
+            &mut a_matched,
+        )
+        .unwrap();
+        b.reader_add(
+            DiscoveredReaderData::new(reader, "Square", "Shape"),
+            &mut b_matched,
+        )
+        .unwrap();
+        exchange(&mut a, &mut a_matched, &mut b, &mut b_matched);
+
+        b_matched.clear();
+        b.participant_remove([1; 12], &mut b_matched);
+        assert_eq!(b_matched, vec![Matched::WriterRemoved(reader, writer)]);
+        assert!(b.publications_reader().matched_writers().is_empty());
+        assert!(b.subscriptions_writer().matched_readers().is_empty());
+    }
+
+    #[test]
+    fn matches_statically_declared_endpoints() {
+        let mut a = TestDiscovery::new([1; 12]);
+        let mut matched = Vec::new();
+
+        let writer = Guid::new([1; 12], [0, 0, 1, 0x02]);
+        let reader = Guid::new([2; 12], [0, 0, 1, 0x07]);
+        a.writer_add(
+            DiscoveredWriterData::new(writer, "Square", "Shape"),
+            &mut matched,
+        )
+        .unwrap();
+        a.remote_reader_add(
+            DiscoveredReaderData {
+                unicast_locators: vec![unicast([2; 12])],
+                ..DiscoveredReaderData::new(reader, "Square", "Shape")
+            },
+            &mut matched,
+        );
+        assert_eq!(
+            matched,
+            vec![Matched::Reader(writer, reader, vec![unicast([2; 12])])]
+        );
+
+        matched.clear();
+        a.remote_reader_remove(reader, &mut matched);
+        assert_eq!(matched, vec![Matched::ReaderRemoved(writer, reader)]);
+        assert_eq!(a.remote_readers().count(), 0);
+    }
+
+    #[test]
+    fn only_matches_advertised_builtin_endpoints() {
+        let mut a = TestDiscovery::new([1; 12]);
+        let mut peer = participant([2; 12]);
+        peer.builtin_endpoints
+            .remove(BuiltinEndpointSet::SUBSCRIPTIONS_DETECTOR);
+        peer.builtin_endpoints
+            .remove(BuiltinEndpointSet::PUBLICATIONS_ANNOUNCER);
+        a.participant_add(peer.clone());
+
+        assert_eq!(a.publications_writer().matched_readers().len(), 1);
+        assert!(a.subscriptions_writer().matched_readers().is_empty());
+        assert!(a.publications_reader().matched_writers().is_empty());
+        assert_eq!(a.subscriptions_reader().matched_writers().len(), 1);
+
+        // a participant which stops advertising an endpoint is unmatched
+        peer.builtin_endpoints
+            .remove(BuiltinEndpointSet::PUBLICATIONS_DETECTOR);
+        a.participant_add(peer);
+        assert!(a.publications_writer().matched_readers().is_empty());
+    }
+
+    thread_local! {
+        /// Whether a [`FallibleCache`] refuses every change
+        static REFUSE_CHANGES: Cell<bool> = Cell::new(false);
+    }
+
+    /// A cache which refuses every change while [`REFUSE_CHANGES`] is set
+    #[derive(Debug, Default)]
+    struct FallibleCache(HistoryCache<Vec<u8>, Prefix, Id>);
+
+    impl Cache<Vec<u8>> for FallibleCache {
+        type Prefix = Prefix;
+        type EntityId = Id;
+        type SqnN = u64;
+        type AddErr = LimitError;
+        type RemErr = NotFoundError;
+
+        fn add(&mut self, change: Change<Vec<u8>, Prefix, Id>) -> Result<u64, LimitError> {
+            if REFUSE_CHANGES.with(Cell::get) {
+                return Err(LimitError::MaxSamples { limit: 0 });
+            }
+            self.0.add(change)
+        }
+
+        fn remove(
+            &mut self,
+            sequence_number: u64,
+        ) -> Result<Change<Vec<u8>, Prefix, Id>, NotFoundError> {
+            self.0.remove(sequence_number)
+        }
+
+        fn get(&self, sequence_number: u64) -> Option<&Change<Vec<u8>, Prefix, Id>> {
+            self.0.get(sequence_number)
+        }
+
+        fn max_sequence_number(&self) -> Option<u64> {
+            self.0.max_sequence_number()
+        }
+
+        fn min_sequence_number(&self) -> Option<u64> {
+            self.0.min_sequence_number()
+        }
+    }
+
+    #[test]
+    fn keeps_the_previous_announcement_if_a_change_fails() {
+        let mut discovery = EndpointDiscovery::<FallibleCache, Prefix, Id>::new([1; 12]);
+        let mut matched = Vec::new();
+        let writer = Guid::new([1; 12], [0, 0, 1, 0x02]);
+        let announcements = |discovery: &EndpointDiscovery<FallibleCache, Prefix, Id>| {
+            discovery
+                .publications_writer()
+                .cache()
+                .0
+                .iter()
+                .map(|(_, change)| match change.kind() {
+                    Kind::Alive(data) => DiscoveredWriterData::<Prefix, Id>::from_bytes(data)
+                        .map(|data| data.topic_name)
+                        .unwrap_or_default(),
+                    _ => String::from("disposed"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        discovery
+            .writer_add(
+                DiscoveredWriterData::new(writer, "Square", "Shape"),
+                &mut matched,
+            )
+            .unwrap();
+
+        REFUSE_CHANGES.with(|refuse| refuse.set(true));
+        assert!(
+            discovery
+                .writer_add(
+                    DiscoveredWriterData::new(writer, "Circle", "Shape"),
+                    &mut matched,
+                )
+                .is_err()
+        );
+        assert!(discovery.writer_remove(writer, &mut matched).is_err());
+        assert_eq!(announcements(&discovery), vec!["Square"]);
+
+        // the writer is still known, so it can be removed later
+        REFUSE_CHANGES.with(|refuse| refuse.set(false));
+        discovery.writer_remove(writer, &mut matched).unwrap();
+        assert_eq!(announcements(&discovery), vec!["disposed", "disposed"]);
+    }
+}
@@ -0,0 +1,821 @@
+//! Centralised discovery through a discovery server
+//!
+//! In large deployments, or networks which don't support multicast, every
+//! participant announcing itself to every other participant doesn't scale.
+//! Instead, each client participant announces itself only to one or more
+//! [`DiscoveryServer`]s, by setting the
+//! [`announcement_locators`](super::spdp::Builder::announcement_locators) of
+//! its [`ParticipantDiscovery`] to the metatraffic locators of the servers.
+//!
+//! The server uses the same SPDP and SEDP data as any other participant. It
+//! announces itself to each client it hears from, and collects the endpoints
+//! of the clients with SEDP. An endpoint is only relayed to the clients in the
+//! same domain which have an endpoint that could match it. The server relays
+//! endpoints to each client through built-in SEDP writers which only that
+//! client is matched with, so they never reach any other client. Clients
+//! never discover each other directly, so the relayed endpoints carry the
+//! default [`Locator`]s of their participant.
+
+use std::{collections::BTreeMap, convert::TryFrom, time::Instant};
+
+use super::{
+    sedp::{matches, ReceiveError},
+    spdp::SPDP_PARTICIPANT_WRITER,
+    DiscoveredParticipantData, DiscoveredReaderData, DiscoveredWriterData, EndpointDiscovery,
+    MatchedEndpoints, ParticipantDiscovery, ParticipantEvent,
+};
+use crate::{
+    behaviour::{
+        reader::WriterProxy,
+        receiver::{Context, ReaderSubMessage, WriterSubMessage},
+        writer::{Output, ReaderLocator, ReaderProxy, SendError},
+    },
+    structure::{history::Cache, Guid, Locator},
+};
+
+/// Collects the endpoints of client participants, and redistributes those
+/// which are relevant to other clients
+///
+/// Like the other behaviours, the server doesn't own any sockets or timers.
+/// Submessages sent to its built-in readers and writers are passed to
+/// [`DiscoveryServer::receive`] and [`DiscoveryServer::receive_from_reader`],
+/// and [`DiscoveryServer::send`] should be called when the server starts, and
+/// then at each [`DiscoveryServer::next_deadline`].
+///
+/// # Example
+///
+/// ```
+/// use rtps_pim::{
+///     discovery::{server::DiscoveryServer, DiscoveredParticipantData},
+///     structure::history::HistoryCache,
+/// };
+/// use std::net::{Ipv4Addr, SocketAddrV4};
+///
+/// let locator = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 7410);
+///
+/// let server: DiscoveryServer<HistoryCache<Vec<u8>, _, _>, _, [u8; 4]> =
+///     DiscoveryServer::new(DiscoveredParticipantData {
+///         metatraffic_unicast_locators: vec![locator.into()],
+///         ..DiscoveredParticipantData::new([1; 12])
+///     });
+///
+/// assert_eq!(server.clients().count(), 0);
+/// ```
+#[derive(Debug)]
+pub struct DiscoveryServer<C, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    participants: ParticipantDiscovery<C, P, Id>,
+    endpoints: EndpointDiscovery<C, P, Id>,
+    announcement_locators: BTreeMap<P, Vec<Locator>>,
+    relays: BTreeMap<P, Relay<C, P, Id>>,
+}
+
+/// The endpoints relayed to a single client
+///
+/// The built-in SEDP writers of the relay are only matched with the built-in
+/// SEDP readers of the client. Its built-in readers are unused, since the
+/// endpoints of every client are collected by the server.
+#[derive(Debug)]
+struct Relay<C, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    endpoints: EndpointDiscovery<C, P, Id>,
+    writers: BTreeMap<Guid<P, Id>, DiscoveredWriterData<P, Id>>,
+    readers: BTreeMap<Guid<P, Id>, DiscoveredReaderData<P, Id>>,
+}
+
+impl<C, P, Id> DiscoveryServer<C, P, Id>
+where
+    C: Cache<Vec<u8>, Prefix = P, EntityId = Id, SqnN = u64> + Default,
+    P: Copy + Ord + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+    Id: Copy + Ord + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]> + From<[u8; 4]>,
+{
+    /// Construct a new [`DiscoveryServer`] which announces the given
+    /// participant to its clients
+    ///
+    /// The metatraffic [`Locator`]s of the participant are those which the
+    /// clients announce themselves to. Each built-in endpoint uses a new,
+    /// default history [`Cache`].
+    #[must_use]
+    pub fn new(local: DiscoveredParticipantData<P>) -> Self {
+        let endpoints = EndpointDiscovery::new(local.guid_prefix);
+        let participants = ParticipantDiscovery::builder(local, C::default()).build();
+        Self {
+            participants,
+            endpoints,
+            announcement_locators: BTreeMap::default(),
+            relays: BTreeMap::default(),
+        }
+    }
+
+    /// The [`DiscoveredParticipantData`] of the server
+    #[must_use]
+    pub fn local(&self) -> &DiscoveredParticipantData<P> {
+        self.participants.local()
+    }
+
+    /// The [`DiscoveredParticipantData`] of every known client
+    pub fn clients(&self) -> impl Iterator<Item = &DiscoveredParticipantData<P>> + '_ {
+        self.participants.participants()
+    }
+
+    /// The [`DiscoveredWriterData`] of every writer which is relayed to the
+    /// given client
+    pub fn relayed_writers(
+        &self,
+        client: P,
+    ) -> impl Iterator<Item = &DiscoveredWriterData<P, Id>> + '_ {
+        self.relays
+            .get(&client)
+            .into_iter()
+            .flat_map(|relay| relay.writers.values())
+    }
+
+    /// The [`DiscoveredReaderData`] of every reader which is relayed to the
+    /// given client
+    pub fn relayed_readers(
+        &self,
+        client: P,
+    ) -> impl Iterator<Item = &DiscoveredReaderData<P, Id>> + '_ {
+        self.relays
+            .get(&client)
+            .into_iter()
+            .flat_map(|relay| relay.readers.values())
+    }
+
+    /// The time at which [`DiscoveryServer::send`] next needs to be called
+    #[must_use]
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.participants
+            .next_deadline()
+            .into_iter()
+            .chain(self.endpoints.next_deadline())
+            .chain(
+                self.relays
+                    .values()
+                    .filter_map(|relay| relay.endpoints.next_deadline()),
+            )
+            .min()
+    }
+
+    /// Process a submessage sent to one of the built-in readers of the server
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if a change can't be added to a cache, or an
+    /// announcement can't be decoded.
+    pub fn receive(
+        &mut self,
+        now: Instant,
+        context: &Context<P>,
+        submessage: ReaderSubMessage<Id>,
+    ) -> Result<(), ReceiveError<C::AddErr>> {
+        let result = if submessage.writer() == Id::from(SPDP_PARTICIPANT_WRITER) {
+            self.participants
+                .receive(now, context, submessage)
+                .map_err(ReceiveError::from)
+        } else {
+            self.endpoints
+                .receive(now, context, submessage, &mut Unmatched)
+        };
+
+        self.update().map_err(ReceiveError::Cache)?;
+        result
+    }
+
+    /// Process a submessage sent to one of the built-in writers of the server
+    /// by a client
+    ///
+    /// The submessage is passed to the writers which relay endpoints to the
+    /// client which sent it.
+    pub fn receive_from_reader(
+        &mut self,
+        now: Instant,
+        writer: Id,
+        context: &Context<P>,
+        submessage: WriterSubMessage<Id>,
+    ) {
+        if let Some(relay) = self.relays.get_mut(&context.source_guid_prefix()) {
+            relay
+                .endpoints
+                .receive_from_reader(now, writer, context, submessage);
+        }
+    }
+
+    /// Announce the server to its clients, relay endpoints, and forget the
+    /// clients whose lease has expired
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if an announcement can't be added to a cache, or
+    /// if a submessage doesn't fit in a message.
+    pub fn send<O>(&mut self, now: Instant, output: &mut O) -> Result<(), SendError<C::AddErr>>
+    where
+        O: Output<P, Id>,
+    {
+        let announced = self.participants.send(now, output);
+        self.update().map_err(SendError::Cache)?;
+
+        let mut result = self.endpoints.send(now, output);
+        for relay in self.relays.values_mut() {
+            result = result.and(relay.endpoints.send(now, output));
+        }
+        result?;
+        announced
+    }
+
+    /// Apply the changes to the table of clients, and update the relayed
+    /// endpoints
+    fn update(&mut self) -> Result<(), C::AddErr> {
+        for event in self.participants.take_events() {
+            match event {
+                ParticipantEvent::Discovered(data) | ParticipantEvent::Updated(data) => {
+                    self.announce_to(data.guid_prefix, &data.metatraffic_unicast_locators);
+                    let local = self.participants.local().guid_prefix;
+                    self.relays
+                        .entry(data.guid_prefix)
+                        .or_insert_with(|| Relay::new(local))
+                        .endpoints
+                        .participant_add(data.clone());
+                    self.endpoints.participant_add(data);
+                }
+                ParticipantEvent::Lost { guid_prefix } => {
+                    self.announce_to(guid_prefix, &[]);
+                    self.relays.remove(&guid_prefix);
+                    self.endpoints
+                        .participant_remove(guid_prefix, &mut Unmatched);
+                }
+            }
+        }
+
+        // the endpoints relayed to the other clients are still updated if
+        // those of one client can't be
+        let mut result = Ok(());
+        let clients: Vec<_> = self.relays.keys().copied().collect();
+        for client in clients {
+            let writers = self.relevant_writers(client);
+            let readers = self.relevant_readers(client);
+            if let Some(relay) = self.relays.get_mut(&client) {
+                result = result.and(relay.update(writers, readers));
+            }
+        }
+        result
+    }
+
+    /// Replace the [`Locator`]s the server is announced to for a client
+    fn announce_to(&mut self, guid_prefix: P, locators: &[Locator]) {
+        let writer = self.participants.writer_mut();
+        for locator in self
+            .announcement_locators
+            .remove(&guid_prefix)
+            .into_iter()
+            .flatten()
+        {
+            writer.reader_locator_remove(locator);
+        }
+
+        for &locator in locators {
+            writer.reader_locator_add(ReaderLocator::new(locator, false));
+        }
+        if !locators.is_empty() {
+            self.announcement_locators
+                .insert(guid_prefix, locators.to_vec());
+        }
+    }
+
+    /// The default [`Locator`]s of a client, if its endpoint doesn't have any
+    /// of its own
+    fn locators(
+        &self,
+        guid_prefix: P,
+        unicast: &[Locator],
+        multicast: &[Locator],
+    ) -> (Vec<Locator>, Vec<Locator>) {
+        match self.participants.participant(guid_prefix) {
+            Some(participant) if unicast.is_empty() && multicast.is_empty() => (
+                participant.default_unicast_locators.clone(),
+                participant.default_multicast_locators.clone(),
+            ),
+            _ => (unicast.to_vec(), multicast.to_vec()),
+        }
+    }
+
+    /// Whether the endpoints of one client may be relayed to another
+    ///
+    /// Endpoints are only relayed between different clients in the same
+    /// domain.
+    fn may_relay(&self, from: P, to: P) -> bool {
+        from != to
+            && match (
+                self.participants.participant(from),
+                self.participants.participant(to),
+            ) {
+                (Some(from), Some(to)) => from.same_domain(to),
+                _ => false,
+            }
+    }
+
+    /// The writers of the other clients in the same domain which match a
+    /// reader of the given client
+    fn relevant_writers(&self, client: P) -> BTreeMap<Guid<P, Id>, DiscoveredWriterData<P, Id>> {
+        self.endpoints
+            .remote_writers()
+            .filter(|writer| {
+                self.may_relay(writer.guid.prefix(), client)
+                    && self
+                        .endpoints
+                        .remote_readers()
+                        .any(|reader| reader.guid.prefix() == client && matches(writer, reader))
+            })
+            .map(|writer| {
+                let (unicast_locators, multicast_locators) = self.locators(
+                    writer.guid.prefix(),
+                    &writer.unicast_locators,
+                    &writer.multicast_locators,
+                );
+                let data = DiscoveredWriterData {
+                    unicast_locators,
+                    multicast_locators,
+                    ..writer.clone()
+                };
+                (writer.guid, data)
+            })
+            .collect()
+    }
+
+    /// The readers of the other clients in the same domain which match a
+    /// writer of the given client
+    fn relevant_readers(&self, client: P) -> BTreeMap<Guid<P, Id>, DiscoveredReaderData<P, Id>> {
+        self.endpoints
+            .remote_readers()
+            .filter(|reader| {
+                self.may_relay(reader.guid.prefix(), client)
+                    && self
+                        .endpoints
+                        .remote_writers()
+                        .any(|writer| writer.guid.prefix() == client && matches(writer, reader))
+            })
+            .map(|reader| {
+                let (unicast_locators, multicast_locators) = self.locators(
+                    reader.guid.prefix(),
+                    &reader.unicast_locators,
+                    &reader.multicast_locators,
+                );
+                let data = DiscoveredReaderData {
+                    unicast_locators,
+                    multicast_locators,
+                    ..reader.clone()
+                };
+                (reader.guid, data)
+            })
+            .collect()
+    }
+}
+
+impl<C, P, Id> Relay<C, P, Id>
+where
+    C: Cache<Vec<u8>, Prefix = P, EntityId = Id, SqnN = u64> + Default,
+    P: Copy + Ord + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+    Id: Copy + Ord + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]> + From<[u8; 4]>,
+{
+    fn new(guid_prefix: P) -> Self {
+        Self {
+            endpoints: EndpointDiscovery::new(guid_prefix),
+            writers: BTreeMap::default(),
+            readers: BTreeMap::default(),
+        }
+    }
+
+    /// Relay the given endpoints to the client, and dispose of those which
+    /// are no longer relevant to it
+    fn update(
+        &mut self,
+        writers: BTreeMap<Guid<P, Id>, DiscoveredWriterData<P, Id>>,
+        readers: BTreeMap<Guid<P, Id>, DiscoveredReaderData<P, Id>>,
+    ) -> Result<(), C::AddErr> {
+        let removed: Vec<_> = self
+            .writers
+            .keys()
+            .filter(|guid| !writers.contains_key(guid))
+            .copied()
+            .collect();
+        for guid in removed {
+            self.endpoints.writer_remove(guid, &mut Unmatched)?;
+            self.writers.remove(&guid);
+        }
+
+        for (guid, data) in writers {
+            if self.writers.get(&guid) != Some(&data) {
+                self.endpoints.writer_add(data.clone(), &mut Unmatched)?;
+                self.writers.insert(guid, data);
+            }
+        }
+
+        let removed: Vec<_> = self
+            .readers
+            .keys()
+            .filter(|guid| !readers.contains_key(guid))
+            .copied()
+            .collect();
+        for guid in removed {
+            self.endpoints.reader_remove(guid, &mut Unmatched)?;
+            self.readers.remove(&guid);
+        }
+
+        for (guid, data) in readers {
+            if self.readers.get(&guid) != Some(&data) {
+                self.endpoints.reader_add(data.clone(), &mut Unmatched)?;
+                self.readers.insert(guid, data);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The server has no endpoints of its own, so matches are ignored
+struct Unmatched;
+
+impl<P, Id> MatchedEndpoints<P, Id> for Unmatched
+where
+    P: Copy,
+    Id: Copy,
+{
+    fn matched_reader_add(&mut self, _writer: Guid<P, Id>, _reader_proxy: ReaderProxy<P, Id>) {}
+
+    fn matched_reader_remove(&mut self, _writer: Guid<P, Id>, _reader: Guid<P, Id>) {}
+
+    fn matched_writer_add(&mut self, _reader: Guid<P, Id>, _writer_proxy: WriterProxy<P, Id>) {}
+
+    fn matched_writer_remove(&mut self, _reader: Guid<P, Id>, _writer: Guid<P, Id>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DiscoveryServer;
+    use crate::{
+        behaviour::{
+            reader::WriterProxy,
+            receiver::{Context, Dispatch, ReaderSubMessage, WriterSubMessage},
+            writer::ReaderProxy,
+            MessageReceiver,
+        },
+        discovery::{
+            spdp::SPDP_PARTICIPANT_WRITER, DiscoveredParticipantData, DiscoveredReaderData,
+            DiscoveredWriterData, EndpointDiscovery, MatchedEndpoints, ParticipantDiscovery,
+            ParticipantEvent,
+        },
+        messages::Message,
+        structure::{history::HistoryCache, Guid, Locator},
+    };
+    use std::{
+        net::{Ipv4Addr, SocketAddrV4},
+        time::Instant,
+    };
+
+    type Prefix = [u8; 12];
+    type Id = [u8; 4];
+    type TestCache = HistoryCache<Vec<u8>, Prefix, Id>;
+    type Sent = Vec<(Locator, Message<Prefix, Id>)>;
+
+    /// A local endpoint, a matched remote endpoint, and its unicast locators
+    type Match = (Guid<Prefix, Id>, Guid<Prefix, Id>, Vec<Locator>);
+
+    /// The matched endpoints of a client
+    #[derive(Debug, Default, PartialEq)]
+    struct Matched(Vec<Match>);
+
+    impl MatchedEndpoints<Prefix, Id> for Matched {
+        fn matched_reader_add(
+            &mut self,
+            writer: Guid<Prefix, Id>,
+            reader_proxy: ReaderProxy<Prefix, Id>,
+        ) {
+            self.0.push((
+                writer,
+                reader_proxy.remote_reader_guid(),
+                reader_proxy.unicast_locators().to_vec(),
+            ));
+        }
+
+        fn matched_reader_remove(&mut self, writer: Guid<Prefix, Id>, reader: Guid<Prefix, Id>) {
+            self.0
+                .retain(|&(local, remote, _)| (local, remote) != (writer, reader));
+        }
+
+        fn matched_writer_add(
+            &mut self,
+            reader: Guid<Prefix, Id>,
+            writer_proxy: WriterProxy<Prefix, Id>,
+        ) {
+            self.0.push((
+                reader,
+                writer_proxy.remote_writer_guid(),
+                writer_proxy.unicast_locators().to_vec(),
+            ));
+        }
+
+        fn matched_writer_remove(&mut self, reader: Guid<Prefix, Id>, writer: Guid<Prefix, Id>) {
+            self.0
+                .retain(|&(local, remote, _)| (local, remote) != (reader, writer));
+        }
+    }
+
+    fn metatraffic(guid_prefix: Prefix) -> Locator {
+        SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, guid_prefix[0]), 7410).into()
+    }
+
+    fn default_unicast(guid_prefix: Prefix) -> Locator {
+        SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, guid_prefix[0]), 7411).into()
+    }
+
+    fn participant(guid_prefix: Prefix) -> DiscoveredParticipantData<Prefix> {
+        DiscoveredParticipantData {
+            default_unicast_locators: vec![default_unicast(guid_prefix)],
+            metatraffic_unicast_locators: vec![metatraffic(guid_prefix)],
+            ..DiscoveredParticipantData::new(guid_prefix)
+        }
+    }
+
+    /// A participant which announces itself only to the server
+    struct Client {
+        participants: ParticipantDiscovery<TestCache, Prefix, Id>,
+        endpoints: EndpointDiscovery<TestCache, Prefix, Id>,
+        matched: Matched,
+    }
+
+    impl Client {
+        fn new(guid_prefix: Prefix, server: Locator) -> Self {
+            Self::with_data(participant(guid_prefix), server)
+        }
+
+        fn with_data(data: DiscoveredParticipantData<Prefix>, server: Locator) -> Self {
+            let guid_prefix = data.guid_prefix;
+            Self {
+                participants: ParticipantDiscovery::builder(data, TestCache::default())
+                    .announcement_locators(vec![server])
+                    .build(),
+                endpoints: EndpointDiscovery::new(guid_prefix),
+                matched: Matched::default(),
+            }
+        }
+    }
+
+    impl Dispatch<Prefix, Id> for Client {
+        fn dispatch_to_reader(
+            &mut self,
+            _reader: Option<Id>,
+            context: &Context<Prefix>,
+            submessage: ReaderSubMessage<Id>,
+        ) {
+            let now = Instant::now();
+            if submessage.writer() == SPDP_PARTICIPANT_WRITER {
+                self.participants.receive(now, context, submessage).unwrap();
+                for event in self.participants.take_events() {
+                    if let ParticipantEvent::Discovered(data) = event {
+                        self.endpoints.participant_add(data);
+                    }
+                }
+            } else {
+                self.endpoints
+                    .receive(now, context, submessage, &mut self.matched)
+                    .unwrap();
+            }
+        }
+
+        fn dispatch_to_writer(
+            &mut self,
+            writer: Id,
+            context: &Context<Prefix>,
+            submessage: WriterSubMessage<Id>,
+        ) {
+            self.endpoints
+                .receive_from_reader(Instant::now(), writer, context, submessage);
+        }
+    }
+
+    impl Dispatch<Prefix, Id> for DiscoveryServer<TestCache, Prefix, Id> {
+        fn dispatch_to_reader(
+            &mut self,
+            _reader: Option<Id>,
+            context: &Context<Prefix>,
+            submessage: ReaderSubMessage<Id>,
+        ) {
+            self.receive(Instant::now(), context, submessage).unwrap();
+        }
+
+        fn dispatch_to_writer(
+            &mut self,
+            writer: Id,
+            context: &Context<Prefix>,
+            submessage: WriterSubMessage<Id>,
+        ) {
+            self.receive_from_reader(Instant::now(), writer, context, submessage);
+        }
+    }
+
+    /// Exchange messages between the server and its clients until they are in
+    /// sync
+    fn exchange(server: &mut DiscoveryServer<TestCache, Prefix, Id>, clients: &mut [Client]) {
+        for _ in 0..6 {
+            let now = Instant::now();
+            let mut sent = Sent::new();
+            server.send(now, &mut sent).unwrap();
+            for client in clients.iter_mut() {
+                client.participants.send(now, &mut sent).unwrap();
+                client.endpoints.send(now, &mut sent).unwrap();
+            }
+
+            for (locator, message) in sent {
+                if locator == metatraffic(server.local().guid_prefix) {
+                    MessageReceiver::new(server.local().guid_prefix)
+                        .receive(None, message, server)
+                        .unwrap();
+                } else if let Some(client) = clients
+                    .iter_mut()
+                    .find(|client| locator == metatraffic(client.participants.local().guid_prefix))
+                {
+                    MessageReceiver::new(client.participants.local().guid_prefix)
+                        .receive(None, message, client)
+                        .unwrap();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn relays_matching_endpoints() {
+        let mut server = DiscoveryServer::new(participant([1; 12]));
+        let mut clients = [
+            Client::new([2; 12], metatraffic([1; 12])),
+            Client::new([3; 12], metatraffic([1; 12])),
+        ];
+
+        let writer = Guid::new([2; 12], [0, 0, 1, 0x02]);
+        let unmatched = Guid::new([2; 12], [0, 0, 2, 0x02]);
+        let reader = Guid::new([3; 12], [0, 0, 1, 0x07]);
+        let client = &mut clients[0];
+        for data in [
+            DiscoveredWriterData::new(writer, "Square", "Shape"),
+            DiscoveredWriterData::new(unmatched, "Circle", "Shape"),
+        ] {
+            client
+                .endpoints
+                .writer_add(data, &mut client.matched)
+                .unwrap();
+        }
+        let client = &mut clients[1];
+        client
+            .endpoints
+            .reader_add(
+                DiscoveredReaderData::new(reader, "Square", "Shape"),
+                &mut client.matched,
+            )
+            .unwrap();
+        exchange(&mut server, &mut clients);
+
+        // the clients only discover the server, and reach each other through
+        // the default locators of the relayed endpoints
+        assert_eq!(server.clients().count(), 2);
+        assert_eq!(clients[0].participants.participants().count(), 1);
+        assert_eq!(
+            clients[0].matched.0,
+            vec![(writer, reader, vec![default_unicast([3; 12])])]
+        );
+        assert_eq!(
+            clients[1].matched.0,
+            vec![(reader, writer, vec![default_unicast([2; 12])])]
+        );
+
+        // endpoints which nothing could match aren't relayed
+        assert_eq!(server.relayed_writers([3; 12]).count(), 1);
+        assert_eq!(server.relayed_readers([2; 12]).count(), 1);
+        assert_eq!(clients[1].endpoints.remote_writers().count(), 1);
+
+        let client = &mut clients[1];
+        client
+            .endpoints
+            .reader_remove(reader, &mut client.matched)
+            .unwrap();
+        exchange(&mut server, &mut clients);
+        assert!(clients[0].matched.0.is_empty());
+        assert_eq!(server.relayed_writers([3; 12]).count(), 0);
+        assert_eq!(clients[1].endpoints.remote_writers().count(), 0);
+    }
+
+    #[test]
+    fn only_relays_within_a_domain() {
+        let mut server = DiscoveryServer::new(participant([1; 12]));
+        let in_domain = |guid_prefix, domain_id| {
+            let data = DiscoveredParticipantData {
+                domain_id: Some(domain_id),
+                ..participant(guid_prefix)
+            };
+            Client::with_data(data, metatraffic([1; 12]))
+        };
+        let mut clients = [in_domain([2; 12], 0), in_domain([3; 12], 1)];
+
+        let writer = Guid::new([2; 12], [0, 0, 1, 0x02]);
+        let reader = Guid::new([3; 12], [0, 0, 1, 0x07]);
+        let client = &mut clients[0];
+        client
+            .endpoints
+            .writer_add(
+                DiscoveredWriterData::new(writer, "Square", "Shape"),
+                &mut client.matched,
+            )
+            .unwrap();
+        let client = &mut clients[1];
+        client
+            .endpoints
+            .reader_add(
+                DiscoveredReaderData::new(reader, "Square", "Shape"),
+                &mut client.matched,
+            )
+            .unwrap();
+        exchange(&mut server, &mut clients);
+
+        // the server serves both domains, but doesn't relay between them
+        assert_eq!(server.clients().count(), 2);
+        assert_eq!(server.relayed_writers([3; 12]).count(), 0);
+        assert_eq!(server.relayed_readers([2; 12]).count(), 0);
+        assert!(clients[0].matched.0.is_empty());
+        assert!(clients[1].matched.0.is_empty());
+    }
+
+    #[test]
+    fn relays_endpoints_only_to_relevant_clients() {
+        let mut server = DiscoveryServer::new(participant([1; 12]));
+        let in_domain = |guid_prefix, domain_id| {
+            let data = DiscoveredParticipantData {
+                domain_id: Some(domain_id),
+                ..participant(guid_prefix)
+            };
+            Client::with_data(data, metatraffic([1; 12]))
+        };
+        let mut clients = [
+            in_domain([2; 12], 0),
+            in_domain([3; 12], 0),
+            in_domain([4; 12], 1),
+        ];
+
+        let writer = Guid::new([2; 12], [0, 0, 1, 0x02]);
+        let reader = Guid::new([3; 12], [0, 0, 1, 0x07]);
+        let other_domain = Guid::new([4; 12], [0, 0, 1, 0x07]);
+        let client = &mut clients[0];
+        client
+            .endpoints
+            .writer_add(
+                DiscoveredWriterData::new(writer, "Square", "Shape"),
+                &mut client.matched,
+            )
+            .unwrap();
+        for (client, reader) in clients[1..].iter_mut().zip([reader, other_domain]) {
+            client
+                .endpoints
+                .reader_add(
+                    DiscoveredReaderData::new(reader, "Square", "Shape"),
+                    &mut client.matched,
+                )
+                .unwrap();
+        }
+        exchange(&mut server, &mut clients);
+
+        assert_eq!(
+            clients[0].matched.0,
+            vec![(writer, reader, vec![default_unicast([3; 12])])]
+        );
+        assert_eq!(
+            clients[1].matched.0,
+            vec![(reader, writer, vec![default_unicast([2; 12])])]
+        );
+
+        // the writer relayed to the reader in domain 0 doesn't reach the
+        // client in domain 1, and no client receives its own endpoints
+        assert!(clients[2].matched.0.is_empty());
+        assert_eq!(clients[2].endpoints.remote_writers().count(), 0);
+        assert_eq!(clients[0].endpoints.remote_writers().count(), 0);
+        assert_eq!(clients[0].endpoints.remote_readers().count(), 1);
+        assert_eq!(clients[1].endpoints.remote_readers().count(), 0);
+        assert_eq!(server.relayed_writers([4; 12]).count(), 0);
+    }
+
+    #[test]
+    fn forgets_lost_clients() {
+        let mut server = DiscoveryServer::new(participant([1; 12]));
+        let mut clients = [Client::new([2; 12], metatraffic([1; 12]))];
+        exchange(&mut server, &mut clients);
+        assert_eq!(server.clients().count(), 1);
+        assert_eq!(server.participants.writer().reader_locators().len(), 1);
+
+        server.participants.participant_remove([2; 12]);
+        server.update().unwrap();
+        assert_eq!(server.clients().count(), 0);
+        assert!(server.participants.writer().reader_locators().is_empty());
+        assert!(server.relays.is_empty());
+    }
+}
@@ -0,0 +1,849 @@
+//! The Simple Participant Discovery Protocol (SPDP)
+//!
+//! Each participant periodically announces a [`DiscoveredParticipantData`]
+//! to a set of well-known multicast locators, using a best-effort
+//! [`StatelessWriter`]. The announcements of remote participants are kept in a
+//! table, and each remote participant is forgotten if it isn't heard from
+//! within its lease duration.
+//!
+//! See [section 8.5.3](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF) of the relevant specification document.
+
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    time::{Duration, Instant},
+};
+
+use super::{
+    encoding::{self, parameter, Parameters},
+    DecodeError,
+};
+use crate::{
+    behaviour::{
+        reader::change_from_data,
+        receiver::{Context, ReaderSubMessage},
+        writer::{Output, ReaderLocator, SendError, StatelessWriter},
+    },
+    messages::submessage::elements::{parameter_id, ParameterList},
+    structure::{
+        history::{Cache, Kind},
+        Guid, KeyHash, Locator, Participant, ParticipantProxy, ProtocolVersion, VendorId,
+    },
+};
+
+pub use crate::structure::DEFAULT_LEASE_DURATION;
+
+/// The entity ID of a participant (`ENTITYID_PARTICIPANT`)
+pub const PARTICIPANT: [u8; 4] = [0x00, 0x00, 0x01, 0xc1];
+
+/// The entity ID of the built-in SPDP writer
+/// (`ENTITYID_SPDP_BUILTIN_PARTICIPANT_WRITER`)
+pub const SPDP_PARTICIPANT_WRITER: [u8; 4] = [0x00, 0x01, 0x00, 0xc2];
+
+/// The entity ID of the built-in SPDP reader
+/// (`ENTITYID_SPDP_BUILTIN_PARTICIPANT_READER`)
+pub const SPDP_PARTICIPANT_READER: [u8; 4] = [0x00, 0x01, 0x00, 0xc7];
+
+/// The default period between announcements
+pub const DEFAULT_ANNOUNCEMENT_PERIOD: Duration = Duration::from_secs(30);
+
+/// The data announced by each participant (`SPDPdiscoveredParticipantData`)
+///
+/// This is the [`ParticipantProxy`] of the participant, which includes its
+/// lease duration.
+pub type DiscoveredParticipantData<P> = ParticipantProxy<P>;
+
+impl<P> ParticipantProxy<P> {
+    /// The [`KeyHash`] of the participant, derived from its [`Guid`]
+    #[must_use]
+    pub fn key_hash(&self) -> KeyHash
+    where
+        P: AsRef<[u8]>,
+    {
+        key_hash(&self.guid_prefix)
+    }
+
+    /// Convert the data to a [`ParameterList`]
+    #[must_use]
+    pub fn to_parameter_list(&self) -> ParameterList
+    where
+        P: Copy + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+    {
+        let locators = |id, locators: &[Locator]| {
+            locators
+                .iter()
+                .map(move |locator| parameter(id, locator))
+                .collect::<Vec<_>>()
+        };
+
+        let guid = Guid::new(self.guid_prefix, PARTICIPANT);
+        vec![
+            parameter(parameter_id::PARTICIPANT_GUID, &guid),
+            parameter(parameter_id::PROTOCOL_VERSION, &self.protocol_version),
+            parameter(parameter_id::VENDOR_ID, &self.vendor_id),
+            parameter(parameter_id::EXPECTS_INLINE_QOS, &self.expects_inline_qos),
+            parameter(parameter_id::BUILTIN_ENDPOINT_SET, &self.builtin_endpoints),
+            parameter(
+                parameter_id::PARTICIPANT_MANUAL_LIVELINESS_COUNT,
+                &self.manual_liveliness_count,
+            ),
+            parameter(
+                parameter_id::PARTICIPANT_LEASE_DURATION,
+                &self.lease_duration,
+            ),
+        ]
+        .into_iter()
+        .chain(
+            self.domain_id
+                .map(|domain_id| parameter(parameter_id::DOMAIN_ID, &domain_id)),
+        )
+        // the empty tag is the default, and isn't announced
+        .chain(
+            Some(&self.domain_tag)
+                .filter(|domain_tag| !domain_tag.is_empty())
+                .map(|domain_tag| parameter(parameter_id::DOMAIN_TAG, domain_tag)),
+        )
+        .chain(locators(
+            parameter_id::DEFAULT_UNICAST_LOCATOR,
+            &self.default_unicast_locators,
+        ))
+        .chain(locators(
+            parameter_id::DEFAULT_MULTICAST_LOCATOR,
+            &self.default_multicast_locators,
+        ))
+        .chain(locators(
+            parameter_id::METATRAFFIC_UNICAST_LOCATOR,
+            &self.metatraffic_unicast_locators,
+        ))
+        .chain(locators(
+            parameter_id::METATRAFFIC_MULTICAST_LOCATOR,
+            &self.metatraffic_multicast_locators,
+        ))
+        .collect()
+    }
+
+    /// Serialize the data as a `PL_CDR` payload
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8>
+    where
+        P: Copy + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+    {
+        encoding::to_bytes(&self.to_parameter_list())
+    }
+
+    /// Deserialize the data from a `PL_CDR` payload
+    ///
+    /// Parameters which aren't recognised are ignored.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the payload is malformed, or the participant
+    /// [`Guid`] is missing.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError>
+    where
+        P: Copy + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+    {
+        Self::from_parameters(&Parameters::from_bytes(bytes)?)
+    }
+
+    pub(crate) fn from_parameters(parameters: &Parameters) -> Result<Self, DecodeError>
+    where
+        P: Copy + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+    {
+        let guid: Guid<P, [u8; 4]> = parameters.required(parameter_id::PARTICIPANT_GUID)?;
+        let mut data = Self::new(guid.prefix());
+
+        // a participant which doesn't advertise its built-in endpoints can't
+        // be assumed to have any
+        data.builtin_endpoints = parameters
+            .optional(parameter_id::BUILTIN_ENDPOINT_SET)?
+            .unwrap_or_default();
+        if let Some(count) =
+            parameters.optional(parameter_id::PARTICIPANT_MANUAL_LIVELINESS_COUNT)?
+        {
+            data.manual_liveliness_count = count;
+        }
+
+        if let Some(protocol_version) = parameters.optional(parameter_id::PROTOCOL_VERSION)? {
+            data.protocol_version = protocol_version;
+        }
+        if let Some(vendor_id) = parameters.optional(parameter_id::VENDOR_ID)? {
+            data.vendor_id = vendor_id;
+        }
+        data.domain_id = parameters.optional(parameter_id::DOMAIN_ID)?;
+        if let Some(domain_tag) = parameters.optional(parameter_id::DOMAIN_TAG)? {
+            data.domain_tag = domain_tag;
+        }
+        if let Some(expects_inline_qos) = parameters.optional(parameter_id::EXPECTS_INLINE_QOS)? {
+            data.expects_inline_qos = expects_inline_qos;
+        }
+        if let Some(lease_duration) =
+            parameters.optional(parameter_id::PARTICIPANT_LEASE_DURATION)?
+        {
+            data.lease_duration = lease_duration;
+        }
+
+        data.default_unicast_locators = parameters.all(parameter_id::DEFAULT_UNICAST_LOCATOR)?;
+        data.default_multicast_locators =
+            parameters.all(parameter_id::DEFAULT_MULTICAST_LOCATOR)?;
+        data.metatraffic_unicast_locators =
+            parameters.all(parameter_id::METATRAFFIC_UNICAST_LOCATOR)?;
+        data.metatraffic_multicast_locators =
+            parameters.all(parameter_id::METATRAFFIC_MULTICAST_LOCATOR)?;
+
+        Ok(data)
+    }
+}
+
+/// The [`KeyHash`] of the participant with the given [`Guid`] prefix
+fn key_hash<P: AsRef<[u8]>>(guid_prefix: &P) -> KeyHash {
+    let mut bytes = [0; 16];
+    let prefix = guid_prefix.as_ref();
+    let len = prefix.len().min(12);
+    bytes[..len].copy_from_slice(&prefix[..len]);
+    bytes[12..].copy_from_slice(&PARTICIPANT);
+    KeyHash::new(bytes)
+}
+
+/// A change to the table of discovered participants
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParticipantEvent<P> {
+    /// A new participant was discovered
+    Discovered(DiscoveredParticipantData<P>),
+
+    /// A known participant announced different data
+    Updated(DiscoveredParticipantData<P>),
+
+    /// A participant left, or its lease expired
+    Lost {
+        /// The [`Guid`] prefix of the participant
+        guid_prefix: P,
+    },
+}
+
+/// A remote participant, and the time at which its lease expires
+#[derive(Debug, Clone)]
+struct Remote<P> {
+    data: DiscoveredParticipantData<P>,
+    lease_expiry: Option<Instant>,
+}
+
+/// A builder for a [`ParticipantDiscovery`]
+///
+/// See the [`ParticipantDiscovery`] docs for details
+#[derive(Debug)]
+#[must_use]
+pub struct Builder<C, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    guid: Guid<P, Id>,
+    local: DiscoveredParticipantData<P>,
+    cache: C,
+    announcement_period: Option<Duration>,
+    announcement_locators: Vec<Locator>,
+}
+
+impl<C, P, Id> Builder<C, P, Id>
+where
+    P: Copy + PartialEq,
+    Id: Copy + From<[u8; 4]>,
+{
+    fn new(local: DiscoveredParticipantData<P>, cache: C) -> Self {
+        Self {
+            guid: Guid::new(local.guid_prefix, Id::from(SPDP_PARTICIPANT_WRITER)),
+            local,
+            cache,
+            announcement_period: None,
+            announcement_locators: Vec::new(),
+        }
+    }
+
+    /// Set the period between announcements
+    ///
+    /// Defaults to [`DEFAULT_ANNOUNCEMENT_PERIOD`]. This should be well within
+    /// the lease duration of the participant.
+    pub fn announcement_period(mut self, period: Duration) -> Self {
+        self.announcement_period = Some(period);
+        self
+    }
+
+    /// Set the [`Locator`]s to which the participant is announced
+    ///
+    /// These are usually the well-known SPDP multicast locators of the
+    /// domain, but may include the unicast locators of known peers. When using
+    /// static discovery, these are only the
+    /// [`peer_locators`](crate::structure::Participant::peer_locators) of the
+    /// participant.
+    pub fn announcement_locators<I, L>(mut self, locators: I) -> Self
+    where
+        I: IntoIterator<Item = L>,
+        L: Into<Locator>,
+    {
+        self.announcement_locators = locators.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Consume the [`Builder`] and return a configured [`ParticipantDiscovery`]
+    #[must_use]
+    pub fn build(self) -> ParticipantDiscovery<C, P, Id> {
+        let mut builder = StatelessWriter::builder(self.guid, self.cache).resend_period(
+            self.announcement_period
+                .unwrap_or(DEFAULT_ANNOUNCEMENT_PERIOD),
+        );
+        if let ProtocolVersion::Specified { major, minor } = self.local.protocol_version {
+            builder = builder.protocol_version(major, minor);
+        }
+        if let VendorId::Known(id) = self.local.vendor_id {
+            builder = builder.vendor_id(id);
+        }
+
+        let mut writer = builder.build();
+        for locator in self.announcement_locators {
+            writer.reader_locator_add(ReaderLocator::new(locator, false));
+        }
+
+        ParticipantDiscovery {
+            local: self.local,
+            writer,
+            announcement: None,
+            announce_pending: true,
+            participants: BTreeMap::default(),
+            events: Vec::default(),
+        }
+    }
+}
+
+/// Announces the local participant, and keeps a table of the remote
+/// participants discovered with SPDP
+///
+/// The local [`DiscoveredParticipantData`] is written to a history [`Cache`]
+/// and periodically sent to the announcement [`Locator`]s by the built-in
+/// [`StatelessWriter`]. Announcements received from remote participants are
+/// passed to [`ParticipantDiscovery::receive`], and each change to the table
+/// of remote participants is reported as a [`ParticipantEvent`].
+///
+/// Like the other behaviours, discovery doesn't own any sockets or timers.
+/// [`ParticipantDiscovery::send`] should be called when the participant
+/// starts, and then at each [`ParticipantDiscovery::next_deadline`].
+///
+/// # Example
+///
+/// ```
+/// use rtps_pim::{
+///     discovery::spdp::{DiscoveredParticipantData, ParticipantDiscovery},
+///     messages::Message,
+///     structure::{history::HistoryCache, Locator},
+/// };
+/// use std::{
+///     net::{Ipv4Addr, SocketAddrV4},
+///     time::Instant,
+/// };
+///
+/// let multicast = SocketAddrV4::new(Ipv4Addr::new(239, 255, 0, 1), 7400);
+///
+/// let mut discovery = ParticipantDiscovery::<_, _, [u8; 4]>::builder(
+///     DiscoveredParticipantData::new([1; 12]),
+///     HistoryCache::default(),
+/// )
+/// .announcement_locators(vec![multicast])
+/// .build();
+///
+/// let mut output: Vec<(Locator, Message<_, _>)> = Vec::new();
+/// discovery.send(Instant::now(), &mut output).unwrap();
+/// assert_eq!(output.len(), 1);
+/// ```
+#[derive(Debug)]
+pub struct ParticipantDiscovery<C, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    local: DiscoveredParticipantData<P>,
+    writer: StatelessWriter<C, P, Id>,
+    announcement: Option<u64>,
+    announce_pending: bool,
+    participants: BTreeMap<P, Remote<P>>,
+    events: Vec<ParticipantEvent<P>>,
+}
+
+impl<C, P, Id> ParticipantDiscovery<C, P, Id>
+where
+    P: Copy + Ord + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+    Id: Copy + PartialEq + From<[u8; 4]>,
+{
+    /// Construct a new [`ParticipantDiscovery`] which announces the given
+    /// local participant
+    pub fn builder(local: DiscoveredParticipantData<P>, cache: C) -> Builder<C, P, Id> {
+        Builder::new(local, cache)
+    }
+
+    /// Construct a new [`ParticipantDiscovery`] which announces the given
+    /// [`Participant`]
+    ///
+    /// The participant is announced with its [`Participant::proxy`], to which
+    /// the metatraffic [`Locator`]s of the transport should be added. If the
+    /// participant uses static discovery, it is announced to its
+    /// [`Participant::peer_locators`]. Otherwise, the announcement locators
+    /// must be set on the returned [`Builder`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtps_pim::{
+    ///     discovery::ParticipantDiscovery,
+    ///     messages::Message,
+    ///     structure::{history::HistoryCache, Locator, Participant},
+    /// };
+    /// use std::{
+    ///     net::{Ipv4Addr, SocketAddrV4},
+    ///     time::Instant,
+    /// };
+    ///
+    /// let peer = SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 2), 7410);
+    /// let participant = Participant::builder([1; 12], [0, 0, 1, 0xc1])
+    ///     .peer_locators(vec![peer])
+    ///     .build();
+    ///
+    /// let mut discovery =
+    ///     ParticipantDiscovery::for_participant(&participant, HistoryCache::default()).build();
+    ///
+    /// let mut output: Vec<(Locator, Message<_, _>)> = Vec::new();
+    /// discovery.send(Instant::now(), &mut output).unwrap();
+    /// assert_eq!(output[0].0, peer.into());
+    /// ```
+    pub fn for_participant(participant: &Participant<P, Id>, cache: C) -> Builder<C, P, Id> {
+        Builder::new(participant.proxy(), cache)
+            .announcement_locators(participant.peer_locators().iter().copied())
+    }
+
+    /// The [`DiscoveredParticipantData`] of the local participant
+    #[must_use]
+    pub fn local(&self) -> &DiscoveredParticipantData<P> {
+        &self.local
+    }
+
+    /// Replace the [`DiscoveredParticipantData`] of the local participant
+    ///
+    /// The new data is announced by the next call to
+    /// [`ParticipantDiscovery::send`].
+    pub fn set_local(&mut self, local: DiscoveredParticipantData<P>) {
+        self.local = local;
+        self.announce_pending = true;
+    }
+
+    /// The built-in SPDP [`StatelessWriter`]
+    #[must_use]
+    pub fn writer(&self) -> &StatelessWriter<C, P, Id> {
+        &self.writer
+    }
+
+    /// Mutable access to the built-in SPDP [`StatelessWriter`]
+    ///
+    /// This can be used to change the [`Locator`]s the participant is
+    /// announced to.
+    pub fn writer_mut(&mut self) -> &mut StatelessWriter<C, P, Id> {
+        &mut self.writer
+    }
+
+    /// The [`DiscoveredParticipantData`] of every known remote participant
+    pub fn participants(&self) -> impl Iterator<Item = &DiscoveredParticipantData<P>> + '_ {
+        self.participants.values().map(|remote| &remote.data)
+    }
+
+    /// The [`DiscoveredParticipantData`] of the remote participant with the
+    /// given [`Guid`] prefix, if it is known
+    #[must_use]
+    pub fn participant(&self, guid_prefix: P) -> Option<&DiscoveredParticipantData<P>> {
+        self.participants
+            .get(&guid_prefix)
+            .map(|remote| &remote.data)
+    }
+
+    /// The time at which [`ParticipantDiscovery::send`] next needs to be
+    /// called, either to announce the local participant, or to expire the
+    /// lease of a remote participant
+    #[must_use]
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.participants
+            .values()
+            .filter_map(|remote| remote.lease_expiry)
+            .chain(self.writer.next_deadline())
+            .min()
+    }
+
+    /// Take the [`ParticipantEvent`]s which have occurred since the last call
+    pub fn take_events(&mut self) -> Vec<ParticipantEvent<P>> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Process a submessage sent to the built-in SPDP reader
+    ///
+    /// Each announcement renews the lease of the remote participant. A
+    /// participant which unregisters or disposes of itself is removed
+    /// immediately. Submessages from other writers, and the announcements of
+    /// the local participant and of participants from other domains, are
+    /// ignored.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if an announcement can't be decoded.
+    pub fn receive(
+        &mut self,
+        now: Instant,
+        context: &Context<P>,
+        submessage: ReaderSubMessage<Id>,
+    ) -> Result<(), DecodeError> {
+        let data = match submessage {
+            ReaderSubMessage::Data(data) if data.writer == Id::from(SPDP_PARTICIPANT_WRITER) => {
+                data
+            }
+            _ => return Ok(()),
+        };
+
+        let source = context.source_guid_prefix();
+        if source == self.local.guid_prefix {
+            return Ok(());
+        }
+
+        let writer = Guid::new(source, data.writer);
+        let change = match change_from_data::<Vec<u8>, _, _>(writer, data) {
+            Some(change) => change,
+            None => return Ok(()),
+        };
+
+        match change.into_kind() {
+            Kind::Alive(bytes) => {
+                let data = DiscoveredParticipantData::from_bytes(&bytes)?;
+                self.participant_update(now, data);
+            }
+            Kind::NotAliveDisposed | Kind::NotAliveUnregistered => self.participant_remove(source),
+            Kind::AliveFiltered => {}
+        }
+
+        Ok(())
+    }
+
+    /// Forget a remote participant
+    pub fn participant_remove(&mut self, guid_prefix: P) {
+        if self.participants.remove(&guid_prefix).is_some() {
+            self.events.push(ParticipantEvent::Lost { guid_prefix });
+        }
+    }
+
+    fn participant_update(&mut self, now: Instant, data: DiscoveredParticipantData<P>) {
+        let guid_prefix = data.guid_prefix;
+        if guid_prefix == self.local.guid_prefix {
+            return;
+        }
+
+        // participants from other domains are never discovered, and one which
+        // moves to another domain is lost
+        if !self.local.same_domain(&data) {
+            self.participant_remove(guid_prefix);
+            return;
+        }
+
+        let lease_expiry = data
+            .lease_duration
+            .map(|lease_duration| now + lease_duration);
+        let event = match self.participants.get(&guid_prefix) {
+            None => Some(ParticipantEvent::Discovered(data.clone())),
+            Some(remote) if remote.data != data => Some(ParticipantEvent::Updated(data.clone())),
+            Some(_) => None,
+        };
+
+        self.participants
+            .insert(guid_prefix, Remote { data, lease_expiry });
+        self.events.extend(event);
+    }
+
+    /// Announce the local participant if it is due, and forget the remote
+    /// participants whose lease has expired
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the announcement can't be added to the cache,
+    /// or doesn't fit in a message.
+    pub fn send<O>(&mut self, now: Instant, output: &mut O) -> Result<(), SendError<C::AddErr>>
+    where
+        C: Cache<Vec<u8>, Prefix = P, EntityId = Id, SqnN = u64>,
+        O: Output<P, Id>,
+    {
+        let expired: Vec<_> = self
+            .participants
+            .iter()
+            .filter(|(_, remote)| remote.lease_expiry.map_or(false, |expiry| expiry <= now))
+            .map(|(&guid_prefix, _)| guid_prefix)
+            .collect();
+        for guid_prefix in expired {
+            self.participant_remove(guid_prefix);
+        }
+
+        if self.announce_pending {
+            let sequence_number = self
+                .writer
+                .new_change(Kind::Alive(self.local.to_bytes()), self.local.key_hash())
+                .map_err(SendError::Cache)?;
+
+            // only the latest announcement is kept
+            if let Some(previous) = self.announcement.replace(sequence_number) {
+                let _ = self.writer.cache_mut().remove(previous);
+            }
+            self.announce_pending = false;
+        }
+
+        self.writer.send(now, output)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DiscoveredParticipantData, ParticipantDiscovery, ParticipantEvent};
+    use crate::{
+        behaviour::{
+            receiver::{Context, Dispatch, ReaderSubMessage, WriterSubMessage},
+            MessageReceiver,
+        },
+        discovery::DecodeError,
+        messages::Message,
+        structure::{
+            history::HistoryCache, BuiltinEndpointSet, Locator, Participant, ProtocolVersion,
+            VendorId,
+        },
+    };
+    use std::{
+        net::{Ipv4Addr, SocketAddrV4},
+        time::{Duration, Instant},
+    };
+    use test_case::test_case;
+
+    type Prefix = [u8; 12];
+    type Id = [u8; 4];
+    type TestDiscovery = ParticipantDiscovery<HistoryCache<Vec<u8>, Prefix, Id>, Prefix, Id>;
+    type Sent = Vec<(Locator, Message<Prefix, Id>)>;
+
+    const PERIOD: Duration = Duration::from_secs(1);
+    const LEASE: Duration = Duration::from_secs(3);
+
+    /// Routes every reader submessage to a single discovery instance
+    struct Router<'a> {
+        discovery: &'a mut TestDiscovery,
+        now: Instant,
+        result: Result<(), DecodeError>,
+    }
+
+    impl Dispatch<Prefix, Id> for Router<'_> {
+        fn dispatch_to_reader(
+            &mut self,
+            _reader: Option<Id>,
+            context: &Context<Prefix>,
+            submessage: ReaderSubMessage<Id>,
+        ) {
+            self.result = self.discovery.receive(self.now, context, submessage);
+        }
+
+        fn dispatch_to_writer(
+            &mut self,
+            _writer: Id,
+            _context: &Context<Prefix>,
+            _submessage: WriterSubMessage<Id>,
+        ) {
+        }
+    }
+
+    fn multicast() -> Locator {
+        SocketAddrV4::new(Ipv4Addr::new(239, 255, 0, 1), 7400).into()
+    }
+
+    fn data(guid_prefix: Prefix) -> DiscoveredParticipantData<Prefix> {
+        let unicast = SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, guid_prefix[0]), 7411);
+        DiscoveredParticipantData {
+            protocol_version: ProtocolVersion::Specified { major: 2, minor: 5 },
+            vendor_id: VendorId::Known([0x01, 0x0f]),
+            default_unicast_locators: vec![unicast.into()],
+            metatraffic_multicast_locators: vec![multicast()],
+            lease_duration: Some(LEASE),
+            ..DiscoveredParticipantData::new(guid_prefix)
+        }
+    }
+
+    fn discovery(guid_prefix: Prefix) -> TestDiscovery {
+        ParticipantDiscovery::builder(data(guid_prefix), HistoryCache::default())
+            .announcement_period(PERIOD)
+            .announcement_locators(vec![multicast()])
+            .build()
+    }
+
+    /// Deliver every announcement sent by `from` at `now` to `to`
+    fn exchange(from: &mut TestDiscovery, to: &mut TestDiscovery, now: Instant) {
+        let mut sent = Sent::new();
+        from.send(now, &mut sent).unwrap();
+        let receiver = MessageReceiver::new(to.local().guid_prefix);
+        for (_, message) in sent {
+            let mut router = Router {
+                discovery: to,
+                now,
+                result: Ok(()),
+            };
+            receiver.receive(None, message, &mut router).unwrap();
+            router.result.unwrap();
+        }
+    }
+
+    #[test]
+    fn participant_data_round_trip() {
+        let data = DiscoveredParticipantData {
+            protocol_version: ProtocolVersion::Specified { major: 2, minor: 4 },
+            expects_inline_qos: true,
+            builtin_endpoints: BuiltinEndpointSet::PARTICIPANT_ANNOUNCER,
+            manual_liveliness_count: 3,
+            domain_id: Some(3),
+            domain_tag: String::from("bench-2"),
+            lease_duration: None,
+            ..data([7; 12])
+        };
+        assert_eq!(
+            DiscoveredParticipantData::from_bytes(&data.to_bytes()).unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    fn announces_a_participant_to_its_peers() {
+        let peer: Locator = SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 2), 7410).into();
+        let participant = Participant::builder([1; 12], [0, 0, 1, 0xc1])
+            .domain_id(3)
+            .peer_locators(vec![peer])
+            .build();
+
+        let mut discovery: TestDiscovery =
+            ParticipantDiscovery::for_participant(&participant, HistoryCache::default()).build();
+        assert_eq!(discovery.local(), &participant.proxy());
+
+        let mut sent = Sent::new();
+        discovery.send(Instant::now(), &mut sent).unwrap();
+        let destinations: Vec<_> = sent.iter().map(|(locator, _)| *locator).collect();
+        assert_eq!(destinations, vec![peer]);
+    }
+
+    #[test]
+    fn discovers_and_expires_peers() {
+        let mut a = discovery([1; 12]);
+        let mut b = discovery([2; 12]);
+        let start = Instant::now();
+
+        exchange(&mut a, &mut b, start);
+        assert_eq!(
+            b.take_events(),
+            vec![ParticipantEvent::Discovered(data([1; 12]))]
+        );
+        assert_eq!(b.participant([1; 12]), Some(&data([1; 12])));
+
+        // periodic announcements renew the lease without raising events
+        exchange(&mut a, &mut b, start + PERIOD);
+        assert!(b.take_events().is_empty());
+        assert_eq!(b.next_deadline(), Some(start + PERIOD + LEASE));
+
+        exchange(&mut a, &mut b, start + PERIOD * 2);
+        b.send(start + PERIOD * 2 + LEASE, &mut Sent::new())
+            .unwrap();
+        assert_eq!(
+            b.take_events(),
+            vec![ParticipantEvent::Lost {
+                guid_prefix: [1; 12]
+            }]
+        );
+        assert_eq!(b.participants().count(), 0);
+    }
+
+    #[test]
+    fn announces_changes_to_the_local_participant() {
+        let mut a = discovery([1; 12]);
+        let mut b = discovery([2; 12]);
+        let start = Instant::now();
+        exchange(&mut a, &mut b, start);
+        b.take_events();
+
+        let updated = DiscoveredParticipantData {
+            expects_inline_qos: true,
+            ..data([1; 12])
+        };
+        a.set_local(updated.clone());
+        exchange(&mut a, &mut b, start);
+        assert_eq!(b.take_events(), vec![ParticipantEvent::Updated(updated)]);
+
+        // only the latest announcement is kept
+        assert_eq!(a.writer().cache().len(), 1);
+    }
+
+    #[test]
+    fn ignores_own_announcements() {
+        let mut a = discovery([1; 12]);
+        let mut sent = Sent::new();
+        a.send(Instant::now(), &mut sent).unwrap();
+
+        let (_, message) = sent.remove(0);
+        let mut router = Router {
+            discovery: &mut a,
+            now: Instant::now(),
+            result: Ok(()),
+        };
+        MessageReceiver::new([1; 12])
+            .receive(None, message, &mut router)
+            .unwrap();
+        assert_eq!(a.participants().count(), 0);
+    }
+
+    #[test_case(Some(1), "", Some(2), "" => false; "different domains")]
+    #[test_case(Some(1), "a", Some(1), "b" => false; "different tags")]
+    #[test_case(Some(1), "a", Some(1), "a" => true; "same domain and tag")]
+    #[test_case(Some(1), "", None, "" => true; "unannounced domain")]
+    fn discovers_only_the_same_domain(
+        domain_id: Option<u32>,
+        domain_tag: &str,
+        remote_domain_id: Option<u32>,
+        remote_domain_tag: &str,
+    ) -> bool {
+        let in_domain = |guid_prefix, domain_id, domain_tag: &str| {
+            ParticipantDiscovery::builder(
+                DiscoveredParticipantData {
+                    domain_id,
+                    domain_tag: domain_tag.to_string(),
+                    ..data(guid_prefix)
+                },
+                HistoryCache::default(),
+            )
+            .announcement_locators(vec![multicast()])
+            .build()
+        };
+        let mut a: TestDiscovery = in_domain([1; 12], remote_domain_id, remote_domain_tag);
+        let mut b: TestDiscovery = in_domain([2; 12], domain_id, domain_tag);
+
+        exchange(&mut a, &mut b, Instant::now());
+        b.participant([1; 12]).is_some()
+    }
+
+    #[test]
+    fn loses_peers_which_change_domain() {
+        let mut a = discovery([1; 12]);
+        let mut b = discovery([2; 12]);
+        let start = Instant::now();
+        exchange(&mut a, &mut b, start);
+        b.take_events();
+
+        a.set_local(DiscoveredParticipantData {
+            domain_tag: String::from("elsewhere"),
+            ..data([1; 12])
+        });
+        exchange(&mut a, &mut b, start);
+        assert_eq!(
+            b.take_events(),
+            vec![ParticipantEvent::Lost {
+                guid_prefix: [1; 12]
+            }]
+        );
+    }
+}
@@ -10,5 +10,7 @@
 )]
 #![warn(clippy::pedantic)]
 
+pub mod behaviour;
+pub mod discovery;
 pub mod messages;
 pub mod structure;
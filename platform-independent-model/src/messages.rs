@@ -1,12 +1,16 @@
 //! Structures and message definitions used in all communications between RTPS
 //! entities
 
+pub mod builder;
 mod byte_order;
+pub(crate) mod encoding;
 mod header;
 pub mod message;
 mod protocol_id;
 pub mod submessage;
 
+#[doc(inline)]
+pub use builder::MessageBuilder;
 #[doc(inline)]
 pub use byte_order::ByteOrder;
 pub use header::{Extension as HeaderExtension, Header};
@@ -0,0 +1,364 @@
+//! Contains the [`MessageBuilder`], which packs [`SubMessage`]s into
+//! [`Message`]s
+
+use chrono::{DateTime, Utc};
+use vec1::Vec1;
+
+use super::{
+    message::HEADER_SIZE,
+    submessage::{InfoDestination, InfoTimestamp},
+    Header, Message, SubMessage,
+};
+
+/// The [`MessageBuilder`] packs [`SubMessage`]s into as few [`Message`]s as
+/// possible, without exceeding a maximum datagram size.
+///
+/// Each submessage is pushed along with the participant it is addressed to
+/// and its source timestamp. The builder only inserts an
+/// [`InfoDestination`] or [`InfoTimestamp`] submessage when these change,
+/// so that consecutive submessages with the same context share them.
+///
+/// # Example
+///
+/// ```
+/// use rtps_pim::{
+///     messages::{submessage::Heartbeat, Header, MessageBuilder},
+///     structure::{ProtocolVersion, VendorId},
+/// };
+///
+/// let header = Header::new(ProtocolVersion::Latest, VendorId::Unknown, [0; 12]);
+/// let mut builder = MessageBuilder::new(header, 1500);
+///
+/// let heartbeat = Heartbeat {
+///     reader: None,
+///     writer: [0, 0, 1, 2],
+///     first_sequence_number: 1,
+///     last_sequence_number: 10,
+///     count: 1,
+///     final_flag: false,
+///     liveliness_flag: false,
+/// };
+///
+/// builder.push(Some([1; 12]), None, heartbeat.into()).unwrap();
+///
+/// let messages = builder.finish();
+/// assert_eq!(messages.len(), 1);
+/// ```
+#[derive(Debug)]
+pub struct MessageBuilder<P, Id> {
+    header: Header<P>,
+    max_size: usize,
+    submessages: Vec<SubMessage<P, Id>>,
+    size: usize,
+    destination: Option<P>,
+    timestamp: Option<DateTime<Utc>>,
+    messages: Vec<Message<P, Id>>,
+}
+
+impl<P, Id> MessageBuilder<P, Id>
+where
+    P: Copy + PartialEq,
+{
+    /// Construct a new [`MessageBuilder`]
+    ///
+    /// Every [`Message`] produced will use the given [`Header`], and will be no
+    /// larger than `max_size` bytes when serialized.
+    #[must_use]
+    pub fn new(header: Header<P>, max_size: usize) -> Self {
+        Self {
+            header,
+            max_size,
+            submessages: Vec::default(),
+            size: HEADER_SIZE,
+            destination: None,
+            timestamp: None,
+            messages: Vec::default(),
+        }
+    }
+
+    /// The maximum size of a serialized [`Message`], in bytes
+    #[must_use]
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// The number of bytes which are still available in the current
+    /// [`Message`] for an entity submessage with the given context.
+    ///
+    /// This accounts for any interpreter submessages which would need to be
+    /// inserted.
+    #[must_use]
+    pub fn remaining(&self, destination: Option<P>, timestamp: Option<DateTime<Utc>>) -> usize {
+        let context_size = self.context_size(destination, timestamp);
+        self.max_size.saturating_sub(self.size + context_size)
+    }
+
+    /// The number of bytes available in an empty [`Message`] for an entity
+    /// submessage with the given context.
+    ///
+    /// A submessage larger than this can never be pushed, and has to be split
+    /// up, for example into `DATA_FRAG`s.
+    #[must_use]
+    pub fn capacity(&self, destination: Option<P>, timestamp: Option<DateTime<Utc>>) -> usize {
+        let mut size = HEADER_SIZE;
+
+        if destination.is_some() {
+            size += SubMessage::<P, Id>::from(InfoDestination {
+                guid_prefix: destination,
+            })
+            .serialized_size();
+        }
+
+        if timestamp.is_some() {
+            size += SubMessage::<P, Id>::from(InfoTimestamp { timestamp }).serialized_size();
+        }
+
+        self.max_size.saturating_sub(size)
+    }
+
+    /// Add a [`SubMessage`] to the builder
+    ///
+    /// `destination` is the [`Guid`](crate::structure::Guid) prefix of the
+    /// participant to which the submessage is addressed, or `None` if it is
+    /// addressed to every participant. `timestamp` is the source timestamp
+    /// which applies to the submessage, if any.
+    ///
+    /// If the submessage doesn't fit in the current [`Message`], that message
+    /// is completed and a new one is started.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the submessage is too large to fit in a
+    /// [`Message`] on its own.
+    pub fn push(
+        &mut self,
+        destination: Option<P>,
+        timestamp: Option<DateTime<Utc>>,
+        submessage: SubMessage<P, Id>,
+    ) -> Result<(), TooLargeError> {
+        let submessage_size = submessage.serialized_size();
+
+        if submessage_size > self.remaining(destination, timestamp) {
+            self.flush();
+        }
+
+        if submessage_size > self.remaining(destination, timestamp) {
+            return Err(TooLargeError::new(submessage_size, self.max_size));
+        }
+
+        if destination != self.destination {
+            self.append(InfoDestination {
+                guid_prefix: destination,
+            });
+            self.destination = destination;
+        }
+
+        if timestamp != self.timestamp {
+            self.append(InfoTimestamp { timestamp });
+            self.timestamp = timestamp;
+        }
+
+        self.append(submessage);
+
+        Ok(())
+    }
+
+    /// Complete the current [`Message`], if it contains any submessages.
+    ///
+    /// Subsequent submessages will be added to a new [`Message`].
+    pub fn flush(&mut self) {
+        let submessages = std::mem::take(&mut self.submessages);
+
+        if let Ok(submessages) = Vec1::try_from_vec(submessages) {
+            self.messages.push(Message::new(self.header, submessages));
+        }
+
+        self.size = HEADER_SIZE;
+        self.destination = None;
+        self.timestamp = None;
+    }
+
+    /// Remove and return any completed [`Message`]s
+    ///
+    /// The [`Message`] which is currently being built is not returned. To
+    /// return every message, use [`MessageBuilder::finish`] instead.
+    pub fn take_messages(&mut self) -> Vec<Message<P, Id>> {
+        std::mem::take(&mut self.messages)
+    }
+
+    /// Complete the current [`Message`] and return every [`Message`] which
+    /// has been built
+    #[must_use]
+    pub fn finish(mut self) -> Vec<Message<P, Id>> {
+        self.flush();
+        self.messages
+    }
+
+    fn append(&mut self, submessage: impl Into<SubMessage<P, Id>>) {
+        let submessage = submessage.into();
+        self.size += submessage.serialized_size();
+        self.submessages.push(submessage);
+    }
+
+    fn context_size(&self, destination: Option<P>, timestamp: Option<DateTime<Utc>>) -> usize {
+        let mut size = 0;
+
+        if destination != self.destination {
+            size += SubMessage::<P, Id>::from(InfoDestination {
+                guid_prefix: destination,
+            })
+            .serialized_size();
+        }
+
+        if timestamp != self.timestamp {
+            size += SubMessage::<P, Id>::from(InfoTimestamp { timestamp }).serialized_size();
+        }
+
+        size
+    }
+}
+
+/// The error returned when a [`SubMessage`] is too large to fit in a single
+/// [`Message`]
+#[derive(Debug, thiserror::Error)]
+#[error("submessage of {size} bytes does not fit in a message of at most {max_size} bytes")]
+pub struct TooLargeError {
+    size: usize,
+    max_size: usize,
+}
+
+impl TooLargeError {
+    pub(crate) fn new(size: usize, max_size: usize) -> Self {
+        Self { size, max_size }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MessageBuilder;
+    use crate::{
+        messages::{
+            submessage::{Data, Heartbeat, InfoDestination, InfoTimestamp, Payload},
+            Header, SubMessage,
+        },
+        structure::{ProtocolVersion, VendorId},
+    };
+    use chrono::{TimeZone, Utc};
+
+    type Prefix = [u8; 12];
+    type Id = [u8; 4];
+
+    fn builder(max_size: usize) -> MessageBuilder<Prefix, Id> {
+        let header = Header::new(ProtocolVersion::Latest, VendorId::Unknown, [0; 12]);
+        MessageBuilder::new(header, max_size)
+    }
+
+    fn data(payload_size: usize) -> SubMessage<Prefix, Id> {
+        Data {
+            reader: None,
+            writer: [0, 0, 1, 2],
+            writer_sequence_number: 1,
+            inline_qos: None,
+            payload: Some(Payload::Data(vec![0; payload_size])),
+            non_standard_payload: false,
+        }
+        .into()
+    }
+
+    fn heartbeat() -> SubMessage<Prefix, Id> {
+        Heartbeat {
+            reader: None,
+            writer: [0, 0, 1, 2],
+            first_sequence_number: 1,
+            last_sequence_number: 1,
+            count: 1,
+            final_flag: false,
+            liveliness_flag: false,
+        }
+        .into()
+    }
+
+    #[test]
+    fn inserts_context_only_when_it_changes() {
+        let timestamp = Utc.timestamp_opt(1_000, 0).unwrap();
+        let mut builder = builder(1500);
+
+        builder
+            .push(Some([1; 12]), Some(timestamp), data(8))
+            .unwrap();
+        builder
+            .push(Some([1; 12]), Some(timestamp), heartbeat())
+            .unwrap();
+        builder
+            .push(Some([2; 12]), Some(timestamp), data(8))
+            .unwrap();
+        builder.push(None, None, heartbeat()).unwrap();
+
+        let messages = builder.finish();
+        assert_eq!(messages.len(), 1);
+
+        let expected: Vec<SubMessage<Prefix, Id>> = vec![
+            InfoDestination {
+                guid_prefix: Some([1; 12]),
+            }
+            .into(),
+            InfoTimestamp {
+                timestamp: Some(timestamp),
+            }
+            .into(),
+            data(8),
+            heartbeat(),
+            InfoDestination {
+                guid_prefix: Some([2; 12]),
+            }
+            .into(),
+            data(8),
+            InfoDestination { guid_prefix: None }.into(),
+            InfoTimestamp { timestamp: None }.into(),
+            heartbeat(),
+        ];
+        assert_eq!(messages[0].submessages(), expected.as_slice());
+    }
+
+    #[test]
+    fn respects_maximum_size() {
+        let max_size = 200;
+        let mut builder = builder(max_size);
+
+        for _ in 0..10 {
+            builder.push(Some([1; 12]), None, data(40)).unwrap();
+        }
+
+        let messages = builder.finish();
+        assert!(messages.len() > 1);
+
+        for message in &messages {
+            assert!(message.serialized_size() <= max_size);
+
+            // every message re-establishes the destination
+            assert!(matches!(
+                message.submessages()[0],
+                SubMessage::InfoDestination(_)
+            ));
+        }
+
+        let n_data: usize = messages
+            .iter()
+            .map(|message| {
+                message
+                    .submessages()
+                    .iter()
+                    .filter(|submessage| matches!(submessage, SubMessage::Data(_)))
+                    .count()
+            })
+            .sum();
+        assert_eq!(n_data, 10);
+    }
+
+    #[test]
+    fn rejects_oversized_submessage() {
+        let mut builder = builder(100);
+        assert!(builder.push(None, None, data(200)).is_err());
+        assert!(builder.finish().is_empty());
+    }
+}
@@ -0,0 +1,770 @@
+//! Encoding of the [`ParameterList`] payloads exchanged by the built-in
+//! discovery endpoints, and of the quality of service policies within them
+//!
+//! Payloads are written as little-endian `PL_CDR`, and may be read in either
+//! byte order.
+//!
+//! See [section 9.6.2](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF) of the relevant specification document.
+
+use std::{
+    convert::TryFrom,
+    num::NonZeroUsize,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6},
+    time::Duration,
+};
+
+use crate::{
+    behaviour::liveliness::{Liveliness, LivelinessKind},
+    messages::{
+        submessage::elements::{parameter_id, Parameter, ParameterList},
+        ByteOrder,
+    },
+    structure::{
+        history::{Policy, ResourceLimits},
+        qos::{
+            Deadline, DestinationOrder, Durability, GroupData, LatencyBudget, Lifespan, Ownership,
+            OwnershipStrength, Partition, Presentation, PresentationAccessScope, Reliability,
+            ReliabilityKind, TimeBasedFilter, TopicData, UserData,
+        },
+        BuiltinEndpointSet, Guid, Locator, ProtocolVersion, VendorId,
+    },
+};
+
+/// The encapsulation identifiers of `PL_CDR` payloads
+const PL_CDR_BE: [u8; 2] = [0x00, 0x02];
+const PL_CDR_LE: [u8; 2] = [0x00, 0x03];
+
+/// The locator kinds of UDP over IPv4 and IPv6
+const LOCATOR_KIND_UDPV4: i32 = 1;
+const LOCATOR_KIND_UDPV6: i32 = 2;
+
+/// The value of an infinite `Duration_t`
+const DURATION_INFINITE: (i32, u32) = (0x7fff_ffff, 0xffff_ffff);
+
+/// The error returned when a discovery payload can't be decoded
+#[derive(Debug, thiserror::Error)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum DecodeError {
+    /// The payload ends in the middle of a parameter
+    #[error("the payload is truncated")]
+    Truncated,
+
+    /// The payload isn't a `PL_CDR` parameter list
+    #[error("unsupported encapsulation {0:?}")]
+    Encapsulation([u8; 2]),
+
+    /// A required parameter is missing
+    #[error("missing parameter {0:#06x}")]
+    MissingParameter(u16),
+
+    /// The value of a parameter is invalid
+    #[error("invalid value for parameter {0:#06x}")]
+    InvalidParameter(u16),
+}
+
+/// A value which can be carried in a [`Parameter`]
+pub(crate) trait Value: Sized {
+    /// Serialize the value as little-endian CDR
+    fn encode(&self) -> Vec<u8>;
+
+    /// Deserialize the value, returning `None` if it is invalid
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self>;
+}
+
+/// A [`ParameterList`] which has been read from a payload, along with the byte
+/// order of its values
+#[derive(Debug)]
+pub(crate) struct Parameters {
+    byte_order: ByteOrder,
+    list: ParameterList,
+}
+
+impl Parameters {
+    /// Read a `PL_CDR` payload
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let header = bytes.get(..4).ok_or(DecodeError::Truncated)?;
+        let byte_order = match [header[0], header[1]] {
+            PL_CDR_LE => ByteOrder::LittleEndian,
+            PL_CDR_BE => ByteOrder::BigEndian,
+            other => return Err(DecodeError::Encapsulation(other)),
+        };
+
+        let mut list = ParameterList::default();
+        let mut remaining = &bytes[4..];
+        loop {
+            let id = u16_from(
+                remaining.get(..2).ok_or(DecodeError::Truncated)?,
+                byte_order,
+            );
+            let length = u16_from(
+                remaining.get(2..4).ok_or(DecodeError::Truncated)?,
+                byte_order,
+            );
+            if id == parameter_id::SENTINEL {
+                break;
+            }
+
+            let end = 4 + usize::from(length);
+            let value = remaining.get(4..end).ok_or(DecodeError::Truncated)?;
+            if id != parameter_id::PAD {
+                list.push(Parameter::new(id, value.to_vec()));
+            }
+            remaining = &remaining[end..];
+        }
+
+        Ok(Self { byte_order, list })
+    }
+
+    /// The value of the first parameter with the given ID, if present
+    pub(crate) fn optional<T: Value>(&self, id: u16) -> Result<Option<T>, DecodeError> {
+        self.list
+            .get(id)
+            .map(|parameter| {
+                T::decode(parameter.value(), self.byte_order)
+                    .ok_or(DecodeError::InvalidParameter(id))
+            })
+            .transpose()
+    }
+
+    /// The value of the first parameter with the given ID
+    pub(crate) fn required<T: Value>(&self, id: u16) -> Result<T, DecodeError> {
+        self.optional(id)?.ok_or(DecodeError::MissingParameter(id))
+    }
+
+    /// The values of every parameter with the given ID
+    pub(crate) fn all<T: Value>(&self, id: u16) -> Result<Vec<T>, DecodeError> {
+        self.list
+            .get_all(id)
+            .map(|parameter| {
+                T::decode(parameter.value(), self.byte_order)
+                    .ok_or(DecodeError::InvalidParameter(id))
+            })
+            .collect()
+    }
+}
+
+/// Construct a [`Parameter`] carrying the given value
+pub(crate) fn parameter<T: Value>(id: u16, value: &T) -> Parameter {
+    Parameter::new(id, value.encode())
+}
+
+/// Write a [`ParameterList`] as a little-endian `PL_CDR` payload
+pub(crate) fn to_bytes(list: &ParameterList) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&PL_CDR_LE);
+    bytes.extend_from_slice(&[0, 0]);
+
+    for parameter in list.iter() {
+        let value = parameter.value();
+        let padding = (4 - value.len() % 4) % 4;
+        let length = u16::try_from(value.len() + padding).unwrap_or(u16::MAX);
+
+        bytes.extend_from_slice(&parameter.id().to_le_bytes());
+        bytes.extend_from_slice(&length.to_le_bytes());
+        bytes.extend_from_slice(value);
+        bytes.resize(bytes.len() + padding, 0);
+    }
+
+    bytes.extend_from_slice(&parameter_id::SENTINEL.to_le_bytes());
+    bytes.extend_from_slice(&[0, 0]);
+    bytes
+}
+
+fn u16_from(bytes: &[u8], byte_order: ByteOrder) -> u16 {
+    let bytes = [bytes[0], bytes[1]];
+    match byte_order {
+        ByteOrder::LittleEndian => u16::from_le_bytes(bytes),
+        ByteOrder::BigEndian => u16::from_be_bytes(bytes),
+    }
+}
+
+fn array<const N: usize>(bytes: &[u8], offset: usize) -> Option<[u8; N]> {
+    <[u8; N]>::try_from(bytes.get(offset..offset + N)?).ok()
+}
+
+impl Value for u32 {
+    fn encode(&self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        let bytes = array(bytes, 0)?;
+        Some(match byte_order {
+            ByteOrder::LittleEndian => Self::from_le_bytes(bytes),
+            ByteOrder::BigEndian => Self::from_be_bytes(bytes),
+        })
+    }
+}
+
+impl Value for i32 {
+    fn encode(&self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        u32::decode(bytes, byte_order).map(|value| Self::from_ne_bytes(value.to_ne_bytes()))
+    }
+}
+
+impl Value for bool {
+    fn encode(&self) -> Vec<u8> {
+        vec![u8::from(*self), 0, 0, 0]
+    }
+
+    fn decode(bytes: &[u8], _byte_order: ByteOrder) -> Option<Self> {
+        bytes.first().map(|&value| value != 0)
+    }
+}
+
+impl Value for String {
+    fn encode(&self) -> Vec<u8> {
+        let length = u32::try_from(self.len() + 1).unwrap_or(u32::MAX);
+        let mut bytes = length.encode();
+        bytes.extend_from_slice(self.as_bytes());
+        bytes.push(0);
+        bytes
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        let length = usize::try_from(u32::decode(bytes, byte_order)?).ok()?;
+        let string = bytes.get(4..4 + length)?;
+        let string = string.strip_suffix(&[0]).unwrap_or(string);
+        String::from_utf8(string.to_vec()).ok()
+    }
+}
+
+/// A `Duration_t`, where `None` is infinite
+impl Value for Option<Duration> {
+    fn encode(&self) -> Vec<u8> {
+        let (seconds, fraction) = match self {
+            Some(duration) => (
+                i32::try_from(duration.as_secs()).unwrap_or(i32::MAX),
+                u32::try_from((u64::from(duration.subsec_nanos()) << 32) / 1_000_000_000)
+                    .unwrap_or(u32::MAX),
+            ),
+            None => DURATION_INFINITE,
+        };
+        let mut bytes = seconds.encode();
+        bytes.extend(fraction.encode());
+        bytes
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        let seconds = i32::decode(bytes, byte_order)?;
+        let fraction = u32::decode(bytes.get(4..)?, byte_order)?;
+        if (seconds, fraction) == DURATION_INFINITE {
+            return Some(None);
+        }
+
+        let nanos = (u64::from(fraction) * 1_000_000_000) >> 32;
+        Some(Some(
+            Duration::from_secs(u64::try_from(seconds).ok()?) + Duration::from_nanos(nanos),
+        ))
+    }
+}
+
+impl Value for Locator {
+    fn encode(&self) -> Vec<u8> {
+        let (kind, port, address) = match self {
+            Self::Udpv4(address) => {
+                let mut bytes = [0; 16];
+                bytes[12..].copy_from_slice(&address.ip().octets());
+                (LOCATOR_KIND_UDPV4, address.port(), bytes)
+            }
+            Self::Udpv6(address) => (LOCATOR_KIND_UDPV6, address.port(), address.ip().octets()),
+        };
+        let mut bytes = kind.encode();
+        bytes.extend(u32::from(port).encode());
+        bytes.extend_from_slice(&address);
+        bytes
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        let kind = i32::decode(bytes, byte_order)?;
+        let port = u16::try_from(u32::decode(bytes.get(4..)?, byte_order)?).ok()?;
+        let address: [u8; 16] = array(bytes, 8)?;
+
+        match kind {
+            LOCATOR_KIND_UDPV4 => {
+                let ip = Ipv4Addr::new(address[12], address[13], address[14], address[15]);
+                Some(SocketAddrV4::new(ip, port).into())
+            }
+            LOCATOR_KIND_UDPV6 => {
+                Some(SocketAddrV6::new(Ipv6Addr::from(address), port, 0, 0).into())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Value for ProtocolVersion {
+    fn encode(&self) -> Vec<u8> {
+        let (major, minor) = match *self {
+            Self::Latest => (2, 5),
+            Self::Specified { major, minor } => (
+                u8::try_from(major).unwrap_or(u8::MAX),
+                u8::try_from(minor).unwrap_or(u8::MAX),
+            ),
+        };
+        vec![major, minor, 0, 0]
+    }
+
+    fn decode(bytes: &[u8], _byte_order: ByteOrder) -> Option<Self> {
+        let [major, minor]: [u8; 2] = array(bytes, 0)?;
+        Some(Self::Specified {
+            major: major.into(),
+            minor: minor.into(),
+        })
+    }
+}
+
+impl Value for VendorId {
+    fn encode(&self) -> Vec<u8> {
+        let id = match self {
+            Self::Unknown => [0, 0],
+            Self::Known(id) => *id,
+        };
+        vec![id[0], id[1], 0, 0]
+    }
+
+    fn decode(bytes: &[u8], _byte_order: ByteOrder) -> Option<Self> {
+        Some(match array(bytes, 0)? {
+            [0, 0] => Self::Unknown,
+            id => Self::Known(id),
+        })
+    }
+}
+
+impl Value for BuiltinEndpointSet {
+    fn encode(&self) -> Vec<u8> {
+        self.bits().encode()
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        u32::decode(bytes, byte_order).map(Self::from_bits)
+    }
+}
+
+impl Value for Liveliness {
+    fn encode(&self) -> Vec<u8> {
+        let kind: u32 = match self.kind {
+            LivelinessKind::Automatic => 0,
+            LivelinessKind::ManualByParticipant => 1,
+            LivelinessKind::ManualByTopic => 2,
+        };
+        let mut bytes = kind.encode();
+        bytes.extend(self.lease_duration.encode());
+        bytes
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        let kind = match u32::decode(bytes, byte_order)? {
+            0 => LivelinessKind::Automatic,
+            1 => LivelinessKind::ManualByParticipant,
+            2 => LivelinessKind::ManualByTopic,
+            _ => return None,
+        };
+        let lease_duration = Option::<Duration>::decode(bytes.get(4..)?, byte_order)?;
+        Some(Self {
+            kind,
+            lease_duration,
+        })
+    }
+}
+
+/// A `Duration_t` which can't be infinite, where infinite is read as the
+/// longest representable duration
+impl Value for Duration {
+    fn encode(&self) -> Vec<u8> {
+        Some(*self).encode()
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        Some(Option::<Self>::decode(bytes, byte_order)?.unwrap_or(Self::MAX))
+    }
+}
+
+/// A `sequence<octet>`
+impl Value for Vec<u8> {
+    fn encode(&self) -> Vec<u8> {
+        let length = u32::try_from(self.len()).unwrap_or(u32::MAX);
+        let mut bytes = length.encode();
+        bytes.extend_from_slice(self);
+        bytes
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        let length = usize::try_from(u32::decode(bytes, byte_order)?).ok()?;
+        Some(bytes.get(4..4 + length)?.to_vec())
+    }
+}
+
+/// Encode an enumerated policy kind, from its index in `kinds`
+fn encode_kind<T: PartialEq>(kinds: &[T], value: &T) -> Vec<u8> {
+    let index = kinds
+        .iter()
+        .position(|kind| kind == value)
+        .unwrap_or_default();
+    u32::try_from(index).unwrap_or_default().encode()
+}
+
+/// Decode an enumerated policy kind, from its index in `kinds`
+fn decode_kind<T: Copy>(kinds: &[T], bytes: &[u8], byte_order: ByteOrder) -> Option<T> {
+    let index = usize::try_from(u32::decode(bytes, byte_order)?).ok()?;
+    kinds.get(index).copied()
+}
+
+const DURABILITY_KINDS: [Durability; 4] = [
+    Durability::Volatile,
+    Durability::TransientLocal,
+    Durability::Transient,
+    Durability::Persistent,
+];
+
+impl Value for Durability {
+    fn encode(&self) -> Vec<u8> {
+        encode_kind(&DURABILITY_KINDS, self)
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        decode_kind(&DURABILITY_KINDS, bytes, byte_order)
+    }
+}
+
+const OWNERSHIP_KINDS: [Ownership; 2] = [Ownership::Shared, Ownership::Exclusive];
+
+impl Value for Ownership {
+    fn encode(&self) -> Vec<u8> {
+        encode_kind(&OWNERSHIP_KINDS, self)
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        decode_kind(&OWNERSHIP_KINDS, bytes, byte_order)
+    }
+}
+
+const DESTINATION_ORDER_KINDS: [DestinationOrder; 2] = [
+    DestinationOrder::ByReceptionTimestamp,
+    DestinationOrder::BySourceTimestamp,
+];
+
+impl Value for DestinationOrder {
+    fn encode(&self) -> Vec<u8> {
+        encode_kind(&DESTINATION_ORDER_KINDS, self)
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        decode_kind(&DESTINATION_ORDER_KINDS, bytes, byte_order)
+    }
+}
+
+/// The kinds of the `RELIABILITY` policy start from one
+const RELIABILITY_KINDS: [ReliabilityKind; 3] = [
+    ReliabilityKind::BestEffort,
+    ReliabilityKind::BestEffort,
+    ReliabilityKind::Reliable,
+];
+
+impl Value for Reliability {
+    fn encode(&self) -> Vec<u8> {
+        let kind: u32 = match self.kind {
+            ReliabilityKind::BestEffort => 1,
+            ReliabilityKind::Reliable => 2,
+        };
+        let mut bytes = kind.encode();
+        bytes.extend(self.max_blocking_time.encode());
+        bytes
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        Some(Self {
+            kind: decode_kind(&RELIABILITY_KINDS, bytes, byte_order)?,
+            max_blocking_time: Option::<Duration>::decode(bytes.get(4..)?, byte_order)?,
+        })
+    }
+}
+
+impl Value for Deadline {
+    fn encode(&self) -> Vec<u8> {
+        self.period.encode()
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        Value::decode(bytes, byte_order).map(|period| Self { period })
+    }
+}
+
+impl Value for LatencyBudget {
+    fn encode(&self) -> Vec<u8> {
+        self.duration.encode()
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        Value::decode(bytes, byte_order).map(|duration| Self { duration })
+    }
+}
+
+impl Value for Lifespan {
+    fn encode(&self) -> Vec<u8> {
+        self.duration.encode()
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        Value::decode(bytes, byte_order).map(|duration| Self { duration })
+    }
+}
+
+impl Value for TimeBasedFilter {
+    fn encode(&self) -> Vec<u8> {
+        self.minimum_separation.encode()
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        Value::decode(bytes, byte_order).map(|minimum_separation| Self { minimum_separation })
+    }
+}
+
+impl Value for OwnershipStrength {
+    fn encode(&self) -> Vec<u8> {
+        self.value.encode()
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        Value::decode(bytes, byte_order).map(|value| Self { value })
+    }
+}
+
+impl Value for UserData {
+    fn encode(&self) -> Vec<u8> {
+        self.value.encode()
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        Value::decode(bytes, byte_order).map(|value| Self { value })
+    }
+}
+
+impl Value for TopicData {
+    fn encode(&self) -> Vec<u8> {
+        self.value.encode()
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        Value::decode(bytes, byte_order).map(|value| Self { value })
+    }
+}
+
+impl Value for GroupData {
+    fn encode(&self) -> Vec<u8> {
+        self.value.encode()
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        Value::decode(bytes, byte_order).map(|value| Self { value })
+    }
+}
+
+/// The `HISTORY` policy, as a kind and a depth
+impl Value for Policy {
+    fn encode(&self) -> Vec<u8> {
+        let (kind, depth): (u32, i32) = match self {
+            Self::KeepLast(depth) => (0, i32::try_from(depth.get()).unwrap_or(i32::MAX)),
+            Self::KeepAll => (1, 1),
+        };
+        let mut bytes = kind.encode();
+        bytes.extend(depth.encode());
+        bytes
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        match u32::decode(bytes, byte_order)? {
+            0 => {
+                let depth = usize::try_from(i32::decode(bytes.get(4..)?, byte_order)?).ok()?;
+                NonZeroUsize::new(depth).map(Self::KeepLast)
+            }
+            1 => Some(Self::KeepAll),
+            _ => None,
+        }
+    }
+}
+
+/// The `RESOURCE_LIMITS` policy, where unlimited resources are written as `-1`
+impl Value for ResourceLimits {
+    fn encode(&self) -> Vec<u8> {
+        [
+            self.max_samples,
+            self.max_instances,
+            self.max_samples_per_instance,
+        ]
+        .iter()
+        .flat_map(|limit| {
+            limit
+                .map_or(-1, |limit| i32::try_from(limit).unwrap_or(i32::MAX))
+                .encode()
+        })
+        .collect()
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        let limit = |offset| {
+            let limit = i32::decode(bytes.get(offset..)?, byte_order)?;
+            Some(usize::try_from(limit).ok())
+        };
+        Some(Self {
+            max_samples: limit(0)?,
+            max_instances: limit(4)?,
+            max_samples_per_instance: limit(8)?,
+        })
+    }
+}
+
+const ACCESS_SCOPES: [PresentationAccessScope; 3] = [
+    PresentationAccessScope::Instance,
+    PresentationAccessScope::Topic,
+    PresentationAccessScope::Group,
+];
+
+impl Value for Presentation {
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = encode_kind(&ACCESS_SCOPES, &self.access_scope);
+        bytes.extend_from_slice(&[
+            u8::from(self.coherent_access),
+            u8::from(self.ordered_access),
+            0,
+            0,
+        ]);
+        bytes
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        let [coherent_access, ordered_access]: [u8; 2] = array(bytes, 4)?;
+        Some(Self {
+            access_scope: decode_kind(&ACCESS_SCOPES, bytes, byte_order)?,
+            coherent_access: coherent_access != 0,
+            ordered_access: ordered_access != 0,
+        })
+    }
+}
+
+/// The `PARTITION` policy, as a `sequence<string>`
+impl Value for Partition {
+    fn encode(&self) -> Vec<u8> {
+        let count = u32::try_from(self.names.len()).unwrap_or(u32::MAX);
+        let mut bytes = count.encode();
+        for name in &self.names {
+            bytes.resize(bytes.len() + (4 - bytes.len() % 4) % 4, 0);
+            bytes.extend(name.encode());
+        }
+        bytes
+    }
+
+    fn decode(bytes: &[u8], byte_order: ByteOrder) -> Option<Self> {
+        let count = u32::decode(bytes, byte_order)?;
+        let mut offset = 4;
+        let mut names = Vec::new();
+        for _ in 0..count {
+            offset += (4 - offset % 4) % 4;
+            let encoded = bytes.get(offset..)?;
+            let length = usize::try_from(u32::decode(encoded, byte_order)?).ok()?;
+            names.push(String::decode(encoded, byte_order)?);
+            offset += 4 + length;
+        }
+        Some(Self { names })
+    }
+}
+
+impl<P, Id> Value for Guid<P, Id>
+where
+    P: Copy + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+    Id: Copy + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+{
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = self.prefix().as_ref().to_vec();
+        bytes.extend_from_slice(self.entity_id().as_ref());
+        bytes
+    }
+
+    fn decode(bytes: &[u8], _byte_order: ByteOrder) -> Option<Self> {
+        let prefix = P::try_from(bytes.get(..12)?).ok()?;
+        let entity_id = Id::try_from(bytes.get(12..16)?).ok()?;
+        Some(Self::new(prefix, entity_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parameter, to_bytes, DecodeError, Parameters, Value};
+    use crate::{
+        messages::{submessage::elements::ParameterList, ByteOrder},
+        structure::{qos::Partition, Locator},
+    };
+    use std::{
+        net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6},
+        time::Duration,
+    };
+    use test_case::test_case;
+
+    #[test_case(Some(Duration::from_millis(1500)))]
+    #[test_case(Some(Duration::from_secs(100)))]
+    #[test_case(None)]
+    fn duration_round_trip(duration: Option<Duration>) {
+        let decoded = Option::<Duration>::decode(&duration.encode(), ByteOrder::LittleEndian);
+        assert_eq!(decoded, Some(duration));
+    }
+
+    #[test]
+    fn partition_names_use_their_encoded_length() {
+        let bytes = [
+            2, 0, 0, 0, // two names
+            4, 0, 0, 0, b'a', b'b', b'c', b'd', // without a terminator
+            2, 0, 0, 0, b'e', 0,
+        ];
+        let partition = Partition::decode(&bytes, ByteOrder::LittleEndian).unwrap();
+        assert_eq!(partition.names, vec!["abcd", "e"]);
+    }
+
+    #[test]
+    fn payload_round_trip() {
+        let v4: Locator = SocketAddrV4::new(Ipv4Addr::new(239, 255, 0, 1), 7400).into();
+        let v6: Locator = SocketAddrV6::new(Ipv6Addr::LOCALHOST, 7410, 0, 0).into();
+        let list: ParameterList = vec![
+            parameter(0x0031, &v4),
+            parameter(0x0031, &v6),
+            parameter(0x0043, &true),
+            parameter(0x0062, &String::from("abc")),
+        ]
+        .into_iter()
+        .collect();
+
+        let parameters = Parameters::from_bytes(&to_bytes(&list)).unwrap();
+        assert_eq!(parameters.all::<Locator>(0x0031).unwrap(), vec![v4, v6]);
+        assert!(parameters.required::<bool>(0x0043).unwrap());
+        assert_eq!(
+            parameters.required::<String>(0x0062).unwrap(),
+            String::from("abc")
+        );
+        assert_eq!(
+            parameters.required::<bool>(0x0044),
+            Err(DecodeError::MissingParameter(0x0044))
+        );
+    }
+
+    #[test]
+    fn reads_big_endian_payloads() {
+        let bytes = [
+            0x00, 0x02, 0, 0, // PL_CDR_BE
+            0x00, 0x02, 0x00, 0x08, // lease duration
+            0, 0, 0, 10, 0x80, 0, 0, 0, // 10.5 seconds
+            0x00, 0x01, 0x00, 0x00, // sentinel
+        ];
+        let parameters = Parameters::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            parameters.required::<Option<Duration>>(0x0002).unwrap(),
+            Some(Duration::from_millis(10_500))
+        );
+        assert_eq!(
+            Parameters::from_bytes(&bytes[..10]).unwrap_err(),
+            DecodeError::Truncated
+        );
+    }
+}
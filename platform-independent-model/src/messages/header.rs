@@ -5,10 +5,52 @@ mod extension;
 pub use extension::Extension;
 
 /// A [`Message`](super::Message) header
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Header<P> {
     protocol_id: ProtocolId,
     protocol_version: ProtocolVersion,
     vendor_id: VendorId,
     guid_prefix: P,
 }
+
+impl<P> Header<P>
+where
+    P: Copy,
+{
+    /// Construct a new [`Header`] for a message sent by the participant with
+    /// the given [`Guid`](crate::structure::Guid) prefix
+    #[must_use]
+    pub fn new(protocol_version: ProtocolVersion, vendor_id: VendorId, guid_prefix: P) -> Self {
+        Self {
+            protocol_id: ProtocolId::Rtps,
+            protocol_version,
+            vendor_id,
+            guid_prefix,
+        }
+    }
+
+    /// The protocol used to encode the message
+    #[must_use]
+    pub fn protocol_id(&self) -> ProtocolId {
+        self.protocol_id
+    }
+
+    /// The version of the protocol used to encode the message
+    #[must_use]
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
+    /// The vendor of the RTPS implementation which sent the message
+    #[must_use]
+    pub fn vendor_id(&self) -> VendorId {
+        self.vendor_id
+    }
+
+    /// The [`Guid`](crate::structure::Guid) prefix of the participant which
+    /// sent the message
+    #[must_use]
+    pub fn guid_prefix(&self) -> P {
+        self.guid_prefix
+    }
+}
@@ -2,7 +2,7 @@ use crate::messages::ByteOrder;
 use chrono::{DateTime, Utc};
 
 /// An optional extension to a [`Message`](super::super::Message)
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct Extension {
     endianess: ByteOrder,
     length: Option<usize>,
@@ -56,8 +56,8 @@ impl Builder {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Checksum;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Parameter;
@@ -1,29 +1,78 @@
 //! Components of a [`Message`]
 
-use crate::structure::{ProtocolVersion, VendorId};
 use vec1::Vec1;
 
-use super::{ProtocolId, SubMessage};
+use super::{Header, HeaderExtension, SubMessage};
+
+/// The size of a serialized [`Header`], in bytes
+pub(crate) const HEADER_SIZE: usize = 20;
 
 /// General structure of any message within the RTPS protocol
-#[derive(Debug)]
-pub struct Message {
-    header: Header,
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message<P, Id> {
+    header: Header<P>,
     header_extension: Option<HeaderExtension>,
-    submessages: Vec1<SubMessage>,
+    submessages: Vec1<SubMessage<P, Id>>,
 }
 
-/// A [`Message`] header
-#[derive(Debug)]
-pub struct Header {
-    protocol_id: ProtocolId,
-    protocol_version: ProtocolVersion,
-    vendor_id: VendorId,
-}
+impl<P, Id> Message<P, Id> {
+    /// Construct a new [`Message`] from a [`Header`] and a non-empty list of
+    /// [`SubMessage`]s
+    #[must_use]
+    pub fn new(header: Header<P>, submessages: Vec1<SubMessage<P, Id>>) -> Self {
+        Self {
+            header,
+            header_extension: None,
+            submessages,
+        }
+    }
+
+    /// Attach a [`HeaderExtension`] to the [`Message`]
+    ///
+    /// The [`HeaderExtension`] was added in version 2.5 of the RTPS
+    /// specification, and is compatible with, but ignored by earlier
+    /// implementations.
+    #[must_use]
+    pub fn with_header_extension(mut self, extension: HeaderExtension) -> Self {
+        self.header_extension = Some(extension);
+        self
+    }
+
+    /// The [`Message`] [`Header`]
+    #[must_use]
+    pub fn header(&self) -> &Header<P> {
+        &self.header
+    }
 
-/// An extension to the [`Message`] header
-///
-/// The [`HeaderExtension`] was added in version 2.5 of the RTPS specification,
-/// and is compatible with, but ignored by earlier implementations.
-#[derive(Debug)]
-pub struct HeaderExtension;
+    /// The optional [`HeaderExtension`]
+    #[must_use]
+    pub fn header_extension(&self) -> Option<&HeaderExtension> {
+        self.header_extension.as_ref()
+    }
+
+    /// The [`SubMessage`]s contained in this [`Message`]
+    #[must_use]
+    pub fn submessages(&self) -> &[SubMessage<P, Id>] {
+        &self.submessages
+    }
+
+    /// The size of this message when serialized using the standard RTPS wire
+    /// representation, in bytes
+    ///
+    /// The [`HeaderExtension`] is not included.
+    #[must_use]
+    pub fn serialized_size(&self) -> usize {
+        HEADER_SIZE
+            + self
+                .submessages
+                .iter()
+                .map(SubMessage::serialized_size)
+                .sum::<usize>()
+    }
+
+    /// Consume the [`Message`], returning its [`Header`] and [`SubMessage`]s
+    #[must_use]
+    pub fn into_parts(self) -> (Header<P>, Vec1<SubMessage<P, Id>>) {
+        (self.header, self.submessages)
+    }
+}
@@ -1,5 +1,5 @@
 /// Identifier for the protocol used
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ProtocolId {
     /// The Real Time Publish Subscribe (RTPS) protocol
     Rtps,
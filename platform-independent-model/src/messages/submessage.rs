@@ -1,95 +1,199 @@
 //! Submessages which make up an RTPS [`Message`](super::Message)
 
-use super::ByteOrder;
-
 mod ack_nack;
 mod data;
 mod data_frag;
 pub mod elements;
+mod gap;
+mod heartbeat;
+mod heartbeat_frag;
+mod info_destination;
+mod info_reply;
+mod info_source;
+mod info_timestamp;
+mod nack_frag;
+
+pub use ack_nack::AckNack;
+pub use data::{Data, Payload};
+pub use data_frag::DataFrag;
+pub use gap::Gap;
+pub use heartbeat::Heartbeat;
+pub use heartbeat_frag::HeartbeatFrag;
+pub use info_destination::InfoDestination;
+pub use info_reply::InfoReply;
+pub use info_source::InfoSource;
+pub use info_timestamp::InfoTimestamp;
+pub use nack_frag::NackFrag;
+
+/// The size of a submessage header, in bytes
+const HEADER_SIZE: usize = 4;
 
 /// A component of a [`Message`](super::Message)
-#[derive(Debug)]
-pub struct SubMessage {
-    header: Header,
-    elements: Vec<Element>,
+///
+/// Submessages are either 'entity' submessages, which are targeted at a
+/// specific RTPS reader or writer, or 'interpreter' submessages, which modify
+/// the way in which subsequent entity submessages are interpreted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubMessage<P, Id> {
+    /// Contains information regarding the value of an application data-object.
+    /// Data Submessages are sent by Writers to Readers.
+    Data(Data<Id>),
+
+    /// Equivalent to Data, but only contains a part of the new value (one or
+    /// more fragments). Allows data to be transmitted as multiple fragments to
+    /// overcome transport message size limitations.
+    DataFrag(DataFrag<Id>),
+
+    /// Describes the information that is available in a Writer. Heartbeat
+    /// messages are sent by a Writer to one or more Readers.
+    Heartbeat(Heartbeat<Id>),
+
+    /// For fragmented data, describes what fragments are available in a
+    /// Writer. `HeartbeatFrag` messages are sent by a Writer to one or more
+    /// Readers.
+    HeartbeatFrag(HeartbeatFrag<Id>),
+
+    /// Describes the information that is no longer relevant to Readers. Gap
+    /// messages are sent by a Writer to one or more Readers.
+    Gap(Gap<Id>),
+
+    /// Provides information on the state of a Reader to a Writer. `AckNack`
+    /// messages are sent by a Reader to one or more Writers.
+    AckNack(AckNack<Id>),
+
+    /// Provides information on the state of a Reader to a Writer, more
+    /// specifically what fragments the Reader is still missing. `NackFrag`
+    /// messages are sent by a Reader to one or more Writers.
+    NackFrag(NackFrag<Id>),
+
+    /// Provides information about the source from which subsequent Entity
+    /// Submessages originated. This Submessage is primarily used for relaying
+    /// RTPS Submessages.
+    InfoSource(InfoSource<P>),
+
+    /// Provides information about the final destination of subsequent Entity
+    /// Submessages. This Submessage is primarily used for relaying RTPS
+    /// Submessages.
+    InfoDestination(InfoDestination<P>),
+
+    /// Provides information about where to reply to the entities that appear
+    /// in subsequent Submessages.
+    InfoReply(InfoReply),
+
+    /// Provides a source timestamp for subsequent Entity Submessages.
+    InfoTimestamp(InfoTimestamp),
+
+    /// Used to add padding to a Message if needed for memory alignment.
+    Pad,
 }
 
-mod submessage_kinds {
-    #[non_exhaustive]
-    pub enum Entity {
-        /// Contains information regarding the value of an application
-        /// data-object. Data Submessages are sent by Writers to
-        /// Readers.
-        Data,
-
-        /// Equivalent to Data, but only contains a part of the new value (one
-        /// or more fragments). Allows data to be transmitted as
-        /// multiple fragments to overcome transport message size
-        /// limitations.
-        DataFrag,
-
-        /// Describes the information that is available in a Writer. Heartbeat
-        /// messages are sent by a Writer to one or more Readers.
-        Heartbeat,
-
-        /// HeartbeatFrag: For fragmented data, describes what fragments are
-        /// available in a Writer. HeartbeatFrag messages are sent by a Writer
-        /// to one or more Readers.
-        HeartbeatFrag,
-
-        /// Describes the information that is no longer relevant to Readers. Gap
-        /// messages are sent by a Writer to one or more Readers.
-        Gap,
-
-        /// Provides information on the state of a Reader to a Writer. AckNack
-        /// messages are sent by a Reader to one or more Writers.
-        Acknack,
-
-        /// Provides information on the state of a Reader to a Writer, more
-        /// specifically what fragments the Reader is still missing. NackFrag
-        /// messages are sent by a Reader to one or more Writers.
-        NackFrag,
+impl<P, Id> SubMessage<P, Id> {
+    /// Returns true if this is an 'interpreter' submessage.
+    ///
+    /// Interpreter submessages modify the context in which subsequent 'entity'
+    /// submessages are interpreted.
+    #[must_use]
+    pub fn is_interpreter(&self) -> bool {
+        matches!(
+            self,
+            Self::InfoSource(_)
+                | Self::InfoDestination(_)
+                | Self::InfoReply(_)
+                | Self::InfoTimestamp(_)
+                | Self::Pad
+        )
+    }
+
+    /// The size of this submessage when serialized using the standard RTPS
+    /// wire representation, including its header.
+    ///
+    /// This assumes the standard 12-byte [`Guid`](crate::structure::Guid)
+    /// prefix and 4-byte entity ID.
+    #[must_use]
+    pub fn serialized_size(&self) -> usize {
+        let body = match self {
+            Self::Data(data) => {
+                20 + data
+                    .inline_qos
+                    .as_ref()
+                    .map_or(0, elements::ParameterList::serialized_size)
+                    + data
+                        .payload
+                        .as_ref()
+                        .map_or(0, |payload| padded(payload.as_bytes().len()))
+            }
+            Self::DataFrag(data_frag) => {
+                32 + data_frag
+                    .inline_qos
+                    .as_ref()
+                    .map_or(0, elements::ParameterList::serialized_size)
+                    + padded(data_frag.fragments.len())
+            }
+            Self::Heartbeat(_) => 28,
+            Self::HeartbeatFrag(_) => 24,
+            Self::Gap(gap) => 16 + gap.gap_list.serialized_size(),
+            Self::AckNack(ack_nack) => 12 + ack_nack.reader_sequence_number_state.serialized_size(),
+            Self::NackFrag(nack_frag) => 20 + nack_frag.fragment_number_state.serialized_size(),
+            Self::InfoSource(_) => 20,
+            Self::InfoDestination(_) => 12,
+            Self::InfoReply(info_reply) => {
+                4 + 24 * info_reply.unicast_locators.len()
+                    + info_reply
+                        .multicast_locators
+                        .as_ref()
+                        .map_or(0, |locators| 4 + 24 * locators.len())
+            }
+            Self::InfoTimestamp(info_timestamp) => {
+                if info_timestamp.timestamp.is_some() {
+                    8
+                } else {
+                    0
+                }
+            }
+            Self::Pad => 0,
+        };
+
+        HEADER_SIZE + body
     }
+}
 
-    pub enum Intepreter {
-        /// Provides additional information that logically belongs in the RTPS
-        /// Header. The additional information is included inside this
-        /// submessage, instead of the HTPS Header, in order to preserve
-        /// interoperability with earlier versions of the RTPS protocol. RTPS
-        /// version 2.4 and earlier version are not able to process the
-        /// HeaderExtension and will skip this submessage.
-        HeaderExtension,
-
-        /// Provides information about the source from which subsequent Entity
-        /// Submessages originated. This Submessage is primarily used for
-        /// relaying RTPS Submessages. This is not discussed in the
-        /// currentspecification.
-        InfoSource,
-
-        /// Provides information about the final destination ofsubsequent Entity
-        /// Submessages. This Submessage is primarily used for relaying RTPS
-        /// Submessages. This is not discussed in the current specification.
-        InfoDestination,
-
-        /// Provides information about where to reply to the entities that
-        /// appear in subsequent Submessages.
-        InfoReply,
-
-        /// Provides a source timestamp for subsequent Entity Submessages.
-        InfoTimestamp,
-
-        /// Used to add padding to a Message if needed for memory alignment.
-        Pad,
+macro_rules! impl_from_submessage {
+    ($($variant:ident<$param:ident>),* $(,)?) => {
+        $(
+            impl<P, Id> From<$variant<$param>> for SubMessage<P, Id> {
+                fn from(submessage: $variant<$param>) -> Self {
+                    Self::$variant(submessage)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_submessage!(
+    Data<Id>,
+    DataFrag<Id>,
+    Heartbeat<Id>,
+    HeartbeatFrag<Id>,
+    Gap<Id>,
+    AckNack<Id>,
+    NackFrag<Id>,
+    InfoSource<P>,
+    InfoDestination<P>,
+);
+
+impl<P, Id> From<InfoReply> for SubMessage<P, Id> {
+    fn from(submessage: InfoReply) -> Self {
+        Self::InfoReply(submessage)
     }
 }
 
-/// A header of a [`SubMessage`]
-#[derive(Debug)]
-pub struct Header {
-    endianess: ByteOrder,
-    length: usize,
+impl<P, Id> From<InfoTimestamp> for SubMessage<P, Id> {
+    fn from(submessage: InfoTimestamp) -> Self {
+        Self::InfoTimestamp(submessage)
+    }
 }
 
-/// An element of a [`SubMessage`]
-#[derive(Debug)]
-pub struct Element;
+/// Round a length up to the next 4-byte boundary
+pub(crate) fn padded(len: usize) -> usize {
+    (len + 3) & !3
+}
@@ -1,4 +1,4 @@
-use crate::messages::ByteOrder;
+use super::elements::SequenceNumberSet;
 
 /// This Submessage is used to communicate the state of a Reader to a Writer.
 /// The Submessage allows the Reader to inform the Writer about the sequence
@@ -6,17 +6,22 @@ use crate::messages::ByteOrder;
 /// can be used to do both positive and negative acknowledgments.
 ///
 /// see [specification pg. 56](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF#page=56)
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AckNack<Id> {
-    header: Header,
-    reader: Id,
-    writer: Id,
-    reader_sequence_number_state: SequenceNumberState,
-    count: u32,
-}
+    /// The reader entity that is sending the acknowledgement
+    pub reader: Id,
 
-pub struct Header {
-    endianess: ByteOrder,
-    response_required: bool,
-}
+    /// The writer entity that is the target of the acknowledgement
+    pub writer: Id,
+
+    /// Every sequence number below the base of this set is acknowledged, while
+    /// every sequence number in the set is negatively acknowledged
+    pub reader_sequence_number_state: SequenceNumberSet,
 
-pub struct SequenceNumberState;
+    /// Incremented each time a new [`AckNack`] is sent, so that duplicates can
+    /// be detected by the writer
+    pub count: u32,
+
+    /// If `true`, the reader does not require the writer to respond
+    pub final_flag: bool,
+}
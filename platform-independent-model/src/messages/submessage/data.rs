@@ -1,21 +1,51 @@
-use super::Header;
+use super::elements::ParameterList;
 
+/// This Submessage is sent from an RTPS Writer to an RTPS Reader to
+/// communicate a change to a data-object belonging to the RTPS Writer.
+///
+/// see [specification section 8.3.8.2](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Data<Id> {
-    header: Header,
-    payload: Payload,
-    non_standard_payload: bool,
-    reader: Id,
-    writer: Id,
-    writer_sequence_number: i64,
-    inline_qos: Vec<Param>,
-    fragment_starting_number: u32,
-    fragments: u32,
-    data_size: u64,
+    /// The reader entity being informed of the change.
+    ///
+    /// `None` indicates that the change is addressed to all readers
+    /// (`ENTITYID_UNKNOWN`)
+    pub reader: Option<Id>,
+
+    /// The writer entity that made the change
+    pub writer: Id,
+
+    /// The sequence number assigned to the change by the writer
+    pub writer_sequence_number: u64,
+
+    /// Quality of service parameters that may affect the interpretation of
+    /// the message
+    pub inline_qos: Option<ParameterList>,
+
+    /// The serialized value of the data-object, or of its key
+    pub payload: Option<Payload>,
+
+    /// If `true`, the serialized payload is not formatted according to the
+    /// standard encapsulation
+    pub non_standard_payload: bool,
 }
 
-enum Payload {
+/// The serialized content of a [`Data`] submessage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Payload {
+    /// The serialized value of the data-object
     Data(Vec<u8>),
+
+    /// The serialized value of the key which identifies the data-object
     Key(Vec<u8>),
 }
 
-pub struct Param;
+impl Payload {
+    /// The raw serialized bytes of the payload
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Data(bytes) | Self::Key(bytes) => bytes,
+        }
+    }
+}
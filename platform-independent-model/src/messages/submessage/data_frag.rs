@@ -1,4 +1,5 @@
-use crate::messages::ByteOrder;
+use super::elements::ParameterList;
+use std::{convert::TryFrom, num::NonZeroU32};
 
 /// The [`DataFrag`] Submessage extends the Data Submessage by enabling the
 /// serializedData to be fragmented and sent as multiple [`DataFrag`]
@@ -6,19 +7,65 @@ use crate::messages::ByteOrder;
 /// then re-assembled by the `RTPSReader`.
 ///
 /// see [specification pg. 59](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF#page=59)
-pub struct Data<Id> {
-    endianess: ByteOrder,
-    inline_qos: Vec<Param>,
-    non_standard_payload: bool,
-    payload: Payload,
-    reader: Id,
-    writer: Id,
-    writer_sequence_number: i64,
-}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataFrag<Id> {
+    /// The reader entity being informed of the change.
+    ///
+    /// `None` indicates that the change is addressed to all readers
+    /// (`ENTITYID_UNKNOWN`)
+    pub reader: Option<Id>,
+
+    /// The writer entity that made the change
+    pub writer: Id,
+
+    /// The sequence number assigned to the change by the writer
+    pub writer_sequence_number: u64,
+
+    /// The number of the first fragment contained in this submessage
+    ///
+    /// Fragments are numbered from 1
+    pub fragment_starting_number: NonZeroU32,
+
+    /// The size of each fragment, in bytes
+    ///
+    /// Every fragment except the last is exactly this size
+    pub fragment_size: u16,
+
+    /// The total size of the serialized payload, in bytes
+    pub sample_size: u32,
 
-enum Payload {
-    Data(Vec<u8>),
-    Key(Vec<u8>),
+    /// Quality of service parameters that may affect the interpretation of
+    /// the message
+    pub inline_qos: Option<ParameterList>,
+
+    /// If `true`, the fragments contain the serialized key rather than the
+    /// serialized value of the data-object
+    pub key: bool,
+
+    /// If `true`, the serialized payload is not formatted according to the
+    /// standard encapsulation
+    pub non_standard_payload: bool,
+
+    /// The consecutive fragments contained in this submessage
+    pub fragments: Vec<u8>,
 }
 
-pub struct Param;
+impl<Id> DataFrag<Id> {
+    /// The number of fragments contained in this submessage
+    #[must_use]
+    pub fn fragments_in_submessage(&self) -> u32 {
+        let fragment_size = usize::from(self.fragment_size.max(1));
+        let n = (self.fragments.len() + fragment_size - 1) / fragment_size;
+        u32::try_from(n).unwrap_or(u32::MAX)
+    }
+
+    /// The total number of fragments which make up the sample
+    #[must_use]
+    pub fn total_fragments(&self) -> u32 {
+        // the sample size comes from the network, so may be close to the
+        // largest u32
+        let fragment_size = u64::from(self.fragment_size.max(1));
+        let n = (u64::from(self.sample_size) + fragment_size - 1) / fragment_size;
+        u32::try_from(n).unwrap_or(u32::MAX)
+    }
+}
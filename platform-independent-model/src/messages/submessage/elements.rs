@@ -1,6 +1,11 @@
 //! Component elements of a [`SubMessage`](super::SubMessage)
 
-use std::{collections::BTreeSet, convert::TryInto, num::NonZeroU64};
+use std::{
+    collections::BTreeSet,
+    convert::TryInto,
+    iter::FromIterator,
+    num::{NonZeroU32, NonZeroU64},
+};
 
 /// [`SequenceNumberSet`] submessage elements are used as parts of several
 /// messages to provide binary information about individual sequence numbers
@@ -93,6 +98,20 @@ impl SequenceNumberSet {
     pub fn max_offset(&self) -> u8 {
         self.offsets.iter().next_back().copied().unwrap_or_default()
     }
+
+    /// The size of the set when serialized as a bitmap, in bytes
+    pub(crate) fn serialized_size(&self) -> usize {
+        12 + bitmap_size(self.offsets.len(), self.max_offset())
+    }
+}
+
+/// The size of a bitmap large enough to hold every offset, in bytes
+fn bitmap_size(len: usize, max_offset: u8) -> usize {
+    if len == 0 {
+        0
+    } else {
+        (usize::from(max_offset) / 32 + 1) * 4
+    }
 }
 
 /// Errors that can occur when inserting a new value into a
@@ -114,6 +133,340 @@ pub enum OutOfBoundsError {
     OffsetTooLarge,
 }
 
+/// [`FragmentNumberSet`] submessage elements are used to provide binary
+/// information about individual fragment numbers within a range.
+///
+/// Like the [`SequenceNumberSet`], the fragment numbers are limited to belong
+/// to an interval with a range no bigger than 256.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FragmentNumberSet {
+    base: NonZeroU32,
+    offsets: BTreeSet<u8>,
+}
+
+impl FragmentNumberSet {
+    /// Create a new [`FragmentNumberSet`]
+    ///
+    /// The 'base' of the set is a lower bound for all values in the set. All
+    /// values in the range are calculated as offsets from the base.
+    #[must_use]
+    pub fn new(base: NonZeroU32) -> Self {
+        let offsets = BTreeSet::default();
+        Self { base, offsets }
+    }
+
+    /// Returns the 'base' of the set
+    #[must_use]
+    pub fn base(&self) -> u32 {
+        self.base.get()
+    }
+
+    /// Return an iterator over the offsets in this set
+    pub fn offsets(&self) -> impl Iterator<Item = u8> + '_ {
+        self.offsets.iter().copied()
+    }
+
+    /// Return an iterator over the values in this set
+    pub fn values(&self) -> impl Iterator<Item = u32> + '_ {
+        self.offsets()
+            .map(move |offset| self.base() + u32::from(offset))
+    }
+
+    /// Inserts a new offset into the set.
+    ///
+    /// `true` is returned if the set did not already contain the value.
+    pub fn insert_offset(&mut self, offset: u8) -> bool {
+        self.offsets.insert(offset)
+    }
+
+    /// Attempt to insert a new value into the set
+    ///
+    /// # Errors
+    ///
+    /// - this method will fail if the provided value is smaller than the 'base'
+    ///   of the set
+    pub fn insert_value(&mut self, value: u32) -> Result<bool, OutOfBoundsError> {
+        if value < self.base() {
+            return Err(OutOfBoundsError::LessThanBase);
+        }
+
+        let offset = (value - self.base())
+            .try_into()
+            .map_err(|_| OutOfBoundsError::OffsetTooLarge)?;
+
+        Ok(self.insert_offset(offset))
+    }
+
+    /// Returns true if the value is contained in the set, or false otherwise
+    #[must_use]
+    pub fn contains(&self, value: u32) -> bool {
+        if value < self.base() {
+            return false;
+        }
+
+        if let Ok(offset) = (value - self.base()).try_into() {
+            self.offsets.contains(&offset)
+        } else {
+            false
+        }
+    }
+
+    /// Return the largest offset in the set. Returns 0 if the set is empty
+    #[must_use]
+    pub fn max_offset(&self) -> u8 {
+        self.offsets.iter().next_back().copied().unwrap_or_default()
+    }
+
+    /// The size of the set when serialized as a bitmap, in bytes
+    pub(crate) fn serialized_size(&self) -> usize {
+        8 + bitmap_size(self.offsets.len(), self.max_offset())
+    }
+}
+
+/// Well-known [`Parameter`] IDs
+///
+/// See [specification section 9.6.4](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+pub mod parameter_id {
+    /// Used to pad the list. The value should be ignored.
+    pub const PAD: u16 = 0x0000;
+
+    /// Marks the end of the list
+    pub const SENTINEL: u16 = 0x0001;
+
+    /// How long a participant should be considered alive after each
+    /// announcement
+    pub const PARTICIPANT_LEASE_DURATION: u16 = 0x0002;
+
+    /// The `TIME_BASED_FILTER` quality of service policy of a reader
+    pub const TIME_BASED_FILTER: u16 = 0x0004;
+
+    /// The name of the topic of an endpoint
+    pub const TOPIC_NAME: u16 = 0x0005;
+
+    /// The `OWNERSHIP_STRENGTH` quality of service policy of a writer
+    pub const OWNERSHIP_STRENGTH: u16 = 0x0006;
+
+    /// The name of the data type of an endpoint
+    pub const TYPE_NAME: u16 = 0x0007;
+
+    /// The ID of the domain to which a participant belongs
+    pub const DOMAIN_ID: u16 = 0x000f;
+
+    /// The protocol version of a participant
+    pub const PROTOCOL_VERSION: u16 = 0x0015;
+
+    /// The vendor ID of a participant
+    pub const VENDOR_ID: u16 = 0x0016;
+
+    /// The `RELIABILITY` quality of service policy of an endpoint
+    pub const RELIABILITY: u16 = 0x001a;
+
+    /// The `LIVELINESS` quality of service policy of an endpoint
+    pub const LIVELINESS: u16 = 0x001b;
+
+    /// The `DURABILITY` quality of service policy of an endpoint
+    pub const DURABILITY: u16 = 0x001d;
+
+    /// The `OWNERSHIP` quality of service policy of an endpoint
+    pub const OWNERSHIP: u16 = 0x001f;
+
+    /// The `PRESENTATION` quality of service policy of an endpoint
+    pub const PRESENTATION: u16 = 0x0021;
+
+    /// The `DEADLINE` quality of service policy of an endpoint
+    pub const DEADLINE: u16 = 0x0023;
+
+    /// The `DESTINATION_ORDER` quality of service policy of an endpoint
+    pub const DESTINATION_ORDER: u16 = 0x0025;
+
+    /// The `LATENCY_BUDGET` quality of service policy of an endpoint
+    pub const LATENCY_BUDGET: u16 = 0x0027;
+
+    /// The `PARTITION` quality of service policy of an endpoint
+    pub const PARTITION: u16 = 0x0029;
+
+    /// The `LIFESPAN` quality of service policy of a writer
+    pub const LIFESPAN: u16 = 0x002b;
+
+    /// The `USER_DATA` quality of service policy of an endpoint
+    pub const USER_DATA: u16 = 0x002c;
+
+    /// The `GROUP_DATA` quality of service policy of an endpoint
+    pub const GROUP_DATA: u16 = 0x002d;
+
+    /// The `TOPIC_DATA` quality of service policy of an endpoint
+    pub const TOPIC_DATA: u16 = 0x002e;
+
+    /// A unicast [`Locator`](crate::structure::Locator) of an endpoint
+    pub const UNICAST_LOCATOR: u16 = 0x002f;
+
+    /// A multicast [`Locator`](crate::structure::Locator) of an endpoint
+    pub const MULTICAST_LOCATOR: u16 = 0x0030;
+
+    /// A unicast [`Locator`](crate::structure::Locator) for user traffic
+    pub const DEFAULT_UNICAST_LOCATOR: u16 = 0x0031;
+
+    /// A unicast [`Locator`](crate::structure::Locator) for discovery traffic
+    pub const METATRAFFIC_UNICAST_LOCATOR: u16 = 0x0032;
+
+    /// A multicast [`Locator`](crate::structure::Locator) for discovery
+    /// traffic
+    pub const METATRAFFIC_MULTICAST_LOCATOR: u16 = 0x0033;
+
+    /// The number of times a participant has manually asserted its
+    /// liveliness
+    pub const PARTICIPANT_MANUAL_LIVELINESS_COUNT: u16 = 0x0034;
+
+    /// The `HISTORY` quality of service policy of an endpoint
+    pub const HISTORY: u16 = 0x0040;
+
+    /// The `RESOURCE_LIMITS` quality of service policy of an endpoint
+    pub const RESOURCE_LIMITS: u16 = 0x0041;
+
+    /// Whether the readers of a participant expect inline quality of service
+    /// parameters
+    pub const EXPECTS_INLINE_QOS: u16 = 0x0043;
+
+    /// A multicast [`Locator`](crate::structure::Locator) for user traffic
+    pub const DEFAULT_MULTICAST_LOCATOR: u16 = 0x0048;
+
+    /// The [`Guid`](crate::structure::Guid) of a participant
+    pub const PARTICIPANT_GUID: u16 = 0x0050;
+
+    /// The [`BuiltinEndpointSet`](crate::structure::BuiltinEndpointSet) of a
+    /// participant
+    pub const BUILTIN_ENDPOINT_SET: u16 = 0x0058;
+
+    /// The [`Guid`](crate::structure::Guid) of an endpoint
+    pub const ENDPOINT_GUID: u16 = 0x005a;
+
+    /// The [`KeyHash`](crate::structure::KeyHash) of the instance to which a
+    /// change applies
+    pub const KEY_HASH: u16 = 0x0070;
+
+    /// Flags describing the lifecycle of the instance to which a change
+    /// applies. See [`status_info`](super::status_info).
+    pub const STATUS_INFO: u16 = 0x0071;
+
+    /// The tag which, along with the domain ID, separates participants into
+    /// independent networks
+    pub const DOMAIN_TAG: u16 = 0x4014;
+}
+
+/// Flags carried in the last byte of the [`parameter_id::STATUS_INFO`]
+/// parameter
+pub mod status_info {
+    /// The instance has been disposed by the writer
+    pub const DISPOSED: u8 = 0x01;
+
+    /// The instance has been unregistered by the writer
+    pub const UNREGISTERED: u8 = 0x02;
+
+    /// The change was filtered out for the reader
+    pub const FILTERED: u8 = 0x04;
+}
+
+/// A single entry in a [`ParameterList`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Parameter {
+    id: u16,
+    value: Vec<u8>,
+}
+
+impl Parameter {
+    /// Create a new [`Parameter`] from its ID and serialized value
+    #[must_use]
+    pub fn new(id: u16, value: Vec<u8>) -> Self {
+        Self { id, value }
+    }
+
+    /// The ID of the parameter, which determines how the value is interpreted
+    #[must_use]
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// The serialized value of the parameter
+    #[must_use]
+    pub fn value(&self) -> &[u8] {
+        &self.value
+    }
+}
+
+/// [`ParameterList`] submessage elements are used to encapsulate a list of
+/// parameters, such as the 'inline' quality of service parameters of a
+/// [`Data`](super::Data) submessage.
+///
+/// The list is extensible. Receivers should ignore any parameters that they
+/// do not recognise.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParameterList {
+    parameters: Vec<Parameter>,
+}
+
+impl ParameterList {
+    /// Append a [`Parameter`] to the end of the list
+    pub fn push(&mut self, parameter: Parameter) {
+        self.parameters.push(parameter);
+    }
+
+    /// Return the first [`Parameter`] with the given ID, if present
+    #[must_use]
+    pub fn get(&self, id: u16) -> Option<&Parameter> {
+        self.iter().find(|parameter| parameter.id() == id)
+    }
+
+    /// Return an iterator over every [`Parameter`] with the given ID
+    ///
+    /// Some parameters (such as locators) may legitimately appear several times
+    /// in a single list.
+    pub fn get_all(&self, id: u16) -> impl Iterator<Item = &Parameter> + '_ {
+        self.iter().filter(move |parameter| parameter.id() == id)
+    }
+
+    /// Return an iterator over the parameters in the list
+    pub fn iter(&self) -> impl Iterator<Item = &Parameter> + '_ {
+        self.parameters.iter()
+    }
+
+    /// Returns the number of parameters in the list
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.parameters.len()
+    }
+
+    /// Returns `true` if the list contains no parameters
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.parameters.is_empty()
+    }
+
+    /// The size of the list when serialized, including the terminating
+    /// sentinel, in bytes
+    pub(crate) fn serialized_size(&self) -> usize {
+        self.iter()
+            .map(|parameter| 4 + super::padded(parameter.value().len()))
+            .sum::<usize>()
+            + 4
+    }
+}
+
+impl FromIterator<Parameter> for ParameterList {
+    fn from_iter<T: IntoIterator<Item = Parameter>>(iter: T) -> Self {
+        let parameters = iter.into_iter().collect();
+        Self { parameters }
+    }
+}
+
+impl IntoIterator for ParameterList {
+    type Item = Parameter;
+    type IntoIter = std::vec::IntoIter<Parameter>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.parameters.into_iter()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{OutOfBoundsError, SequenceNumberSet};
@@ -0,0 +1,33 @@
+use super::elements::SequenceNumberSet;
+
+/// This Submessage is sent from an RTPS Writer to an RTPS Reader and indicates
+/// to the RTPS Reader that a range of sequence numbers is no longer relevant.
+///
+/// see [specification section 8.3.8.4](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gap<Id> {
+    /// The reader entity that is the target of the [`Gap`].
+    ///
+    /// `None` indicates that the message is addressed to all readers
+    /// (`ENTITYID_UNKNOWN`)
+    pub reader: Option<Id>,
+
+    /// The writer entity whose changes are no longer relevant
+    pub writer: Id,
+
+    /// The first sequence number in the contiguous range of irrelevant
+    /// sequence numbers
+    pub gap_start: u64,
+
+    /// The base of this set marks the end of the contiguous range which
+    /// starts at `gap_start`. Every sequence number in the set is also
+    /// irrelevant
+    pub gap_list: SequenceNumberSet,
+}
+
+impl<Id> Gap<Id> {
+    /// Return an iterator over every irrelevant sequence number
+    pub fn sequence_numbers(&self) -> impl Iterator<Item = u64> + '_ {
+        (self.gap_start..self.gap_list.base()).chain(self.gap_list.values())
+    }
+}
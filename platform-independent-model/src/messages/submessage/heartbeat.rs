@@ -0,0 +1,32 @@
+/// This Submessage is sent from an RTPS Writer to an RTPS Reader to
+/// communicate the sequence numbers of changes that the Writer has available.
+///
+/// see [specification section 8.3.8.5](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heartbeat<Id> {
+    /// The reader entity that is the target of the [`Heartbeat`].
+    ///
+    /// `None` indicates that the message is addressed to all readers
+    /// (`ENTITYID_UNKNOWN`)
+    pub reader: Option<Id>,
+
+    /// The writer entity that is sending the [`Heartbeat`]
+    pub writer: Id,
+
+    /// The first (lowest) sequence number available in the writer
+    pub first_sequence_number: u64,
+
+    /// The last (highest) sequence number available in the writer
+    pub last_sequence_number: u64,
+
+    /// Incremented each time a new [`Heartbeat`] is sent, so that duplicates
+    /// can be detected by the reader
+    pub count: u32,
+
+    /// If `true`, the reader is not required to respond
+    pub final_flag: bool,
+
+    /// If `true`, the [`Heartbeat`] is also used to assert the liveliness of
+    /// the writer
+    pub liveliness_flag: bool,
+}
@@ -0,0 +1,25 @@
+/// This Submessage is sent from an RTPS Writer to an RTPS Reader to
+/// communicate which fragments of a change the Writer has available.
+///
+/// see [specification section 8.3.8.6](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeartbeatFrag<Id> {
+    /// The reader entity that is the target of the [`HeartbeatFrag`].
+    ///
+    /// `None` indicates that the message is addressed to all readers
+    /// (`ENTITYID_UNKNOWN`)
+    pub reader: Option<Id>,
+
+    /// The writer entity that is sending the [`HeartbeatFrag`]
+    pub writer: Id,
+
+    /// The sequence number of the fragmented change
+    pub writer_sequence_number: u64,
+
+    /// All fragments up to and including this fragment number are available
+    pub last_fragment_number: u32,
+
+    /// Incremented each time a new [`HeartbeatFrag`] is sent, so that
+    /// duplicates can be detected by the reader
+    pub count: u32,
+}
@@ -0,0 +1,14 @@
+/// This Submessage is sent from an RTPS Writer to an RTPS Reader to modify the
+/// [`Guid`](crate::structure::Guid) prefix used to interpret the entity IDs of
+/// subsequent submessages.
+///
+/// see [specification section 8.3.8.7](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InfoDestination<P> {
+    /// The prefix of the participant which subsequent submessages are
+    /// addressed to.
+    ///
+    /// `None` indicates that subsequent submessages are addressed to every
+    /// participant (`GUIDPREFIX_UNKNOWN`)
+    pub guid_prefix: Option<P>,
+}
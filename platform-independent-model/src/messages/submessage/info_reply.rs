@@ -0,0 +1,17 @@
+use crate::structure::Locator;
+
+/// This Submessage is sent from an RTPS Reader to an RTPS Writer. It contains
+/// explicit information on where to send a reply to the submessages that follow
+/// it within the same message.
+///
+/// see [specification section 8.3.8.8](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InfoReply {
+    /// The unicast [`Locator`]s to which replies should be sent
+    pub unicast_locators: Vec<Locator>,
+
+    /// The multicast [`Locator`]s to which replies may be sent.
+    ///
+    /// `None` indicates that the multicast reply locators are unchanged
+    pub multicast_locators: Option<Vec<Locator>>,
+}
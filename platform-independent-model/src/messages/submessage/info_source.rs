@@ -0,0 +1,16 @@
+use crate::structure::{ProtocolVersion, VendorId};
+
+/// This Submessage modifies the logical source of the Submessages that follow.
+///
+/// see [specification section 8.3.8.9](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InfoSource<P> {
+    /// The protocol version used by the source participant
+    pub protocol_version: ProtocolVersion,
+
+    /// The vendor ID of the source participant
+    pub vendor_id: VendorId,
+
+    /// The [`Guid`](crate::structure::Guid) prefix of the source participant
+    pub guid_prefix: P,
+}
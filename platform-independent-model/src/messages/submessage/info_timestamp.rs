@@ -0,0 +1,13 @@
+use chrono::{DateTime, Utc};
+
+/// This Submessage is used to send a timestamp which applies to the
+/// Submessages that follow within the same message.
+///
+/// see [specification section 8.3.8.10](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InfoTimestamp {
+    /// The timestamp which should be used to interpret subsequent submessages.
+    ///
+    /// `None` invalidates any previously received timestamp
+    pub timestamp: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,24 @@
+use super::elements::FragmentNumberSet;
+
+/// This Submessage is sent from an RTPS Reader to an RTPS Writer to request
+/// that the Writer resend some of the fragments of a change.
+///
+/// see [specification section 8.3.8.11](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NackFrag<Id> {
+    /// The reader entity that is requesting the fragments
+    pub reader: Id,
+
+    /// The writer entity that is the target of the request
+    pub writer: Id,
+
+    /// The sequence number of the fragmented change
+    pub writer_sequence_number: u64,
+
+    /// The fragments which the reader is missing
+    pub fragment_number_state: FragmentNumberSet,
+
+    /// Incremented each time a new [`NackFrag`] is sent, so that duplicates
+    /// can be detected by the writer
+    pub count: u32,
+}
@@ -3,23 +3,29 @@
 //!
 //! See [section 8.2](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF#page=21) of the relevant specification document.
 
-mod endpoint;
+pub mod endpoint;
 mod entity;
 pub mod group;
 mod guid;
 pub mod history;
+mod key;
 mod locator;
 pub mod participant;
+mod participant_proxy;
 mod protocol_version;
+pub mod qos;
 mod vendor_id;
 
-pub use endpoint::Endpoint;
-pub use entity::Entity;
+#[doc(inline)]
+pub use endpoint::{Endpoint, TopicKind};
+pub use entity::{entity_kind, Entity};
 #[doc(inline)]
 pub use group::{Group, Publisher, Subscriber};
 pub use guid::Guid;
+pub use key::{InstanceHandle, KeyField, KeyHash, Keyed};
 pub use locator::Locator;
 #[doc(inline)]
 pub use participant::Participant;
+pub use participant_proxy::{BuiltinEndpointSet, ParticipantProxy, DEFAULT_LEASE_DURATION};
 pub use protocol_version::ProtocolVersion;
 pub use vendor_id::VendorId;
@@ -1,5 +1,19 @@
+//! Contains the [`Endpoint`] and its [`Builder`]
+
 use super::{entity::Entity, guid::Guid, locator::Locator};
 
+/// Whether the topic of an [`Endpoint`] distinguishes instances by a key
+///
+/// See [section 8.2.4.2](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF) of the relevant specification document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopicKind {
+    /// The topic has no key, and so has a single instance
+    NoKey,
+
+    /// Each instance of the topic is identified by a key
+    WithKey,
+}
+
 /// An [`Endpoint`] may be a subscriber or a publisher of 'changes'
 #[derive(Debug)]
 pub struct Endpoint<P, Id>
@@ -8,15 +22,134 @@ where
     Id: Copy,
 {
     guid: Guid<P, Id>,
+    topic_kind: TopicKind,
+    unicast_locators: Vec<Locator>,
+    multicast_locators: Vec<Locator>,
+}
+
+/// A builder for an [`Endpoint`]
+///
+/// See the [`Endpoint`] docs for details
+#[derive(Debug)]
+#[must_use]
+pub struct Builder<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    guid: Guid<P, Id>,
+    topic_kind: TopicKind,
     unicast_locators: Vec<Locator>,
     multicast_locators: Vec<Locator>,
 }
 
+impl<P, Id> Builder<P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    pub(crate) fn new(guid: Guid<P, Id>, topic_kind: TopicKind) -> Self {
+        Self {
+            guid,
+            topic_kind,
+            unicast_locators: Vec::default(),
+            multicast_locators: Vec::default(),
+        }
+    }
+
+    /// Set the unicast [`Locator`]s of the endpoint
+    ///
+    /// When created by a [`Group`](super::Group), these default to the
+    /// default unicast locators of the [`Participant`](super::Participant).
+    pub fn unicast_locators<I, L>(mut self, locators: I) -> Self
+    where
+        I: IntoIterator<Item = L>,
+        L: Into<Locator>,
+    {
+        self.unicast_locators = locators.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the multicast [`Locator`]s of the endpoint
+    ///
+    /// When created by a [`Group`](super::Group), these default to the
+    /// default multicast locators of the [`Participant`](super::Participant).
+    pub fn multicast_locators<I, L>(mut self, locators: I) -> Self
+    where
+        I: IntoIterator<Item = L>,
+        L: Into<Locator>,
+    {
+        self.multicast_locators = locators.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Consume the [`Builder`] and return a configured [`Endpoint`]
+    #[must_use]
+    pub fn build(self) -> Endpoint<P, Id> {
+        Endpoint {
+            guid: self.guid,
+            topic_kind: self.topic_kind,
+            unicast_locators: self.unicast_locators,
+            multicast_locators: self.multicast_locators,
+        }
+    }
+}
+
 impl<P, Id> Endpoint<P, Id>
 where
     P: Copy,
     Id: Copy,
 {
+    /// Construct a new [`Endpoint`] without any [`Locator`]s
+    ///
+    /// Endpoints are usually created by the
+    /// [`Publisher`](super::Publisher)s and [`Subscriber`](super::Subscriber)s
+    /// of a [`Participant`](super::Participant), which allocate their entity
+    /// IDs.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtps_pim::structure::{Endpoint, Entity, Guid, TopicKind};
+    ///
+    /// let guid = Guid::new([0; 12], [0, 0, 1, 0x02]);
+    ///
+    /// let endpoint = Endpoint::new(guid, TopicKind::WithKey);
+    ///
+    /// assert_eq!(endpoint.guid(), guid);
+    /// ```
+    #[must_use]
+    pub fn new(guid: Guid<P, Id>, topic_kind: TopicKind) -> Self {
+        Builder::new(guid, topic_kind).build()
+    }
+
+    /// Construct a new [`Endpoint`] with additional options
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtps_pim::structure::{Endpoint, Guid, TopicKind};
+    /// use std::net::{Ipv4Addr, SocketAddrV4};
+    ///
+    /// let guid = Guid::new([0; 12], [0, 0, 1, 0x04]);
+    /// let unicast_locators = vec![SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 7411)];
+    ///
+    /// let endpoint = Endpoint::builder(guid, TopicKind::NoKey)
+    ///     .unicast_locators(unicast_locators)
+    ///     .build();
+    ///
+    /// assert_eq!(endpoint.unicast_locators().len(), 1);
+    /// ```
+    pub fn builder(guid: Guid<P, Id>, topic_kind: TopicKind) -> Builder<P, Id> {
+        Builder::new(guid, topic_kind)
+    }
+
+    /// Whether the topic of this endpoint is keyed
+    #[must_use]
+    pub fn topic_kind(&self) -> TopicKind {
+        self.topic_kind
+    }
+
     /// Returns this endpoint's unicast [`Locator`]s
     #[must_use]
     pub fn unicast_locators(&self) -> &Vec<Locator> {
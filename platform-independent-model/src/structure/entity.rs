@@ -1,3 +1,8 @@
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
 use super::Guid;
 
 /// Trait implemented by every actor in the protocol 'structure'
@@ -24,3 +29,81 @@ where
         self.guid().entity_id()
     }
 }
+
+/// The kinds of user-defined entity, carried in the last byte of the entity ID
+///
+/// See [section 9.3.1.2](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF) of the relevant specification document.
+pub mod entity_kind {
+    /// A writer of a keyed topic
+    pub const USER_WRITER_WITH_KEY: u8 = 0x02;
+
+    /// A writer of a topic without a key
+    pub const USER_WRITER_NO_KEY: u8 = 0x03;
+
+    /// A reader of a topic without a key
+    pub const USER_READER_NO_KEY: u8 = 0x04;
+
+    /// A reader of a keyed topic
+    pub const USER_READER_WITH_KEY: u8 = 0x07;
+
+    /// A group of writers ([`Publisher`](crate::structure::Publisher))
+    pub const USER_WRITER_GROUP: u8 = 0x08;
+
+    /// A group of readers ([`Subscriber`](crate::structure::Subscriber))
+    pub const USER_READER_GROUP: u8 = 0x09;
+}
+
+/// The largest key of a user-defined entity ID, which fills its first three
+/// bytes
+const MAX_ENTITY_KEY: u32 = 0x00ff_ffff;
+
+/// Allocates the entity IDs of the user-defined entities of a
+/// [`Participant`](super::Participant)
+///
+/// The allocator is shared between the participant and its groups, so that
+/// each entity ID is unique within the participant.
+#[derive(Debug, Clone)]
+pub(crate) struct EntityIds(Arc<AtomicU32>);
+
+impl Default for EntityIds {
+    fn default() -> Self {
+        Self(Arc::new(AtomicU32::new(1)))
+    }
+}
+
+impl EntityIds {
+    /// Allocate a new entity ID of the given [`entity_kind`]
+    ///
+    /// The first three bytes of the entity ID are a key which is unique within
+    /// the participant. Returns `None` once every key has been allocated.
+    pub(crate) fn next<Id>(&self, kind: u8) -> Option<Id>
+    where
+        Id: From<[u8; 4]>,
+    {
+        let key = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |key| {
+                (key <= MAX_ENTITY_KEY).then(|| key + 1)
+            })
+            .ok()?;
+        let [_, a, b, c] = key.to_be_bytes();
+        Some(Id::from([a, b, c, kind]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicU32, Arc};
+
+    use super::{entity_kind, EntityIds, MAX_ENTITY_KEY};
+
+    #[test]
+    fn entity_ids_are_exhausted() {
+        let entity_ids = EntityIds(Arc::new(AtomicU32::new(MAX_ENTITY_KEY)));
+        let kind = entity_kind::USER_WRITER_GROUP;
+
+        assert_eq!(entity_ids.next(kind), Some([0xff, 0xff, 0xff, kind]));
+        assert_eq!(entity_ids.next::<[u8; 4]>(kind), None);
+        assert_eq!(entity_ids.next::<[u8; 4]>(kind), None);
+    }
+}
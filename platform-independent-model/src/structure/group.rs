@@ -1,11 +1,20 @@
-//! Types associated with [`Group`]s of [`Endpoint`](super::Endpoint)s
+//! Types associated with [`Group`]s of [`Endpoint`]s
 
-use super::{entity::Entity, guid::Guid};
+use super::{
+    endpoint::{self, Endpoint, TopicKind},
+    entity::{entity_kind, Entity, EntityIds},
+    guid::Guid,
+    locator::Locator,
+};
 
-/// A [`Group`] represents a collection of [`Endpoint`](super::Endpoint)s
+/// A [`Group`] represents a collection of [`Endpoint`]s
 ///
 /// A [`Group`] of 'writers' is a [`Publisher`], while a [`Group`] of 'readers'
 /// is a [`Subscriber`].
+///
+/// Groups are created by a [`Participant`](super::Participant). The entity IDs
+/// of their endpoints are allocated by the participant, and the endpoints use
+/// the default [`Locator`]s of the participant unless others are given.
 #[derive(Debug)]
 pub struct Group<T, P, Id>
 where
@@ -13,6 +22,9 @@ where
     Id: Copy,
 {
     guid: Guid<P, Id>,
+    entity_ids: EntityIds,
+    default_unicast_locators: Vec<Locator>,
+    default_multicast_locators: Vec<Locator>,
     marker: std::marker::PhantomData<T>,
 }
 
@@ -31,10 +43,97 @@ where
     P: Copy,
     Id: Copy,
 {
-    pub(crate) fn new(guid_prefix: P, entity_id: Id) -> Self {
-        let guid = Guid::new(guid_prefix, entity_id);
+    pub(crate) fn new(
+        guid: Guid<P, Id>,
+        entity_ids: EntityIds,
+        default_unicast_locators: Vec<Locator>,
+        default_multicast_locators: Vec<Locator>,
+    ) -> Self {
         let marker = std::marker::PhantomData;
-        Self { guid, marker }
+        Self {
+            guid,
+            entity_ids,
+            default_unicast_locators,
+            default_multicast_locators,
+            marker,
+        }
+    }
+
+    /// A builder for an [`Endpoint`] of this group, with a newly allocated
+    /// entity ID of the given [`entity_kind`]
+    fn endpoint(&self, kind: u8, topic_kind: TopicKind) -> Option<endpoint::Builder<P, Id>>
+    where
+        Id: From<[u8; 4]>,
+    {
+        let guid = Guid::new(self.guid.prefix(), self.entity_ids.next(kind)?);
+        let builder = Endpoint::builder(guid, topic_kind)
+            .unicast_locators(self.default_unicast_locators.iter().copied())
+            .multicast_locators(self.default_multicast_locators.iter().copied());
+        Some(builder)
+    }
+}
+
+impl<P, Id> Publisher<P, Id>
+where
+    P: Copy,
+    Id: Copy + From<[u8; 4]>,
+{
+    /// Construct a new writer [`Endpoint`] in this [`Publisher`]
+    ///
+    /// The writer is allocated a new entity ID, and uses the default
+    /// [`Locator`]s of the participant unless others are given. Returns `None`
+    /// if the participant has run out of entity IDs.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtps_pim::structure::{Entity, Participant, TopicKind};
+    ///
+    /// let participant = Participant::new([0; 12], [0, 0, 1, 0xc1]);
+    /// let publisher = participant.publisher().unwrap();
+    ///
+    /// let writer = publisher.writer(TopicKind::WithKey).unwrap().build();
+    ///
+    /// assert_eq!(writer.entity_id()[3], 0x02);
+    /// ```
+    pub fn writer(&self, topic_kind: TopicKind) -> Option<endpoint::Builder<P, Id>> {
+        let kind = match topic_kind {
+            TopicKind::NoKey => entity_kind::USER_WRITER_NO_KEY,
+            TopicKind::WithKey => entity_kind::USER_WRITER_WITH_KEY,
+        };
+        self.endpoint(kind, topic_kind)
+    }
+}
+
+impl<P, Id> Subscriber<P, Id>
+where
+    P: Copy,
+    Id: Copy + From<[u8; 4]>,
+{
+    /// Construct a new reader [`Endpoint`] in this [`Subscriber`]
+    ///
+    /// The reader is allocated a new entity ID, and uses the default
+    /// [`Locator`]s of the participant unless others are given. Returns `None`
+    /// if the participant has run out of entity IDs.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtps_pim::structure::{Entity, Participant, TopicKind};
+    ///
+    /// let participant = Participant::new([0; 12], [0, 0, 1, 0xc1]);
+    /// let subscriber = participant.subscriber().unwrap();
+    ///
+    /// let reader = subscriber.reader(TopicKind::NoKey).unwrap().build();
+    ///
+    /// assert_eq!(reader.entity_id()[3], 0x04);
+    /// ```
+    pub fn reader(&self, topic_kind: TopicKind) -> Option<endpoint::Builder<P, Id>> {
+        let kind = match topic_kind {
+            TopicKind::NoKey => entity_kind::USER_READER_NO_KEY,
+            TopicKind::WithKey => entity_kind::USER_READER_WITH_KEY,
+        };
+        self.endpoint(kind, topic_kind)
     }
 }
 
@@ -46,8 +145,76 @@ pub struct Reader;
 #[derive(Debug)]
 pub struct Writer;
 
+/// A [`Group`] of writers
+pub type Publisher<P, Id> = Group<Writer, P, Id>;
+
 /// A [`Group`] of readers
-pub type Publisher<P, Id> = Group<Reader, P, Id>;
+pub type Subscriber<P, Id> = Group<Reader, P, Id>;
 
-/// A [`Group`] of writers
-pub type Subscriber<P, Id> = Group<Writer, P, Id>;
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeSet,
+        net::{Ipv4Addr, SocketAddrV4},
+    };
+
+    use crate::structure::{Entity, Locator, Participant, Publisher, Subscriber, TopicKind};
+
+    fn locator(port: u16) -> Locator {
+        SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 2), port).into()
+    }
+
+    fn participant() -> Participant<[u8; 12], [u8; 4]> {
+        Participant::builder([1; 12], [0, 0, 1, 0xc1])
+            .default_unicast_locators(vec![locator(7411)])
+            .default_multicast_locators(vec![locator(7401)])
+            .build()
+    }
+
+    #[test]
+    fn allocates_unique_entity_ids() {
+        let participant = participant();
+        let publisher = participant.publisher().unwrap();
+        let subscriber = participant.subscriber().unwrap();
+        let writer = |publisher: &Publisher<_, _>, topic_kind| {
+            publisher.writer(topic_kind).unwrap().build().entity_id()
+        };
+        let reader = |subscriber: &Subscriber<_, _>, topic_kind| {
+            subscriber.reader(topic_kind).unwrap().build().entity_id()
+        };
+
+        let entity_ids: Vec<_> = vec![
+            publisher.entity_id(),
+            subscriber.entity_id(),
+            writer(&publisher, TopicKind::WithKey),
+            writer(&publisher, TopicKind::NoKey),
+            reader(&subscriber, TopicKind::WithKey),
+            reader(&subscriber, TopicKind::NoKey),
+            writer(&participant.publisher().unwrap(), TopicKind::NoKey),
+        ];
+
+        let kinds: Vec<_> = entity_ids.iter().map(|id| id[3]).collect();
+        assert_eq!(kinds, vec![0x08, 0x09, 0x02, 0x03, 0x07, 0x04, 0x03]);
+
+        let keys: BTreeSet<_> = entity_ids.iter().map(|id| [id[0], id[1], id[2]]).collect();
+        assert_eq!(keys.len(), entity_ids.len());
+        assert_eq!(publisher.guid_prefix(), [1; 12]);
+    }
+
+    #[test]
+    fn endpoints_inherit_default_locators() {
+        let publisher = participant().publisher().unwrap();
+
+        let writer = publisher.writer(TopicKind::WithKey).unwrap().build();
+        assert_eq!(writer.unicast_locators(), &vec![locator(7411)]);
+        assert_eq!(writer.multicast_locators(), &vec![locator(7401)]);
+
+        let writer = publisher
+            .writer(TopicKind::WithKey)
+            .unwrap()
+            .unicast_locators(vec![locator(7413)])
+            .build();
+        assert_eq!(writer.unicast_locators(), &vec![locator(7413)]);
+        assert_eq!(writer.multicast_locators(), &vec![locator(7401)]);
+    }
+}
@@ -1,6 +1,13 @@
 //! Objects related to the persistent caching of RTPS messages
 
-use super::guid::Guid;
+use super::{guid::Guid, key::KeyHash};
+use std::num::NonZeroUsize;
+
+mod memory;
+mod persistent;
+
+pub use memory::{HistoryCache, LimitError, NotFoundError};
+pub use persistent::{PersistError, PersistentHistoryCache};
 
 /// A persisted cache of changes.
 ///
@@ -32,7 +39,10 @@ pub trait Cache<Data> {
     ///
     /// This method can fail. Specific failure modes will depend on the
     /// implementation.
-    fn add(change: Change<Data, Self::Prefix, Self::EntityId>) -> Result<Self::SqnN, Self::AddErr>;
+    fn add(
+        &mut self,
+        change: Change<Data, Self::Prefix, Self::EntityId>,
+    ) -> Result<Self::SqnN, Self::AddErr>;
 
     /// Remove a change from the cache, indexed by 'sequence number'
     ///
@@ -41,19 +51,31 @@ pub trait Cache<Data> {
     /// This method can fail. Specific failure modes will depend on the
     /// implementation.
     fn remove(
+        &mut self,
         sequence_number: Self::SqnN,
     ) -> Result<Change<Data, Self::Prefix, Self::EntityId>, Self::RemErr>;
 
+    /// Return a reference to a change in the cache, indexed by 'sequence
+    /// number'
+    fn get(
+        &self,
+        sequence_number: Self::SqnN,
+    ) -> Option<&Change<Data, Self::Prefix, Self::EntityId>>;
+
     /// The maximum 'sequence number' stored in the cache
-    fn max_sequence_number(&self) -> Self::SqnN;
+    ///
+    /// Returns `None` if the cache is empty.
+    fn max_sequence_number(&self) -> Option<Self::SqnN>;
 
     /// The minimum 'sequence number' stored in the cache
-    fn min_sequence_number(&self) -> Self::SqnN;
+    ///
+    /// Returns `None` if the cache is empty.
+    fn min_sequence_number(&self) -> Option<Self::SqnN>;
 }
 
 /// A packet of information representing some change to the state of a data
 /// object in the [`Cache`]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Change<Data, P, Id>
 where
     P: Copy,
@@ -61,21 +83,99 @@ where
 {
     kind: Kind<Data>,
     writer_guid: Guid<P, Id>,
-    instance_guid: Guid<P, Id>,
+    instance: KeyHash,
+}
+
+impl<Data, P, Id> Change<Data, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    /// Construct a new [`Change`]
+    ///
+    /// `writer_guid` identifies the writer which made the change, while
+    /// `instance` is the [`KeyHash`] of the data-object instance which was
+    /// changed.
+    #[must_use]
+    pub fn new(kind: Kind<Data>, writer_guid: Guid<P, Id>, instance: KeyHash) -> Self {
+        Self {
+            kind,
+            writer_guid,
+            instance,
+        }
+    }
+
+    /// The [`Kind`] of the change
+    #[must_use]
+    pub fn kind(&self) -> &Kind<Data> {
+        &self.kind
+    }
+
+    /// The [`Guid`] of the writer which made the change
+    #[must_use]
+    pub fn writer_guid(&self) -> Guid<P, Id> {
+        self.writer_guid
+    }
+
+    /// The [`KeyHash`] of the data-object instance which was changed
+    #[must_use]
+    pub fn instance(&self) -> KeyHash {
+        self.instance
+    }
+
+    /// Consume the change, returning its [`Kind`]
+    #[must_use]
+    pub fn into_kind(self) -> Kind<Data> {
+        self.kind
+    }
 }
 
 /// The type of [`Change`]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Kind<Data> {
-    /// TODO
+    /// The writer has written a new value of the instance
     Alive(Data),
 
-    /// TODO
+    /// The writer has written a new value of the instance, but it was
+    /// filtered out for the receiving reader (for example, by a content
+    /// filter), so the value is not included
     AliveFiltered,
 
-    /// TODO
+    /// The writer has disposed of the instance, meaning that it no longer
+    /// exists
     NotAliveDisposed,
 
-    /// TODO
+    /// The writer has unregistered the instance, meaning that it will no
+    /// longer write to it. The instance continues to exist for as long as
+    /// other writers are registered with it.
     NotAliveUnregistered,
 }
+
+/// Controls how many changes are kept in a [`Cache`] for each instance
+///
+/// This corresponds to the `HISTORY` quality of service policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// Keep only the most recent changes for each instance, up to the given
+    /// depth. Older changes are evicted to make room for new ones.
+    KeepLast(NonZeroUsize),
+
+    /// Keep every change, subject to the configured [`ResourceLimits`].
+    KeepAll,
+}
+
+/// Limits on the resources which may be consumed by a [`Cache`]
+///
+/// This corresponds to the `RESOURCE_LIMITS` quality of service policy. A
+/// value of `None` means that the resource is unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// The maximum number of changes, across all instances
+    pub max_samples: Option<usize>,
+
+    /// The maximum number of instances
+    pub max_instances: Option<usize>,
+
+    /// The maximum number of changes for any single instance
+    pub max_samples_per_instance: Option<usize>,
+}
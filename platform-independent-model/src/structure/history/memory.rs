@@ -0,0 +1,458 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use super::{Cache, Change, Policy, ResourceLimits};
+use crate::structure::{Guid, KeyHash};
+
+/// A [`Cache`] which stores its changes in memory
+///
+/// Each change is assigned a sequence number when it is added. Sequence
+/// numbers start at 1 and are never reused, even after a change has been
+/// removed.
+///
+/// By default, the cache keeps every change without limit. Use
+/// [`HistoryCache::new`] to configure a history [`Policy`] and
+/// [`ResourceLimits`].
+///
+/// # Example
+///
+/// ```
+/// use rtps_pim::structure::{
+///     history::{Cache, Change, HistoryCache, Kind},
+///     Guid, KeyHash,
+/// };
+///
+/// let writer = Guid::new([0; 12], [0, 0, 1, 2]);
+/// let instance = KeyHash::new([1; 16]);
+///
+/// let mut cache = HistoryCache::default();
+/// assert_eq!(cache.max_sequence_number(), None);
+///
+/// let sequence_number = cache
+///     .add(Change::new(Kind::Alive("hello"), writer, instance))
+///     .unwrap();
+///
+/// assert_eq!(sequence_number, 1);
+/// assert_eq!(cache.max_sequence_number(), Some(1));
+/// ```
+#[derive(Debug)]
+pub struct HistoryCache<Data, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    changes: BTreeMap<u64, Change<Data, P, Id>>,
+    instances: BTreeMap<KeyHash, VecDeque<u64>>,
+    next_sequence_number: u64,
+    policy: Policy,
+    limits: ResourceLimits,
+}
+
+impl<Data, P, Id> Default for HistoryCache<Data, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    fn default() -> Self {
+        Self {
+            changes: BTreeMap::default(),
+            instances: BTreeMap::default(),
+            next_sequence_number: 1,
+            policy: Policy::KeepAll,
+            limits: ResourceLimits::default(),
+        }
+    }
+}
+
+impl<Data, P, Id> HistoryCache<Data, P, Id>
+where
+    P: Copy + Ord,
+    Id: Copy + Ord,
+{
+    /// Construct a new [`HistoryCache`] with the given history [`Policy`] and
+    /// [`ResourceLimits`]
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtps_pim::structure::{
+    ///     history::{Cache, Change, HistoryCache, Kind, Policy, ResourceLimits},
+    ///     Guid, KeyHash,
+    /// };
+    /// use std::num::NonZeroUsize;
+    ///
+    /// let writer = Guid::new([0; 12], [0, 0, 1, 2]);
+    /// let instance = KeyHash::new([1; 16]);
+    ///
+    /// let depth = NonZeroUsize::new(2).unwrap();
+    /// let mut cache = HistoryCache::new(Policy::KeepLast(depth), ResourceLimits::default());
+    ///
+    /// for value in 0..5 {
+    ///     cache
+    ///         .add(Change::new(Kind::Alive(value), writer, instance))
+    ///         .unwrap();
+    /// }
+    ///
+    /// // only the two most recent changes are kept
+    /// assert_eq!(cache.min_sequence_number(), Some(4));
+    /// assert_eq!(cache.max_sequence_number(), Some(5));
+    /// ```
+    #[must_use]
+    pub fn new(policy: Policy, limits: ResourceLimits) -> Self {
+        Self {
+            policy,
+            limits,
+            ..Self::default()
+        }
+    }
+
+    /// The history [`Policy`] of the cache
+    #[must_use]
+    pub fn policy(&self) -> Policy {
+        self.policy
+    }
+
+    /// The [`ResourceLimits`] of the cache
+    #[must_use]
+    pub fn limits(&self) -> ResourceLimits {
+        self.limits
+    }
+
+    /// The number of instances with at least one change in the cache
+    #[must_use]
+    pub fn instances(&self) -> usize {
+        self.instances.len()
+    }
+
+    /// Return an iterator over the changes in the cache, and their sequence
+    /// numbers, in order of increasing sequence number
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &Change<Data, P, Id>)> + '_ {
+        self.changes
+            .iter()
+            .map(|(&sequence_number, change)| (sequence_number, change))
+    }
+
+    /// Return an iterator over the changes made by a particular writer, in
+    /// order of increasing sequence number
+    pub fn changes_from_writer(
+        &self,
+        writer_guid: Guid<P, Id>,
+    ) -> impl Iterator<Item = (u64, &Change<Data, P, Id>)> + '_ {
+        self.iter()
+            .filter(move |(_, change)| change.writer_guid() == writer_guid)
+    }
+
+    /// The number of changes in the cache
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Returns `true` if the cache contains no changes
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl<Data, P, Id> HistoryCache<Data, P, Id>
+where
+    P: Copy + Ord,
+    Id: Copy + Ord,
+{
+    /// Check that a change to the given instance can be added without
+    /// exceeding the resource limits.
+    ///
+    /// Returns the sequence number of the change which must be evicted to make
+    /// room for it, if any.
+    pub(super) fn check_limits(&self, instance: KeyHash) -> Result<Option<u64>, LimitError> {
+        let samples = self.instances.get(&instance);
+        let instance_len = samples.map_or(0, VecDeque::len);
+
+        if samples.is_none() {
+            if let Some(limit) = self.limits.max_instances {
+                if self.instances.len() >= limit {
+                    return Err(LimitError::MaxInstances { limit });
+                }
+            }
+        }
+
+        let evict = match self.policy {
+            Policy::KeepLast(depth) if instance_len >= depth.get() => {
+                samples.and_then(|samples| samples.front().copied())
+            }
+            Policy::KeepLast(_) => None,
+            Policy::KeepAll => {
+                if let Some(limit) = self.limits.max_samples_per_instance {
+                    if instance_len >= limit {
+                        return Err(LimitError::MaxSamplesPerInstance { limit });
+                    }
+                }
+                None
+            }
+        };
+
+        if let Some(limit) = self.limits.max_samples {
+            if evict.is_none() && self.changes.len() >= limit {
+                return Err(LimitError::MaxSamples { limit });
+            }
+        }
+
+        Ok(evict)
+    }
+
+    /// Insert a change with a known sequence number, bypassing the resource
+    /// limits
+    pub(super) fn insert(&mut self, sequence_number: u64, change: Change<Data, P, Id>) {
+        self.reserve(sequence_number);
+        self.instances
+            .entry(change.instance())
+            .or_default()
+            .push_back(sequence_number);
+        self.changes.insert(sequence_number, change);
+    }
+
+    /// Ensure that the given sequence number will never be assigned to a new
+    /// change
+    pub(super) fn reserve(&mut self, sequence_number: u64) {
+        self.next_sequence_number = self.next_sequence_number.max(sequence_number + 1);
+    }
+
+    /// The sequence number which will be assigned to the next change
+    pub(super) fn next_sequence_number(&self) -> u64 {
+        self.next_sequence_number
+    }
+}
+
+impl<Data, P, Id> Cache<Data> for HistoryCache<Data, P, Id>
+where
+    P: Copy + Ord,
+    Id: Copy + Ord,
+{
+    type Prefix = P;
+    type EntityId = Id;
+    type SqnN = u64;
+    type AddErr = LimitError;
+    type RemErr = NotFoundError;
+
+    fn add(&mut self, change: Change<Data, P, Id>) -> Result<u64, Self::AddErr> {
+        if let Some(evicted) = self.check_limits(change.instance())? {
+            let _ = self.remove(evicted);
+        }
+
+        let sequence_number = self.next_sequence_number;
+        self.insert(sequence_number, change);
+
+        Ok(sequence_number)
+    }
+
+    fn remove(&mut self, sequence_number: u64) -> Result<Change<Data, P, Id>, Self::RemErr> {
+        let change = self
+            .changes
+            .remove(&sequence_number)
+            .ok_or(NotFoundError { sequence_number })?;
+
+        let instance = change.instance();
+        if let Some(samples) = self.instances.get_mut(&instance) {
+            samples.retain(|&n| n != sequence_number);
+            if samples.is_empty() {
+                self.instances.remove(&instance);
+            }
+        }
+
+        Ok(change)
+    }
+
+    fn get(&self, sequence_number: u64) -> Option<&Change<Data, P, Id>> {
+        self.changes.get(&sequence_number)
+    }
+
+    fn max_sequence_number(&self) -> Option<u64> {
+        self.changes.keys().next_back().copied()
+    }
+
+    fn min_sequence_number(&self) -> Option<u64> {
+        self.changes.keys().next().copied()
+    }
+}
+
+/// The error returned when adding a [`Change`] would exceed the
+/// [`ResourceLimits`] of the cache
+#[derive(Debug, thiserror::Error)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum LimitError {
+    /// The cache already holds the maximum number of changes
+    #[error("the cache already holds the maximum number of samples ({limit})")]
+    MaxSamples {
+        /// The configured limit
+        limit: usize,
+    },
+
+    /// The change is for a new instance, and the cache already holds the
+    /// maximum number of instances
+    #[error("the cache already holds the maximum number of instances ({limit})")]
+    MaxInstances {
+        /// The configured limit
+        limit: usize,
+    },
+
+    /// The cache already holds the maximum number of changes for this instance
+    #[error("the cache already holds the maximum number of samples for this instance ({limit})")]
+    MaxSamplesPerInstance {
+        /// The configured limit
+        limit: usize,
+    },
+}
+
+/// The error returned when attempting to remove a [`Change`] which is not in
+/// the cache
+#[derive(Debug, thiserror::Error)]
+#[cfg_attr(test, derive(PartialEq))]
+#[error("no change with sequence number {sequence_number} in the cache")]
+pub struct NotFoundError {
+    pub(super) sequence_number: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cache, Change, HistoryCache, LimitError, NotFoundError};
+    use crate::structure::{
+        history::{Kind, Policy, ResourceLimits},
+        Guid, KeyHash,
+    };
+    use std::num::NonZeroUsize;
+
+    type TestCache = HistoryCache<u32, [u8; 12], [u8; 4]>;
+
+    fn change(writer: u8, value: u32) -> Change<u32, [u8; 12], [u8; 4]> {
+        Change::new(
+            Kind::Alive(value),
+            Guid::new([0; 12], [0, 0, writer, 2]),
+            KeyHash::new([1; 16]),
+        )
+    }
+
+    #[test]
+    fn empty() {
+        let cache = TestCache::default();
+        assert_eq!(cache.min_sequence_number(), None);
+        assert_eq!(cache.max_sequence_number(), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn sequence_numbers_are_not_reused() {
+        let mut cache = TestCache::default();
+
+        assert_eq!(cache.add(change(1, 10)).unwrap(), 1);
+        assert_eq!(cache.add(change(1, 20)).unwrap(), 2);
+        assert_eq!(cache.remove(2).unwrap(), change(1, 20));
+        assert_eq!(cache.add(change(1, 30)).unwrap(), 3);
+
+        assert_eq!(cache.min_sequence_number(), Some(1));
+        assert_eq!(cache.max_sequence_number(), Some(3));
+        assert_eq!(cache.remove(2), Err(NotFoundError { sequence_number: 2 }));
+    }
+
+    #[test]
+    fn lookup_by_writer() {
+        let mut cache = TestCache::default();
+        cache.add(change(1, 10)).unwrap();
+        cache.add(change(2, 20)).unwrap();
+        cache.add(change(1, 30)).unwrap();
+
+        let writer = Guid::new([0; 12], [0, 0, 1, 2]);
+        let sequence_numbers: Vec<_> = cache
+            .changes_from_writer(writer)
+            .map(|(sequence_number, _)| sequence_number)
+            .collect();
+
+        assert_eq!(sequence_numbers, vec![1, 3]);
+    }
+
+    #[test]
+    fn iterates_in_order() {
+        let mut cache = TestCache::default();
+        for value in 0..5 {
+            cache.add(change(1, value)).unwrap();
+        }
+        cache.remove(3).unwrap();
+
+        let sequence_numbers: Vec<_> = cache
+            .iter()
+            .map(|(sequence_number, _)| sequence_number)
+            .collect();
+        assert_eq!(sequence_numbers, vec![1, 2, 4, 5]);
+        assert_eq!(cache.get(4), Some(&change(1, 3)));
+    }
+
+    fn instance_change(instance: u8, value: u32) -> Change<u32, [u8; 12], [u8; 4]> {
+        Change::new(
+            Kind::Alive(value),
+            Guid::new([0; 12], [0, 0, 1, 2]),
+            KeyHash::new([instance; 16]),
+        )
+    }
+
+    #[test]
+    fn keep_last_evicts_per_instance() {
+        let depth = NonZeroUsize::new(2).unwrap();
+        let mut cache = TestCache::new(Policy::KeepLast(depth), ResourceLimits::default());
+
+        cache.add(instance_change(1, 1)).unwrap();
+        cache.add(instance_change(2, 2)).unwrap();
+        cache.add(instance_change(1, 3)).unwrap();
+        cache.add(instance_change(1, 4)).unwrap();
+
+        let sequence_numbers: Vec<_> = cache.iter().map(|(n, _)| n).collect();
+        assert_eq!(sequence_numbers, vec![2, 3, 4]);
+        assert_eq!(cache.instances(), 2);
+    }
+
+    #[test]
+    fn keep_last_respects_max_samples() {
+        let depth = NonZeroUsize::new(2).unwrap();
+        let limits = ResourceLimits {
+            max_samples: Some(2),
+            ..ResourceLimits::default()
+        };
+        let mut cache = TestCache::new(Policy::KeepLast(depth), limits);
+
+        cache.add(instance_change(1, 1)).unwrap();
+        cache.add(instance_change(2, 2)).unwrap();
+        assert_eq!(
+            cache.add(instance_change(2, 3)),
+            Err(LimitError::MaxSamples { limit: 2 })
+        );
+    }
+
+    #[test]
+    fn keep_all_limits() {
+        let limits = ResourceLimits {
+            max_samples: Some(3),
+            max_instances: Some(2),
+            max_samples_per_instance: Some(2),
+        };
+        let mut cache = TestCache::new(Policy::KeepAll, limits);
+
+        cache.add(instance_change(1, 1)).unwrap();
+        cache.add(instance_change(1, 2)).unwrap();
+        assert_eq!(
+            cache.add(instance_change(1, 3)),
+            Err(LimitError::MaxSamplesPerInstance { limit: 2 })
+        );
+
+        cache.add(instance_change(2, 4)).unwrap();
+        assert_eq!(
+            cache.add(instance_change(3, 5)),
+            Err(LimitError::MaxInstances { limit: 2 })
+        );
+        assert_eq!(
+            cache.add(instance_change(2, 6)),
+            Err(LimitError::MaxSamples { limit: 3 })
+        );
+
+        // removing a change frees up space
+        cache.remove(1).unwrap();
+        cache.add(instance_change(2, 6)).unwrap();
+    }
+}
@@ -0,0 +1,737 @@
+use std::{
+    convert::TryFrom,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{Cache, Change, HistoryCache, Kind, LimitError, NotFoundError, Policy, ResourceLimits};
+use crate::structure::{Guid, KeyHash};
+
+/// The log is compacted once it contains at least this many stale records,
+/// and more stale records than live ones
+const COMPACTION_THRESHOLD: usize = 1024;
+
+/// The size of the length and checksum which precede each record in the log
+const FRAME_HEADER_SIZE: usize = 8;
+
+const TAG_ADD: u8 = 1;
+const TAG_REMOVE: u8 = 2;
+const TAG_RESERVE: u8 = 3;
+
+/// A [`Cache`] which persists its changes to an append-only log on disk, so
+/// that they survive a restart of the process.
+///
+/// This supports the 'PERSISTENT' durability quality of service. Every change
+/// is written to the log, along with its sequence number, before it is added
+/// to the cache. Removing a change appends a record to the log rather than
+/// rewriting it, and the log is compacted once it contains enough stale
+/// records. Compaction is best-effort: if it fails, the change has still been
+/// committed, and compaction is retried by the next change.
+///
+/// When the cache is reopened, the log is replayed to rebuild its contents.
+/// Sequence numbers continue from where they left off, so they are never
+/// reused. A partially written record at the end of the log (left by a crash
+/// part way through a write) is detected using its checksum, and discarded. A
+/// damaged record anywhere else in the log is reported as an error, rather
+/// than discarding the valid records which follow it.
+///
+/// # Example
+///
+/// ```
+/// use rtps_pim::structure::{
+///     history::{Cache, Change, Kind, PersistentHistoryCache, Policy, ResourceLimits},
+///     Guid, KeyHash,
+/// };
+///
+/// # let dir = tempfile::tempdir().unwrap();
+/// # let path = dir.path().join("history.log");
+/// let writer = Guid::new([0; 12], [0, 0, 1, 2]);
+/// let instance = KeyHash::new([1; 16]);
+///
+/// let mut cache: PersistentHistoryCache<Vec<u8>, _, _> =
+///     PersistentHistoryCache::open(&path, Policy::KeepAll, ResourceLimits::default()).unwrap();
+/// cache
+///     .add(Change::new(Kind::Alive(vec![1, 2, 3]), writer, instance))
+///     .unwrap();
+/// drop(cache);
+///
+/// // the change survives reopening the cache
+/// let cache: PersistentHistoryCache<Vec<u8>, [u8; 12], [u8; 4]> =
+///     PersistentHistoryCache::open(&path, Policy::KeepAll, ResourceLimits::default()).unwrap();
+/// assert_eq!(cache.max_sequence_number(), Some(1));
+/// ```
+#[derive(Debug)]
+pub struct PersistentHistoryCache<Data, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    cache: HistoryCache<Data, P, Id>,
+    path: PathBuf,
+    log: File,
+    log_len: u64,
+    stale_records: usize,
+}
+
+impl<Data, P, Id> PersistentHistoryCache<Data, P, Id>
+where
+    Data: AsRef<[u8]> + From<Vec<u8>>,
+    P: Copy + Ord + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+    Id: Copy + Ord + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+{
+    /// Open the cache stored at the given path, creating it if it doesn't
+    /// exist
+    ///
+    /// The history [`Policy`] and [`ResourceLimits`] are applied to any
+    /// changes which are added after the cache is opened.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the log can't be read, or if it contains a
+    /// damaged record which isn't at the end of the log, or a complete record
+    /// which can't be decoded.
+    pub fn open(
+        path: impl AsRef<Path>,
+        policy: Policy,
+        limits: ResourceLimits,
+    ) -> Result<Self, PersistError> {
+        let path = path.as_ref().to_path_buf();
+        let mut log = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+
+        let mut bytes = Vec::new();
+        log.read_to_end(&mut bytes)?;
+
+        let mut cache = HistoryCache::new(policy, limits);
+        let mut offset = 0;
+        let mut records = 0;
+
+        loop {
+            match next_frame(&bytes[offset..]) {
+                Frame::Valid(body, len) => {
+                    match decode(body).ok_or(PersistError::Corrupt { offset })? {
+                        Record::Add(sequence_number, change) => {
+                            cache.insert(sequence_number, change);
+                        }
+                        Record::Remove(sequence_number) => {
+                            let _ = cache.remove(sequence_number);
+                        }
+                        Record::Reserve(sequence_number) => cache.reserve(sequence_number),
+                    }
+                    offset += len;
+                    records += 1;
+                }
+                Frame::Invalid(len) if offset + len < bytes.len() => {
+                    // a torn write can only damage the final record
+                    return Err(PersistError::Corrupt { offset });
+                }
+                Frame::Invalid(_) | Frame::Incomplete => break,
+            }
+        }
+
+        if offset < bytes.len() {
+            // discard a partially written record
+            log.set_len(offset as u64)?;
+            log.sync_all()?;
+        }
+
+        let stale_records = records - cache.len();
+
+        Ok(Self {
+            cache,
+            path,
+            log,
+            log_len: offset as u64,
+            stale_records,
+        })
+    }
+
+    /// The path of the log file
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Return an iterator over the changes in the cache, and their sequence
+    /// numbers, in order of increasing sequence number
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &Change<Data, P, Id>)> + '_ {
+        self.cache.iter()
+    }
+
+    /// The number of changes in the cache
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Returns `true` if the cache contains no changes
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Rewrite the log so that it only contains the live changes.
+    ///
+    /// The compacted log is written to a temporary file, which then atomically
+    /// replaces the original, so a crash part way through compaction leaves
+    /// the original log intact.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the compacted log can't be written, in which
+    /// case the original log is left in place and remains in use.
+    pub fn compact(&mut self) -> Result<(), PersistError> {
+        let mut buffer = Vec::new();
+
+        for (sequence_number, change) in self.cache.iter() {
+            encode_add(&mut buffer, sequence_number, change)?;
+        }
+
+        let last_assigned = self.cache.next_sequence_number() - 1;
+        if last_assigned > 0 && self.cache.max_sequence_number() != Some(last_assigned) {
+            encode_sequence_number(&mut buffer, TAG_RESERVE, last_assigned)?;
+        }
+
+        let mut temp_path = self.path.clone().into_os_string();
+        temp_path.push(".compact");
+        let temp_path = PathBuf::from(temp_path);
+
+        // the compacted log is opened for appending before it replaces the
+        // original, so that there is nothing left to fail once it has
+        let _ = fs::remove_file(&temp_path);
+        let result = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create_new(true)
+            .open(&temp_path)
+            .and_then(|mut temp| {
+                temp.write_all(&buffer)?;
+                temp.sync_all()?;
+                fs::rename(&temp_path, &self.path)?;
+                Ok(temp)
+            });
+
+        let temp = match result {
+            Ok(temp) => temp,
+            Err(error) => {
+                let _ = fs::remove_file(&temp_path);
+                return Err(error.into());
+            }
+        };
+
+        if let Some(dir) = self.path.parent() {
+            // not supported on every platform, so this is best-effort
+            let _ = File::open(dir).and_then(|dir| dir.sync_all());
+        }
+
+        self.log = temp;
+        self.log_len = buffer.len() as u64;
+        self.stale_records = 0;
+
+        Ok(())
+    }
+
+    /// Append the given records to the log
+    ///
+    /// If the records can't be written, the log is rolled back to the end of
+    /// the last complete record, so that a partial write doesn't corrupt the
+    /// records which are appended after it.
+    fn append(&mut self, buffer: &[u8]) -> Result<(), PersistError> {
+        let result = self
+            .log
+            .write_all(buffer)
+            .and_then(|()| self.log.sync_data());
+
+        if let Err(error) = result {
+            let _ = self.log.set_len(self.log_len);
+            return Err(error.into());
+        }
+
+        self.log_len += buffer.len() as u64;
+        Ok(())
+    }
+
+    /// Compact the log if it contains enough stale records
+    ///
+    /// This is called once a change has been committed, so a failure is
+    /// ignored rather than reported as a failure of the change. The original
+    /// log remains in use, and compaction is retried after the next change.
+    fn compact_if_required(&mut self) {
+        if self.stale_records >= COMPACTION_THRESHOLD && self.stale_records > self.cache.len() {
+            let _ = self.compact();
+        }
+    }
+}
+
+impl<Data, P, Id> Cache<Data> for PersistentHistoryCache<Data, P, Id>
+where
+    Data: AsRef<[u8]> + From<Vec<u8>>,
+    P: Copy + Ord + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+    Id: Copy + Ord + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+{
+    type Prefix = P;
+    type EntityId = Id;
+    type SqnN = u64;
+    type AddErr = PersistError;
+    type RemErr = PersistError;
+
+    fn add(&mut self, change: Change<Data, P, Id>) -> Result<u64, Self::AddErr> {
+        let evicted = self.cache.check_limits(change.instance())?;
+        let sequence_number = self.cache.next_sequence_number();
+
+        let mut buffer = Vec::new();
+        encode_add(&mut buffer, sequence_number, &change)?;
+        if let Some(evicted) = evicted {
+            encode_sequence_number(&mut buffer, TAG_REMOVE, evicted)?;
+        }
+        self.append(&buffer)?;
+
+        if let Some(evicted) = evicted {
+            let _ = self.cache.remove(evicted);
+            self.stale_records += 2;
+        }
+        self.cache.insert(sequence_number, change);
+
+        self.compact_if_required();
+
+        Ok(sequence_number)
+    }
+
+    fn remove(&mut self, sequence_number: u64) -> Result<Change<Data, P, Id>, Self::RemErr> {
+        if self.cache.get(sequence_number).is_none() {
+            return Err(NotFoundError { sequence_number }.into());
+        }
+
+        let mut buffer = Vec::new();
+        encode_sequence_number(&mut buffer, TAG_REMOVE, sequence_number)?;
+        self.append(&buffer)?;
+
+        let change = self.cache.remove(sequence_number)?;
+        self.stale_records += 2;
+
+        self.compact_if_required();
+
+        Ok(change)
+    }
+
+    fn get(&self, sequence_number: u64) -> Option<&Change<Data, P, Id>> {
+        self.cache.get(sequence_number)
+    }
+
+    fn max_sequence_number(&self) -> Option<u64> {
+        self.cache.max_sequence_number()
+    }
+
+    fn min_sequence_number(&self) -> Option<u64> {
+        self.cache.min_sequence_number()
+    }
+}
+
+/// Errors that can occur when reading or writing a [`PersistentHistoryCache`]
+#[derive(Debug, thiserror::Error)]
+pub enum PersistError {
+    /// The log could not be read or written
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// Adding the change would exceed the [`ResourceLimits`] of the cache
+    #[error(transparent)]
+    Limit(#[from] LimitError),
+
+    /// The change to be removed is not in the cache
+    #[error(transparent)]
+    NotFound(#[from] NotFoundError),
+
+    /// The log contains a record which failed its checksum, but isn't at the
+    /// end of the log, or a record which passed its checksum but could not be
+    /// decoded
+    #[error("corrupt record in history log at byte offset {offset}")]
+    Corrupt {
+        /// The position of the record in the log
+        offset: usize,
+    },
+
+    /// The change is too large to be written to the log
+    #[error("a record of {size} bytes is too large for the history log")]
+    TooLarge {
+        /// The size of the change, or of the value within it, in bytes
+        size: usize,
+    },
+}
+
+/// A single entry in the log
+enum Record<Data, P, Id>
+where
+    P: Copy,
+    Id: Copy,
+{
+    /// A change was added with the given sequence number
+    Add(u64, Change<Data, P, Id>),
+
+    /// The change with the given sequence number was removed
+    Remove(u64),
+
+    /// Every sequence number up to and including this one has been assigned
+    Reserve(u64),
+}
+
+/// The next frame at the front of the log
+enum Frame<'a> {
+    /// A complete frame which passed its checksum, with its body and the
+    /// total length of the frame
+    Valid(&'a [u8], usize),
+
+    /// A complete frame which failed its checksum, with the total length of
+    /// the frame
+    Invalid(usize),
+
+    /// The log ends part way through the frame
+    Incomplete,
+}
+
+/// Split the next frame from the front of the log
+fn next_frame(bytes: &[u8]) -> Frame<'_> {
+    let header = match bytes.get(..FRAME_HEADER_SIZE) {
+        Some(header) => header,
+        None => return Frame::Incomplete,
+    };
+    let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let checksum = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    let body = match bytes.get(FRAME_HEADER_SIZE..FRAME_HEADER_SIZE + len) {
+        Some(body) => body,
+        None => return Frame::Incomplete,
+    };
+
+    if crc32(body) == checksum {
+        Frame::Valid(body, FRAME_HEADER_SIZE + len)
+    } else {
+        Frame::Invalid(FRAME_HEADER_SIZE + len)
+    }
+}
+
+fn write_frame(buffer: &mut Vec<u8>, body: &[u8]) -> Result<(), PersistError> {
+    let len = encode_len(body.len())?;
+    buffer.extend_from_slice(&len.to_le_bytes());
+    buffer.extend_from_slice(&crc32(body).to_le_bytes());
+    buffer.extend_from_slice(body);
+    Ok(())
+}
+
+fn encode_len(size: usize) -> Result<u32, PersistError> {
+    u32::try_from(size).map_err(|_| PersistError::TooLarge { size })
+}
+
+fn encode_sequence_number(
+    buffer: &mut Vec<u8>,
+    tag: u8,
+    sequence_number: u64,
+) -> Result<(), PersistError> {
+    let mut body = vec![tag];
+    body.extend_from_slice(&sequence_number.to_le_bytes());
+    write_frame(buffer, &body)
+}
+
+fn encode_add<Data, P, Id>(
+    buffer: &mut Vec<u8>,
+    sequence_number: u64,
+    change: &Change<Data, P, Id>,
+) -> Result<(), PersistError>
+where
+    Data: AsRef<[u8]>,
+    P: Copy + AsRef<[u8]>,
+    Id: Copy + AsRef<[u8]>,
+{
+    let mut body = vec![TAG_ADD];
+    body.extend_from_slice(&sequence_number.to_le_bytes());
+    encode_guid(&mut body, change.writer_guid())?;
+    encode_bytes(&mut body, change.instance().as_ref())?;
+
+    match change.kind() {
+        Kind::Alive(data) => {
+            body.push(0);
+            encode_bytes(&mut body, data.as_ref())?;
+        }
+        Kind::AliveFiltered => body.push(1),
+        Kind::NotAliveDisposed => body.push(2),
+        Kind::NotAliveUnregistered => body.push(3),
+    }
+
+    write_frame(buffer, &body)
+}
+
+fn encode_guid<P, Id>(body: &mut Vec<u8>, guid: Guid<P, Id>) -> Result<(), PersistError>
+where
+    P: Copy + AsRef<[u8]>,
+    Id: Copy + AsRef<[u8]>,
+{
+    encode_bytes(body, guid.prefix().as_ref())?;
+    encode_bytes(body, guid.entity_id().as_ref())
+}
+
+fn encode_bytes(body: &mut Vec<u8>, bytes: &[u8]) -> Result<(), PersistError> {
+    let len = encode_len(bytes.len())?;
+    body.extend_from_slice(&len.to_le_bytes());
+    body.extend_from_slice(bytes);
+    Ok(())
+}
+
+fn decode<Data, P, Id>(mut body: &[u8]) -> Option<Record<Data, P, Id>>
+where
+    Data: From<Vec<u8>>,
+    P: Copy + for<'a> TryFrom<&'a [u8]>,
+    Id: Copy + for<'a> TryFrom<&'a [u8]>,
+{
+    let tag = take_u8(&mut body)?;
+    let sequence_number = u64::from_le_bytes(TryFrom::try_from(take(&mut body, 8)?).ok()?);
+
+    let record = match tag {
+        TAG_ADD => {
+            let writer_guid = decode_guid(&mut body)?;
+            let instance = KeyHash::new(TryFrom::try_from(take_bytes(&mut body)?).ok()?);
+            let kind = match take_u8(&mut body)? {
+                0 => Kind::Alive(take_bytes(&mut body)?.to_vec().into()),
+                1 => Kind::AliveFiltered,
+                2 => Kind::NotAliveDisposed,
+                3 => Kind::NotAliveUnregistered,
+                _ => return None,
+            };
+            Record::Add(sequence_number, Change::new(kind, writer_guid, instance))
+        }
+        TAG_REMOVE => Record::Remove(sequence_number),
+        TAG_RESERVE => Record::Reserve(sequence_number),
+        _ => return None,
+    };
+
+    Some(record)
+}
+
+fn decode_guid<P, Id>(body: &mut &[u8]) -> Option<Guid<P, Id>>
+where
+    P: Copy + for<'a> TryFrom<&'a [u8]>,
+    Id: Copy + for<'a> TryFrom<&'a [u8]>,
+{
+    let prefix = P::try_from(take_bytes(body)?).ok()?;
+    let entity_id = Id::try_from(take_bytes(body)?).ok()?;
+    Some(Guid::new(prefix, entity_id))
+}
+
+fn take<'a>(body: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if body.len() < len {
+        return None;
+    }
+    let (head, tail) = body.split_at(len);
+    *body = tail;
+    Some(head)
+}
+
+fn take_u8(body: &mut &[u8]) -> Option<u8> {
+    take(body, 1).map(|bytes| bytes[0])
+}
+
+fn take_bytes<'a>(body: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = u32::from_le_bytes(TryFrom::try_from(take(body, 4)?).ok()?);
+    take(body, len as usize)
+}
+
+/// The CRC-32 (IEEE) checksum of the given bytes
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, PersistError, PersistentHistoryCache};
+    use crate::structure::{
+        history::{Cache, Change, Kind, Policy, ResourceLimits},
+        Guid, KeyHash,
+    };
+    use std::{fs::OpenOptions, io::Write, num::NonZeroUsize, path::Path};
+
+    type TestCache = PersistentHistoryCache<Vec<u8>, [u8; 12], [u8; 4]>;
+
+    fn open(path: &Path) -> TestCache {
+        TestCache::open(path, Policy::KeepAll, ResourceLimits::default()).unwrap()
+    }
+
+    fn change(instance: u8, kind: Kind<Vec<u8>>) -> Change<Vec<u8>, [u8; 12], [u8; 4]> {
+        Change::new(
+            kind,
+            Guid::new([1; 12], [0, 0, 1, 2]),
+            KeyHash::new([instance; 16]),
+        )
+    }
+
+    #[test]
+    fn checksum() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn restart_resumes_sequence_numbers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.log");
+
+        let mut cache = open(&path);
+        cache.add(change(1, Kind::Alive(vec![1]))).unwrap();
+        cache.add(change(2, Kind::NotAliveDisposed)).unwrap();
+        cache.add(change(1, Kind::Alive(vec![3]))).unwrap();
+        cache.remove(3).unwrap();
+        drop(cache);
+
+        let mut cache = open(&path);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(1), Some(&change(1, Kind::Alive(vec![1]))));
+        assert_eq!(cache.get(2), Some(&change(2, Kind::NotAliveDisposed)));
+        assert_eq!(cache.add(change(1, Kind::Alive(vec![4]))).unwrap(), 4);
+    }
+
+    #[test]
+    fn discards_partially_written_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.log");
+
+        let mut cache = open(&path);
+        cache.add(change(1, Kind::Alive(vec![1]))).unwrap();
+        cache.add(change(1, Kind::Alive(vec![2]))).unwrap();
+        drop(cache);
+
+        // simulate a crash part way through writing a record
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[40, 0, 0, 0, 1, 2, 3]).unwrap();
+        drop(file);
+
+        let mut cache = open(&path);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.add(change(1, Kind::Alive(vec![3]))).unwrap(), 3);
+        drop(cache);
+
+        let cache = open(&path);
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn discards_final_record_with_bad_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.log");
+
+        let mut cache = open(&path);
+        cache.add(change(1, Kind::Alive(vec![1]))).unwrap();
+        cache.add(change(1, Kind::Alive(vec![2]))).unwrap();
+        drop(cache);
+
+        // simulate a crash which left the last record's data unwritten
+        let mut bytes = std::fs::read(&path).unwrap();
+        *bytes.last_mut().unwrap() ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let cache = open(&path);
+        assert_eq!(cache.len(), 1);
+        assert!(std::fs::metadata(&path).unwrap().len() < bytes.len() as u64);
+    }
+
+    #[test]
+    fn rejects_corrupt_record_before_valid_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.log");
+
+        let mut cache = open(&path);
+        cache.add(change(1, Kind::Alive(vec![1]))).unwrap();
+        cache.add(change(1, Kind::Alive(vec![2]))).unwrap();
+        drop(cache);
+
+        // damage the body of the first record
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[9] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let error = TestCache::open(&path, Policy::KeepAll, ResourceLimits::default()).unwrap_err();
+        assert!(matches!(error, PersistError::Corrupt { offset: 0 }));
+
+        // the valid records which follow are left in place
+        assert_eq!(std::fs::read(&path).unwrap(), bytes);
+    }
+
+    #[test]
+    fn compaction_preserves_sequence_numbers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.log");
+
+        let mut cache = open(&path);
+        for value in 0..10 {
+            cache.add(change(1, Kind::Alive(vec![value]))).unwrap();
+        }
+        for sequence_number in 2..=10 {
+            cache.remove(sequence_number).unwrap();
+        }
+
+        let before = std::fs::metadata(&path).unwrap().len();
+        cache.compact().unwrap();
+        let after = std::fs::metadata(&path).unwrap().len();
+        assert!(after < before);
+        drop(cache);
+
+        let mut cache = open(&path);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.add(change(1, Kind::Alive(vec![0]))).unwrap(), 11);
+    }
+
+    #[test]
+    fn failed_compaction_does_not_fail_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.log");
+
+        // a directory in place of the temporary file makes compaction fail
+        let temp_path = dir.path().join("history.log.compact");
+        std::fs::create_dir(&temp_path).unwrap();
+
+        let mut cache = open(&path);
+        for sequence_number in 1..=600_u64 {
+            let data = sequence_number.to_le_bytes().to_vec();
+            cache.add(change(1, Kind::Alive(data))).unwrap();
+        }
+        for sequence_number in 1..=600_u64 {
+            let removed = cache.remove(sequence_number).unwrap();
+            let data = sequence_number.to_le_bytes().to_vec();
+            assert_eq!(removed.kind(), &Kind::Alive(data));
+        }
+
+        // compaction is retried by the next change
+        let before = std::fs::metadata(&path).unwrap().len();
+        std::fs::remove_dir(&temp_path).unwrap();
+        assert_eq!(cache.add(change(1, Kind::Alive(vec![0]))).unwrap(), 601);
+        assert!(std::fs::metadata(&path).unwrap().len() < before);
+        drop(cache);
+
+        let cache = open(&path);
+        let sequence_numbers: Vec<_> = cache.iter().map(|(n, _)| n).collect();
+        assert_eq!(sequence_numbers, vec![601]);
+    }
+
+    #[test]
+    fn eviction_is_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.log");
+        let policy = Policy::KeepLast(NonZeroUsize::new(1).unwrap());
+
+        let mut cache = TestCache::open(&path, policy, ResourceLimits::default()).unwrap();
+        cache.add(change(1, Kind::Alive(vec![1]))).unwrap();
+        cache.add(change(1, Kind::Alive(vec![2]))).unwrap();
+        drop(cache);
+
+        let cache = TestCache::open(&path, policy, ResourceLimits::default()).unwrap();
+        let sequence_numbers: Vec<_> = cache.iter().map(|(n, _)| n).collect();
+        assert_eq!(sequence_numbers, vec![2]);
+    }
+}
@@ -0,0 +1,327 @@
+//! Identification of data-object instances by their key
+
+use std::{convert::TryFrom, fmt};
+
+/// The size of a [`KeyHash`], in bytes
+const KEY_HASH_SIZE: usize = 16;
+
+/// A 16-byte hash which uniquely identifies a data-object instance.
+///
+/// The key hash is derived from the big-endian CDR serialization of the
+/// fields which make up the instance's key. If the serialized key is
+/// guaranteed to fit in 16 bytes it is used directly, padded with zeros.
+/// Otherwise, it is the MD5 hash of the serialized key.
+///
+/// For details, see the [specification, pg. 168](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF#page=168)
+///
+/// # Example
+///
+/// ```
+/// use rtps_pim::structure::{KeyHash, Keyed};
+///
+/// struct Vehicle {
+///     fleet: u16,
+///     id: u32,
+///     speed: f64,
+/// }
+///
+/// impl Keyed for Vehicle {
+///     type Key = (u16, u32);
+///
+///     fn key(&self) -> Self::Key {
+///         (self.fleet, self.id)
+///     }
+/// }
+///
+/// let vehicle = Vehicle {
+///     fleet: 1,
+///     id: 7,
+///     speed: 12.5,
+/// };
+///
+/// assert_eq!(
+///     vehicle.key_hash(),
+///     KeyHash::new([0, 1, 0, 0, 0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0, 0])
+/// );
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct KeyHash([u8; KEY_HASH_SIZE]);
+
+/// A handle which identifies a data-object instance within the local
+/// participant
+///
+/// Instances are identified by their [`KeyHash`].
+pub type InstanceHandle = KeyHash;
+
+impl KeyHash {
+    /// Construct a [`KeyHash`] from its raw bytes
+    #[must_use]
+    pub const fn new(bytes: [u8; KEY_HASH_SIZE]) -> Self {
+        Self(bytes)
+    }
+
+    /// Compute the [`KeyHash`] of a serialized key
+    ///
+    /// `serialized_key` is the big-endian CDR serialization of the key fields.
+    /// `max_size` is the maximum size of the serialized key for any instance
+    /// of the type, or `None` if it is unbounded. The key is hashed using MD5
+    /// unless it is guaranteed to fit in 16 bytes. A key longer than 16 bytes
+    /// is always hashed, even if `max_size` claims it should fit.
+    #[must_use]
+    pub fn from_serialized_key(serialized_key: &[u8], max_size: Option<usize>) -> Self {
+        match max_size {
+            Some(max_size)
+                if max_size <= KEY_HASH_SIZE && serialized_key.len() <= KEY_HASH_SIZE =>
+            {
+                let mut bytes = [0; KEY_HASH_SIZE];
+                bytes[..serialized_key.len()].copy_from_slice(serialized_key);
+                Self(bytes)
+            }
+            _ => Self(md5::compute(serialized_key).0),
+        }
+    }
+
+    /// The raw bytes of the [`KeyHash`]
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8; KEY_HASH_SIZE] {
+        &self.0
+    }
+}
+
+impl From<[u8; KEY_HASH_SIZE]> for KeyHash {
+    fn from(bytes: [u8; KEY_HASH_SIZE]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl AsRef<[u8]> for KeyHash {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for KeyHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KeyHash(")?;
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// A data type whose instances are identified by a key
+///
+/// The key is made up of one or more fields of the type, returned by
+/// [`Keyed::key`]. Multiple fields are returned as a tuple, in the order they
+/// are declared in the type.
+pub trait Keyed {
+    /// The fields which make up the key
+    type Key: KeyField;
+
+    /// Return the key of this instance
+    fn key(&self) -> Self::Key;
+
+    /// Compute the [`KeyHash`] which identifies this instance
+    fn key_hash(&self) -> KeyHash {
+        let mut buffer = Vec::new();
+        self.key().serialize(&mut buffer);
+        KeyHash::from_serialized_key(&buffer, Self::Key::max_end(0))
+    }
+}
+
+/// A type which can be used as (part of) a key
+///
+/// This provides the big-endian CDR serialization of the field. Implementations
+/// are provided for primitive types, strings, arrays and tuples.
+pub trait KeyField {
+    /// Append the big-endian CDR serialization of the field to the buffer.
+    ///
+    /// Alignment is relative to the start of the buffer.
+    fn serialize(&self, buffer: &mut Vec<u8>);
+
+    /// The maximum end position of the field, when it is serialized starting
+    /// at `offset`, including alignment padding.
+    ///
+    /// Returns `None` if the serialized size is unbounded.
+    fn max_end(offset: usize) -> Option<usize>;
+}
+
+fn align(buffer: &mut Vec<u8>, alignment: usize) {
+    let len = aligned(buffer.len(), alignment);
+    buffer.resize(len, 0);
+}
+
+fn aligned(offset: usize, alignment: usize) -> usize {
+    (offset + alignment - 1) / alignment * alignment
+}
+
+macro_rules! impl_key_field_for_primitive {
+    ($($ty:ty),*) => {
+        $(
+            impl KeyField for $ty {
+                fn serialize(&self, buffer: &mut Vec<u8>) {
+                    align(buffer, std::mem::size_of::<$ty>());
+                    buffer.extend_from_slice(&self.to_be_bytes());
+                }
+
+                fn max_end(offset: usize) -> Option<usize> {
+                    let size = std::mem::size_of::<$ty>();
+                    Some(aligned(offset, size) + size)
+                }
+            }
+        )*
+    };
+}
+
+impl_key_field_for_primitive!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+
+impl KeyField for bool {
+    fn serialize(&self, buffer: &mut Vec<u8>) {
+        buffer.push(u8::from(*self));
+    }
+
+    fn max_end(offset: usize) -> Option<usize> {
+        Some(offset + 1)
+    }
+}
+
+impl KeyField for String {
+    fn serialize(&self, buffer: &mut Vec<u8>) {
+        self.as_str().serialize(buffer);
+    }
+
+    fn max_end(_offset: usize) -> Option<usize> {
+        None
+    }
+}
+
+impl KeyField for &str {
+    fn serialize(&self, buffer: &mut Vec<u8>) {
+        string_len(self.len()).serialize(buffer);
+        buffer.extend_from_slice(self.as_bytes());
+        buffer.push(0);
+    }
+
+    fn max_end(_offset: usize) -> Option<usize> {
+        None
+    }
+}
+
+/// The CDR length of a string of `len` bytes, which includes the terminating
+/// nul character
+///
+/// A string too long for its length to fit in a `u32` is given the largest
+/// length instead. Its bytes are still serialized in full, and since strings
+/// are unbounded their keys are always hashed, so the key hash still depends
+/// on the whole string.
+fn string_len(len: usize) -> u32 {
+    u32::try_from(len.saturating_add(1)).unwrap_or(u32::MAX)
+}
+
+impl<T: KeyField, const N: usize> KeyField for [T; N] {
+    fn serialize(&self, buffer: &mut Vec<u8>) {
+        for element in self {
+            element.serialize(buffer);
+        }
+    }
+
+    fn max_end(offset: usize) -> Option<usize> {
+        (0..N).try_fold(offset, |offset, _| T::max_end(offset))
+    }
+}
+
+macro_rules! impl_key_field_for_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: KeyField),+> KeyField for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn serialize(&self, buffer: &mut Vec<u8>) {
+                let ($($name,)+) = self;
+                $($name.serialize(buffer);)+
+            }
+
+            fn max_end(offset: usize) -> Option<usize> {
+                $(let offset = $name::max_end(offset)?;)+
+                Some(offset)
+            }
+        }
+    };
+}
+
+impl_key_field_for_tuple!(A);
+impl_key_field_for_tuple!(A, B);
+impl_key_field_for_tuple!(A, B, C);
+impl_key_field_for_tuple!(A, B, C, D);
+impl_key_field_for_tuple!(A, B, C, D, E);
+impl_key_field_for_tuple!(A, B, C, D, E, F);
+
+#[cfg(test)]
+mod tests {
+    use super::{string_len, KeyField, KeyHash, Keyed};
+    use test_case::test_case;
+
+    struct Keys<K>(K);
+
+    impl<K: KeyField + Clone> Keyed for Keys<K> {
+        type Key = K;
+
+        fn key(&self) -> K {
+            self.0.clone()
+        }
+    }
+
+    #[test_case((1_u8, 2_u32) => Some(8) ; "aligns fields")]
+    #[test_case([0_u64; 2] => Some(16) ; "array")]
+    #[test_case((1_u8, String::new()) => None ; "unbounded")]
+    fn max_size<K: KeyField>(_key: K) -> Option<usize> {
+        K::max_end(0)
+    }
+
+    #[test_case(0 => 1 ; "empty")]
+    #[test_case(5 => 6 ; "short")]
+    #[test_case(0xffff_fffe => u32::MAX ; "longest")]
+    #[test_case(0xffff_ffff => u32::MAX ; "too long")]
+    #[test_case(usize::MAX => u32::MAX ; "maximum")]
+    fn string_lengths(len: usize) -> u32 {
+        string_len(len)
+    }
+
+    #[test]
+    fn short_keys_are_padded() {
+        let key = Keys((1_u8, 0x0203_0405_u32, -1_i16));
+        let expected = KeyHash::new([1, 0, 0, 0, 2, 3, 4, 5, 0xff, 0xff, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(key.key_hash(), expected);
+    }
+
+    #[test]
+    fn long_keys_are_hashed() {
+        let key = Keys([1_u64, 2, 3]);
+
+        let mut serialized = vec![0; 24];
+        serialized[7] = 1;
+        serialized[15] = 2;
+        serialized[23] = 3;
+
+        assert_eq!(key.key_hash(), KeyHash::new(md5::compute(&serialized).0));
+    }
+
+    #[test]
+    fn oversized_keys_are_hashed() {
+        // the serialized key doesn't fit, despite the maximum size
+        let serialized = [7; 17];
+
+        assert_eq!(
+            KeyHash::from_serialized_key(&serialized, Some(16)),
+            KeyHash::new(md5::compute(serialized).0)
+        );
+    }
+
+    #[test]
+    fn unbounded_keys_are_hashed() {
+        // a string key is hashed even when it would fit in 16 bytes
+        let key = Keys(String::from("a"));
+        let serialized = [0, 0, 0, 2, b'a', 0];
+
+        assert_eq!(key.key_hash(), KeyHash::new(md5::compute(serialized).0));
+    }
+}
@@ -1,7 +1,7 @@
-use std::net::{SocketAddrV4, SocketAddrV6};
+use std::net::{SocketAddr, SocketAddrV4, SocketAddrV6};
 
 /// Generalisation of a possible connection
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(u32)]
 pub enum Locator {
     // Invalid,
@@ -9,7 +9,7 @@ pub enum Locator {
     /// An IPv4 UDP socket address
     Udpv4(SocketAddrV4),
 
-    /// An IPv4 UDP socket address
+    /// An IPv6 UDP socket address
     Udpv6(SocketAddrV6),
     /* AddressInvalid,
      * PortInvalid, */
@@ -20,3 +20,18 @@ impl From<SocketAddrV4> for Locator {
         Self::Udpv4(socket_addr)
     }
 }
+
+impl From<SocketAddrV6> for Locator {
+    fn from(socket_addr: SocketAddrV6) -> Self {
+        Self::Udpv6(socket_addr)
+    }
+}
+
+impl From<SocketAddr> for Locator {
+    fn from(socket_addr: SocketAddr) -> Self {
+        match socket_addr {
+            SocketAddr::V4(socket_addr) => socket_addr.into(),
+            SocketAddr::V6(socket_addr) => socket_addr.into(),
+        }
+    }
+}
@@ -1,10 +1,11 @@
 //! Contains the [`Participant`] and its [`Builder`]
 
 use super::{
-    entity::Entity,
+    entity::{entity_kind, Entity, EntityIds},
     group::{Group, Publisher, Subscriber},
     guid::Guid,
     locator::Locator,
+    participant_proxy::ParticipantProxy,
     protocol_version::ProtocolVersion,
     vendor_id::VendorId,
 };
@@ -20,8 +21,12 @@ where
     guid: Guid<P, Id>,
     protocol_version: ProtocolVersion,
     vendor_id: VendorId,
+    domain_id: u32,
+    domain_tag: String,
     default_unicast_locators: Vec<Locator>,
     default_multicast_locators: Vec<Locator>,
+    peer_locators: Vec<Locator>,
+    entity_ids: EntityIds,
 }
 
 /// A builder for a [`Participant`]
@@ -37,8 +42,11 @@ where
     guid: Guid<P, Id>,
     protocol_version: Option<ProtocolVersion>,
     vendor_id: Option<VendorId>,
+    domain_id: Option<u32>,
+    domain_tag: String,
     default_unicast_locators: Vec<Locator>,
     default_multicast_locators: Vec<Locator>,
+    peer_locators: Vec<Locator>,
 }
 
 impl<P, Id> Builder<P, Id>
@@ -50,14 +58,20 @@ where
         let guid = Guid::new(guid_prefix, entity_id);
         let protocol_version = None;
         let vendor_id = None;
+        let domain_id = None;
+        let domain_tag = String::new();
         let default_unicast_locators = Vec::default();
         let default_multicast_locators = Vec::default();
+        let peer_locators = Vec::default();
         Self {
             guid,
             protocol_version,
             vendor_id,
+            domain_id,
+            domain_tag,
             default_unicast_locators,
             default_multicast_locators,
+            peer_locators,
         }
     }
 
@@ -75,6 +89,25 @@ where
         self
     }
 
+    /// Set the ID of the domain to which the participant belongs
+    ///
+    /// Defaults to domain 0. Participants only discover the participants of
+    /// the same domain.
+    pub fn domain_id(mut self, domain_id: u32) -> Self {
+        self.domain_id = Some(domain_id);
+        self
+    }
+
+    /// Set the domain tag of the participant
+    ///
+    /// Defaults to the empty tag. Participants only discover the participants
+    /// with the same domain tag, which separates networks which share a
+    /// domain ID.
+    pub fn domain_tag(mut self, domain_tag: impl Into<String>) -> Self {
+        self.domain_tag = domain_tag.into();
+        self
+    }
+
     /// If configured, the default unicast locators will be used when creating
     /// new [`Group`]s
     pub fn default_unicast_locators<I, L>(mut self, locators: I) -> Self
@@ -99,6 +132,23 @@ where
         self
     }
 
+    /// If configured, the participant uses static discovery, and announces
+    /// itself only to these unicast locators
+    ///
+    /// This is for networks which don't support multicast. The locators are
+    /// usually the metatraffic unicast locators of the known peers. They are
+    /// used as the announcement locators of
+    /// [`ParticipantDiscovery::for_participant`](crate::discovery::ParticipantDiscovery::for_participant).
+    pub fn peer_locators<I, L>(mut self, locators: I) -> Self
+    where
+        I: IntoIterator<Item = L>,
+        L: Into<Locator>,
+    {
+        let peer_locators = locators.into_iter().map(Into::into).collect();
+        self.peer_locators = peer_locators;
+        self
+    }
+
     /// Consume the [`Builder`] and return a configured [`Participant`]
     #[must_use]
     pub fn build(self) -> Participant<P, Id> {
@@ -106,8 +156,12 @@ where
             guid: self.guid,
             protocol_version: self.protocol_version.unwrap_or_default(),
             vendor_id: self.vendor_id.unwrap_or_default(),
+            domain_id: self.domain_id.unwrap_or_default(),
+            domain_tag: self.domain_tag,
             default_unicast_locators: self.default_unicast_locators,
             default_multicast_locators: self.default_multicast_locators,
+            peer_locators: self.peer_locators,
+            entity_ids: EntityIds::default(),
         }
     }
 }
@@ -180,22 +234,79 @@ where
         self.vendor_id
     }
 
+    /// The ID of the domain to which this participant belongs
+    #[must_use]
+    pub fn domain_id(&self) -> u32 {
+        self.domain_id
+    }
+
+    /// The domain tag of this participant
+    #[must_use]
+    pub fn domain_tag(&self) -> &str {
+        &self.domain_tag
+    }
+
     /// A set of default unicast locators used when constructing new
-    /// [`Endpoint`](super::endpoint::Endpoint)s
+    /// [`Endpoint`](super::Endpoint)s
     #[must_use]
     pub fn default_unicast_locators(&self) -> &Vec<Locator> {
         &self.default_unicast_locators
     }
 
     /// A set of default multicast locators used when constructing new
-    /// [`Endpoint`](super::endpoint::Endpoint)s
+    /// [`Endpoint`](super::Endpoint)s
     #[must_use]
     pub fn default_multicast_locators(&self) -> &Vec<Locator> {
         &self.default_multicast_locators
     }
 
+    /// The unicast locators of the peers to which the participant is
+    /// announced, when using static discovery
+    ///
+    /// This is empty if the participant uses multicast discovery.
+    #[must_use]
+    pub fn peer_locators(&self) -> &Vec<Locator> {
+        &self.peer_locators
+    }
+
+    /// The [`ParticipantProxy`] which announces this participant with SPDP
+    ///
+    /// The metatraffic [`Locator`]s depend on the transport, and are left
+    /// empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtps_pim::structure::Participant;
+    ///
+    /// let participant = Participant::builder([1; 12], [0, 0, 1, 0xc1])
+    ///     .domain_id(3)
+    ///     .domain_tag("bench-2")
+    ///     .build();
+    ///
+    /// let proxy = participant.proxy();
+    /// assert_eq!(proxy.domain_id, Some(3));
+    /// assert_eq!(proxy.domain_tag, "bench-2");
+    /// ```
+    #[must_use]
+    pub fn proxy(&self) -> ParticipantProxy<P> {
+        ParticipantProxy {
+            protocol_version: self.protocol_version,
+            vendor_id: self.vendor_id,
+            domain_id: Some(self.domain_id),
+            domain_tag: self.domain_tag.clone(),
+            default_unicast_locators: self.default_unicast_locators.clone(),
+            default_multicast_locators: self.default_multicast_locators.clone(),
+            ..ParticipantProxy::new(self.guid_prefix())
+        }
+    }
+
     /// Construct a new [`Publisher`] [`Group`] using the [`Guid`] prefix of the
-    /// [`Participant`] and a given entity ID
+    /// [`Participant`] and a newly allocated entity ID
+    ///
+    /// The writers of the publisher use the default [`Locator`]s of the
+    /// participant, unless others are given. Returns `None` if the participant
+    /// has run out of entity IDs.
     ///
     /// # Example
     ///
@@ -203,23 +314,57 @@ where
     /// # use rtps_pim::structure::Participant;
     /// #
     /// # let guid_prefix = [0; 12];
-    /// # let entity_id = [0, 0, 0, 1];
+    /// # let entity_id = [0, 0, 1, 0xc1];
     /// #
     /// # let participant = Participant::new(guid_prefix, entity_id);
-    /// let publisher_id = [0, 0, 0, 2];
-    /// let publisher = participant.publisher(publisher_id);
+    /// use rtps_pim::structure::Entity;
+    ///
+    /// let publisher = participant.publisher().unwrap();
+    /// assert_eq!(publisher.entity_id(), [0, 0, 1, 0x08]);
     /// ```
     #[must_use]
-    pub fn publisher(&self, entity_id: Id) -> Publisher<P, Id> {
-        Group::new(self.guid_prefix(), entity_id)
+    pub fn publisher(&self) -> Option<Publisher<P, Id>>
+    where
+        Id: From<[u8; 4]>,
+    {
+        self.group(entity_kind::USER_WRITER_GROUP)
     }
 
     /// Construct a new [`Subscriber`] [`Group`] using the [`Guid`] prefix of
-    /// the [`Participant`] and a given entity ID
+    /// the [`Participant`] and a newly allocated entity ID
+    ///
+    /// The readers of the subscriber use the default [`Locator`]s of the
+    /// participant, unless others are given. Returns `None` if the participant
+    /// has run out of entity IDs.
     ///
     /// # Example
+    ///
+    /// ```
+    /// # use rtps_pim::structure::Participant;
+    /// #
+    /// # let participant = Participant::new([0; 12], [0, 0, 1, 0xc1]);
+    /// use rtps_pim::structure::Entity;
+    ///
+    /// let subscriber = participant.subscriber().unwrap();
+    /// assert_eq!(subscriber.entity_id(), [0, 0, 1, 0x09]);
+    /// ```
     #[must_use]
-    pub fn subscriber(&self, entity_id: Id) -> Subscriber<P, Id> {
-        Group::new(self.guid_prefix(), entity_id)
+    pub fn subscriber(&self) -> Option<Subscriber<P, Id>>
+    where
+        Id: From<[u8; 4]>,
+    {
+        self.group(entity_kind::USER_READER_GROUP)
+    }
+
+    fn group<T>(&self, kind: u8) -> Option<Group<T, P, Id>>
+    where
+        Id: From<[u8; 4]>,
+    {
+        Some(Group::new(
+            Guid::new(self.guid_prefix(), self.entity_ids.next(kind)?),
+            self.entity_ids.clone(),
+            self.default_unicast_locators.clone(),
+            self.default_multicast_locators.clone(),
+        ))
     }
 }
@@ -0,0 +1,312 @@
+use std::{ops::BitOr, time::Duration};
+
+use super::{locator::Locator, protocol_version::ProtocolVersion, vendor_id::VendorId};
+
+/// The default lease duration of a participant
+pub const DEFAULT_LEASE_DURATION: Duration = Duration::from_secs(100);
+
+/// The set of built-in endpoints which a participant advertises
+/// (`BuiltinEndpointSet_t`)
+///
+/// Built-in endpoints are only matched with remote participants which
+/// advertise the corresponding endpoint.
+///
+/// See [section 8.5.3.2](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF) of the relevant specification document.
+///
+/// # Example
+///
+/// ```
+/// use rtps_pim::structure::BuiltinEndpointSet;
+///
+/// let endpoints =
+///     BuiltinEndpointSet::PUBLICATIONS_ANNOUNCER | BuiltinEndpointSet::SUBSCRIPTIONS_DETECTOR;
+///
+/// assert!(endpoints.contains(BuiltinEndpointSet::PUBLICATIONS_ANNOUNCER));
+/// assert!(!endpoints.contains(BuiltinEndpointSet::PUBLICATIONS_DETECTOR));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct BuiltinEndpointSet(u32);
+
+impl BuiltinEndpointSet {
+    /// The SPDP writer
+    pub const PARTICIPANT_ANNOUNCER: Self = Self(1 << 0);
+
+    /// The SPDP reader
+    pub const PARTICIPANT_DETECTOR: Self = Self(1 << 1);
+
+    /// The SEDP writer which announces publications
+    pub const PUBLICATIONS_ANNOUNCER: Self = Self(1 << 2);
+
+    /// The SEDP reader which receives publications
+    pub const PUBLICATIONS_DETECTOR: Self = Self(1 << 3);
+
+    /// The SEDP writer which announces subscriptions
+    pub const SUBSCRIPTIONS_ANNOUNCER: Self = Self(1 << 4);
+
+    /// The SEDP reader which receives subscriptions
+    pub const SUBSCRIPTIONS_DETECTOR: Self = Self(1 << 5);
+
+    /// The writer which asserts the liveliness of the participant
+    pub const PARTICIPANT_MESSAGE_WRITER: Self = Self(1 << 10);
+
+    /// The reader which monitors the liveliness of remote participants
+    pub const PARTICIPANT_MESSAGE_READER: Self = Self(1 << 11);
+
+    /// The built-in endpoints which are implemented by this crate
+    pub const SUPPORTED: Self = Self(
+        Self::PARTICIPANT_ANNOUNCER.0
+            | Self::PARTICIPANT_DETECTOR.0
+            | Self::PUBLICATIONS_ANNOUNCER.0
+            | Self::PUBLICATIONS_DETECTOR.0
+            | Self::SUBSCRIPTIONS_ANNOUNCER.0
+            | Self::SUBSCRIPTIONS_DETECTOR.0
+            | Self::PARTICIPANT_MESSAGE_WRITER.0
+            | Self::PARTICIPANT_MESSAGE_READER.0,
+    );
+
+    /// Construct a [`BuiltinEndpointSet`] from its raw bitmask
+    ///
+    /// Unknown bits are preserved.
+    #[must_use]
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// The raw bitmask
+    #[must_use]
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Whether every endpoint in `other` is also in this set
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Add the endpoints in `other` to this set
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    /// Remove the endpoints in `other` from this set
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+}
+
+impl BitOr for BuiltinEndpointSet {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// A model of a remote participant, as announced by SPDP
+///
+/// See [section 8.5.3.2](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF) of the relevant specification document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParticipantProxy<P> {
+    /// The [`Guid`](super::Guid) prefix of the participant
+    pub guid_prefix: P,
+
+    /// The protocol version of the participant
+    pub protocol_version: ProtocolVersion,
+
+    /// The vendor ID of the participant
+    pub vendor_id: VendorId,
+
+    /// The ID of the domain to which the participant belongs
+    ///
+    /// `None` if the participant didn't announce its domain, in which case it
+    /// is assumed to belong to the same domain as the local participant.
+    pub domain_id: Option<u32>,
+
+    /// The domain tag of the participant
+    ///
+    /// Participants only discover each other if their domain tags are equal.
+    /// The default is the empty tag.
+    pub domain_tag: String,
+
+    /// Whether the readers of the participant expect inline quality of
+    /// service parameters
+    pub expects_inline_qos: bool,
+
+    /// The built-in endpoints of the participant
+    pub builtin_endpoints: BuiltinEndpointSet,
+
+    /// The number of times the participant has manually asserted its
+    /// liveliness
+    pub manual_liveliness_count: i32,
+
+    /// The unicast [`Locator`]s for user traffic
+    pub default_unicast_locators: Vec<Locator>,
+
+    /// The multicast [`Locator`]s for user traffic
+    pub default_multicast_locators: Vec<Locator>,
+
+    /// The unicast [`Locator`]s for discovery traffic
+    pub metatraffic_unicast_locators: Vec<Locator>,
+
+    /// The multicast [`Locator`]s for discovery traffic
+    pub metatraffic_multicast_locators: Vec<Locator>,
+
+    /// How long the participant should be considered alive after each
+    /// announcement
+    ///
+    /// `None` is an infinite lease.
+    pub lease_duration: Option<Duration>,
+}
+
+impl<P> ParticipantProxy<P> {
+    /// Construct a new [`ParticipantProxy`] for the participant with the
+    /// given [`Guid`](super::Guid) prefix
+    ///
+    /// The participant has no locators, no domain ID, the empty domain tag,
+    /// the [`SUPPORTED`](BuiltinEndpointSet::SUPPORTED) built-in endpoints,
+    /// and the [`DEFAULT_LEASE_DURATION`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtps_pim::structure::{BuiltinEndpointSet, ParticipantProxy};
+    ///
+    /// let proxy = ParticipantProxy::new([1; 12]);
+    ///
+    /// assert!(proxy
+    ///     .builtin_endpoints
+    ///     .contains(BuiltinEndpointSet::PUBLICATIONS_DETECTOR));
+    /// ```
+    #[must_use]
+    pub fn new(guid_prefix: P) -> Self {
+        Self {
+            guid_prefix,
+            protocol_version: ProtocolVersion::default(),
+            vendor_id: VendorId::default(),
+            domain_id: None,
+            domain_tag: String::new(),
+            expects_inline_qos: false,
+            builtin_endpoints: BuiltinEndpointSet::SUPPORTED,
+            manual_liveliness_count: 0,
+            default_unicast_locators: Vec::new(),
+            default_multicast_locators: Vec::new(),
+            metatraffic_unicast_locators: Vec::new(),
+            metatraffic_multicast_locators: Vec::new(),
+            lease_duration: Some(DEFAULT_LEASE_DURATION),
+        }
+    }
+
+    /// Whether the participant belongs to the same domain, with the same
+    /// domain tag, as another
+    ///
+    /// Participants which don't belong to the same domain never discover each
+    /// other. A participant which didn't announce its domain ID is assumed to
+    /// belong to the same domain.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtps_pim::structure::ParticipantProxy;
+    ///
+    /// let local = ParticipantProxy {
+    ///     domain_id: Some(3),
+    ///     ..ParticipantProxy::new([1; 12])
+    /// };
+    /// let remote = ParticipantProxy {
+    ///     domain_id: Some(3),
+    ///     domain_tag: String::from("bench-2"),
+    ///     ..ParticipantProxy::new([2; 12])
+    /// };
+    ///
+    /// assert!(!local.same_domain(&remote));
+    /// ```
+    #[must_use]
+    pub fn same_domain<Q>(&self, other: &ParticipantProxy<Q>) -> bool {
+        let same_id = self
+            .domain_id
+            .zip(other.domain_id)
+            .map_or(true, |(id, other_id)| id == other_id);
+        same_id && self.domain_tag == other.domain_tag
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BuiltinEndpointSet, ParticipantProxy, DEFAULT_LEASE_DURATION};
+    use crate::structure::{ProtocolVersion, VendorId};
+    use test_case::test_case;
+
+    #[test_case(BuiltinEndpointSet::PARTICIPANT_ANNOUNCER => 0x0001)]
+    #[test_case(BuiltinEndpointSet::PARTICIPANT_DETECTOR => 0x0002)]
+    #[test_case(BuiltinEndpointSet::PUBLICATIONS_ANNOUNCER => 0x0004)]
+    #[test_case(BuiltinEndpointSet::PUBLICATIONS_DETECTOR => 0x0008)]
+    #[test_case(BuiltinEndpointSet::SUBSCRIPTIONS_ANNOUNCER => 0x0010)]
+    #[test_case(BuiltinEndpointSet::SUBSCRIPTIONS_DETECTOR => 0x0020)]
+    #[test_case(BuiltinEndpointSet::PARTICIPANT_MESSAGE_WRITER => 0x0400)]
+    #[test_case(BuiltinEndpointSet::PARTICIPANT_MESSAGE_READER => 0x0800)]
+    #[test_case(BuiltinEndpointSet::SUPPORTED => 0x0c3f ; "supported")]
+    fn endpoint_bits(endpoints: BuiltinEndpointSet) -> u32 {
+        endpoints.bits()
+    }
+
+    #[test]
+    fn unknown_endpoints_are_preserved() {
+        let mut endpoints = BuiltinEndpointSet::from_bits(0x1_0000 | 0x0001);
+
+        endpoints.insert(BuiltinEndpointSet::PARTICIPANT_DETECTOR);
+        assert_eq!(endpoints.bits(), 0x1_0003);
+
+        endpoints.remove(BuiltinEndpointSet::PARTICIPANT_ANNOUNCER);
+        assert_eq!(endpoints.bits(), 0x1_0002);
+        assert!(!BuiltinEndpointSet::SUPPORTED.contains(endpoints));
+    }
+
+    #[test]
+    fn contains_every_endpoint_of_a_subset() {
+        let announcers = BuiltinEndpointSet::PUBLICATIONS_ANNOUNCER
+            | BuiltinEndpointSet::SUBSCRIPTIONS_ANNOUNCER;
+
+        assert!(BuiltinEndpointSet::SUPPORTED.contains(announcers));
+        assert!(!announcers.contains(BuiltinEndpointSet::SUPPORTED));
+        assert!(announcers.contains(BuiltinEndpointSet::default()));
+    }
+
+    #[test]
+    fn defaults() {
+        let proxy = ParticipantProxy::new([1; 12]);
+
+        assert_eq!(proxy.guid_prefix, [1; 12]);
+        assert_eq!(proxy.protocol_version, ProtocolVersion::default());
+        assert_eq!(proxy.vendor_id, VendorId::default());
+        assert_eq!(proxy.domain_id, None);
+        assert!(proxy.domain_tag.is_empty());
+        assert!(!proxy.expects_inline_qos);
+        assert_eq!(proxy.builtin_endpoints, BuiltinEndpointSet::SUPPORTED);
+        assert_eq!(proxy.manual_liveliness_count, 0);
+        assert!(proxy.default_unicast_locators.is_empty());
+        assert!(proxy.default_multicast_locators.is_empty());
+        assert!(proxy.metatraffic_unicast_locators.is_empty());
+        assert!(proxy.metatraffic_multicast_locators.is_empty());
+        assert_eq!(proxy.lease_duration, Some(DEFAULT_LEASE_DURATION));
+    }
+
+    #[test_case(None, None, "" => true ; "unannounced")]
+    #[test_case(Some(1), None, "" => true ; "one unannounced")]
+    #[test_case(Some(1), Some(1), "" => true ; "equal domain")]
+    #[test_case(Some(1), Some(2), "" => false ; "different domain")]
+    #[test_case(Some(1), Some(1), "bench-2" => false ; "different tag")]
+    fn same_domain(domain_id: Option<u32>, other_id: Option<u32>, other_tag: &str) -> bool {
+        let proxy = ParticipantProxy {
+            domain_id,
+            ..ParticipantProxy::new([1; 12])
+        };
+        let other = ParticipantProxy {
+            domain_id: other_id,
+            domain_tag: String::from(other_tag),
+            ..ParticipantProxy::new([2; 12])
+        };
+        assert_eq!(proxy.same_domain(&other), other.same_domain(&proxy));
+        proxy.same_domain(&other)
+    }
+}
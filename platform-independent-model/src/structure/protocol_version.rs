@@ -1,5 +1,5 @@
 /// A description of the protocol version supported by this implementation
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum ProtocolVersion {
     /// An alias to the latest available version
     #[default]
@@ -0,0 +1,1001 @@
+//! Quality of service policies, and the rules for matching the policies
+//! offered by a writer with those requested by a reader
+//!
+//! Each policy has the default value given by the DDS specification. Only the
+//! policies which differ from their default are written to a
+//! [`ParameterList`], and missing policies are read as their default.
+//!
+//! See [section 2.2.3](https://www.omg.org/spec/DDS/1.4/PDF) of the DDS specification.
+//!
+//! # Example
+//!
+//! ```
+//! use rtps_pim::structure::qos::{
+//!     check_compatibility, Durability, Policy, ReaderQos, ReliabilityKind, WriterQos,
+//! };
+//!
+//! let offered = WriterQos::default();
+//! let mut requested = ReaderQos::default();
+//! requested.reliability.kind = ReliabilityKind::Reliable;
+//! assert!(check_compatibility(&offered, &requested).is_ok());
+//!
+//! requested.durability = Durability::TransientLocal;
+//! let error = check_compatibility(&offered, &requested).unwrap_err();
+//! assert_eq!(error.policies(), &[Policy::Durability]);
+//! ```
+
+use std::{num::NonZeroUsize, time::Duration};
+
+use crate::messages::{
+    encoding::{parameter, DecodeError, Parameters, Value},
+    submessage::elements::{parameter_id, ParameterList},
+};
+
+#[doc(no_inline)]
+pub use super::history::{Policy as History, ResourceLimits};
+#[doc(no_inline)]
+pub use crate::behaviour::{
+    liveliness::{Liveliness, LivelinessKind},
+    reader::DestinationOrder,
+};
+
+/// Whether a writer retransmits changes which a reader missed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum ReliabilityKind {
+    /// Changes are sent once, and may be lost
+    #[default]
+    BestEffort,
+
+    /// Missed changes are repaired
+    Reliable,
+}
+
+/// The `RELIABILITY` quality of service policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reliability {
+    /// Whether missed changes are repaired
+    pub kind: ReliabilityKind,
+
+    /// How long a reliable writer may block when its history is full
+    ///
+    /// `None` blocks indefinitely.
+    pub max_blocking_time: Option<Duration>,
+}
+
+impl Default for Reliability {
+    fn default() -> Self {
+        Self {
+            kind: ReliabilityKind::default(),
+            max_blocking_time: Some(Duration::from_millis(100)),
+        }
+    }
+}
+
+/// The `DURABILITY` quality of service policy, which controls whether changes
+/// are kept for readers which join later
+///
+/// Each kind is stronger than the one before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Durability {
+    /// Changes are only sent to the readers which are matched when they are
+    /// written
+    #[default]
+    Volatile,
+
+    /// Changes are kept by the writer for late-joining readers
+    TransientLocal,
+
+    /// Changes outlive the writer, for as long as the system is running
+    Transient,
+
+    /// Changes are kept in permanent storage
+    Persistent,
+}
+
+/// The `DEADLINE` quality of service policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Deadline {
+    /// The maximum period between changes to each instance
+    ///
+    /// `None` is an infinite period.
+    pub period: Option<Duration>,
+}
+
+/// The `LATENCY_BUDGET` quality of service policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LatencyBudget {
+    /// The maximum acceptable delay between writing and receiving a change
+    pub duration: Duration,
+}
+
+/// The `OWNERSHIP` quality of service policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ownership {
+    /// Every writer may update an instance
+    #[default]
+    Shared,
+
+    /// Only the writer with the highest [`OwnershipStrength`] may update an
+    /// instance
+    Exclusive,
+}
+
+/// The `OWNERSHIP_STRENGTH` quality of service policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OwnershipStrength {
+    /// The strength of the writer, when the [`Ownership`] is exclusive
+    pub value: i32,
+}
+
+/// The `LIFESPAN` quality of service policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Lifespan {
+    /// How long a change remains valid after it is written
+    ///
+    /// `None` is an infinite lifespan.
+    pub duration: Option<Duration>,
+}
+
+/// The `PARTITION` quality of service policy
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Partition {
+    /// The names of the partitions to which the endpoint belongs
+    ///
+    /// An empty list is the default partition, whose name is the empty
+    /// string. Names may be `fnmatch`-style patterns, using `*`, `?` and
+    /// `[...]`.
+    pub names: Vec<String>,
+}
+
+impl Partition {
+    /// Whether the partitions of two endpoints overlap
+    ///
+    /// At least one name in each partition must match. A pattern matches the
+    /// names it describes, but two patterns never match each other.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtps_pim::structure::qos::Partition;
+    ///
+    /// let robot = Partition {
+    ///     names: vec!["robot-7".to_string()],
+    /// };
+    /// let robots = Partition {
+    ///     names: vec!["robot-*".to_string()],
+    /// };
+    ///
+    /// assert!(robot.matches(&robots));
+    /// assert!(!robot.matches(&Partition::default()));
+    /// ```
+    #[must_use]
+    pub fn matches(&self, other: &Self) -> bool {
+        self.names_or_default().any(|name| {
+            other
+                .names_or_default()
+                .any(|other| name_matches(name, other))
+        })
+    }
+
+    fn names_or_default(&self) -> impl Iterator<Item = &str> + '_ {
+        let default = if self.names.is_empty() {
+            Some("")
+        } else {
+            None
+        };
+        self.names.iter().map(String::as_str).chain(default)
+    }
+}
+
+/// Whether two partition names match
+fn name_matches(name: &str, other: &str) -> bool {
+    let is_pattern = |name: &str| name.contains(|c| matches!(c, '*' | '?' | '['));
+    let chars = |name: &str| name.chars().collect::<Vec<_>>();
+
+    match (is_pattern(name), is_pattern(other)) {
+        (false, false) => name == other,
+        (true, false) => glob_matches(&chars(name), &chars(other)),
+        (false, true) => glob_matches(&chars(other), &chars(name)),
+        (true, true) => false,
+    }
+}
+
+/// Whether a name matches an `fnmatch`-style pattern
+///
+/// Only the most recent `*` is ever backtracked to, so this takes time
+/// proportional to the product of the lengths of the pattern and the name.
+fn glob_matches(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // the position after the most recent `*`, and the name position it has
+    // been matched up to
+    let mut star = None;
+
+    while n < name.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                star = Some((p, n));
+                continue;
+            }
+            Some('?') => Some(1),
+            Some('[') => bracket(&pattern[p + 1..])
+                .filter(|(class, _)| class(name[n]))
+                .map(|(_, rest)| pattern.len() - rest.len() - p),
+            Some(&c) => (c == name[n]).then(|| 1),
+            None => None,
+        };
+
+        match (step, star) {
+            (Some(step), _) => {
+                p += step;
+                n += 1;
+            }
+            (None, Some((star_p, star_n))) => {
+                // let the `*` match one more character
+                p = star_p;
+                n = star_n + 1;
+                star = Some((star_p, n));
+            }
+            (None, None) => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Parse a bracket expression (after the opening `[`), returning a predicate
+/// for the characters it matches, and the rest of the pattern
+fn bracket(pattern: &[char]) -> Option<(impl Fn(char) -> bool + '_, &[char])> {
+    let (negated, class) = match pattern.split_first() {
+        Some(('!' | '^', class)) => (true, class),
+        _ => (false, pattern),
+    };
+
+    // a `]` which opens the class is an ordinary character
+    let end = 1 + class.get(1..)?.iter().position(|&c| c == ']')?;
+    let (class, rest) = (&class[..end], &class[end + 1..]);
+
+    let predicate = move |c: char| {
+        let mut index = 0;
+        let mut matched = false;
+        while index < class.len() {
+            if let [start, '-', end, ..] = class[index..] {
+                matched |= (start..=end).contains(&c);
+                index += 3;
+            } else {
+                matched |= class[index] == c;
+                index += 1;
+            }
+        }
+        matched != negated
+    };
+    Some((predicate, rest))
+}
+
+/// The scope over which the [`Presentation`] policy applies
+///
+/// Each scope is wider than the one before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum PresentationAccessScope {
+    /// Changes to each instance are independent
+    #[default]
+    Instance,
+
+    /// Changes to the instances of a single writer are related
+    Topic,
+
+    /// Changes to the instances of all of the writers in a group are related
+    Group,
+}
+
+/// The `PRESENTATION` quality of service policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Presentation {
+    /// The scope of coherent and ordered access
+    pub access_scope: PresentationAccessScope,
+
+    /// Whether sets of changes are made visible all at once
+    pub coherent_access: bool,
+
+    /// Whether the order of changes is preserved across the access scope
+    pub ordered_access: bool,
+}
+
+/// The `TIME_BASED_FILTER` quality of service policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TimeBasedFilter {
+    /// The minimum period between the changes to each instance which a
+    /// reader is interested in
+    pub minimum_separation: Duration,
+}
+
+/// The `USER_DATA` quality of service policy
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct UserData {
+    /// Application data attached to an endpoint
+    pub value: Vec<u8>,
+}
+
+/// The `TOPIC_DATA` quality of service policy
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TopicData {
+    /// Application data attached to a topic
+    pub value: Vec<u8>,
+}
+
+/// The `GROUP_DATA` quality of service policy
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct GroupData {
+    /// Application data attached to a publisher or subscriber
+    pub value: Vec<u8>,
+}
+
+/// The quality of service policies offered by a writer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriterQos {
+    /// The [`Durability`] policy
+    pub durability: Durability,
+
+    /// The [`Deadline`] policy
+    pub deadline: Deadline,
+
+    /// The [`LatencyBudget`] policy
+    pub latency_budget: LatencyBudget,
+
+    /// The [`Liveliness`] policy
+    pub liveliness: Liveliness,
+
+    /// The [`Reliability`] policy
+    pub reliability: Reliability,
+
+    /// The [`Lifespan`] policy
+    pub lifespan: Lifespan,
+
+    /// The [`UserData`] policy
+    pub user_data: UserData,
+
+    /// The [`Ownership`] policy
+    pub ownership: Ownership,
+
+    /// The [`OwnershipStrength`] policy
+    pub ownership_strength: OwnershipStrength,
+
+    /// The [`DestinationOrder`] policy
+    pub destination_order: DestinationOrder,
+
+    /// The [`History`] policy
+    pub history: History,
+
+    /// The [`ResourceLimits`] policy
+    pub resource_limits: ResourceLimits,
+
+    /// The [`Presentation`] policy
+    pub presentation: Presentation,
+
+    /// The [`Partition`] policy
+    pub partition: Partition,
+
+    /// The [`TopicData`] policy
+    pub topic_data: TopicData,
+
+    /// The [`GroupData`] policy
+    pub group_data: GroupData,
+}
+
+impl Default for WriterQos {
+    /// The default policies of a writer, which are reliable
+    fn default() -> Self {
+        Self {
+            durability: Durability::default(),
+            deadline: Deadline::default(),
+            latency_budget: LatencyBudget::default(),
+            liveliness: Liveliness::default(),
+            reliability: Reliability {
+                kind: ReliabilityKind::Reliable,
+                ..Reliability::default()
+            },
+            lifespan: Lifespan::default(),
+            user_data: UserData::default(),
+            ownership: Ownership::default(),
+            ownership_strength: OwnershipStrength::default(),
+            destination_order: DestinationOrder::default(),
+            history: default_history(),
+            resource_limits: ResourceLimits::default(),
+            presentation: Presentation::default(),
+            partition: Partition::default(),
+            topic_data: TopicData::default(),
+            group_data: GroupData::default(),
+        }
+    }
+}
+
+impl WriterQos {
+    /// Convert the policies which differ from their defaults to a
+    /// [`ParameterList`]
+    #[must_use]
+    pub fn to_parameter_list(&self) -> ParameterList {
+        let default = Self::default();
+        let mut list = ParameterList::default();
+        push(
+            &mut list,
+            parameter_id::DURABILITY,
+            &self.durability,
+            &default.durability,
+        );
+        push(
+            &mut list,
+            parameter_id::DEADLINE,
+            &self.deadline,
+            &default.deadline,
+        );
+        push(
+            &mut list,
+            parameter_id::LATENCY_BUDGET,
+            &self.latency_budget,
+            &default.latency_budget,
+        );
+        push(
+            &mut list,
+            parameter_id::LIVELINESS,
+            &self.liveliness,
+            &default.liveliness,
+        );
+        push(
+            &mut list,
+            parameter_id::RELIABILITY,
+            &self.reliability,
+            &default.reliability,
+        );
+        push(
+            &mut list,
+            parameter_id::LIFESPAN,
+            &self.lifespan,
+            &default.lifespan,
+        );
+        push(
+            &mut list,
+            parameter_id::USER_DATA,
+            &self.user_data,
+            &default.user_data,
+        );
+        push(
+            &mut list,
+            parameter_id::OWNERSHIP,
+            &self.ownership,
+            &default.ownership,
+        );
+        push(
+            &mut list,
+            parameter_id::OWNERSHIP_STRENGTH,
+            &self.ownership_strength,
+            &default.ownership_strength,
+        );
+        push(
+            &mut list,
+            parameter_id::DESTINATION_ORDER,
+            &self.destination_order,
+            &default.destination_order,
+        );
+        push(
+            &mut list,
+            parameter_id::HISTORY,
+            &self.history,
+            &default.history,
+        );
+        push(
+            &mut list,
+            parameter_id::RESOURCE_LIMITS,
+            &self.resource_limits,
+            &default.resource_limits,
+        );
+        push(
+            &mut list,
+            parameter_id::PRESENTATION,
+            &self.presentation,
+            &default.presentation,
+        );
+        push(
+            &mut list,
+            parameter_id::PARTITION,
+            &self.partition,
+            &default.partition,
+        );
+        push(
+            &mut list,
+            parameter_id::TOPIC_DATA,
+            &self.topic_data,
+            &default.topic_data,
+        );
+        push(
+            &mut list,
+            parameter_id::GROUP_DATA,
+            &self.group_data,
+            &default.group_data,
+        );
+        list
+    }
+
+    pub(crate) fn from_parameters(parameters: &Parameters) -> Result<Self, DecodeError> {
+        let default = Self::default();
+        Ok(Self {
+            durability: read(parameters, parameter_id::DURABILITY, default.durability)?,
+            deadline: read(parameters, parameter_id::DEADLINE, default.deadline)?,
+            latency_budget: read(
+                parameters,
+                parameter_id::LATENCY_BUDGET,
+                default.latency_budget,
+            )?,
+            liveliness: read(parameters, parameter_id::LIVELINESS, default.liveliness)?,
+            reliability: read(parameters, parameter_id::RELIABILITY, default.reliability)?,
+            lifespan: read(parameters, parameter_id::LIFESPAN, default.lifespan)?,
+            user_data: read(parameters, parameter_id::USER_DATA, default.user_data)?,
+            ownership: read(parameters, parameter_id::OWNERSHIP, default.ownership)?,
+            ownership_strength: read(
+                parameters,
+                parameter_id::OWNERSHIP_STRENGTH,
+                default.ownership_strength,
+            )?,
+            destination_order: read(
+                parameters,
+                parameter_id::DESTINATION_ORDER,
+                default.destination_order,
+            )?,
+            history: read(parameters, parameter_id::HISTORY, default.history)?,
+            resource_limits: read(
+                parameters,
+                parameter_id::RESOURCE_LIMITS,
+                default.resource_limits,
+            )?,
+            presentation: read(parameters, parameter_id::PRESENTATION, default.presentation)?,
+            partition: read(parameters, parameter_id::PARTITION, default.partition)?,
+            topic_data: read(parameters, parameter_id::TOPIC_DATA, default.topic_data)?,
+            group_data: read(parameters, parameter_id::GROUP_DATA, default.group_data)?,
+        })
+    }
+}
+
+/// The quality of service policies requested by a reader
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReaderQos {
+    /// The [`Durability`] policy
+    pub durability: Durability,
+
+    /// The [`Deadline`] policy
+    pub deadline: Deadline,
+
+    /// The [`LatencyBudget`] policy
+    pub latency_budget: LatencyBudget,
+
+    /// The [`Liveliness`] policy
+    pub liveliness: Liveliness,
+
+    /// The [`Reliability`] policy
+    pub reliability: Reliability,
+
+    /// The [`Ownership`] policy
+    pub ownership: Ownership,
+
+    /// The [`DestinationOrder`] policy
+    pub destination_order: DestinationOrder,
+
+    /// The [`UserData`] policy
+    pub user_data: UserData,
+
+    /// The [`TimeBasedFilter`] policy
+    pub time_based_filter: TimeBasedFilter,
+
+    /// The [`History`] policy
+    pub history: History,
+
+    /// The [`ResourceLimits`] policy
+    pub resource_limits: ResourceLimits,
+
+    /// The [`Presentation`] policy
+    pub presentation: Presentation,
+
+    /// The [`Partition`] policy
+    pub partition: Partition,
+
+    /// The [`TopicData`] policy
+    pub topic_data: TopicData,
+
+    /// The [`GroupData`] policy
+    pub group_data: GroupData,
+}
+
+impl Default for ReaderQos {
+    /// The default policies of a reader, which are best-effort
+    fn default() -> Self {
+        Self {
+            durability: Durability::default(),
+            deadline: Deadline::default(),
+            latency_budget: LatencyBudget::default(),
+            liveliness: Liveliness::default(),
+            reliability: Reliability::default(),
+            ownership: Ownership::default(),
+            destination_order: DestinationOrder::default(),
+            user_data: UserData::default(),
+            time_based_filter: TimeBasedFilter::default(),
+            history: default_history(),
+            resource_limits: ResourceLimits::default(),
+            presentation: Presentation::default(),
+            partition: Partition::default(),
+            topic_data: TopicData::default(),
+            group_data: GroupData::default(),
+        }
+    }
+}
+
+impl ReaderQos {
+    /// Convert the policies which differ from their defaults to a
+    /// [`ParameterList`]
+    #[must_use]
+    pub fn to_parameter_list(&self) -> ParameterList {
+        let default = Self::default();
+        let mut list = ParameterList::default();
+        push(
+            &mut list,
+            parameter_id::DURABILITY,
+            &self.durability,
+            &default.durability,
+        );
+        push(
+            &mut list,
+            parameter_id::DEADLINE,
+            &self.deadline,
+            &default.deadline,
+        );
+        push(
+            &mut list,
+            parameter_id::LATENCY_BUDGET,
+            &self.latency_budget,
+            &default.latency_budget,
+        );
+        push(
+            &mut list,
+            parameter_id::LIVELINESS,
+            &self.liveliness,
+            &default.liveliness,
+        );
+        push(
+            &mut list,
+            parameter_id::RELIABILITY,
+            &self.reliability,
+            &default.reliability,
+        );
+        push(
+            &mut list,
+            parameter_id::OWNERSHIP,
+            &self.ownership,
+            &default.ownership,
+        );
+        push(
+            &mut list,
+            parameter_id::DESTINATION_ORDER,
+            &self.destination_order,
+            &default.destination_order,
+        );
+        push(
+            &mut list,
+            parameter_id::USER_DATA,
+            &self.user_data,
+            &default.user_data,
+        );
+        push(
+            &mut list,
+            parameter_id::TIME_BASED_FILTER,
+            &self.time_based_filter,
+            &default.time_based_filter,
+        );
+        push(
+            &mut list,
+            parameter_id::HISTORY,
+            &self.history,
+            &default.history,
+        );
+        push(
+            &mut list,
+            parameter_id::RESOURCE_LIMITS,
+            &self.resource_limits,
+            &default.resource_limits,
+        );
+        push(
+            &mut list,
+            parameter_id::PRESENTATION,
+            &self.presentation,
+            &default.presentation,
+        );
+        push(
+            &mut list,
+            parameter_id::PARTITION,
+            &self.partition,
+            &default.partition,
+        );
+        push(
+            &mut list,
+            parameter_id::TOPIC_DATA,
+            &self.topic_data,
+            &default.topic_data,
+        );
+        push(
+            &mut list,
+            parameter_id::GROUP_DATA,
+            &self.group_data,
+            &default.group_data,
+        );
+        list
+    }
+
+    pub(crate) fn from_parameters(parameters: &Parameters) -> Result<Self, DecodeError> {
+        let default = Self::default();
+        Ok(Self {
+            durability: read(parameters, parameter_id::DURABILITY, default.durability)?,
+            deadline: read(parameters, parameter_id::DEADLINE, default.deadline)?,
+            latency_budget: read(
+                parameters,
+                parameter_id::LATENCY_BUDGET,
+                default.latency_budget,
+            )?,
+            liveliness: read(parameters, parameter_id::LIVELINESS, default.liveliness)?,
+            reliability: read(parameters, parameter_id::RELIABILITY, default.reliability)?,
+            ownership: read(parameters, parameter_id::OWNERSHIP, default.ownership)?,
+            destination_order: read(
+                parameters,
+                parameter_id::DESTINATION_ORDER,
+                default.destination_order,
+            )?,
+            user_data: read(parameters, parameter_id::USER_DATA, default.user_data)?,
+            time_based_filter: read(
+                parameters,
+                parameter_id::TIME_BASED_FILTER,
+                default.time_based_filter,
+            )?,
+            history: read(parameters, parameter_id::HISTORY, default.history)?,
+            resource_limits: read(
+                parameters,
+                parameter_id::RESOURCE_LIMITS,
+                default.resource_limits,
+            )?,
+            presentation: read(parameters, parameter_id::PRESENTATION, default.presentation)?,
+            partition: read(parameters, parameter_id::PARTITION, default.partition)?,
+            topic_data: read(parameters, parameter_id::TOPIC_DATA, default.topic_data)?,
+            group_data: read(parameters, parameter_id::GROUP_DATA, default.group_data)?,
+        })
+    }
+}
+
+/// The default `HISTORY` policy, which keeps the latest change to each
+/// instance
+fn default_history() -> History {
+    NonZeroUsize::new(1).map_or(History::KeepAll, History::KeepLast)
+}
+
+/// Append a policy to a [`ParameterList`], unless it has its default value
+fn push<T: Value + PartialEq>(list: &mut ParameterList, id: u16, value: &T, default: &T) {
+    if value != default {
+        list.push(parameter(id, value));
+    }
+}
+
+/// Read a policy, which has the given default value if it is missing
+fn read<T: Value>(parameters: &Parameters, id: u16, default: T) -> Result<T, DecodeError> {
+    Ok(parameters.optional(id)?.unwrap_or(default))
+}
+
+/// A quality of service policy which takes part in requested-versus-offered
+/// matching
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// The [`Durability`] offered is weaker than requested
+    Durability,
+
+    /// The [`Presentation`] offered is weaker than requested
+    Presentation,
+
+    /// The [`Deadline`] offered is longer than requested
+    Deadline,
+
+    /// The [`LatencyBudget`] offered is longer than requested
+    LatencyBudget,
+
+    /// The [`Ownership`] offered differs from that requested
+    Ownership,
+
+    /// The [`Liveliness`] offered is weaker, or its lease longer, than
+    /// requested
+    Liveliness,
+
+    /// The [`Reliability`] offered is weaker than requested
+    Reliability,
+
+    /// The [`DestinationOrder`] offered is weaker than requested
+    DestinationOrder,
+}
+
+/// The error returned when the policies offered by a writer don't satisfy
+/// those requested by a reader
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("incompatible quality of service policies: {policies:?}")]
+pub struct IncompatibleQos {
+    policies: Vec<Policy>,
+}
+
+impl IncompatibleQos {
+    /// The policies which are incompatible
+    #[must_use]
+    pub fn policies(&self) -> &[Policy] {
+        &self.policies
+    }
+}
+
+/// Check that the policies offered by a writer satisfy those requested by a
+/// reader
+///
+/// A writer and a reader may only be matched if, for each policy, the writer
+/// offers at least what the reader requests.
+///
+/// # Errors
+///
+/// This method will fail if any policy is incompatible. The error lists every
+/// incompatible [`Policy`].
+pub fn check_compatibility(
+    offered: &WriterQos,
+    requested: &ReaderQos,
+) -> Result<(), IncompatibleQos> {
+    let presentation = offered.presentation.access_scope >= requested.presentation.access_scope
+        && (offered.presentation.coherent_access || !requested.presentation.coherent_access)
+        && (offered.presentation.ordered_access || !requested.presentation.ordered_access);
+    let liveliness = offered.liveliness.kind >= requested.liveliness.kind
+        && at_most(
+            offered.liveliness.lease_duration,
+            requested.liveliness.lease_duration,
+        );
+    let destination_order = offered.destination_order == requested.destination_order
+        || requested.destination_order == DestinationOrder::ByReceptionTimestamp;
+
+    let policies: Vec<_> = [
+        (
+            Policy::Durability,
+            offered.durability >= requested.durability,
+        ),
+        (Policy::Presentation, presentation),
+        (
+            Policy::Deadline,
+            at_most(offered.deadline.period, requested.deadline.period),
+        ),
+        (
+            Policy::LatencyBudget,
+            offered.latency_budget.duration <= requested.latency_budget.duration,
+        ),
+        (Policy::Ownership, offered.ownership == requested.ownership),
+        (Policy::Liveliness, liveliness),
+        (
+            Policy::Reliability,
+            offered.reliability.kind >= requested.reliability.kind,
+        ),
+        (Policy::DestinationOrder, destination_order),
+    ]
+    .iter()
+    .filter(|(_, compatible)| !compatible)
+    .map(|&(policy, _)| policy)
+    .collect();
+
+    if policies.is_empty() {
+        Ok(())
+    } else {
+        Err(IncompatibleQos { policies })
+    }
+}
+
+/// Whether a possibly infinite duration is no longer than another
+fn at_most(offered: Option<Duration>, requested: Option<Duration>) -> bool {
+    match (offered, requested) {
+        (_, None) => true,
+        (None, Some(_)) => false,
+        (Some(offered), Some(requested)) => offered <= requested,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_compatibility, Deadline, DestinationOrder, Durability, History, Liveliness,
+        LivelinessKind, Ownership, Partition, Policy, Presentation, PresentationAccessScope,
+        ReaderQos, Reliability, ReliabilityKind, UserData, WriterQos,
+    };
+    use crate::messages::encoding::{self, Parameters};
+    use std::{num::NonZeroUsize, time::Duration};
+    use test_case::test_case;
+
+    #[test_case(&[], &[] => true ; "default partitions")]
+    #[test_case(&["a"], &[] => false ; "named and default")]
+    #[test_case(&["*"], &[] => true ; "wildcard and default")]
+    #[test_case(&["a", "b"], &["c", "b"] => true ; "any name")]
+    #[test_case(&["robot-?"], &["robot-7"] => true ; "single character")]
+    #[test_case(&["robot-?"], &["robot-17"] => false ; "single character mismatch")]
+    #[test_case(&["robot-[0-4]"], &["robot-3"] => true ; "range")]
+    #[test_case(&["robot-[!0-4]"], &["robot-3"] => false ; "negated range")]
+    #[test_case(&["site/*/arm"], &["site/b/arm"] => true ; "nested wildcard")]
+    #[test_case(&["robot-*"], &["robot-*"] => false ; "two patterns")]
+    #[test_case(&["*-arm*"], &["robot-arm"] => true ; "trailing wildcard")]
+    #[test_case(&["robot-[0-4"], &["robot-3"] => false ; "unclosed bracket")]
+    #[test_case(&["*a*a*a*a*a*a*a*a*a*a*a*a*b"], &["aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"] => false ; "backtracking")]
+    fn partitions_match(names: &[&str], other: &[&str]) -> bool {
+        let partition = |names: &[&str]| Partition {
+            names: names.iter().map(ToString::to_string).collect(),
+        };
+        let (partition, other) = (partition(names), partition(other));
+        assert_eq!(partition.matches(&other), other.matches(&partition));
+        partition.matches(&other)
+    }
+
+    #[test]
+    fn default_policies_are_omitted() {
+        assert!(WriterQos::default().to_parameter_list().is_empty());
+        assert!(ReaderQos::default().to_parameter_list().is_empty());
+    }
+
+    #[test]
+    fn round_trip() {
+        let qos = WriterQos {
+            durability: Durability::TransientLocal,
+            deadline: Deadline {
+                period: Some(Duration::from_millis(250)),
+            },
+            liveliness: Liveliness {
+                kind: LivelinessKind::ManualByParticipant,
+                lease_duration: Some(Duration::from_secs(2)),
+            },
+            reliability: Reliability {
+                kind: ReliabilityKind::BestEffort,
+                max_blocking_time: None,
+            },
+            user_data: UserData {
+                value: vec![1, 2, 3],
+            },
+            ownership: Ownership::Exclusive,
+            history: History::KeepLast(NonZeroUsize::new(8).unwrap()),
+            presentation: Presentation {
+                access_scope: PresentationAccessScope::Topic,
+                coherent_access: true,
+                ordered_access: false,
+            },
+            partition: Partition {
+                names: vec!["a".to_string(), "sensors*".to_string()],
+            },
+            ..WriterQos::default()
+        };
+
+        let bytes = encoding::to_bytes(&qos.to_parameter_list());
+        let parameters = Parameters::from_bytes(&bytes).unwrap();
+        assert_eq!(WriterQos::from_parameters(&parameters).unwrap(), qos);
+    }
+
+    #[test_case(|_, _| {} => Ok(()) ; "defaults")]
+    #[test_case(|_, r| r.reliability.kind = ReliabilityKind::Reliable => Ok(()) ; "reliable")]
+    #[test_case(|w, _| w.reliability.kind = ReliabilityKind::BestEffort => Ok(()) ; "best effort")]
+    #[test_case(|w, r| {
+        w.reliability.kind = ReliabilityKind::BestEffort;
+        r.reliability.kind = ReliabilityKind::Reliable;
+    } => Err(vec![Policy::Reliability]) ; "reliable requested")]
+    #[test_case(|_, r| r.durability = Durability::Persistent => Err(vec![Policy::Durability]) ; "durability")]
+    #[test_case(|w, r| {
+        w.deadline.period = Some(Duration::from_secs(2));
+        r.deadline.period = Some(Duration::from_secs(1));
+    } => Err(vec![Policy::Deadline]) ; "deadline")]
+    #[test_case(|w, r| {
+        w.liveliness.kind = LivelinessKind::ManualByTopic;
+        r.liveliness.lease_duration = Some(Duration::from_secs(1));
+    } => Err(vec![Policy::Liveliness]) ; "liveliness lease")]
+    #[test_case(|w, r| {
+        w.ownership = Ownership::Exclusive;
+        r.destination_order = DestinationOrder::BySourceTimestamp;
+    } => Err(vec![Policy::Ownership, Policy::DestinationOrder]) ; "several")]
+    #[test_case(|_, r| r.presentation.coherent_access = true => Err(vec![Policy::Presentation]) ; "presentation")]
+    fn compatibility(change: fn(&mut WriterQos, &mut ReaderQos)) -> Result<(), Vec<Policy>> {
+        let mut offered = WriterQos::default();
+        let mut requested = ReaderQos::default();
+        change(&mut offered, &mut requested);
+        check_compatibility(&offered, &requested).map_err(|error| error.policies().to_vec())
+    }
+}
@@ -1,5 +1,5 @@
 /// The vendor ID associated with this implementation of the RTPS.
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum VendorId {
     /// Represents and unknown or unspecified vendor ID
     #[default]
@@ -0,0 +1,216 @@
+//! A standalone RTPS discovery server
+//!
+//! Client participants announce themselves to the server's address, rather
+//! than to the multicast group, and the server relays the endpoints of each
+//! client to the other clients which could match them.
+//!
+//! ```text
+//! discovery-server <address> <guid-prefix> [<domain-id> [<domain-tag>]]
+//! ```
+//!
+//! where `<address>` is the socket address to listen on (for example
+//! `0.0.0.0:7400`), and `<guid-prefix>` is the
+//! [`Guid`](rtps_pim::structure::Guid) prefix of the server, as 24 hexadecimal
+//! digits. The server only serves clients in its domain, which defaults to
+//! domain 0 with the empty domain tag.
+
+#![deny(
+    clippy::all,
+    missing_debug_implementations,
+    missing_docs,
+    clippy::cargo,
+    unsafe_code
+)]
+#![warn(clippy::pedantic)]
+
+use std::{
+    env,
+    io::{self, ErrorKind},
+    net::{SocketAddr, UdpSocket},
+    process,
+    time::{Duration, Instant},
+};
+
+use rtps_pim::{
+    behaviour::receiver::{Context, Dispatch, MessageReceiver, ReaderSubMessage, WriterSubMessage},
+    discovery::{DiscoveredParticipantData, DiscoveryServer},
+    structure::{history::HistoryCache, Locator},
+};
+use rtps_udp::messages::{self, Message};
+
+type Server = DiscoveryServer<HistoryCache<Vec<u8>, [u8; 12], [u8; 4]>, [u8; 12], [u8; 4]>;
+
+/// The largest UDP datagram
+const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+/// How long to wait for a datagram when the server has nothing to send
+const IDLE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The command line arguments of the server
+#[derive(Debug)]
+struct Args {
+    address: SocketAddr,
+    guid_prefix: [u8; 12],
+    domain_id: u32,
+    domain_tag: String,
+}
+
+fn main() {
+    if let Some(args) = parse_args(env::args().skip(1)) {
+        if let Err(e) = run(args) {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    } else {
+        eprintln!("usage: discovery-server <address> <guid-prefix> [<domain-id> [<domain-tag>]]");
+        process::exit(2);
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Option<Args> {
+    let address = args.next()?.parse().ok()?;
+    let guid_prefix = args.next()?;
+    if guid_prefix.len() != 24 || !guid_prefix.is_ascii() {
+        return None;
+    }
+
+    let mut prefix = [0; 12];
+    for (i, byte) in prefix.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&guid_prefix[2 * i..2 * i + 2], 16).ok()?;
+    }
+
+    let domain_id = match args.next() {
+        Some(domain_id) => domain_id.parse().ok()?,
+        None => 0,
+    };
+    let domain_tag = args.next().unwrap_or_default();
+    if args.next().is_some() {
+        return None;
+    }
+
+    Some(Args {
+        address,
+        guid_prefix: prefix,
+        domain_id,
+        domain_tag,
+    })
+}
+
+fn run(args: Args) -> io::Result<()> {
+    let socket = UdpSocket::bind(args.address)?;
+    let receiver = MessageReceiver::new(args.guid_prefix);
+    let mut server = Server::new(DiscoveredParticipantData {
+        domain_id: Some(args.domain_id),
+        domain_tag: args.domain_tag,
+        metatraffic_unicast_locators: vec![socket.local_addr()?.into()],
+        ..DiscoveredParticipantData::new(args.guid_prefix)
+    });
+    eprintln!(
+        "listening on {} in domain {}",
+        socket.local_addr()?,
+        args.domain_id
+    );
+
+    let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+    let mut deadline = Some(Instant::now());
+    loop {
+        let now = Instant::now();
+        if deadline.map_or(false, |deadline| deadline <= now) {
+            send(&socket, &mut server, now);
+            deadline = server.next_deadline();
+        }
+
+        let timeout = deadline.map_or(IDLE_TIMEOUT, |deadline| {
+            deadline
+                .saturating_duration_since(Instant::now())
+                .max(Duration::from_millis(1))
+        });
+        socket.set_read_timeout(Some(timeout))?;
+
+        let (len, source) = match socket.recv_from(&mut buffer) {
+            Ok(datagram) => datagram,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e),
+        };
+
+        let message = match messages::from_bytes(&buffer[..len]) {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("discarding message from {}: {}", source, e);
+                continue;
+            }
+        };
+
+        let mut dispatcher = Dispatcher {
+            server: &mut server,
+            now: Instant::now(),
+        };
+        if let Err(e) = receiver.receive(Some(source.into()), message, &mut dispatcher) {
+            eprintln!("discarding message from {}: {}", source, e);
+            continue;
+        }
+
+        // received submessages may need a prompt response
+        send(&socket, &mut server, Instant::now());
+        deadline = server.next_deadline();
+    }
+}
+
+fn send(socket: &UdpSocket, server: &mut Server, now: Instant) {
+    let mut output: Vec<(Locator, Message)> = Vec::new();
+    if let Err(e) = server.send(now, &mut output) {
+        eprintln!("failed to announce the server: {}", e);
+    }
+
+    // a bad locator from one client mustn't stop the server serving the others
+    for (locator, message) in output {
+        let address = socket_address(locator);
+        let bytes = match messages::to_bytes(&message) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                eprintln!("failed to encode a message to {}: {}", address, e);
+                continue;
+            }
+        };
+        if let Err(e) = socket.send_to(&bytes, address) {
+            eprintln!("failed to send to {}: {}", address, e);
+        }
+    }
+}
+
+fn socket_address(locator: Locator) -> SocketAddr {
+    match locator {
+        Locator::Udpv4(address) => address.into(),
+        Locator::Udpv6(address) => address.into(),
+    }
+}
+
+/// Routes the submessages of each received message to the server
+#[derive(Debug)]
+struct Dispatcher<'a> {
+    server: &'a mut Server,
+    now: Instant,
+}
+
+impl Dispatch<[u8; 12], [u8; 4]> for Dispatcher<'_> {
+    fn dispatch_to_reader(
+        &mut self,
+        _reader: Option<[u8; 4]>,
+        context: &Context<[u8; 12]>,
+        submessage: ReaderSubMessage<[u8; 4]>,
+    ) {
+        if let Err(e) = self.server.receive(self.now, context, submessage) {
+            eprintln!("failed to process an announcement: {}", e);
+        }
+    }
+
+    fn dispatch_to_writer(
+        &mut self,
+        writer: [u8; 4],
+        context: &Context<[u8; 12]>,
+        submessage: WriterSubMessage<[u8; 4]>,
+    ) {
+        self.server
+            .receive_from_reader(self.now, writer, context, submessage);
+    }
+}
@@ -1,5 +1,5 @@
 use rtps_pim::messages::ByteOrder;
-use safer_bytes::{BufMut, SafeBuf};
+use safer_bytes::{error::Truncated, BufMut, SafeBuf};
 
 /// Trait which represents the ability to convert an object to and from raw
 /// bytes in the Common Data Representation (CDR)
@@ -19,7 +19,7 @@ pub(crate) trait FromCdr {
         B: SafeBuf;
 }
 
-#[allow(clippy::module_name_repetitions)]
+#[allow(clippy::module_name_repetitions, dead_code)]
 pub(crate) trait IntoCdr {
     fn to_buffer<B>(&self, buffer: B)
     where
@@ -39,6 +39,7 @@ pub(crate) trait FromCdrEndian {
         Self: Sized,
         B: SafeBuf;
 
+    #[allow(dead_code)]
     fn from_bytes_be<B>(bytes: B) -> Result<Self, Self::DecodeErr>
     where
         Self: Sized,
@@ -47,6 +48,7 @@ pub(crate) trait FromCdrEndian {
         Self::from_bytes_endian(ByteOrder::BigEndian, bytes)
     }
 
+    #[allow(dead_code)]
     fn from_bytes_le<B>(bytes: B) -> Result<Self, Self::DecodeErr>
     where
         Self: Sized,
@@ -61,6 +63,7 @@ pub(crate) trait IntoCdrEndian {
     where
         B: BufMut;
 
+    #[allow(dead_code)]
     fn to_buffer_le<B>(&self, buffer: B)
     where
         B: BufMut,
@@ -68,6 +71,7 @@ pub(crate) trait IntoCdrEndian {
         self.to_buffer_endian(ByteOrder::LittleEndian, buffer);
     }
 
+    #[allow(dead_code)]
     fn to_buffer_be<B>(&self, buffer: B)
     where
         B: BufMut,
@@ -75,21 +79,84 @@ pub(crate) trait IntoCdrEndian {
         self.to_buffer_endian(ByteOrder::BigEndian, buffer);
     }
 
+    #[allow(dead_code)]
     fn as_bytes_endian(&self, endianess: ByteOrder) -> Vec<u8> {
         let mut buffer = Vec::default();
         self.to_buffer_endian(endianess, &mut buffer);
         buffer
     }
 
+    #[allow(dead_code)]
     fn as_bytes_be(&self) -> Vec<u8> {
         let mut buffer = Vec::default();
         self.to_buffer_endian(ByteOrder::BigEndian, &mut buffer);
         buffer
     }
 
+    #[allow(dead_code)]
     fn as_bytes_le(&self) -> Vec<u8> {
         let mut buffer = Vec::default();
         self.to_buffer_endian(ByteOrder::LittleEndian, &mut buffer);
         buffer
     }
 }
+
+/// Like [`IntoCdrEndian`], for types which can't always be encoded, such as
+/// those with lengths which may not fit in their length fields
+pub(crate) trait TryIntoCdrEndian {
+    type EncodeErr: std::error::Error;
+    fn try_to_buffer_endian<B>(
+        &self,
+        endianess: ByteOrder,
+        buffer: B,
+    ) -> Result<(), Self::EncodeErr>
+    where
+        B: BufMut;
+
+    fn try_as_bytes_endian(&self, endianess: ByteOrder) -> Result<Vec<u8>, Self::EncodeErr> {
+        let mut buffer = Vec::default();
+        self.try_to_buffer_endian(endianess, &mut buffer)?;
+        Ok(buffer)
+    }
+}
+
+macro_rules! impl_cdr_endian {
+    ($($t:ty: $put:ident, $put_le:ident, $get:ident, $get_le:ident);* $(;)?) => {
+        $(
+            impl IntoCdrEndian for $t {
+                fn to_buffer_endian<B>(&self, endianess: ByteOrder, mut buffer: B)
+                where
+                    B: BufMut,
+                {
+                    match endianess {
+                        ByteOrder::BigEndian => buffer.$put(*self),
+                        ByteOrder::LittleEndian => buffer.$put_le(*self),
+                    }
+                }
+            }
+
+            impl FromCdrEndian for $t {
+                type DecodeErr = Truncated;
+
+                fn from_bytes_endian<B>(
+                    endianess: ByteOrder,
+                    mut buffer: B,
+                ) -> Result<Self, Self::DecodeErr>
+                where
+                    B: SafeBuf,
+                {
+                    match endianess {
+                        ByteOrder::BigEndian => SafeBuf::$get(&mut buffer),
+                        ByteOrder::LittleEndian => SafeBuf::$get_le(&mut buffer),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_cdr_endian!(
+    u16: put_u16, put_u16_le, try_get_u16, try_get_u16_le;
+    u32: put_u32, put_u32_le, try_get_u32, try_get_u32_le;
+    i32: put_i32, put_i32_le, try_get_i32, try_get_i32_le;
+);
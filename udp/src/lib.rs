@@ -11,3 +11,6 @@
 
 mod cdr;
 mod model;
+pub use model::messages;
+pub mod port_mapping;
+pub mod static_discovery;
@@ -1,3 +1,3 @@
 mod behaviour;
-mod messages;
+pub mod messages;
 mod structure;
@@ -1 +1,373 @@
+//! The wire representation of RTPS messages
+//!
+//! See [section 9.4](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF) of the relevant specification document.
+
 mod submessage;
+
+use rtps_pim::messages::{ByteOrder, Header};
+use safer_bytes::{error::Truncated, BufMut, SafeBuf};
+use vec1::Vec1;
+
+use crate::cdr::{FromCdr, FromCdrEndian, TryIntoCdrEndian};
+
+/// An RTPS message, with the standard 12-byte [`Guid`](rtps_pim::structure::Guid) prefix
+/// and 4-byte entity ID
+pub type Message = rtps_pim::messages::Message<[u8; 12], [u8; 4]>;
+
+type SubMessage = rtps_pim::messages::SubMessage<[u8; 12], [u8; 4]>;
+
+/// The error type for decoding a [`Message`]
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    /// The message ended before the end of an element
+    #[error("not enough bytes left in the buffer")]
+    Truncated(#[from] Truncated),
+
+    /// The message doesn't start with the `RTPS` protocol ID
+    #[error("not an RTPS message")]
+    ProtocolId,
+
+    /// The message doesn't contain any submessages which could be decoded
+    #[error("the message contains no known submessages")]
+    Empty,
+
+    /// The base of a sequence or fragment number set is 0
+    #[error("the base of the sequence cannot be 0")]
+    ZeroBase,
+
+    /// A sequence number is negative
+    #[error("invalid sequence number")]
+    SequenceNumber,
+
+    /// A sequence or fragment number set has more than 256 bits
+    #[error("a bitmap of {0} bits is too large")]
+    Bitmap(u32),
+
+    /// A locator has an unsupported kind, or an invalid port
+    #[error("unsupported locator")]
+    Locator,
+
+    /// A timestamp is out of range
+    #[error("invalid timestamp")]
+    Timestamp,
+}
+
+/// The error type for encoding a [`Message`]
+#[derive(Debug, thiserror::Error)]
+pub enum EncodeError {
+    /// The value of a parameter is too long for the length field of its
+    /// header
+    #[error("parameter {id:#06x} of {size} bytes is too large to encode")]
+    Parameter {
+        /// The ID of the parameter
+        id: u16,
+
+        /// The size of the value of the parameter, in bytes
+        size: usize,
+    },
+
+    /// A submessage is too long for the length field of its header, and should
+    /// be fragmented instead
+    #[error("a submessage of {0} bytes is too large to encode")]
+    SubMessage(usize),
+}
+
+impl TryIntoCdrEndian for Message {
+    type EncodeErr = EncodeError;
+
+    fn try_to_buffer_endian<B>(
+        &self,
+        endianess: ByteOrder,
+        mut buffer: B,
+    ) -> Result<(), EncodeError>
+    where
+        B: BufMut,
+    {
+        let header = self.header();
+        submessage::put_header(
+            header.protocol_version(),
+            header.vendor_id(),
+            header.guid_prefix(),
+            &mut buffer,
+        );
+        for submessage in self.submessages() {
+            submessage::put(endianess, submessage, &mut buffer)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromCdr for Message {
+    type DecodeErr = DecodeError;
+
+    fn from_bytes<B>(mut bytes: B) -> Result<Self, Self::DecodeErr>
+    where
+        Self: Sized,
+        B: SafeBuf,
+    {
+        let bytes = bytes.try_copy_to_bytes(bytes.remaining())?;
+        let mut buffer: &[u8] = &bytes;
+        let (protocol_version, vendor_id, guid_prefix) = submessage::get_header(&mut buffer)?;
+
+        let mut submessages = Vec::new();
+        while !buffer.is_empty() {
+            let id = buffer.try_get_u8()?;
+            let flags = buffer.try_get_u8()?;
+            let endianess = submessage::endianess(flags);
+
+            // a length of 0 means the submessage extends to the end of the
+            // message, except for PAD and INFO_TS
+            let len = usize::from(u16::from_bytes_endian(endianess, &mut buffer)?);
+            let len = if len == 0 && id != submessage::id::PAD && id != submessage::id::INFO_TS {
+                buffer.len()
+            } else {
+                len
+            };
+            if buffer.len() < len {
+                return Err(Truncated.into());
+            }
+
+            let (body, rest) = buffer.split_at(len);
+            submessages.extend(submessage::get(id, flags, body)?);
+            buffer = rest;
+        }
+
+        let submessages = Vec1::try_from_vec(submessages).map_err(|_| DecodeError::Empty)?;
+        Ok(Self::new(
+            Header::new(protocol_version, vendor_id, guid_prefix),
+            submessages,
+        ))
+    }
+}
+
+/// Serialize a [`Message`], with every submessage in little-endian byte order
+///
+/// Serialized payloads are padded to a multiple of 4 bytes, so payloads which
+/// aren't already aligned won't survive a round trip unchanged.
+///
+/// # Errors
+///
+/// This method will fail if a submessage, or one of its parameters, is too
+/// large for the length field of its header.
+pub fn to_bytes(message: &Message) -> Result<Vec<u8>, EncodeError> {
+    message.try_as_bytes_endian(ByteOrder::LittleEndian)
+}
+
+/// Deserialize a [`Message`]
+///
+/// Submessages which aren't recognised are skipped.
+///
+/// # Errors
+///
+/// This method will fail if the message is malformed, or contains no known
+/// submessages.
+pub fn from_bytes(bytes: &[u8]) -> Result<Message, DecodeError> {
+    Message::from_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{Ipv4Addr, SocketAddrV4},
+        num::{NonZeroU32, NonZeroU64},
+    };
+
+    use chrono::{TimeZone, Utc};
+    use rtps_pim::{
+        messages::{
+            submessage::{
+                elements::{FragmentNumberSet, Parameter, SequenceNumberSet},
+                AckNack, Data, DataFrag, Gap, Heartbeat, HeartbeatFrag, InfoDestination, InfoReply,
+                InfoSource, InfoTimestamp, NackFrag, Payload,
+            },
+            ByteOrder, Header,
+        },
+        structure::{ProtocolVersion, VendorId},
+    };
+    use test_case::test_case;
+    use vec1::vec1;
+
+    use super::{from_bytes, to_bytes, DecodeError, EncodeError, Message, SubMessage};
+    use crate::cdr::TryIntoCdrEndian;
+
+    const PREFIX: [u8; 12] = [1; 12];
+    const READER: [u8; 4] = [0, 0, 1, 0x07];
+    const WRITER: [u8; 4] = [0, 0, 1, 0x02];
+
+    fn header() -> Header<[u8; 12]> {
+        Header::new(
+            ProtocolVersion::Specified { major: 2, minor: 5 },
+            VendorId::Known([0x01, 0x10]),
+            PREFIX,
+        )
+    }
+
+    fn message() -> Message {
+        let mut sequence_numbers = SequenceNumberSet::new(NonZeroU64::new(3).unwrap());
+        sequence_numbers.insert_offset(0);
+        sequence_numbers.insert_offset(40);
+
+        let mut fragment_numbers = FragmentNumberSet::new(NonZeroU32::new(2).unwrap());
+        fragment_numbers.insert_offset(1);
+
+        let locator = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 7410).into();
+
+        Message::new(
+            header(),
+            vec1![
+                SubMessage::InfoSource(InfoSource {
+                    protocol_version: ProtocolVersion::Specified { major: 2, minor: 4 },
+                    vendor_id: VendorId::Unknown,
+                    guid_prefix: [2; 12],
+                }),
+                SubMessage::InfoDestination(InfoDestination {
+                    guid_prefix: Some([3; 12]),
+                }),
+                SubMessage::InfoDestination(InfoDestination { guid_prefix: None }),
+                SubMessage::InfoReply(InfoReply {
+                    unicast_locators: vec![locator],
+                    multicast_locators: Some(vec![locator, locator]),
+                }),
+                SubMessage::InfoTimestamp(InfoTimestamp {
+                    timestamp: Some(Utc.timestamp_opt(1_600_000_000, 0).unwrap()),
+                }),
+                SubMessage::InfoTimestamp(InfoTimestamp { timestamp: None }),
+                SubMessage::Data(Data {
+                    reader: None,
+                    writer: WRITER,
+                    writer_sequence_number: 1 << 33,
+                    inline_qos: Some(
+                        vec![Parameter::new(0x0071, vec![1, 0, 0, 0])]
+                            .into_iter()
+                            .collect()
+                    ),
+                    payload: Some(Payload::Data(vec![0, 1, 0, 0, 42, 0, 0, 0])),
+                    non_standard_payload: false,
+                }),
+                SubMessage::Data(Data {
+                    reader: Some(READER),
+                    writer: WRITER,
+                    writer_sequence_number: 2,
+                    inline_qos: None,
+                    payload: Some(Payload::Key(vec![7; 16])),
+                    non_standard_payload: true,
+                }),
+                SubMessage::DataFrag(DataFrag {
+                    reader: Some(READER),
+                    writer: WRITER,
+                    writer_sequence_number: 3,
+                    fragment_starting_number: NonZeroU32::new(2).unwrap(),
+                    fragment_size: 4,
+                    sample_size: 7,
+                    inline_qos: None,
+                    key: false,
+                    non_standard_payload: false,
+                    fragments: vec![5, 6, 7],
+                }),
+                SubMessage::Heartbeat(Heartbeat {
+                    reader: None,
+                    writer: WRITER,
+                    first_sequence_number: 1,
+                    last_sequence_number: 3,
+                    count: 4,
+                    final_flag: true,
+                    liveliness_flag: false,
+                }),
+                SubMessage::HeartbeatFrag(HeartbeatFrag {
+                    reader: Some(READER),
+                    writer: WRITER,
+                    writer_sequence_number: 3,
+                    last_fragment_number: 2,
+                    count: 1,
+                }),
+                SubMessage::Gap(Gap {
+                    reader: Some(READER),
+                    writer: WRITER,
+                    gap_start: 1,
+                    gap_list: sequence_numbers.clone(),
+                }),
+                SubMessage::AckNack(AckNack {
+                    reader: READER,
+                    writer: WRITER,
+                    reader_sequence_number_state: sequence_numbers,
+                    count: 2,
+                    final_flag: false,
+                }),
+                SubMessage::NackFrag(NackFrag {
+                    reader: READER,
+                    writer: WRITER,
+                    writer_sequence_number: 3,
+                    fragment_number_state: fragment_numbers,
+                    count: 1,
+                }),
+                SubMessage::Pad,
+            ],
+        )
+    }
+
+    #[test_case(ByteOrder::BigEndian)]
+    #[test_case(ByteOrder::LittleEndian)]
+    fn round_trip(endianess: ByteOrder) {
+        let expected = message();
+
+        let bytes = expected.try_as_bytes_endian(endianess).unwrap();
+        assert_eq!(bytes.len(), expected.serialized_size());
+
+        let actual = from_bytes(&bytes).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn skips_unknown_submessages() {
+        let mut bytes = to_bytes(&Message::new(header(), vec1![SubMessage::Pad])).unwrap();
+        bytes.splice(20..20, vec![0x80, 0x01, 4, 0, 1, 2, 3, 4]);
+
+        let message = from_bytes(&bytes).unwrap();
+
+        assert_eq!(message.submessages(), &[SubMessage::Pad]);
+    }
+
+    #[test]
+    fn rejects_empty_messages() {
+        let mut bytes = to_bytes(&Message::new(header(), vec1![SubMessage::Pad])).unwrap();
+        bytes.truncate(20);
+
+        assert!(matches!(from_bytes(&bytes), Err(DecodeError::Empty)));
+    }
+
+    #[test]
+    fn rejects_other_protocols() {
+        let mut bytes = to_bytes(&message()).unwrap();
+        bytes[..4].copy_from_slice(b"RTPX");
+
+        assert!(matches!(from_bytes(&bytes), Err(DecodeError::ProtocolId)));
+    }
+
+    #[test]
+    fn refuses_to_encode_oversized_submessages() {
+        let data = |payload_size| {
+            SubMessage::Data(Data {
+                reader: None,
+                writer: WRITER,
+                writer_sequence_number: 1,
+                inline_qos: None,
+                payload: Some(Payload::Data(vec![0; payload_size])),
+                non_standard_payload: false,
+            })
+        };
+
+        assert!(to_bytes(&Message::new(header(), vec1![data(65_000)])).is_ok());
+        assert!(matches!(
+            to_bytes(&Message::new(header(), vec1![data(70_000)])),
+            Err(EncodeError::SubMessage(70_020))
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_messages() {
+        let mut bytes = to_bytes(&message()).unwrap();
+        bytes.truncate(bytes.len() - 3);
+
+        assert!(from_bytes(&bytes).is_err());
+    }
+}
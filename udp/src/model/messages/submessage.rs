@@ -1 +1,553 @@
 mod elements;
+
+use std::{
+    convert::{TryFrom, TryInto},
+    num::NonZeroU32,
+};
+
+use chrono::{DateTime, TimeZone, Utc};
+use rtps_pim::{
+    messages::{
+        submessage::{
+            elements::{FragmentNumberSet, ParameterList, SequenceNumberSet},
+            AckNack, Data, DataFrag, Gap, Heartbeat, HeartbeatFrag, InfoDestination, InfoReply,
+            InfoSource, InfoTimestamp, NackFrag, Payload,
+        },
+        ByteOrder,
+    },
+    structure::{Locator, ProtocolVersion, VendorId},
+};
+use safer_bytes::{BufMut, SafeBuf};
+
+use self::elements::{get_sequence_number, put_sequence_number};
+use super::{DecodeError, EncodeError, SubMessage};
+use crate::cdr::{FromCdrEndian, IntoCdrEndian, TryIntoCdrEndian};
+
+/// The submessage IDs
+///
+/// See [section 9.4.5.1.1](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF) of the relevant specification document.
+pub(super) mod id {
+    pub const PAD: u8 = 0x01;
+    pub const ACKNACK: u8 = 0x06;
+    pub const HEARTBEAT: u8 = 0x07;
+    pub const GAP: u8 = 0x08;
+    pub const INFO_TS: u8 = 0x09;
+    pub const INFO_SRC: u8 = 0x0c;
+    pub const INFO_DST: u8 = 0x0e;
+    pub const INFO_REPLY: u8 = 0x0f;
+    pub const NACK_FRAG: u8 = 0x12;
+    pub const HEARTBEAT_FRAG: u8 = 0x13;
+    pub const DATA: u8 = 0x15;
+    pub const DATA_FRAG: u8 = 0x16;
+}
+
+/// The endianness flag, common to every submessage
+const FLAG_ENDIANESS: u8 = 0x01;
+
+/// The entity ID used when a submessage isn't addressed to a specific reader
+const ENTITYID_UNKNOWN: [u8; 4] = [0; 4];
+
+/// The size of the fixed part of a DATA submessage, after the inline quality
+/// of service offset
+const DATA_HEADER_SIZE: u16 = 16;
+
+/// The size of the fixed part of a `DATA_FRAG` submessage, after the inline
+/// quality of service offset
+const DATA_FRAG_HEADER_SIZE: u16 = 28;
+
+/// Serialize a submessage, including its header
+pub(super) fn put<B>(
+    endianess: ByteOrder,
+    submessage: &SubMessage,
+    mut buffer: B,
+) -> Result<(), EncodeError>
+where
+    B: BufMut,
+{
+    let mut body = Vec::new();
+    let (id, flags) = match submessage {
+        SubMessage::Data(data) => (id::DATA, put_data(endianess, data, &mut body)?),
+        SubMessage::DataFrag(data_frag) => (
+            id::DATA_FRAG,
+            put_data_frag(endianess, data_frag, &mut body)?,
+        ),
+        SubMessage::Heartbeat(heartbeat) => {
+            put_entity_ids(heartbeat.reader, heartbeat.writer, &mut body);
+            put_sequence_number(endianess, heartbeat.first_sequence_number, &mut body);
+            put_sequence_number(endianess, heartbeat.last_sequence_number, &mut body);
+            heartbeat.count.to_buffer_endian(endianess, &mut body);
+            let flags = flag(heartbeat.final_flag, 0x02) | flag(heartbeat.liveliness_flag, 0x04);
+            (id::HEARTBEAT, flags)
+        }
+        SubMessage::HeartbeatFrag(heartbeat_frag) => {
+            put_entity_ids(heartbeat_frag.reader, heartbeat_frag.writer, &mut body);
+            put_sequence_number(endianess, heartbeat_frag.writer_sequence_number, &mut body);
+            heartbeat_frag
+                .last_fragment_number
+                .to_buffer_endian(endianess, &mut body);
+            heartbeat_frag.count.to_buffer_endian(endianess, &mut body);
+            (id::HEARTBEAT_FRAG, 0)
+        }
+        SubMessage::Gap(gap) => {
+            put_entity_ids(gap.reader, gap.writer, &mut body);
+            put_sequence_number(endianess, gap.gap_start, &mut body);
+            gap.gap_list.to_buffer_endian(endianess, &mut body);
+            (id::GAP, 0)
+        }
+        SubMessage::AckNack(ack_nack) => {
+            put_entity_ids(Some(ack_nack.reader), ack_nack.writer, &mut body);
+            ack_nack
+                .reader_sequence_number_state
+                .to_buffer_endian(endianess, &mut body);
+            ack_nack.count.to_buffer_endian(endianess, &mut body);
+            (id::ACKNACK, flag(ack_nack.final_flag, 0x02))
+        }
+        SubMessage::NackFrag(nack_frag) => {
+            put_entity_ids(Some(nack_frag.reader), nack_frag.writer, &mut body);
+            put_sequence_number(endianess, nack_frag.writer_sequence_number, &mut body);
+            nack_frag
+                .fragment_number_state
+                .to_buffer_endian(endianess, &mut body);
+            nack_frag.count.to_buffer_endian(endianess, &mut body);
+            (id::NACK_FRAG, 0)
+        }
+        SubMessage::InfoSource(info_source) => {
+            0_u32.to_buffer_endian(endianess, &mut body);
+            put_protocol_version(info_source.protocol_version, &mut body);
+            put_vendor_id(info_source.vendor_id, &mut body);
+            body.put_slice(&info_source.guid_prefix);
+            (id::INFO_SRC, 0)
+        }
+        SubMessage::InfoDestination(info_destination) => {
+            body.put_slice(&info_destination.guid_prefix.unwrap_or_default());
+            (id::INFO_DST, 0)
+        }
+        SubMessage::InfoReply(info_reply) => {
+            put_locators(endianess, &info_reply.unicast_locators, &mut body);
+            if let Some(multicast_locators) = &info_reply.multicast_locators {
+                put_locators(endianess, multicast_locators, &mut body);
+            }
+            (
+                id::INFO_REPLY,
+                flag(info_reply.multicast_locators.is_some(), 0x02),
+            )
+        }
+        SubMessage::InfoTimestamp(info_timestamp) => {
+            if let Some(timestamp) = info_timestamp.timestamp {
+                put_timestamp(endianess, timestamp, &mut body);
+            }
+            (id::INFO_TS, flag(info_timestamp.timestamp.is_none(), 0x02))
+        }
+        SubMessage::Pad => (id::PAD, 0),
+    };
+
+    let length = u16::try_from(body.len()).map_err(|_| EncodeError::SubMessage(body.len()))?;
+    let flags = flags | flag(endianess == ByteOrder::LittleEndian, FLAG_ENDIANESS);
+    buffer.put_u8(id);
+    buffer.put_u8(flags);
+    length.to_buffer_endian(endianess, &mut buffer);
+    buffer.put_slice(&body);
+    Ok(())
+}
+
+/// Deserialize a submessage, given its header
+///
+/// Returns `None` for submessages which aren't recognised.
+pub(super) fn get(id: u8, flags: u8, mut body: &[u8]) -> Result<Option<SubMessage>, DecodeError> {
+    let endianess = endianess(flags);
+    let buffer = &mut body;
+
+    let submessage = match id {
+        id::DATA => SubMessage::Data(get_data(endianess, flags, buffer)?),
+        id::DATA_FRAG => SubMessage::DataFrag(get_data_frag(endianess, flags, buffer)?),
+        id::HEARTBEAT => {
+            let (reader, writer) = get_entity_ids(buffer)?;
+            SubMessage::Heartbeat(Heartbeat {
+                reader,
+                writer,
+                first_sequence_number: get_sequence_number(endianess, &mut *buffer)?,
+                last_sequence_number: get_sequence_number(endianess, &mut *buffer)?,
+                count: u32::from_bytes_endian(endianess, &mut *buffer)?,
+                final_flag: flags & 0x02 != 0,
+                liveliness_flag: flags & 0x04 != 0,
+            })
+        }
+        id::HEARTBEAT_FRAG => {
+            let (reader, writer) = get_entity_ids(buffer)?;
+            SubMessage::HeartbeatFrag(HeartbeatFrag {
+                reader,
+                writer,
+                writer_sequence_number: get_sequence_number(endianess, &mut *buffer)?,
+                last_fragment_number: u32::from_bytes_endian(endianess, &mut *buffer)?,
+                count: u32::from_bytes_endian(endianess, &mut *buffer)?,
+            })
+        }
+        id::GAP => {
+            let (reader, writer) = get_entity_ids(buffer)?;
+            SubMessage::Gap(Gap {
+                reader,
+                writer,
+                gap_start: get_sequence_number(endianess, &mut *buffer)?,
+                gap_list: SequenceNumberSet::from_bytes_endian(endianess, &mut *buffer)?,
+            })
+        }
+        id::ACKNACK => {
+            let (reader, writer) = get_entity_ids(buffer)?;
+            SubMessage::AckNack(AckNack {
+                reader: reader.unwrap_or(ENTITYID_UNKNOWN),
+                writer,
+                reader_sequence_number_state: SequenceNumberSet::from_bytes_endian(
+                    endianess,
+                    &mut *buffer,
+                )?,
+                count: u32::from_bytes_endian(endianess, &mut *buffer)?,
+                final_flag: flags & 0x02 != 0,
+            })
+        }
+        id::NACK_FRAG => {
+            let (reader, writer) = get_entity_ids(buffer)?;
+            SubMessage::NackFrag(NackFrag {
+                reader: reader.unwrap_or(ENTITYID_UNKNOWN),
+                writer,
+                writer_sequence_number: get_sequence_number(endianess, &mut *buffer)?,
+                fragment_number_state: FragmentNumberSet::from_bytes_endian(
+                    endianess,
+                    &mut *buffer,
+                )?,
+                count: u32::from_bytes_endian(endianess, &mut *buffer)?,
+            })
+        }
+        id::INFO_SRC => {
+            u32::from_bytes_endian(endianess, &mut *buffer)?;
+            SubMessage::InfoSource(InfoSource {
+                protocol_version: get_protocol_version(buffer)?,
+                vendor_id: get_vendor_id(buffer)?,
+                guid_prefix: get_array(buffer)?,
+            })
+        }
+        id::INFO_DST => {
+            let guid_prefix = get_array(buffer)?;
+            SubMessage::InfoDestination(InfoDestination {
+                guid_prefix: Some(guid_prefix).filter(|&prefix| prefix != [0; 12]),
+            })
+        }
+        id::INFO_REPLY => SubMessage::InfoReply(InfoReply {
+            unicast_locators: get_locators(endianess, buffer)?,
+            multicast_locators: if flags & 0x02 == 0 {
+                None
+            } else {
+                Some(get_locators(endianess, buffer)?)
+            },
+        }),
+        id::INFO_TS => SubMessage::InfoTimestamp(InfoTimestamp {
+            timestamp: if flags & 0x02 == 0 {
+                Some(get_timestamp(endianess, buffer)?)
+            } else {
+                None
+            },
+        }),
+        id::PAD => SubMessage::Pad,
+        _ => return Ok(None),
+    };
+
+    Ok(Some(submessage))
+}
+
+/// The byte order of a submessage, given its flags
+pub(super) fn endianess(flags: u8) -> ByteOrder {
+    if flags & FLAG_ENDIANESS == 0 {
+        ByteOrder::BigEndian
+    } else {
+        ByteOrder::LittleEndian
+    }
+}
+
+fn flag(set: bool, flag: u8) -> u8 {
+    if set { flag } else { 0 }
+}
+
+fn padding(len: usize) -> usize {
+    (4 - len % 4) % 4
+}
+
+fn put_entity_ids<B>(reader: Option<[u8; 4]>, writer: [u8; 4], mut buffer: B)
+where
+    B: BufMut,
+{
+    buffer.put_slice(&reader.unwrap_or(ENTITYID_UNKNOWN));
+    buffer.put_slice(&writer);
+}
+
+fn get_entity_ids(buffer: &mut &[u8]) -> Result<(Option<[u8; 4]>, [u8; 4]), DecodeError> {
+    let reader = get_array(buffer)?;
+    let writer = get_array(buffer)?;
+    Ok((Some(reader).filter(|&id| id != ENTITYID_UNKNOWN), writer))
+}
+
+fn get_array<const N: usize>(buffer: &mut &[u8]) -> Result<[u8; N], DecodeError> {
+    let mut array = [0; N];
+    buffer.try_copy_to_slice(&mut array)?;
+    Ok(array)
+}
+
+fn put_data<B>(endianess: ByteOrder, data: &Data<[u8; 4]>, mut buffer: B) -> Result<u8, EncodeError>
+where
+    B: BufMut,
+{
+    0_u16.to_buffer_endian(endianess, &mut buffer);
+    DATA_HEADER_SIZE.to_buffer_endian(endianess, &mut buffer);
+    put_entity_ids(data.reader, data.writer, &mut buffer);
+    put_sequence_number(endianess, data.writer_sequence_number, &mut buffer);
+    if let Some(inline_qos) = &data.inline_qos {
+        inline_qos.try_to_buffer_endian(endianess, &mut buffer)?;
+    }
+    if let Some(payload) = &data.payload {
+        let bytes = payload.as_bytes();
+        buffer.put_slice(bytes);
+        buffer.put_bytes(0, padding(bytes.len()));
+    }
+
+    Ok(flag(data.inline_qos.is_some(), 0x02)
+        | flag(matches!(data.payload, Some(Payload::Data(_))), 0x04)
+        | flag(matches!(data.payload, Some(Payload::Key(_))), 0x08)
+        | flag(data.non_standard_payload, 0x10))
+}
+
+fn get_data(
+    endianess: ByteOrder,
+    flags: u8,
+    buffer: &mut &[u8],
+) -> Result<Data<[u8; 4]>, DecodeError> {
+    u16::from_bytes_endian(endianess, &mut *buffer)?;
+    let octets_to_inline_qos = u16::from_bytes_endian(endianess, &mut *buffer)?;
+    let (reader, writer) = get_entity_ids(buffer)?;
+    let writer_sequence_number = get_sequence_number(endianess, &mut *buffer)?;
+    skip(
+        buffer,
+        octets_to_inline_qos.saturating_sub(DATA_HEADER_SIZE),
+    )?;
+
+    let inline_qos = if flags & 0x02 == 0 {
+        None
+    } else {
+        Some(ParameterList::from_bytes_endian(endianess, &mut *buffer)?)
+    };
+    let payload = if flags & 0x04 != 0 {
+        Some(Payload::Data(buffer.to_vec()))
+    } else if flags & 0x08 != 0 {
+        Some(Payload::Key(buffer.to_vec()))
+    } else {
+        None
+    };
+
+    Ok(Data {
+        reader,
+        writer,
+        writer_sequence_number,
+        inline_qos,
+        payload,
+        non_standard_payload: flags & 0x10 != 0,
+    })
+}
+
+fn put_data_frag<B>(
+    endianess: ByteOrder,
+    data_frag: &DataFrag<[u8; 4]>,
+    mut buffer: B,
+) -> Result<u8, EncodeError>
+where
+    B: BufMut,
+{
+    0_u16.to_buffer_endian(endianess, &mut buffer);
+    DATA_FRAG_HEADER_SIZE.to_buffer_endian(endianess, &mut buffer);
+    put_entity_ids(data_frag.reader, data_frag.writer, &mut buffer);
+    put_sequence_number(endianess, data_frag.writer_sequence_number, &mut buffer);
+    data_frag
+        .fragment_starting_number
+        .get()
+        .to_buffer_endian(endianess, &mut buffer);
+    u16::try_from(data_frag.fragments_in_submessage())
+        .map_err(|_| EncodeError::SubMessage(data_frag.fragments.len()))?
+        .to_buffer_endian(endianess, &mut buffer);
+    data_frag
+        .fragment_size
+        .to_buffer_endian(endianess, &mut buffer);
+    data_frag
+        .sample_size
+        .to_buffer_endian(endianess, &mut buffer);
+    if let Some(inline_qos) = &data_frag.inline_qos {
+        inline_qos.try_to_buffer_endian(endianess, &mut buffer)?;
+    }
+    buffer.put_slice(&data_frag.fragments);
+    buffer.put_bytes(0, padding(data_frag.fragments.len()));
+
+    Ok(flag(data_frag.inline_qos.is_some(), 0x02)
+        | flag(data_frag.key, 0x04)
+        | flag(data_frag.non_standard_payload, 0x08))
+}
+
+fn get_data_frag(
+    endianess: ByteOrder,
+    flags: u8,
+    buffer: &mut &[u8],
+) -> Result<DataFrag<[u8; 4]>, DecodeError> {
+    u16::from_bytes_endian(endianess, &mut *buffer)?;
+    let octets_to_inline_qos = u16::from_bytes_endian(endianess, &mut *buffer)?;
+    let (reader, writer) = get_entity_ids(buffer)?;
+    let writer_sequence_number = get_sequence_number(endianess, &mut *buffer)?;
+    let fragment_starting_number: NonZeroU32 = u32::from_bytes_endian(endianess, &mut *buffer)?
+        .try_into()
+        .map_err(|_| DecodeError::ZeroBase)?;
+    let fragments_in_submessage = u16::from_bytes_endian(endianess, &mut *buffer)?;
+    let fragment_size = u16::from_bytes_endian(endianess, &mut *buffer)?;
+    let sample_size = u32::from_bytes_endian(endianess, &mut *buffer)?;
+    skip(
+        buffer,
+        octets_to_inline_qos.saturating_sub(DATA_FRAG_HEADER_SIZE),
+    )?;
+
+    let inline_qos = if flags & 0x02 == 0 {
+        None
+    } else {
+        Some(ParameterList::from_bytes_endian(endianess, &mut *buffer)?)
+    };
+
+    // the fragments are padded, and the last fragment of the sample may be
+    // shorter than the others
+    let offset = (u64::from(fragment_starting_number.get()) - 1) * u64::from(fragment_size);
+    let len = (u64::from(fragments_in_submessage) * u64::from(fragment_size))
+        .min(u64::from(sample_size).saturating_sub(offset));
+    let len = usize::try_from(len).unwrap_or(usize::MAX).min(buffer.len());
+
+    Ok(DataFrag {
+        reader,
+        writer,
+        writer_sequence_number,
+        fragment_starting_number,
+        fragment_size,
+        sample_size,
+        inline_qos,
+        key: flags & 0x04 != 0,
+        non_standard_payload: flags & 0x08 != 0,
+        fragments: buffer[..len].to_vec(),
+    })
+}
+
+fn skip(buffer: &mut &[u8], len: u16) -> Result<(), DecodeError> {
+    buffer.try_copy_to_bytes(len.into())?;
+    Ok(())
+}
+
+fn put_protocol_version<B>(protocol_version: ProtocolVersion, mut buffer: B)
+where
+    B: BufMut,
+{
+    let (major, minor) = match protocol_version {
+        ProtocolVersion::Latest => (2, 5),
+        ProtocolVersion::Specified { major, minor } => (
+            u8::try_from(major).unwrap_or(u8::MAX),
+            u8::try_from(minor).unwrap_or(u8::MAX),
+        ),
+    };
+    buffer.put_u8(major);
+    buffer.put_u8(minor);
+}
+
+pub(super) fn get_protocol_version(buffer: &mut &[u8]) -> Result<ProtocolVersion, DecodeError> {
+    let [major, minor] = get_array(buffer)?;
+    Ok(ProtocolVersion::Specified {
+        major: major.into(),
+        minor: minor.into(),
+    })
+}
+
+fn put_vendor_id<B>(vendor_id: VendorId, mut buffer: B)
+where
+    B: BufMut,
+{
+    buffer.put_slice(&match vendor_id {
+        VendorId::Unknown => [0, 0],
+        VendorId::Known(id) => id,
+    });
+}
+
+pub(super) fn get_vendor_id(buffer: &mut &[u8]) -> Result<VendorId, DecodeError> {
+    Ok(match get_array(buffer)? {
+        [0, 0] => VendorId::Unknown,
+        id => VendorId::Known(id),
+    })
+}
+
+/// Write the header of a message
+pub(super) fn put_header<B>(
+    protocol_version: ProtocolVersion,
+    vendor_id: VendorId,
+    guid_prefix: [u8; 12],
+    mut buffer: B,
+) where
+    B: BufMut,
+{
+    buffer.put_slice(b"RTPS");
+    put_protocol_version(protocol_version, &mut buffer);
+    put_vendor_id(vendor_id, &mut buffer);
+    buffer.put_slice(&guid_prefix);
+}
+
+/// Read the header of a message
+pub(super) fn get_header(
+    buffer: &mut &[u8],
+) -> Result<(ProtocolVersion, VendorId, [u8; 12]), DecodeError> {
+    if get_array(buffer)? != *b"RTPS" {
+        return Err(DecodeError::ProtocolId);
+    }
+    Ok((
+        get_protocol_version(buffer)?,
+        get_vendor_id(buffer)?,
+        get_array(buffer)?,
+    ))
+}
+
+fn put_locators<B>(endianess: ByteOrder, locators: &[Locator], mut buffer: B)
+where
+    B: BufMut,
+{
+    u32::try_from(locators.len())
+        .unwrap_or(u32::MAX)
+        .to_buffer_endian(endianess, &mut buffer);
+    for locator in locators {
+        locator.to_buffer_endian(endianess, &mut buffer);
+    }
+}
+
+fn get_locators(endianess: ByteOrder, buffer: &mut &[u8]) -> Result<Vec<Locator>, DecodeError> {
+    let len = u32::from_bytes_endian(endianess, &mut *buffer)?;
+    (0..len)
+        .map(|_| Locator::from_bytes_endian(endianess, &mut *buffer))
+        .collect()
+}
+
+/// Write a timestamp as seconds and fractions (1/2^32) of a second since the
+/// Unix epoch
+fn put_timestamp<B>(endianess: ByteOrder, timestamp: DateTime<Utc>, mut buffer: B)
+where
+    B: BufMut,
+{
+    let seconds = i32::try_from(timestamp.timestamp()).unwrap_or(i32::MAX);
+    let fraction = (u64::from(timestamp.timestamp_subsec_nanos()) << 32) / 1_000_000_000;
+    seconds.to_buffer_endian(endianess, &mut buffer);
+    u32::try_from(fraction)
+        .unwrap_or(u32::MAX)
+        .to_buffer_endian(endianess, &mut buffer);
+}
+
+fn get_timestamp(endianess: ByteOrder, buffer: &mut &[u8]) -> Result<DateTime<Utc>, DecodeError> {
+    let seconds = i32::from_bytes_endian(endianess, &mut *buffer)?;
+    let fraction = u32::from_bytes_endian(endianess, &mut *buffer)?;
+    // rounded up, so that whole nanoseconds survive a round trip
+    let nanos = ((u64::from(fraction) * 1_000_000_000 + (1 << 32) - 1) >> 32)
+        .try_into()
+        .unwrap_or(999_999_999);
+    Utc.timestamp_opt(seconds.into(), nanos)
+        .single()
+        .ok_or(DecodeError::Timestamp)
+}
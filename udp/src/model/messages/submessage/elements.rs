@@ -1,45 +1,186 @@
-use std::convert::TryInto;
+use std::{
+    convert::{TryFrom, TryInto},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6},
+};
 
-use bit_vec::BitVec;
-use rtps_pim::messages::{submessage::elements::SequenceNumberSet, ByteOrder};
+use rtps_pim::{
+    messages::{
+        submessage::elements::{
+            parameter_id, FragmentNumberSet, Parameter, ParameterList, SequenceNumberSet,
+        },
+        ByteOrder,
+    },
+    structure::Locator,
+};
 use safer_bytes::{BufMut, SafeBuf};
 
-use crate::cdr::{FromCdrEndian, IntoCdrEndian};
+use crate::{
+    cdr::{FromCdrEndian, IntoCdrEndian, TryIntoCdrEndian},
+    model::messages::{DecodeError, EncodeError},
+};
+
+const LOCATOR_KIND_UDPV4: i32 = 1;
+const LOCATOR_KIND_UDPV6: i32 = 2;
+
+/// The largest number of bits in the bitmap of a [`SequenceNumberSet`] or
+/// [`FragmentNumberSet`]
+const MAX_BITS: u32 = 256;
+
+/// Write a sequence number as its high (signed) and low (unsigned) words
+pub(crate) fn put_sequence_number<B>(endianess: ByteOrder, value: u64, mut buffer: B)
+where
+    B: BufMut,
+{
+    let high = i32::try_from(value >> 32).unwrap_or(i32::MAX);
+    let low = u32::try_from(value & u64::from(u32::MAX)).unwrap_or_default();
+    high.to_buffer_endian(endianess, &mut buffer);
+    low.to_buffer_endian(endianess, &mut buffer);
+}
+
+/// Read a sequence number from its high (signed) and low (unsigned) words
+pub(crate) fn get_sequence_number<B>(
+    endianess: ByteOrder,
+    mut buffer: B,
+) -> Result<u64, DecodeError>
+where
+    B: SafeBuf,
+{
+    let high = i32::from_bytes_endian(endianess, &mut buffer)?;
+    let low = u32::from_bytes_endian(endianess, &mut buffer)?;
+    let high = u64::try_from(high).map_err(|_| DecodeError::SequenceNumber)?;
+    Ok(high << 32 | u64::from(low))
+}
+
+/// Write the number of bits, and the bitmap, of a set of offsets
+///
+/// The first offset is the most significant bit of the first word.
+fn put_bitmap<B>(endianess: ByteOrder, offsets: impl Iterator<Item = u8>, mut buffer: B)
+where
+    B: BufMut,
+{
+    let offsets: Vec<_> = offsets.collect();
+    let n_bits = offsets.last().map_or(0, |&max| u32::from(max) + 1);
+    let mut blocks = vec![0_u32; ((n_bits + 31) / 32) as usize];
+    for offset in offsets {
+        blocks[usize::from(offset / 32)] |= 1 << (31 - offset % 32);
+    }
+
+    n_bits.to_buffer_endian(endianess, &mut buffer);
+    for block in blocks {
+        block.to_buffer_endian(endianess, &mut buffer);
+    }
+}
+
+/// Read the number of bits, and the bitmap, of a set of offsets
+fn get_bitmap<B>(endianess: ByteOrder, mut buffer: B) -> Result<Vec<u8>, DecodeError>
+where
+    B: SafeBuf,
+{
+    let n_bits = u32::from_bytes_endian(endianess, &mut buffer)?;
+    if n_bits > MAX_BITS {
+        return Err(DecodeError::Bitmap(n_bits));
+    }
+
+    let mut offsets = Vec::new();
+    for block_index in 0..(n_bits + 31) / 32 {
+        let block = u32::from_bytes_endian(endianess, &mut buffer)?;
+        for bit in 0..32 {
+            let offset = block_index * 32 + bit;
+            if offset < n_bits && block & (1 << (31 - bit)) != 0 {
+                offsets.push(offset.try_into().map_err(|_| DecodeError::Bitmap(n_bits))?);
+            }
+        }
+    }
+    Ok(offsets)
+}
 
 impl IntoCdrEndian for SequenceNumberSet {
     fn to_buffer_endian<B>(&self, endianess: ByteOrder, mut buffer: B)
     where
         B: BufMut,
     {
-        let mut bitmask = BitVec::from_elem((self.max_offset() + 1) as usize, false);
+        put_sequence_number(endianess, self.base(), &mut buffer);
+        put_bitmap(endianess, self.offsets(), buffer);
+    }
+}
+
+impl FromCdrEndian for SequenceNumberSet {
+    type DecodeErr = DecodeError;
 
-        for offset in self.offsets() {
-            bitmask.set(offset as usize, true);
+    fn from_bytes_endian<B>(endianess: ByteOrder, mut buffer: B) -> Result<Self, Self::DecodeErr>
+    where
+        Self: Sized,
+        B: SafeBuf,
+    {
+        let base = get_sequence_number(endianess, &mut buffer)?;
+        let mut set = Self::new(base.try_into().map_err(|_| DecodeError::ZeroBase)?);
+        for offset in get_bitmap(endianess, buffer)? {
+            set.insert_offset(offset);
         }
+        Ok(set)
+    }
+}
 
-        let blocks = bitmask.blocks();
-        let n_bits = blocks.len() * 32;
+impl IntoCdrEndian for FragmentNumberSet {
+    fn to_buffer_endian<B>(&self, endianess: ByteOrder, mut buffer: B)
+    where
+        B: BufMut,
+    {
+        self.base().to_buffer_endian(endianess, &mut buffer);
+        put_bitmap(endianess, self.offsets(), buffer);
+    }
+}
 
-        match endianess {
-            ByteOrder::BigEndian => {
-                buffer.put_u64(self.base());
-                buffer.put_u32(n_bits.try_into().unwrap());
-                for block in blocks {
-                    buffer.put_u32(block);
-                }
-            }
-            ByteOrder::LittleEndian => {
-                buffer.put_u64_le(self.base());
-                buffer.put_u32_le(n_bits.try_into().unwrap());
-                for block in blocks {
-                    buffer.put_u32_le(block);
-                }
-            }
+impl FromCdrEndian for FragmentNumberSet {
+    type DecodeErr = DecodeError;
+
+    fn from_bytes_endian<B>(endianess: ByteOrder, mut buffer: B) -> Result<Self, Self::DecodeErr>
+    where
+        Self: Sized,
+        B: SafeBuf,
+    {
+        let base = u32::from_bytes_endian(endianess, &mut buffer)?;
+        let mut set = Self::new(base.try_into().map_err(|_| DecodeError::ZeroBase)?);
+        for offset in get_bitmap(endianess, buffer)? {
+            set.insert_offset(offset);
         }
+        Ok(set)
     }
 }
 
-impl FromCdrEndian for SequenceNumberSet {
+impl TryIntoCdrEndian for ParameterList {
+    type EncodeErr = EncodeError;
+
+    fn try_to_buffer_endian<B>(
+        &self,
+        endianess: ByteOrder,
+        mut buffer: B,
+    ) -> Result<(), EncodeError>
+    where
+        B: BufMut,
+    {
+        for parameter in self.iter() {
+            let value = parameter.value();
+            let padding = (4 - value.len() % 4) % 4;
+            let length =
+                u16::try_from(value.len() + padding).map_err(|_| EncodeError::Parameter {
+                    id: parameter.id(),
+                    size: value.len(),
+                })?;
+
+            parameter.id().to_buffer_endian(endianess, &mut buffer);
+            length.to_buffer_endian(endianess, &mut buffer);
+            buffer.put_slice(value);
+            buffer.put_bytes(0, padding);
+        }
+
+        parameter_id::SENTINEL.to_buffer_endian(endianess, &mut buffer);
+        0_u16.to_buffer_endian(endianess, &mut buffer);
+        Ok(())
+    }
+}
+
+impl FromCdrEndian for ParameterList {
     type DecodeErr = DecodeError;
 
     fn from_bytes_endian<B>(endianess: ByteOrder, mut buffer: B) -> Result<Self, Self::DecodeErr>
@@ -47,61 +188,88 @@ impl FromCdrEndian for SequenceNumberSet {
         Self: Sized,
         B: SafeBuf,
     {
-        let (base, blocks) = match endianess {
-            ByteOrder::BigEndian => {
-                let base = buffer.try_get_u64()?;
-                let n_blocks = buffer.try_get_u32()? / 32;
-                let mut blocks = Vec::with_capacity(n_blocks.try_into().unwrap());
-                for _ in 0..n_blocks {
-                    blocks.push(buffer.try_get_u32()?);
-                }
-
-                (base, blocks)
+        let mut parameters = Self::default();
+        loop {
+            let id = u16::from_bytes_endian(endianess, &mut buffer)?;
+            let length = u16::from_bytes_endian(endianess, &mut buffer)?;
+            if id == parameter_id::SENTINEL {
+                return Ok(parameters);
             }
-            ByteOrder::LittleEndian => {
-                let base = buffer.try_get_u64_le()?;
-                let n_blocks = buffer.try_get_u32_le()? / 32;
-                let mut blocks = Vec::with_capacity(n_blocks.try_into().unwrap());
-                for _ in 0..n_blocks {
-                    blocks.push(buffer.try_get_u32_le()?);
-                }
-
-                (base, blocks)
-            }
-        };
 
-        let mut set = Self::new(base.try_into().map_err(|_| DecodeError::ZeroBase)?);
-
-        for (i, block) in blocks.into_iter().enumerate() {
-            let offset_base = i * 32;
-
-            for n in 0..32 {
-                if block & (1 << n) != 0 {
-                    set.insert_offset((offset_base + n).try_into().unwrap());
-                }
+            let value = buffer.try_copy_to_bytes(length.into())?;
+            if id != parameter_id::PAD {
+                parameters.push(Parameter::new(id, value.to_vec()));
             }
         }
+    }
+}
 
-        Ok(set)
+impl IntoCdrEndian for Locator {
+    fn to_buffer_endian<B>(&self, endianess: ByteOrder, mut buffer: B)
+    where
+        B: BufMut,
+    {
+        let (kind, port, address) = match self {
+            Self::Udpv4(address) => {
+                let mut bytes = [0; 16];
+                bytes[12..].copy_from_slice(&address.ip().octets());
+                (LOCATOR_KIND_UDPV4, address.port(), bytes)
+            }
+            Self::Udpv6(address) => (LOCATOR_KIND_UDPV6, address.port(), address.ip().octets()),
+        };
+
+        kind.to_buffer_endian(endianess, &mut buffer);
+        u32::from(port).to_buffer_endian(endianess, &mut buffer);
+        buffer.put_slice(&address);
     }
 }
 
-#[derive(Debug, thiserror::Error)]
-pub enum DecodeError {
-    #[error("the base of the sequence cannot be 0")]
-    ZeroBase,
+impl FromCdrEndian for Locator {
+    type DecodeErr = DecodeError;
+
+    fn from_bytes_endian<B>(endianess: ByteOrder, mut buffer: B) -> Result<Self, Self::DecodeErr>
+    where
+        Self: Sized,
+        B: SafeBuf,
+    {
+        let kind = i32::from_bytes_endian(endianess, &mut buffer)?;
+        let port = u32::from_bytes_endian(endianess, &mut buffer)?;
+        let mut address = [0; 16];
+        SafeBuf::try_copy_to_slice(&mut buffer, &mut address)?;
 
-    #[error("not enough bytes left in the buffer")]
-    Truncated(#[from] safer_bytes::error::Truncated),
+        let port = u16::try_from(port).map_err(|_| DecodeError::Locator)?;
+        match kind {
+            LOCATOR_KIND_UDPV4 => {
+                let ip = Ipv4Addr::new(address[12], address[13], address[14], address[15]);
+                Ok(SocketAddrV4::new(ip, port).into())
+            }
+            LOCATOR_KIND_UDPV6 => Ok(SocketAddrV6::new(Ipv6Addr::from(address), port, 0, 0).into()),
+            _ => Err(DecodeError::Locator),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use rtps_pim::messages::{submessage::elements::SequenceNumberSet, ByteOrder};
-    use std::num::NonZeroU64;
+    use rtps_pim::{
+        messages::{
+            submessage::elements::{
+                FragmentNumberSet, Parameter, ParameterList, SequenceNumberSet,
+            },
+            ByteOrder,
+        },
+        structure::Locator,
+    };
+    use std::{
+        net::{Ipv4Addr, SocketAddrV4},
+        num::{NonZeroU32, NonZeroU64},
+    };
     use test_case::test_case;
 
-    use crate::cdr::{FromCdrEndian, IntoCdrEndian};
+    use crate::{
+        cdr::{FromCdrEndian, IntoCdrEndian, TryIntoCdrEndian},
+        model::messages::EncodeError,
+    };
 
     #[test_case(ByteOrder::BigEndian)]
     #[test_case(ByteOrder::LittleEndian)]
@@ -118,4 +286,80 @@ mod tests {
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn sequence_number_set_bytes() {
+        let mut set = SequenceNumberSet::new(NonZeroU64::new((1 << 32) + 5).unwrap());
+        set.insert_offset(0);
+        set.insert_offset(33);
+
+        assert_eq!(
+            set.as_bytes_le(),
+            vec![
+                1, 0, 0, 0, // high
+                5, 0, 0, 0, // low
+                34, 0, 0, 0, // number of bits
+                0, 0, 0, 0x80, // offset 0
+                0, 0, 0, 0x40, // offset 33
+            ]
+        );
+    }
+
+    #[test_case(ByteOrder::BigEndian)]
+    #[test_case(ByteOrder::LittleEndian)]
+    fn fragment_number_set_round_trip(endianess: ByteOrder) {
+        let mut expected = FragmentNumberSet::new(NonZeroU32::new(3).unwrap());
+        expected.insert_offset(0);
+        expected.insert_offset(255);
+
+        let bytes = expected.as_bytes_endian(endianess);
+        let actual = FragmentNumberSet::from_bytes_endian(endianess, bytes.as_slice()).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test_case(ByteOrder::BigEndian)]
+    #[test_case(ByteOrder::LittleEndian)]
+    fn parameter_list_round_trip(endianess: ByteOrder) {
+        let expected: ParameterList = vec![
+            Parameter::new(0x0005, vec![1, 2, 3, 4]),
+            Parameter::new(0x0070, vec![5; 16]),
+        ]
+        .into_iter()
+        .collect();
+
+        let bytes = expected.try_as_bytes_endian(endianess).unwrap();
+        let actual = ParameterList::from_bytes_endian(endianess, bytes.as_slice()).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test_case(65_532 => true ; "largest")]
+    #[test_case(65_533 => false ; "padded length overflows")]
+    #[test_case(70_000 => false ; "length overflows")]
+    fn parameter_lengths(size: usize) -> bool {
+        let list: ParameterList = vec![Parameter::new(0x0070, vec![0; size])]
+            .into_iter()
+            .collect();
+
+        match list.try_as_bytes_endian(ByteOrder::LittleEndian) {
+            Ok(_) => true,
+            Err(EncodeError::Parameter { id, size: actual }) => {
+                assert_eq!((id, actual), (0x0070, size));
+                false
+            }
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test_case(ByteOrder::BigEndian)]
+    #[test_case(ByteOrder::LittleEndian)]
+    fn locator_round_trip(endianess: ByteOrder) {
+        let expected: Locator = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 7410).into();
+
+        let bytes = expected.as_bytes_endian(endianess);
+        let actual = Locator::from_bytes_endian(endianess, bytes.as_slice()).unwrap();
+
+        assert_eq!(expected, actual);
+    }
 }
@@ -1 +1,2 @@
+#[allow(dead_code)]
 pub type Guid = rtps_pim::structure::Guid<[u8; 12], [u8; 4]>;
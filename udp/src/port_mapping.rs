@@ -0,0 +1,466 @@
+//! The well-known ports of each domain and participant
+//!
+//! Participants in the same domain find each other without any configuration
+//! by announcing themselves to a multicast port derived from the domain ID.
+//! Each participant in the domain also has a participant ID, which selects its
+//! unicast ports. The ports are
+//!
+//! | traffic   | multicast               | unicast                                    |
+//! | --------- | ----------------------- | ------------------------------------------ |
+//! | discovery | `PB + DG * domain + d0` | `PB + DG * domain + d1 + PG * participant` |
+//! | user      | `PB + DG * domain + d2` | `PB + DG * domain + d3 + PG * participant` |
+//!
+//! [`PortMapping::bind`] allocates a participant ID, and the resulting
+//! [`Locators`] configure a [`Participant`] and its [`ParticipantDiscovery`]
+//! so that it finds the other participants in its domain.
+//!
+//! See [section 9.6.1.1](https://www.omg.org/spec/DDSI-RTPS/2.5/PDF) of the relevant specification document.
+
+use std::{
+    convert::TryFrom,
+    io::{self, ErrorKind},
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+};
+
+use rtps_pim::{
+    discovery::{spdp, DiscoveredParticipantData, ParticipantDiscovery},
+    structure::{participant, Locator, Participant},
+};
+
+/// The default multicast address used for discovery and user traffic
+pub const DEFAULT_MULTICAST_ADDRESS: Ipv4Addr = Ipv4Addr::new(239, 255, 0, 1);
+
+/// The error type for computing and binding the well-known ports
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The port of the domain and participant doesn't fit in 16 bits
+    #[error("domain {domain_id} and participant {participant_id} don't map to a valid port")]
+    PortOutOfRange {
+        /// The domain ID
+        domain_id: u32,
+
+        /// The participant ID
+        participant_id: u32,
+    },
+
+    /// Every participant ID of the domain is already in use on this host
+    #[error("no free participant ID in domain {0}")]
+    NoFreeParticipantId(u32),
+
+    /// A socket couldn't be bound, for a reason other than the port being in
+    /// use
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// The parameters which map domain and participant IDs to ports
+///
+/// The [`Default`] values are those recommended by the specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortMapping {
+    /// The port of domain 0 (`PB`)
+    pub port_base: u16,
+
+    /// The gap between the ports of consecutive domains (`DG`)
+    pub domain_id_gain: u16,
+
+    /// The gap between the unicast ports of consecutive participants (`PG`)
+    pub participant_id_gain: u16,
+
+    /// The offset of the discovery multicast port (`d0`)
+    pub metatraffic_multicast_offset: u16,
+
+    /// The offset of the discovery unicast ports (`d1`)
+    pub metatraffic_unicast_offset: u16,
+
+    /// The offset of the user multicast port (`d2`)
+    pub user_multicast_offset: u16,
+
+    /// The offset of the user unicast ports (`d3`)
+    pub user_unicast_offset: u16,
+}
+
+impl Default for PortMapping {
+    fn default() -> Self {
+        Self {
+            port_base: 7400,
+            domain_id_gain: 250,
+            participant_id_gain: 2,
+            metatraffic_multicast_offset: 0,
+            metatraffic_unicast_offset: 10,
+            user_multicast_offset: 1,
+            user_unicast_offset: 11,
+        }
+    }
+}
+
+impl PortMapping {
+    /// The largest participant ID whose unicast ports don't overlap with
+    /// those of the next domain
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtps_udp::port_mapping::PortMapping;
+    ///
+    /// assert_eq!(PortMapping::default().max_participant_id(), 119);
+    /// ```
+    #[must_use]
+    pub fn max_participant_id(&self) -> u32 {
+        let offset = self
+            .metatraffic_unicast_offset
+            .max(self.user_unicast_offset);
+        u32::from(self.domain_id_gain.saturating_sub(offset.saturating_add(1)))
+            / u32::from(self.participant_id_gain.max(1))
+    }
+
+    /// The multicast port for discovery traffic in a domain
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the port doesn't fit in 16 bits.
+    pub fn metatraffic_multicast_port(&self, domain_id: u32) -> Result<u16, Error> {
+        self.port(domain_id, 0, self.metatraffic_multicast_offset, 0)
+    }
+
+    /// The unicast port for discovery traffic of a participant
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the port doesn't fit in 16 bits.
+    pub fn metatraffic_unicast_port(
+        &self,
+        domain_id: u32,
+        participant_id: u32,
+    ) -> Result<u16, Error> {
+        self.port(
+            domain_id,
+            participant_id,
+            self.metatraffic_unicast_offset,
+            self.participant_id_gain,
+        )
+    }
+
+    /// The multicast port for user traffic in a domain
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the port doesn't fit in 16 bits.
+    pub fn user_multicast_port(&self, domain_id: u32) -> Result<u16, Error> {
+        self.port(domain_id, 0, self.user_multicast_offset, 0)
+    }
+
+    /// The unicast port for user traffic of a participant
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the port doesn't fit in 16 bits.
+    pub fn user_unicast_port(&self, domain_id: u32, participant_id: u32) -> Result<u16, Error> {
+        self.port(
+            domain_id,
+            participant_id,
+            self.user_unicast_offset,
+            self.participant_id_gain,
+        )
+    }
+
+    /// The [`Locators`] of a participant, using the
+    /// [`DEFAULT_MULTICAST_ADDRESS`]
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtps_udp::port_mapping::PortMapping;
+    /// use std::net::{Ipv4Addr, SocketAddrV4};
+    ///
+    /// let locators = PortMapping::default()
+    ///     .locators(Ipv4Addr::new(192, 168, 0, 2).into(), 0, 1)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(
+    ///     locators.metatraffic_unicast,
+    ///     SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 2), 7412).into()
+    /// );
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if any of the ports don't fit in 16 bits.
+    pub fn locators(
+        &self,
+        address: IpAddr,
+        domain_id: u32,
+        participant_id: u32,
+    ) -> Result<Locators, Error> {
+        let locator = |port| Locator::from(SocketAddr::new(address, port));
+        let multicast =
+            |port| Locator::from(SocketAddr::new(DEFAULT_MULTICAST_ADDRESS.into(), port));
+        Ok(Locators {
+            domain_id,
+            participant_id,
+            metatraffic_unicast: locator(self.metatraffic_unicast_port(domain_id, participant_id)?),
+            metatraffic_multicast: multicast(self.metatraffic_multicast_port(domain_id)?),
+            default_unicast: locator(self.user_unicast_port(domain_id, participant_id)?),
+            default_multicast: multicast(self.user_multicast_port(domain_id)?),
+        })
+    }
+
+    /// Allocate the lowest participant ID whose unicast ports are free on the
+    /// given address, and bind a socket to each of them
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if every participant ID up to
+    /// [`PortMapping::max_participant_id`] is in use, or if a socket can't be
+    /// bound for any other reason.
+    pub fn bind(&self, address: IpAddr, domain_id: u32) -> Result<Sockets, Error> {
+        for participant_id in 0..=self.max_participant_id() {
+            let locators = self.locators(address, domain_id, participant_id)?;
+            let metatraffic_port = self.metatraffic_unicast_port(domain_id, participant_id)?;
+            let user_port = self.user_unicast_port(domain_id, participant_id)?;
+
+            let metatraffic_unicast = match probe(SocketAddr::new(address, metatraffic_port))? {
+                Some(socket) => socket,
+                None => continue,
+            };
+            let default_unicast = match probe(SocketAddr::new(address, user_port))? {
+                Some(socket) => socket,
+                None => continue,
+            };
+
+            return Ok(Sockets {
+                locators,
+                metatraffic_unicast,
+                default_unicast,
+            });
+        }
+
+        Err(Error::NoFreeParticipantId(domain_id))
+    }
+
+    fn port(
+        &self,
+        domain_id: u32,
+        participant_id: u32,
+        offset: u16,
+        gain: u16,
+    ) -> Result<u16, Error> {
+        let port = u64::from(self.port_base)
+            + u64::from(self.domain_id_gain) * u64::from(domain_id)
+            + u64::from(offset)
+            + u64::from(gain) * u64::from(participant_id);
+        u16::try_from(port).map_err(|_| Error::PortOutOfRange {
+            domain_id,
+            participant_id,
+        })
+    }
+}
+
+/// Bind a socket, or return `None` if the port is already in use
+fn probe(address: SocketAddr) -> io::Result<Option<UdpSocket>> {
+    match UdpSocket::bind(address) {
+        Ok(socket) => Ok(Some(socket)),
+        Err(e) if e.kind() == ErrorKind::AddrInUse => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// The well-known [`Locator`]s of a participant in a domain
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locators {
+    /// The domain ID
+    pub domain_id: u32,
+
+    /// The participant ID
+    pub participant_id: u32,
+
+    /// The unicast [`Locator`] for discovery traffic
+    pub metatraffic_unicast: Locator,
+
+    /// The multicast [`Locator`] for discovery traffic, which is shared by
+    /// every participant in the domain
+    pub metatraffic_multicast: Locator,
+
+    /// The unicast [`Locator`] for user traffic
+    pub default_unicast: Locator,
+
+    /// The multicast [`Locator`] for user traffic, which is shared by every
+    /// participant in the domain
+    pub default_multicast: Locator,
+}
+
+impl Locators {
+    /// The [`Locator`]s to which SPDP announcements are sent, so that every
+    /// participant in the domain receives them
+    #[must_use]
+    pub fn announcement_locators(&self) -> Vec<Locator> {
+        vec![self.metatraffic_multicast]
+    }
+
+    /// A [`participant::Builder`] for a participant which belongs to the
+    /// domain, and uses these locators by default
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtps_pim::{
+    ///     discovery::ParticipantDiscovery,
+    ///     messages::Message,
+    ///     structure::{history::HistoryCache, Locator},
+    /// };
+    /// use rtps_udp::port_mapping::PortMapping;
+    /// use std::{net::Ipv4Addr, time::Instant};
+    ///
+    /// let locators = PortMapping::default()
+    ///     .locators(Ipv4Addr::new(192, 168, 0, 2).into(), 0, 1)
+    ///     .unwrap();
+    /// let participant = locators.participant([1; 12], [0, 0, 1, 0xc1]).build();
+    /// let mut discovery: ParticipantDiscovery<HistoryCache<Vec<u8>, _, _>, _, _> = locators
+    ///     .participant_discovery(&participant, HistoryCache::default())
+    ///     .build();
+    ///
+    /// // the participant is announced to every participant in the domain
+    /// let mut output: Vec<(Locator, Message<_, _>)> = Vec::new();
+    /// discovery.send(Instant::now(), &mut output).unwrap();
+    /// assert_eq!(output[0].0, locators.metatraffic_multicast);
+    /// ```
+    pub fn participant<P, Id>(&self, guid_prefix: P, entity_id: Id) -> participant::Builder<P, Id>
+    where
+        P: Copy,
+        Id: Copy,
+    {
+        Participant::builder(guid_prefix, entity_id)
+            .domain_id(self.domain_id)
+            .default_unicast_locators(vec![self.default_unicast])
+            .default_multicast_locators(vec![self.default_multicast])
+    }
+
+    /// The [`DiscoveredParticipantData`] which announces a participant
+    /// listening on these locators
+    #[must_use]
+    pub fn participant_data<P, Id>(
+        &self,
+        participant: &Participant<P, Id>,
+    ) -> DiscoveredParticipantData<P>
+    where
+        P: Copy,
+        Id: Copy,
+    {
+        DiscoveredParticipantData {
+            metatraffic_unicast_locators: vec![self.metatraffic_unicast],
+            metatraffic_multicast_locators: vec![self.metatraffic_multicast],
+            ..participant.proxy()
+        }
+    }
+
+    /// A [`spdp::Builder`] for the [`ParticipantDiscovery`] of a participant
+    /// listening on these locators
+    ///
+    /// The participant is announced to its
+    /// [`peer_locators`](Participant::peer_locators) if it uses static
+    /// discovery, or to the [`Locators::announcement_locators`] otherwise.
+    pub fn participant_discovery<C, P, Id>(
+        &self,
+        participant: &Participant<P, Id>,
+        cache: C,
+    ) -> spdp::Builder<C, P, Id>
+    where
+        P: Copy + Ord + AsRef<[u8]> + for<'a> TryFrom<&'a [u8]>,
+        Id: Copy + PartialEq + From<[u8; 4]>,
+    {
+        let announcement_locators = if participant.peer_locators().is_empty() {
+            self.announcement_locators()
+        } else {
+            participant.peer_locators().clone()
+        };
+        ParticipantDiscovery::builder(self.participant_data(participant), cache)
+            .announcement_locators(announcement_locators)
+    }
+}
+
+/// The unicast sockets of a participant, bound by [`PortMapping::bind`]
+///
+/// The multicast sockets are shared with the other participants on the host,
+/// so they aren't bound here.
+#[derive(Debug)]
+pub struct Sockets {
+    /// The well-known locators of the participant
+    pub locators: Locators,
+
+    /// The socket bound to the unicast port for discovery traffic
+    pub metatraffic_unicast: UdpSocket,
+
+    /// The socket bound to the unicast port for user traffic
+    pub default_unicast: UdpSocket,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use test_case::test_case;
+
+    use super::{Error, PortMapping};
+
+    #[test_case(0, 0 => (7400, 7410, 7401, 7411); "domain 0")]
+    #[test_case(0, 1 => (7400, 7412, 7401, 7413); "second participant")]
+    #[test_case(1, 0 => (7650, 7660, 7651, 7661); "domain 1")]
+    #[test_case(2, 3 => (7900, 7916, 7901, 7917); "domain 2")]
+    fn ports(domain_id: u32, participant_id: u32) -> (u16, u16, u16, u16) {
+        let mapping = PortMapping::default();
+        (
+            mapping.metatraffic_multicast_port(domain_id).unwrap(),
+            mapping
+                .metatraffic_unicast_port(domain_id, participant_id)
+                .unwrap(),
+            mapping.user_multicast_port(domain_id).unwrap(),
+            mapping
+                .user_unicast_port(domain_id, participant_id)
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn large_offsets_leave_no_participant_ids() {
+        let mapping = PortMapping {
+            metatraffic_unicast_offset: u16::MAX,
+            ..PortMapping::default()
+        };
+        assert_eq!(mapping.max_participant_id(), 0);
+    }
+
+    #[test]
+    fn port_out_of_range() {
+        let result = PortMapping::default().metatraffic_unicast_port(233, 0);
+        assert!(matches!(
+            result,
+            Err(Error::PortOutOfRange {
+                domain_id: 233,
+                participant_id: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn allocates_free_participant_ids() {
+        let mapping = PortMapping {
+            port_base: 27400,
+            ..PortMapping::default()
+        };
+        let localhost = IpAddr::from(Ipv4Addr::LOCALHOST);
+
+        let first = mapping.bind(localhost, 3).unwrap();
+        let second = mapping.bind(localhost, 3).unwrap();
+
+        assert_eq!(first.locators.participant_id, 0);
+        assert_eq!(second.locators.participant_id, 1);
+        assert_eq!(
+            second.metatraffic_unicast.local_addr().unwrap().port(),
+            28162
+        );
+        assert_eq!(
+            second.locators.metatraffic_multicast,
+            first.locators.metatraffic_multicast
+        );
+    }
+}
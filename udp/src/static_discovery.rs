@@ -0,0 +1,452 @@
+//! Static discovery, for networks which don't support multicast
+//!
+//! Instead of announcing itself to the well-known multicast locators, a
+//! participant announces itself only to a fixed list of peers. Remote
+//! endpoints may also be declared up front, so that they're matched without
+//! the endpoint discovery protocol (SEDP).
+//!
+//! The configuration is loaded from a TOML or JSON file.
+//!
+//! ```toml
+//! peers = ["192.168.0.2:7410", "192.168.0.3:7410"]
+//!
+//! [[writers]]
+//! guid = "020202020202020202020202.00000102"
+//! topic = "Square"
+//! type = "ShapeType"
+//! unicast_locators = ["192.168.0.2:7411"]
+//! reliability = "reliable"
+//!
+//! [[readers]]
+//! guid = "030303030303030303030303.00000107"
+//! topic = "Square"
+//! type = "ShapeType"
+//! durability = "transient_local"
+//! partitions = ["sensors"]
+//! ```
+
+use std::{
+    convert::TryFrom,
+    fmt, fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use rtps_pim::{
+    discovery::{DiscoveredReaderData, DiscoveredWriterData},
+    structure::{qos, Locator},
+};
+use serde::{de, Deserialize, Deserializer};
+
+/// A [`Guid`](rtps_pim::structure::Guid) with 12-byte prefix and 4-byte
+/// entity id
+pub type Guid = rtps_pim::structure::Guid<[u8; 12], [u8; 4]>;
+
+/// The error type for loading a static discovery [`Config`]
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The file couldn't be read
+    #[error("unable to read {path}")]
+    Io {
+        /// The path of the file
+        path: PathBuf,
+
+        /// The underlying error
+        #[source]
+        source: io::Error,
+    },
+
+    /// The TOML configuration is invalid
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+
+    /// The JSON configuration is invalid
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    /// The file extension is neither `toml` nor `json`
+    #[error("unknown configuration format for {0} (expected a .toml or .json file)")]
+    UnknownFormat(PathBuf),
+}
+
+/// The configuration of static discovery
+///
+/// # Example
+///
+/// ```
+/// use rtps_udp::static_discovery::Config;
+///
+/// let config = Config::from_toml(
+///     r#"
+///     peers = ["192.168.0.2:7410"]
+///
+///     [[readers]]
+///     guid = "020202020202020202020202.00000107"
+///     topic = "Square"
+///     type = "ShapeType"
+///     "#,
+/// )
+/// .unwrap();
+///
+/// assert_eq!(config.peer_locators().count(), 1);
+/// assert_eq!(config.remote_readers().count(), 1);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The unicast socket addresses to which the participant is announced
+    #[serde(default)]
+    pub peers: Vec<SocketAddr>,
+
+    /// The statically declared remote writers
+    #[serde(default)]
+    pub writers: Vec<Endpoint>,
+
+    /// The statically declared remote readers
+    #[serde(default)]
+    pub readers: Vec<Endpoint>,
+}
+
+impl Config {
+    /// Parse a [`Config`] from a TOML document
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the document is invalid.
+    pub fn from_toml(s: &str) -> Result<Self, Error> {
+        Ok(toml::from_str(s)?)
+    }
+
+    /// Parse a [`Config`] from a JSON document
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the document is invalid.
+    pub fn from_json(s: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(s)?)
+    }
+
+    /// Load a [`Config`] from a file
+    ///
+    /// The format is chosen by the file extension, which must be `toml` or
+    /// `json`.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the file can't be read, has an
+    /// unknown extension, or is invalid.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let parse = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml,
+            Some("json") => Self::from_json,
+            _ => return Err(Error::UnknownFormat(path.to_path_buf())),
+        };
+        let s = fs::read_to_string(path).map_err(|source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        parse(&s)
+    }
+
+    /// The [`Locator`]s to which the participant is announced
+    ///
+    /// These are intended for
+    /// [`participant::Builder::peer_locators`](rtps_pim::structure::participant::Builder::peer_locators).
+    pub fn peer_locators(&self) -> impl Iterator<Item = Locator> + '_ {
+        self.peers.iter().copied().map(Locator::from)
+    }
+
+    /// The [`DiscoveredWriterData`] of the statically declared remote writers
+    pub fn remote_writers(
+        &self,
+    ) -> impl Iterator<Item = DiscoveredWriterData<[u8; 12], [u8; 4]>> + '_ {
+        self.writers.iter().map(Endpoint::writer_data)
+    }
+
+    /// The [`DiscoveredReaderData`] of the statically declared remote readers
+    pub fn remote_readers(
+        &self,
+    ) -> impl Iterator<Item = DiscoveredReaderData<[u8; 12], [u8; 4]>> + '_ {
+        self.readers.iter().map(Endpoint::reader_data)
+    }
+}
+
+/// A statically declared remote endpoint
+///
+/// Policies which aren't configured take their default values.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Endpoint {
+    /// The [`Guid`] of the endpoint, formatted as the hexadecimal prefix and
+    /// entity id separated by a `.`
+    #[serde(deserialize_with = "deserialize_guid")]
+    pub guid: Guid,
+
+    /// The name of the topic
+    pub topic: String,
+
+    /// The name of the type
+    #[serde(rename = "type")]
+    pub type_name: String,
+
+    /// The unicast socket addresses of the endpoint
+    #[serde(default)]
+    pub unicast_locators: Vec<SocketAddr>,
+
+    /// The multicast socket addresses of the endpoint
+    #[serde(default)]
+    pub multicast_locators: Vec<SocketAddr>,
+
+    /// The `RELIABILITY` kind of the endpoint
+    #[serde(default)]
+    pub reliability: Option<ReliabilityKind>,
+
+    /// The `DURABILITY` of the endpoint
+    #[serde(default)]
+    pub durability: Option<Durability>,
+
+    /// The partitions of the endpoint
+    #[serde(default)]
+    pub partitions: Vec<String>,
+}
+
+impl Endpoint {
+    /// The [`DiscoveredWriterData`] of this endpoint, as a writer
+    #[must_use]
+    pub fn writer_data(&self) -> DiscoveredWriterData<[u8; 12], [u8; 4]> {
+        let mut qos = qos::WriterQos::default();
+        if let Some(kind) = self.reliability {
+            qos.reliability.kind = kind.into();
+        }
+        if let Some(durability) = self.durability {
+            qos.durability = durability.into();
+        }
+        qos.partition.names.clone_from(&self.partitions);
+
+        DiscoveredWriterData {
+            unicast_locators: locators(&self.unicast_locators),
+            multicast_locators: locators(&self.multicast_locators),
+            qos,
+            ..DiscoveredWriterData::new(self.guid, self.topic.clone(), self.type_name.clone())
+        }
+    }
+
+    /// The [`DiscoveredReaderData`] of this endpoint, as a reader
+    #[must_use]
+    pub fn reader_data(&self) -> DiscoveredReaderData<[u8; 12], [u8; 4]> {
+        let mut qos = qos::ReaderQos::default();
+        if let Some(kind) = self.reliability {
+            qos.reliability.kind = kind.into();
+        }
+        if let Some(durability) = self.durability {
+            qos.durability = durability.into();
+        }
+        qos.partition.names.clone_from(&self.partitions);
+
+        DiscoveredReaderData {
+            unicast_locators: locators(&self.unicast_locators),
+            multicast_locators: locators(&self.multicast_locators),
+            qos,
+            ..DiscoveredReaderData::new(self.guid, self.topic.clone(), self.type_name.clone())
+        }
+    }
+}
+
+/// The configured [`qos::ReliabilityKind`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReliabilityKind {
+    /// [`qos::ReliabilityKind::BestEffort`]
+    BestEffort,
+
+    /// [`qos::ReliabilityKind::Reliable`]
+    Reliable,
+}
+
+impl From<ReliabilityKind> for qos::ReliabilityKind {
+    fn from(kind: ReliabilityKind) -> Self {
+        match kind {
+            ReliabilityKind::BestEffort => Self::BestEffort,
+            ReliabilityKind::Reliable => Self::Reliable,
+        }
+    }
+}
+
+/// The configured [`qos::Durability`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Durability {
+    /// [`qos::Durability::Volatile`]
+    Volatile,
+
+    /// [`qos::Durability::TransientLocal`]
+    TransientLocal,
+
+    /// [`qos::Durability::Transient`]
+    Transient,
+
+    /// [`qos::Durability::Persistent`]
+    Persistent,
+}
+
+impl From<Durability> for qos::Durability {
+    fn from(durability: Durability) -> Self {
+        match durability {
+            Durability::Volatile => Self::Volatile,
+            Durability::TransientLocal => Self::TransientLocal,
+            Durability::Transient => Self::Transient,
+            Durability::Persistent => Self::Persistent,
+        }
+    }
+}
+
+fn locators(socket_addrs: &[SocketAddr]) -> Vec<Locator> {
+    socket_addrs.iter().copied().map(Locator::from).collect()
+}
+
+/// Parse a [`Guid`] formatted as `<24 hex digits>.<8 hex digits>`
+fn parse_guid(s: &str) -> Option<Guid> {
+    let (prefix, entity_id) = s.split_once('.')?;
+    let prefix = <[u8; 12]>::try_from(parse_hex(prefix)?).ok()?;
+    let entity_id = <[u8; 4]>::try_from(parse_hex(entity_id)?).ok()?;
+    Some(Guid::new(prefix, entity_id))
+}
+
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 || !s.is_ascii() {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+fn deserialize_guid<'de, D>(deserializer: D) -> Result<Guid, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct Visitor;
+
+    impl de::Visitor<'_> for Visitor {
+        type Value = Guid;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a GUID formatted as <24 hex digits>.<8 hex digits>")
+        }
+
+        fn visit_str<E>(self, s: &str) -> Result<Guid, E>
+        where
+            E: de::Error,
+        {
+            parse_guid(s).ok_or_else(|| E::invalid_value(de::Unexpected::Str(s), &self))
+        }
+    }
+
+    deserializer.deserialize_str(Visitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_guid, Config, Durability, Endpoint, Guid, ReliabilityKind};
+    use rtps_pim::structure::qos;
+    use test_case::test_case;
+
+    fn square_reader() -> Endpoint {
+        Endpoint {
+            guid: Guid::new([2; 12], [0, 0, 1, 0x07]),
+            topic: "Square".to_string(),
+            type_name: "ShapeType".to_string(),
+            unicast_locators: vec!["192.168.0.2:7411".parse().unwrap()],
+            multicast_locators: Vec::new(),
+            reliability: Some(ReliabilityKind::Reliable),
+            durability: Some(Durability::TransientLocal),
+            partitions: vec!["sensors".to_string()],
+        }
+    }
+
+    #[test_case("020202020202020202020202.00000107" => Some(Guid::new([2; 12], [0, 0, 1, 0x07])) ; "valid")]
+    #[test_case("0202020202020202020202.00000107" => None ; "short prefix")]
+    #[test_case("020202020202020202020202.0000010" => None ; "odd length")]
+    #[test_case("020202020202020202020202" => None ; "missing entity id")]
+    #[test_case("02020202020202020202020g.00000107" => None ; "not hex")]
+    fn guid(s: &str) -> Option<Guid> {
+        parse_guid(s)
+    }
+
+    #[test]
+    fn from_toml() {
+        let config = Config::from_toml(
+            r#"
+            peers = ["192.168.0.2:7410"]
+
+            [[readers]]
+            guid = "020202020202020202020202.00000107"
+            topic = "Square"
+            type = "ShapeType"
+            unicast_locators = ["192.168.0.2:7411"]
+            reliability = "reliable"
+            durability = "transient_local"
+            partitions = ["sensors"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                peers: vec!["192.168.0.2:7410".parse().unwrap()],
+                writers: Vec::new(),
+                readers: vec![square_reader()],
+            }
+        );
+    }
+
+    #[test]
+    fn from_json() {
+        let config = Config::from_json(
+            r#"{
+                "readers": [{
+                    "guid": "020202020202020202020202.00000107",
+                    "topic": "Square",
+                    "type": "ShapeType",
+                    "unicast_locators": ["192.168.0.2:7411"],
+                    "reliability": "reliable",
+                    "durability": "transient_local",
+                    "partitions": ["sensors"]
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(config.peers.is_empty());
+        assert_eq!(config.readers, vec![square_reader()]);
+    }
+
+    #[test]
+    fn rejects_invalid_guids() {
+        let error = Config::from_json(
+            r#"{ "writers": [{ "guid": "0202", "topic": "Square", "type": "ShapeType" }] }"#,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("GUID"));
+    }
+
+    #[test]
+    fn endpoint_data() {
+        let reader = Config {
+            readers: vec![square_reader()],
+            ..Config::default()
+        }
+        .remote_readers()
+        .next()
+        .unwrap();
+
+        assert_eq!(reader.topic_name, "Square");
+        assert_eq!(reader.qos.reliability.kind, qos::ReliabilityKind::Reliable);
+        assert_eq!(reader.qos.durability, qos::Durability::TransientLocal);
+        assert_eq!(reader.qos.partition.names, vec!["sensors".to_string()]);
+        assert_eq!(reader.unicast_locators.len(), 1);
+    }
+}